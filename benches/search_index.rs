@@ -0,0 +1,120 @@
+//! Query latency of the in-memory search index on a synthetic project of about 2M characters.
+//!
+//! Run with `cargo bench --bench search_index`.
+
+use novel_outline_tool::search::index::SearchIndex;
+use novel_outline_tool::search::{SearchMode, SearchOptions};
+use novel_outline_tool::storage::{self, ProjectStore};
+use std::time::{Duration, Instant};
+
+const CHAPTERS: usize = 300;
+const CHARS_PER_CHAPTER: usize = 6_700;
+const BUDGET: Duration = Duration::from_millis(50);
+
+/// Deterministic pseudo-random text: sentences of plain Chinese prose in shuffled order, with
+/// the phrase `青云剑` and some Latin words mixed in.
+fn chapter_text(seed: usize) -> String {
+    const SENTENCES: &[&str] = &[
+        "天色渐渐暗了下来，山路两旁的松树在风里沙沙作响",
+        "他停下脚步，回头望了一眼远处的村子，炊烟已经升起来了",
+        "客栈里人声嘈杂，掌柜的一边拨着算盘，一边招呼新来的客人",
+        "她把信折好放进袖中，沉默了很久才开口说话",
+        "城门口的守卫换了班，街上的行人也比白天少了许多",
+        "少年握紧了手里的木棍，心里却一点也不害怕",
+        "雨下了整整三天，河水涨得几乎漫过了石桥",
+        "老人摇了摇头，说这件事远没有他们想的那么简单",
+        "屋檐下挂着一盏旧灯笼，被夜风吹得来回摇晃",
+        "他们在山脚下分开，约好明年春天再在这里见面",
+        "书房的桌上摊着一张地图，上面用红笔圈出了几个地方",
+        "远处传来一阵马蹄声，众人不约而同地站了起来",
+        "她笑着递过一碗热茶，说路上辛苦了，先歇一歇吧",
+        "这一夜谁也没有睡着，只听见窗外的虫鸣一直响到天亮",
+    ];
+    let words = ["the", "sword", "Qingyun", "ABC"];
+    let mut state = (seed as u64).wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+    let mut next = || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (state >> 33) as usize
+    };
+    let mut out = String::new();
+    let mut count = 0;
+    while count < CHARS_PER_CHAPTER {
+        let sentence = SENTENCES[next() % SENTENCES.len()];
+        out.push_str(sentence);
+        count += sentence.chars().count() + 2;
+        match next() % 80 {
+            0 => out.push_str("，青云剑"),
+            1 => {
+                out.push(' ');
+                out.push_str(words[next() % words.len()]);
+            }
+            _ => {}
+        }
+        out.push_str("。\n");
+    }
+    out
+}
+
+fn time_query(index: &SearchIndex, label: &str, query: &str, options: &SearchOptions) {
+    let mut samples = Vec::new();
+    let mut hits = 0;
+    for _ in 0..20 {
+        let start = Instant::now();
+        let results = index.search(query, options).expect("query");
+        samples.push(start.elapsed());
+        hits = results.iter().map(|f| f.hits.len()).sum();
+    }
+    samples.sort();
+    let median = samples[samples.len() / 2];
+    let verdict = if median <= BUDGET { "ok" } else { "SLOW" };
+    println!("{:<24} {:>8.2} ms  {:>5} hits  {}", label, median.as_secs_f64() * 1000.0, hits, verdict);
+}
+
+fn main() {
+    let root = std::env::temp_dir().join(format!("novel-outline-bench-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    ProjectStore::open_or_init(root.clone()).expect("init project");
+    let chapters = root.join("chapters");
+    for i in 0..CHAPTERS {
+        let path = chapters.join(format!("{:04}-第{}章.md", i + 1, i + 1));
+        storage::write_text_atomic(&path, &chapter_text(i)).expect("write chapter");
+    }
+
+    let start = Instant::now();
+    let mut index = SearchIndex::build(&root).expect("build index");
+    println!(
+        "indexed {} files, {} chars in {:.0} ms",
+        index.file_count(),
+        index.char_count(),
+        start.elapsed().as_secs_f64() * 1000.0
+    );
+
+    let plain = SearchOptions::default();
+    let word = SearchOptions {
+        mode: SearchMode::WholeWord,
+        ..SearchOptions::default()
+    };
+    let regex = SearchOptions {
+        mode: SearchMode::Regex,
+        ..SearchOptions::default()
+    };
+    time_query(&index, "phrase 青云剑", "青云剑", &plain);
+    time_query(&index, "single char 青", "青", &plain);
+    time_query(&index, "absent 不存在的词", "不存在的词", &plain);
+    time_query(&index, "latin sword", "sword", &plain);
+    time_query(&index, "whole word abc", "abc", &word);
+    time_query(&index, "regex 青云.?剑", "青云.?剑", &regex);
+
+    let start = Instant::now();
+    index.refresh().expect("refresh");
+    println!("refresh without changes   {:>8.2} ms", start.elapsed().as_secs_f64() * 1000.0);
+
+    let path = chapters.join("0150-第150章.md");
+    let text = chapter_text(9999);
+    storage::write_text_atomic(&path, &text).expect("write chapter");
+    let start = Instant::now();
+    index.update_file(&path, &text);
+    println!("update one chapter        {:>8.2} ms", start.elapsed().as_secs_f64() * 1000.0);
+
+    let _ = std::fs::remove_dir_all(&root);
+}
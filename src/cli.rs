@@ -0,0 +1,900 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use novel_outline_tool::domain::diff::{diff_lines, format_diff};
+use novel_outline_tool::domain::scene::{parse_scenes, scene_label};
+use novel_outline_tool::domain::timeline::TimelineRow;
+use novel_outline_tool::domain::{Calendars, Project, Section};
+use novel_outline_tool::export::{self, CompileOptions};
+use novel_outline_tool::search::replace::{self, ReplaceScope};
+use novel_outline_tool::search::{self, SearchMode, SearchOptions};
+use novel_outline_tool::stats::history::{self, WritingHistory};
+use novel_outline_tool::stats::{self, TextCount};
+use novel_outline_tool::storage::git::GitRepo;
+use novel_outline_tool::storage::outline::{self, OutlineItem, OutlineKind};
+use novel_outline_tool::storage::watch::ProjectWatcher;
+use novel_outline_tool::storage::{self, links, recovery, snapshots, versions, ProjectStore};
+
+const USAGE: &str = "用法: novel-outline [-p <项目文件夹>] <命令> [参数]
+
+命令:
+  init [文件夹]                          初始化项目（默认当前目录）
+  list <section>                         列出条目（chapters/characters/world/timeline），章节按卷缩进
+  new <section> [标题] [--in <卷>]       新建条目，章节自动编号（默认放入最后一卷）
+  new volume [标题] [--in <卷>]          新建卷（chapters/ 下的 NN-标题 文件夹）
+  move-chapter <章节|卷> --before <章节|卷>
+                                         移动章节或卷并逐级重编号（也可用 --after、--into <卷>）
+  rename <section> <条目> <新名称>       重命名条目，章节保留序号
+  scenes [章节]                          列出章节中的场景（<!-- scene: 标题 --> 标记）：视角、地点、概要与字数；
+                                         不给章节时列出全部章节
+  cards                                  卡片墙：按大纲顺序列出每章的状态、视角、字数与概要
+  delete <section> <条目>                删除条目（删除前写入 .backup/）
+  migrate-characters                     将旧版列表式角色卡迁移为结构化 front matter
+  export [目标文件夹]                    导出项目副本（默认 exports/）
+  compile [--no-headings] [--no-toc] [目标文件夹]
+                                         合并全部章节为单个 书名.md（默认 exports/）
+  epub [目标文件夹]                      导出 EPUB 3 电子书 书名.epub（默认 exports/）
+  graph [目标文件夹]                     导出人物关系图（Graphviz DOT 与 SVG，默认 exports/）
+  threads [--dormant <章数>] [目标文件夹]
+                                         导出故事线报告（Markdown 与 HTML，默认 exports/），
+                                         列出连续多章未出现与始终未收束的故事线
+  timeline [check|sort]                  按时间列出全部事件；check 检查时间线，sort 按时间重排表格
+  links <section> <条目>                 列出条目的 [[链接]] 与反向链接
+  links broken                           列出指向不存在条目或标题的链接
+  search [--regex|--word] [--case] <关键词>
+                                         全文搜索全部条目与 project.md，显示行号与上下文
+  goal                                   写作进度：今日字数、每日目标、连续达成天数、截止日期所需速度
+  goal set [--daily <字数>] [--total <字数>] [--deadline <YYYY-MM-DD>]
+                                         设置每日目标、全书目标字数与截止日期（none 表示清除）
+  history <section> <条目> [版本 [--restore]]
+                                         列出条目的历史版本；给出版本序号时显示与当前内容的差异，
+                                         加 --restore 恢复该版本（恢复前先备份当前内容）
+  backup [list|gc]                       为全部条目创建快照；list 列出快照，gc 按保留策略清理
+                                         （一小时内全部保留，一天内每小时一个，一个月内每天一个）
+  recover [<序号> [--restore|--discard]]
+                                         列出 .recovery/ 中上次未能保存的内容；给出序号时显示与文件的差异，
+                                         加 --restore 恢复（恢复前先备份文件）或 --discard 丢弃
+  git init [--interval <分钟>|none] [--no-backup|--backup]
+                                         启用 Git 版本管理（没有仓库时创建），并提交当前内容；
+                                         --interval 设置图形界面定时提交的间隔，--no-backup 不再写入 .backup/
+  git commit                             提交改动过的条目，提交说明如“更新 0003-决战.md (+1200字)”
+  git log <section> <条目>               列出条目的 Git 提交记录
+  watch                                  持续监视项目文件夹，逐批输出被新增、删除、重命名或修改的文件（Ctrl+C 结束）
+  stats [section]                        字数统计：逐条列出（默认 chapters），并汇总各模块与全书
+  replace [--regex|--word] [--case] [--only <范围>] [--skip <范围>] [--yes] <查找> <替换为>
+                                         全文替换；默认只预览，加 --yes 才写入（写入前备份到 .backup/）
+                                         范围用逗号分隔：project/chapters/characters/world/timeline
+
+<条目> 可以是文件名、不带扩展名的文件名，章节还可以直接写序号。";
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let mut root = PathBuf::from(".");
+    if let Some(pos) = args.iter().position(|a| a == "-p" || a == "--project") {
+        if pos + 1 >= args.len() {
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+        root = PathBuf::from(args.remove(pos + 1));
+        args.remove(pos);
+    }
+
+    let Some(cmd) = args.first().cloned() else {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    };
+    let rest = &args[1..];
+
+    let result = match cmd.as_str() {
+        "init" => cmd_init(rest.first().map(PathBuf::from).unwrap_or(root)),
+        "list" => open_project(&root).and_then(|p| cmd_list(&p, rest)),
+        "new" => open_project(&root).and_then(|p| cmd_new(&p, rest)),
+        "move-chapter" => open_project(&root).and_then(|p| cmd_move_chapter(&p, rest)),
+        "rename" => open_project(&root).and_then(|p| cmd_rename(&p, rest)),
+        "delete" => open_project(&root).and_then(|p| cmd_delete(&p, rest)),
+        "scenes" => open_project(&root).and_then(|p| cmd_scenes(&p, rest)),
+        "cards" => open_project(&root).and_then(|p| cmd_cards(&p)),
+        "migrate-characters" => open_project(&root).and_then(|p| cmd_migrate_characters(&p)),
+        "export" => open_project(&root).and_then(|p| cmd_export(&p, rest)),
+        "compile" => open_project(&root).and_then(|p| cmd_compile(&p, rest)),
+        "epub" => open_project(&root).and_then(|p| cmd_epub(&p, rest)),
+        "graph" => open_project(&root).and_then(|p| cmd_graph(&p, rest)),
+        "threads" => open_project(&root).and_then(|p| cmd_threads(&p, rest)),
+        "timeline" => open_project(&root).and_then(|p| cmd_timeline(&p, rest)),
+        "links" => open_project(&root).and_then(|p| cmd_links(&p, rest)),
+        "search" => open_project(&root).and_then(|p| cmd_search(&p, rest)),
+        "history" => open_project(&root).and_then(|p| cmd_history(&p, rest)),
+        "backup" => open_project(&root).and_then(|p| cmd_backup(&p, rest)),
+        "recover" => open_project(&root).and_then(|p| cmd_recover(&p, rest)),
+        "git" => open_project(&root).and_then(|p| cmd_git(p, rest)),
+        "watch" => open_project(&root).and_then(|p| cmd_watch(&p)),
+        "stats" => open_project(&root).and_then(|p| cmd_stats(&p, rest)),
+        "goal" => open_project(&root).and_then(|p| cmd_goal(p, rest)),
+        "replace" => open_project(&root).and_then(|p| cmd_replace(&p, rest)),
+        "help" | "-h" | "--help" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        other => Err(format!("未知命令: {}\n\n{}", other, USAGE)),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn open_project(root: &Path) -> Result<Project, String> {
+    if !root.join("project.md").is_file() {
+        return Err(format!("{} 不是项目文件夹（缺少 project.md），请先运行 init", root.display()));
+    }
+    ProjectStore::open_or_init(root.to_path_buf())
+}
+
+fn parse_section(arg: Option<&String>) -> Result<Section, String> {
+    let name = arg.ok_or_else(|| "缺少 section 参数".to_string())?;
+    Section::from_dir_name(name).ok_or_else(|| format!("未知 section: {}（可选 chapters/characters/world/timeline）", name))
+}
+
+fn find_item(project: &Project, section: Section, key: &str) -> Result<PathBuf, String> {
+    let dir = project.root.join(section.dir_name());
+    if section == Section::Chapters {
+        let items = outline::read_outline(&dir).map_err(|e| e.to_string())?;
+        let item = &items[find_outline_item(&items, &dir, key)?];
+        if item.kind != OutlineKind::Chapter {
+            return Err(format!("{} 是卷，不是章节", key));
+        }
+        return Ok(item.path.clone());
+    }
+    let paths = storage::list_markdown_files(&dir).map_err(|e| e.to_string())?;
+    let found = paths.into_iter().find(|p| {
+        let stem = p.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+        let name = p.file_name().and_then(|s| s.to_str()).unwrap_or("");
+        stem == key || name == key
+    });
+    found.ok_or_else(|| format!("找不到条目: {}", key))
+}
+
+/// Looks `key` up in the chapter outline: a number counts chapters in outline order, anything else
+/// names a chapter or volume by file name, by title or by its path below `chapters/`.
+fn find_outline_item(items: &[OutlineItem], chapters_dir: &Path, key: &str) -> Result<usize, String> {
+    let found = match key.parse::<usize>() {
+        Ok(n) => items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.kind == OutlineKind::Chapter)
+            .nth(n.wrapping_sub(1))
+            .map(|(i, _)| i),
+        Err(_) => items.iter().position(|item| {
+            let rel = item.path.strip_prefix(chapters_dir).unwrap_or(&item.path).to_string_lossy().replace('\\', "/");
+            let stem = item.path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+            rel == key || rel.strip_suffix(".md") == Some(key) || stem == key || file_name(&item.path) == key || item.title() == key
+        }),
+    };
+    found.ok_or_else(|| format!("找不到条目: {}", key))
+}
+
+fn file_name(path: &Path) -> &str {
+    path.file_name().and_then(|s| s.to_str()).unwrap_or("")
+}
+
+fn cmd_init(root: PathBuf) -> Result<(), String> {
+    let project = ProjectStore::open_or_init(root)?;
+    println!("已初始化: {}", project.root.display());
+    Ok(())
+}
+
+fn cmd_list(project: &Project, args: &[String]) -> Result<(), String> {
+    let section = parse_section(args.first())?;
+    let dir = project.root.join(section.dir_name());
+    if section == Section::Chapters {
+        for item in outline::read_outline(&dir).map_err(|e| e.to_string())? {
+            let indent = "  ".repeat(item.depth);
+            match item.kind {
+                OutlineKind::Volume => println!("{}{}/", indent, file_name(&item.path)),
+                OutlineKind::Chapter => println!("{}{}", indent, item.path.file_stem().and_then(|s| s.to_str()).unwrap_or("")),
+            }
+        }
+        return Ok(());
+    }
+    for path in storage::list_markdown_files(&dir).map_err(|e| e.to_string())? {
+        println!("{}", path.file_stem().and_then(|s| s.to_str()).unwrap_or(""));
+    }
+    Ok(())
+}
+
+fn cmd_new(project: &Project, args: &[String]) -> Result<(), String> {
+    let mut args = args.to_vec();
+    let volume = match args.iter().position(|a| a == "--in") {
+        Some(pos) if pos + 1 < args.len() => {
+            let key = args.remove(pos + 1);
+            args.remove(pos);
+            Some(key)
+        }
+        Some(_) => return Err("--in 需要卷名".to_string()),
+        None => None,
+    };
+    let chapters_dir = project.root.join(Section::Chapters.dir_name());
+    let parent = match &volume {
+        Some(key) => {
+            let items = outline::read_outline(&chapters_dir).map_err(|e| e.to_string())?;
+            let item = &items[find_outline_item(&items, &chapters_dir, key)?];
+            if item.kind != OutlineKind::Volume {
+                return Err(format!("{} 不是卷", key));
+            }
+            Some(item.path.clone())
+        }
+        None => None,
+    };
+    if args.first().map(String::as_str) == Some("volume") {
+        let title = args.get(1).map(|s| s.trim()).filter(|s| !s.is_empty()).unwrap_or("新卷");
+        let path = outline::new_volume_path(parent.as_deref().unwrap_or(&chapters_dir), title).map_err(|e| e.to_string())?;
+        std::fs::create_dir(&path).map_err(|e| e.to_string())?;
+        println!("已新建卷: {}", file_name(&path));
+        return Ok(());
+    }
+    let section = parse_section(args.first())?;
+    let title = args.get(1).map(|s| s.trim()).filter(|s| !s.is_empty()).unwrap_or(section.default_title());
+    let path = match (&parent, section) {
+        (Some(dir), Section::Chapters) => storage::new_chapter_path(dir, title),
+        (Some(_), _) => return Err("--in 只能用于章节".to_string()),
+        (None, _) => storage::new_item_path(&project.root, section, title),
+    }
+    .map_err(|e| e.to_string())?;
+    storage::write_text_atomic(&path, &storage::new_item_md(section, title)).map_err(|e| e.to_string())?;
+    println!("已新建: {}", file_name(&path));
+    Ok(())
+}
+
+fn cmd_move_chapter(project: &Project, args: &[String]) -> Result<(), String> {
+    let (src_key, flag, dst_key) = match args {
+        [src, flag, dst] if flag == "--before" || flag == "--after" || flag == "--into" => (src, flag, dst),
+        _ => return Err("用法: move-chapter <章节|卷> --before|--after <章节|卷>，或 --into <卷>".to_string()),
+    };
+    let chapters_dir = project.root.join(Section::Chapters.dir_name());
+    let mut items = outline::read_outline(&chapters_dir).map_err(|e| e.to_string())?;
+    let src = find_outline_item(&items, &chapters_dir, src_key)?;
+    let dst = find_outline_item(&items, &chapters_dir, dst_key)?;
+    if src == dst {
+        return Ok(());
+    }
+
+    let moved = match flag.as_str() {
+        "--before" => outline::move_before(&mut items, src, dst),
+        "--after" => outline::move_after(&mut items, src, dst),
+        _ if items[dst].kind != OutlineKind::Volume => return Err(format!("{} 不是卷", dst_key)),
+        _ => outline::move_into(&mut items, src, dst),
+    };
+    if !moved {
+        return Err("不能把卷移动到它自己里面".to_string());
+    }
+    let pairs = outline::renumber_outline(&chapters_dir, &items);
+    let edits = links::link_rewrites(&project.root, &pairs).map_err(|e| e.to_string())?;
+    storage::apply_rename_pairs(&pairs).map_err(|e| e.to_string())?;
+    links::apply_link_edits(&edits).map_err(|e| e.to_string())?;
+    for (from, to) in &pairs {
+        let rel = |p: &Path| p.strip_prefix(&chapters_dir).unwrap_or(p).display().to_string();
+        println!("{} -> {}", rel(from), rel(to));
+    }
+    print_link_edits(&edits);
+    Ok(())
+}
+
+fn cmd_rename(project: &Project, args: &[String]) -> Result<(), String> {
+    let section = parse_section(args.first())?;
+    let (Some(key), Some(new_name)) = (args.get(1), args.get(2)) else {
+        return Err("用法: rename <section> <条目> <新名称>".to_string());
+    };
+    let new_name = new_name.trim();
+    if new_name.is_empty() {
+        return Err("新名称不能为空".to_string());
+    }
+    let from = find_item(project, section, key)?;
+    let to = storage::renamed_item_path(&project.root, section, &from, new_name);
+    if from == to {
+        return Ok(());
+    }
+    let edits = links::link_rewrites(&project.root, &[(from.clone(), to.clone())]).map_err(|e| e.to_string())?;
+    std::fs::rename(&from, &to).map_err(|e| e.to_string())?;
+    links::apply_link_edits(&edits).map_err(|e| e.to_string())?;
+    println!("{} -> {}", file_name(&from), file_name(&to));
+    print_link_edits(&edits);
+    Ok(())
+}
+
+fn print_link_edits(edits: &[links::LinkEdit]) {
+    for edit in edits {
+        println!("已更新链接: {}", file_name(&edit.path));
+    }
+}
+
+fn cmd_scenes(project: &Project, args: &[String]) -> Result<(), String> {
+    let paths = match args.first() {
+        Some(key) => vec![find_item(project, Section::Chapters, key)?],
+        None => storage::list_section_files(&project.root, Section::Chapters).map_err(|e| e.to_string())?,
+    };
+    for path in paths {
+        let text = storage::read_text(&path).map_err(|e| e.to_string())?;
+        let scenes = parse_scenes(&text);
+        if scenes.is_empty() && args.is_empty() {
+            continue;
+        }
+        println!("{}", file_name(&path));
+        for (i, scene) in scenes.iter().enumerate() {
+            let mut parts = vec![scene_label(scene, i)];
+            parts.extend(scene.pov.as_ref().map(|p| format!("视角: {}", p)));
+            parts.extend(scene.location.as_ref().map(|l| format!("地点: {}", l)));
+            parts.push(format!("{} 字", stats::count_markdown(&text[scene.range.clone()]).total()));
+            println!("  {}. {}  （第 {} 行）", i + 1, parts.join(" · "), scene.line);
+            if let Some(summary) = &scene.summary {
+                println!("     {}", summary);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn cmd_cards(project: &Project) -> Result<(), String> {
+    let cards = outline::read_cards(&project.root.join(Section::Chapters.dir_name())).map_err(|e| e.to_string())?;
+    let mut volume = None;
+    for card in &cards {
+        if card.volume != volume {
+            volume = card.volume.clone();
+            println!("【{}】", volume.as_deref().unwrap_or("未分卷"));
+        }
+        let mut tags: Vec<String> = Vec::new();
+        tags.extend(card.status.map(|s| s.label().to_string()));
+        tags.extend(card.pov.clone());
+        let tags = if tags.is_empty() { String::new() } else { format!(" [{}]", tags.join(" · ")) };
+        let stem = card.path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+        println!("  {}{} {} 字", stem, tags, card.words);
+        if !card.summary.is_empty() {
+            println!("    {}", card.summary);
+        }
+    }
+    Ok(())
+}
+
+fn cmd_delete(project: &Project, args: &[String]) -> Result<(), String> {
+    let section = parse_section(args.first())?;
+    let Some(key) = args.get(1) else {
+        return Err("用法: delete <section> <条目>".to_string());
+    };
+    let path = find_item(project, section, key)?;
+    let contents = storage::read_text(&path).map_err(|e| e.to_string())?;
+    storage::backup_text(&project.root, &path, &contents).map_err(|e| e.to_string())?;
+    std::fs::remove_file(&path).map_err(|e| e.to_string())?;
+    println!("已删除: {}", file_name(&path));
+    Ok(())
+}
+
+fn cmd_migrate_characters(project: &Project) -> Result<(), String> {
+    let migrated = storage::migrate_characters(&project.root).map_err(|e| e.to_string())?;
+    for path in &migrated {
+        println!("已迁移: {}", file_name(path));
+    }
+    println!("共迁移 {} 个角色文件", migrated.len());
+    Ok(())
+}
+
+fn cmd_export(project: &Project, args: &[String]) -> Result<(), String> {
+    let dst_root = args.first().map(PathBuf::from).unwrap_or_else(|| project.root.join("exports"));
+    let name = storage::sanitize_filename(&project.meta.name);
+    let base = if name.is_empty() { "project".to_string() } else { name };
+    let dst = dst_root.join(format!("{}-export-{}", base, novel_outline_tool::domain::now_unix()));
+    storage::copy_project(&project.root, &dst).map_err(|e| e.to_string())?;
+    println!("已导出: {}", dst.display());
+    Ok(())
+}
+
+fn cmd_compile(project: &Project, args: &[String]) -> Result<(), String> {
+    let mut options = CompileOptions::default();
+    let mut dst: Option<PathBuf> = None;
+    for arg in args {
+        match arg.as_str() {
+            "--no-headings" => options.inject_headings = false,
+            "--no-toc" => options.table_of_contents = false,
+            other if other.starts_with("--") => return Err(format!("未知选项: {}", other)),
+            other => dst = Some(PathBuf::from(other)),
+        }
+    }
+    let path = export::export_markdown(project, dst.as_deref(), &options).map_err(|e| e.to_string())?;
+    println!("已导出: {}", path.display());
+    Ok(())
+}
+
+fn cmd_epub(project: &Project, args: &[String]) -> Result<(), String> {
+    let dst = args.first().map(PathBuf::from);
+    let path = export::epub::export_epub(project, dst.as_deref()).map_err(|e| e.to_string())?;
+    println!("已导出: {}", path.display());
+    Ok(())
+}
+
+fn cmd_graph(project: &Project, args: &[String]) -> Result<(), String> {
+    let dst = args.first().map(PathBuf::from);
+    let (dot, svg) = export::graph::export_relation_graph(project, dst.as_deref()).map_err(|e| e.to_string())?;
+    println!("已导出: {}", dot.display());
+    println!("已导出: {}", svg.display());
+    Ok(())
+}
+
+fn cmd_threads(project: &Project, args: &[String]) -> Result<(), String> {
+    let mut dormant = None;
+    let mut dst = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--dormant" => {
+                let value = iter.next().ok_or("--dormant 需要章数")?;
+                dormant = Some(value.parse::<usize>().map_err(|_| format!("章数无效: {}", value))?);
+            }
+            _ => dst = Some(PathBuf::from(arg)),
+        }
+    }
+    let (report, md, html) = export::threads::export_thread_report(project, dst.as_deref(), dormant).map_err(|e| e.to_string())?;
+    for line in report.dormant_lines() {
+        println!("沉寂: {}", line);
+    }
+    for line in report.unresolved_lines() {
+        println!("未收束: {}", line);
+    }
+    println!("已导出: {}", md.display());
+    println!("已导出: {}", html.display());
+    Ok(())
+}
+
+fn cmd_timeline(project: &Project, args: &[String]) -> Result<(), String> {
+    match args.first().map(String::as_str) {
+        None => {
+            let docs = storage::load_timelines(&project.root).map_err(|e| e.to_string())?;
+            let calendars = Calendars::new(&project.meta.calendars);
+            let mut events: Vec<(Option<i64>, &Path, &TimelineRow)> = docs
+                .iter()
+                .flat_map(|(path, doc)| doc.rows().map(move |row| (row.date().and_then(|d| calendars.ordinal(&d)), path.as_path(), row)))
+                .filter(|(_, _, row)| !row.time().is_empty() || !row.event().is_empty())
+                .collect();
+            events.sort_by_key(|(ord, _, _)| (ord.is_none(), *ord));
+            let mut previous: Option<i64> = None;
+            for (ord, path, row) in events {
+                // Time elapsed since the previous event, in the calendar of this row.
+                let elapsed = match (previous, ord) {
+                    (Some(prev), Some(ord)) => format!("+{}", calendars.format_duration(row.time(), ord - prev)),
+                    _ => String::new(),
+                };
+                previous = ord.or(previous);
+                println!("{}\t{}\t{}\t{}\t{}", row.time(), elapsed, row.event(), row.links_text(), file_name(path));
+            }
+            Ok(())
+        }
+        Some("check") => {
+            let issues = storage::check_timelines(project).map_err(|e| e.to_string())?;
+            for issue in &issues {
+                println!("{}:{}: {} {}", file_name(&issue.file), issue.line, issue.kind.label(), issue.message);
+            }
+            if issues.is_empty() {
+                println!("时间线没有发现问题");
+                Ok(())
+            } else {
+                Err(format!("时间线发现 {} 个问题", issues.len()))
+            }
+        }
+        Some("sort") => {
+            let changed = storage::sort_timelines(project).map_err(|e| e.to_string())?;
+            for path in &changed {
+                println!("已重排: {}", file_name(path));
+            }
+            println!("共重排 {} 个时间线文件", changed.len());
+            Ok(())
+        }
+        Some(other) => Err(format!("未知 timeline 子命令: {}", other)),
+    }
+}
+
+fn link_text(link: &links::IndexedLink) -> String {
+    match &link.link.anchor {
+        Some(anchor) => format!("[[{}#{}]]", link.link.target, anchor),
+        None => format!("[[{}]]", link.link.target),
+    }
+}
+
+fn cmd_links(project: &Project, args: &[String]) -> Result<(), String> {
+    let index = links::LinkIndex::build(&project.root).map_err(|e| e.to_string())?;
+    if args.first().map(String::as_str) == Some("broken") {
+        let broken: Vec<_> = index.broken().collect();
+        for link in &broken {
+            println!("{}:{}: {}", file_name(&link.source), link.link.line, link_text(link));
+        }
+        if broken.is_empty() {
+            println!("没有失效的链接");
+            return Ok(());
+        }
+        return Err(format!("发现 {} 个失效链接", broken.len()));
+    }
+
+    let section = parse_section(args.first())?;
+    let Some(key) = args.get(1) else {
+        return Err("用法: links <section> <条目> 或 links broken".to_string());
+    };
+    let path = find_item(project, section, key)?;
+    println!("链接:");
+    for link in index.outgoing(&path) {
+        let target = link.target.as_deref().map(file_name).unwrap_or("（不存在）");
+        println!("  {}:{} {} -> {}", file_name(&path), link.link.line, link_text(link), target);
+    }
+    println!("反向链接:");
+    for link in index.backlinks(&path) {
+        println!("  {}:{} {}", file_name(&link.source), link.link.line, link_text(link));
+    }
+    Ok(())
+}
+
+fn cmd_search(project: &Project, args: &[String]) -> Result<(), String> {
+    let mut options = SearchOptions::default();
+    let mut query: Option<&str> = None;
+    for arg in args {
+        match arg.as_str() {
+            "--regex" => options.mode = SearchMode::Regex,
+            "--word" => options.mode = SearchMode::WholeWord,
+            "--case" => options.case_sensitive = true,
+            other if other.starts_with("--") => return Err(format!("未知选项: {}", other)),
+            other => query = Some(other),
+        }
+    }
+    let query = query.filter(|q| !q.is_empty()).ok_or_else(|| "用法: search [--regex|--word] [--case] <关键词>".to_string())?;
+    let results = search::search_project(&project.root, query, &options)?;
+    let mut total = 0;
+    for file in &results {
+        let rel = file.path.strip_prefix(&project.root).unwrap_or(&file.path);
+        for hit in &file.hits {
+            println!("{}:{}: {}", rel.display(), hit.line, hit.snippet);
+        }
+        total += file.hits.len();
+    }
+    println!("共 {} 处，{} 个文件", total, results.len());
+    Ok(())
+}
+
+fn cmd_history(project: &Project, args: &[String]) -> Result<(), String> {
+    const USAGE: &str = "用法: history <section> <条目> [版本 [--restore]]";
+    let section = parse_section(args.first())?;
+    let key = args.get(1).ok_or_else(|| USAGE.to_string())?;
+    let path = find_item(project, section, key)?;
+    let current = storage::read_text(&path).map_err(|e| e.to_string())?;
+    let versions = versions::list_versions(&project.root, &path).map_err(|e| e.to_string())?;
+
+    let Some(number) = args.get(2) else {
+        let current_count = stats::count_document(&current).total() as i64;
+        for (i, version) in versions.iter().enumerate() {
+            let text = version.read().map_err(|e| e.to_string())?;
+            let count = stats::count_document(&text).total() as i64;
+            let note = version.note().map(|n| format!("  Git: {}", n)).unwrap_or_default();
+            println!(
+                "{:>3}  {}  {} 字（比当前 {:+}）{}",
+                i + 1,
+                storage::format_local_time(version.saved_unix),
+                count,
+                count - current_count,
+                note
+            );
+        }
+        println!("共 {} 个历史版本", versions.len());
+        return Ok(());
+    };
+    let version = number
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_sub(1))
+        .and_then(|i| versions.get(i))
+        .ok_or_else(|| format!("没有第 {} 个版本（共 {} 个）", number, versions.len()))?;
+    let old = version.read().map_err(|e| e.to_string())?;
+    match args.get(3).map(String::as_str) {
+        None => {
+            let diff = format_diff(&diff_lines(&old, &current), 3);
+            if diff.is_empty() {
+                println!("与当前内容相同");
+            } else {
+                print!("{}", diff);
+            }
+        }
+        Some("--restore") => {
+            storage::backup_text(&project.root, &path, &current).map_err(|e| e.to_string())?;
+            storage::write_text_atomic(&path, &old).map_err(|e| e.to_string())?;
+            println!("已恢复 {} 的版本: {}", storage::format_local_time(version.saved_unix), file_name(&path));
+        }
+        Some(other) => return Err(format!("未知选项: {}", other)),
+    }
+    Ok(())
+}
+
+fn cmd_backup(project: &Project, args: &[String]) -> Result<(), String> {
+    match args.first().map(String::as_str) {
+        None => match snapshots::snapshot_project(&project.root, &[]).map_err(|e| e.to_string())? {
+            Some(snapshot) => println!("已创建快照，共 {} 个文件", snapshot.manifest.files.len()),
+            None => println!("自上次快照以来没有变化"),
+        },
+        Some("list") => {
+            let all = snapshots::list_snapshots(&project.root).map_err(|e| e.to_string())?;
+            for snapshot in &all {
+                println!(
+                    "{}  {} 个文件",
+                    storage::format_local_time(snapshot.manifest.created_unix),
+                    snapshot.manifest.files.len()
+                );
+            }
+            println!("共 {} 个快照", all.len());
+        }
+        Some("gc") => {
+            let policy = snapshots::RetentionPolicy::default();
+            let report = snapshots::gc(&project.root, &policy, novel_outline_tool::domain::now_unix())
+                .map_err(|e| e.to_string())?;
+            println!(
+                "保留 {} 个快照，删除 {} 个快照、{} 个不再引用的文件",
+                report.kept_snapshots, report.removed_snapshots, report.removed_objects
+            );
+        }
+        Some(other) => return Err(format!("未知选项: {}\n用法: backup [list|gc]", other)),
+    }
+    Ok(())
+}
+
+fn cmd_recover(project: &Project, args: &[String]) -> Result<(), String> {
+    let entries = recovery::pending_journals(&project.root).map_err(|e| e.to_string())?;
+    let Some(number) = args.first() else {
+        for (i, entry) in entries.iter().enumerate() {
+            let rel = entry.path.strip_prefix(&project.root).unwrap_or(&entry.path);
+            println!(
+                "{:>3}  {}  {}  {} 字（文件中 {} 字）",
+                i + 1,
+                storage::format_local_time(entry.saved_unix),
+                rel.display(),
+                stats::count_document(&entry.text).total(),
+                stats::count_document(&entry.disk_text()).total()
+            );
+        }
+        println!("共 {} 个未保存的内容", entries.len());
+        return Ok(());
+    };
+    let entry = number
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_sub(1))
+        .and_then(|i| entries.get(i))
+        .ok_or_else(|| format!("没有第 {} 个未保存的内容（共 {} 个）", number, entries.len()))?;
+    match args.get(1).map(String::as_str) {
+        None => print!("{}", format_diff(&diff_lines(&entry.disk_text(), &entry.text), 3)),
+        Some("--restore") => {
+            entry.restore(&project.root).map_err(|e| e.to_string())?;
+            println!("已恢复: {}", file_name(&entry.path));
+        }
+        Some("--discard") => {
+            entry.discard().map_err(|e| e.to_string())?;
+            println!("已丢弃: {}", file_name(&entry.path));
+        }
+        Some(other) => return Err(format!("未知选项: {}", other)),
+    }
+    Ok(())
+}
+
+fn cmd_git(mut project: Project, args: &[String]) -> Result<(), String> {
+    const USAGE: &str = "用法: git init [--interval <分钟>|none] [--no-backup|--backup] | git commit | git log <section> <条目>";
+    let open_repo = |project: &Project| -> Result<GitRepo, String> {
+        if project.meta.git.is_none() {
+            return Err("项目未启用 Git 版本管理，请先运行 git init".to_string());
+        }
+        GitRepo::open(&project.root)
+            .map_err(|e| format!("无法运行 git: {}", e))?
+            .ok_or_else(|| "项目不在 Git 仓库中，请运行 git init".to_string())
+    };
+    match args.first().map(String::as_str) {
+        Some("init") => {
+            let mut settings = project.meta.git.clone().unwrap_or_default();
+            let mut i = 1;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--interval" => {
+                        settings.commit_interval_minutes = match args.get(i + 1).map(String::as_str) {
+                            Some("none") => None,
+                            Some(v) => Some(v.parse().map_err(|_| "--interval 需要分钟数或 none".to_string())?),
+                            None => return Err("--interval 需要分钟数或 none".to_string()),
+                        };
+                        i += 1;
+                    }
+                    "--no-backup" => settings.disable_backup = true,
+                    "--backup" => settings.disable_backup = false,
+                    other => return Err(format!("未知选项: {}\n{}", other, USAGE)),
+                }
+                i += 1;
+            }
+            let repo = GitRepo::init(&project.root).map_err(|e| format!("无法运行 git: {}", e))?;
+            if project.meta.git.as_ref() != Some(&settings) {
+                ProjectStore::update_project_meta(&mut project, |meta| meta.git = Some(settings))?;
+            }
+            println!("已启用 Git 版本管理: {}", repo.top().display());
+            print_commit(&repo)
+        }
+        Some("commit") => print_commit(&open_repo(&project)?),
+        Some("log") => {
+            let repo = open_repo(&project)?;
+            let section = parse_section(args.get(1))?;
+            let key = args.get(2).ok_or_else(|| USAGE.to_string())?;
+            let path = find_item(&project, section, key)?;
+            let log = repo.file_history(&path).map_err(|e| e.to_string())?;
+            for version in &log {
+                println!(
+                    "{}  {}  {}",
+                    &version.commit[..version.commit.len().min(8)],
+                    storage::format_local_time(version.time_unix),
+                    version.subject
+                );
+            }
+            println!("共 {} 次提交", log.len());
+            Ok(())
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn print_commit(repo: &GitRepo) -> Result<(), String> {
+    match repo.commit_changes().map_err(|e| e.to_string())? {
+        Some(summary) => println!("已提交 {}: {}", summary.commit, summary.subject),
+        None => println!("没有需要提交的改动"),
+    }
+    Ok(())
+}
+
+fn cmd_watch(project: &Project) -> Result<(), String> {
+    let mut watcher = ProjectWatcher::new(&project.root).map_err(|e| e.to_string())?;
+    println!("正在监视 {}（Ctrl+C 结束）", project.root.display());
+    loop {
+        for path in watcher.poll().map_err(|e| e.to_string())? {
+            let state = if path.exists() { "变更" } else { "删除" };
+            println!("{}  {}", state, path.strip_prefix(&project.root).unwrap_or(&path).display());
+        }
+        std::thread::sleep(std::time::Duration::from_millis(200));
+    }
+}
+
+fn count_text(count: &TextCount) -> String {
+    format!("{} 字（汉字 {}，英文词 {}）", count.total(), count.cjk_chars, count.latin_words)
+}
+
+fn cmd_stats(project: &Project, args: &[String]) -> Result<(), String> {
+    let detail = match args.first() {
+        Some(_) => parse_section(args.first())?,
+        None => Section::Chapters,
+    };
+    let report = stats::project_stats(&project.root).map_err(|e| e.to_string())?;
+    if let Some(section) = report.section(detail) {
+        for file in &section.files {
+            println!("{}  {}", file_name(&file.path), count_text(&file.count));
+        }
+        println!();
+    }
+    for section in &report.sections {
+        println!("{}（{} 个）  {}", section.section.label(), section.files.len(), count_text(&section.total));
+    }
+    println!("全部  {}", count_text(&report.total));
+    Ok(())
+}
+
+fn parse_goal_value(flag: &str, value: Option<&String>) -> Result<Option<u64>, String> {
+    match value.map(String::as_str) {
+        Some("none") => Ok(None),
+        Some(v) => v.parse::<u64>().map(Some).map_err(|_| format!("{} 需要一个字数或 none", flag)),
+        None => Err(format!("{} 缺少参数", flag)),
+    }
+}
+
+fn cmd_goal(mut project: Project, args: &[String]) -> Result<(), String> {
+    if args.first().map(String::as_str) == Some("set") {
+        let mut goals = (project.meta.daily_target, project.meta.word_target, project.meta.deadline.clone());
+        let mut i = 1;
+        while i < args.len() {
+            let value = args.get(i + 1);
+            match args[i].as_str() {
+                "--daily" => goals.0 = parse_goal_value("--daily", value)?,
+                "--total" => goals.1 = parse_goal_value("--total", value)?,
+                "--deadline" => {
+                    goals.2 = match value.map(String::as_str) {
+                        Some("none") => None,
+                        Some(day) if history::day_ordinal(day).is_some() => Some(day.to_string()),
+                        _ => return Err("--deadline 需要 YYYY-MM-DD 格式的日期或 none".to_string()),
+                    }
+                }
+                other => return Err(format!("未知选项: {}", other)),
+            }
+            i += 2;
+        }
+        ProjectStore::update_project_meta(&mut project, |meta| (meta.daily_target, meta.word_target, meta.deadline) = goals)?;
+    } else if let Some(other) = args.first() {
+        return Err(format!("未知参数: {}（可选 set）", other));
+    }
+
+    let report = stats::project_stats(&project.root).map_err(|e| e.to_string())?;
+    let total = report.section(Section::Chapters).map_or(0, |s| s.total.total()) as u64;
+    let history = WritingHistory::load(&project.root).map_err(|e| e.to_string())?;
+    let progress = history::goal_progress(&project.meta, &history, total, &history::today());
+    for line in progress.lines() {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// Parses `project,chapters,...`; `None` stands for `project.md`.
+fn parse_scope_list(arg: Option<&String>) -> Result<Vec<Option<Section>>, String> {
+    let list = arg.ok_or_else(|| "缺少范围参数".to_string())?;
+    list.split(',')
+        .filter(|s| !s.trim().is_empty())
+        .map(|name| match name.trim() {
+            "project" => Ok(None),
+            other => Section::from_dir_name(other)
+                .map(Some)
+                .ok_or_else(|| format!("未知范围: {}（可选 project/chapters/characters/world/timeline）", other)),
+        })
+        .collect()
+}
+
+fn cmd_replace(project: &Project, args: &[String]) -> Result<(), String> {
+    const USAGE: &str = "用法: replace [--regex|--word] [--case] [--only <范围>] [--skip <范围>] [--yes] <查找> <替换为>";
+    let mut options = SearchOptions::default();
+    let mut scope = ReplaceScope::default();
+    let mut confirmed = false;
+    let mut positional: Vec<&str> = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--regex" => options.mode = SearchMode::Regex,
+            "--word" => options.mode = SearchMode::WholeWord,
+            "--case" => options.case_sensitive = true,
+            "--yes" => confirmed = true,
+            flag @ ("--only" | "--skip") => {
+                i += 1;
+                let list = parse_scope_list(args.get(i))?;
+                if flag == "--only" {
+                    scope.project_md = list.contains(&None);
+                    scope.sections = list.into_iter().flatten().collect();
+                } else {
+                    scope.project_md &= !list.contains(&None);
+                    scope.sections.retain(|s| !list.contains(&Some(*s)));
+                }
+            }
+            other if other.starts_with("--") => return Err(format!("未知选项: {}", other)),
+            other => positional.push(other),
+        }
+        i += 1;
+    }
+    let [query, replacement] = positional[..] else {
+        return Err(USAGE.to_string());
+    };
+    if query.is_empty() {
+        return Err(USAGE.to_string());
+    }
+
+    let plan = replace::plan_replace(&project.root, query, replacement, &options, &scope)?;
+    let mut total = 0;
+    for file in &plan {
+        let rel = file.path.strip_prefix(&project.root).unwrap_or(&file.path);
+        for preview in &file.previews {
+            println!("{}:{}: {} → {}", rel.display(), preview.line, preview.before, preview.after);
+        }
+        total += file.previews.len();
+    }
+    if !confirmed {
+        println!("共 {} 处，{} 个文件（预览，加 --yes 执行替换）", total, plan.len());
+        return Ok(());
+    }
+    for file in &plan {
+        storage::backup_text(&project.root, &file.path, &file.before).map_err(|e| e.to_string())?;
+    }
+    let skipped = replace::apply_replace(&plan, false).map_err(|e| e.to_string())?;
+    for file in plan.iter().filter(|f| skipped.contains(&f.path)) {
+        let rel = file.path.strip_prefix(&project.root).unwrap_or(&file.path);
+        eprintln!("{} 在预览之后被修改过，未替换", rel.display());
+        total -= file.previews.len();
+    }
+    println!("已替换 {} 处，{} 个文件", total, plan.len() - skipped.len());
+    Ok(())
+}
@@ -0,0 +1,207 @@
+//! In-world calendars declared in `project.md`, used to put timeline dates on one day scale.
+
+use super::timeline::{parse_day, DateParts};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MonthDef {
+    pub name: String,
+    pub days: u32,
+}
+
+/// A named era counting its own years from `start_year` of the calendar, e.g. `永安` starting in 天元历 350 年.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EraDef {
+    pub name: String,
+    pub start_year: i64,
+}
+
+/// A calendar such as `天元历` with 13 months. Dates are counted without a year zero:
+/// year `-1` is the year right before year `1`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CalendarDef {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    pub months: Vec<MonthDef>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub eras: Vec<EraDef>,
+    /// Day number of 1 年 1 月 1 日 on the shared scale (days since 1970-01-01 in the Gregorian
+    /// calendar), so that dates of several calendars can be sorted together.
+    #[serde(default)]
+    pub epoch_offset: i64,
+    /// Used for dates written without an era or calendar name.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub default: bool,
+}
+
+impl CalendarDef {
+    pub fn year_days(&self) -> i64 {
+        self.months.iter().map(|m| m.days as i64).sum()
+    }
+
+    /// Whether `era` (the text before the year) names this calendar; returns the first year of the matched era.
+    fn match_era(&self, era: &str) -> Option<i64> {
+        let era = era.trim().trim_end_matches('第').trim();
+        if era == self.name || self.aliases.iter().any(|a| a == era) {
+            return Some(1);
+        }
+        self.eras
+            .iter()
+            .find(|e| era == e.name || era == format!("{}{}", self.name, e.name))
+            .map(|e| e.start_year)
+    }
+
+    /// Day number on the shared scale, or `None` when the month or day does not exist in this calendar.
+    pub fn ordinal(&self, year: i64, month: u32, day: u32) -> Option<i64> {
+        let year_days = self.year_days();
+        if month == 0 || month as usize > self.months.len() || year == 0 || year_days == 0 {
+            return None;
+        }
+        let month_def = &self.months[month as usize - 1];
+        if day == 0 || day > month_def.days {
+            return None;
+        }
+        let years_before = year_index(year);
+        let days_before_month: i64 = self.months[..month as usize - 1].iter().map(|m| m.days as i64).sum();
+        Some(self.epoch_offset + years_before * year_days + days_before_month + day as i64 - 1)
+    }
+
+    /// Month and day from text such as `霜月初五` or `三月十二日`.
+    fn month_day(&self, date: &DateParts) -> Option<(u32, u32)> {
+        if date.rest.is_empty() {
+            return Some((date.month.unwrap_or(1), date.day.unwrap_or(1)));
+        }
+        let (index, name) = self
+            .months
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| date.rest.starts_with(m.name.as_str()))
+            .max_by_key(|(_, m)| m.name.len())?;
+        let after = &date.rest[name.name.len()..];
+        let day = if after.trim().is_empty() { 1 } else { parse_day(after)? };
+        Some((index as u32 + 1, day))
+    }
+
+    /// Splits a number of days into whole years and remaining days of this calendar.
+    pub fn split_days(&self, days: i64) -> (i64, i64) {
+        let year_days = self.year_days().max(1);
+        (days / year_days, days % year_days)
+    }
+}
+
+/// The calendars of a project; dates matching none of them fall back to the Gregorian calendar.
+#[derive(Debug, Clone, Copy)]
+pub struct Calendars<'a> {
+    pub defs: &'a [CalendarDef],
+}
+
+impl<'a> Calendars<'a> {
+    pub fn new(defs: &'a [CalendarDef]) -> Self {
+        Self { defs }
+    }
+
+    /// The calendar a date is written in: named by its era, else the default calendar. In
+    /// `天元历前三年` the `前` only gives the year its sign, so the era is still `天元历`.
+    pub fn calendar_for(&self, date: &DateParts) -> Option<(&'a CalendarDef, i64)> {
+        let era = date.era.trim();
+        let era = era.strip_suffix('前').unwrap_or(era).trim_end();
+        if !era.is_empty() {
+            if let Some(found) = self.defs.iter().find_map(|c| c.match_era(era).map(|start| (c, start))) {
+                return Some(found);
+            }
+            return None;
+        }
+        self.defs.iter().find(|c| c.default).map(|c| (c, 1))
+    }
+
+    pub fn ordinal(&self, date: &DateParts) -> Option<i64> {
+        match self.calendar_for(date) {
+            Some((calendar, start_year)) => {
+                let (month, day) = calendar.month_day(date)?;
+                // Count in year indexes (year 1 is index 0) so eras may start before the epoch.
+                let index = year_index(start_year) + year_index(date.year);
+                calendar.ordinal(if index >= 0 { index + 1 } else { index }, month, day)
+            }
+            None if date.rest.is_empty() => Some(date.gregorian_ordinal()),
+            None => None,
+        }
+    }
+
+    pub fn parse(&self, text: &str) -> Option<i64> {
+        super::timeline::parse_date(text).and_then(|d| self.ordinal(&d))
+    }
+
+    /// Days from `from` to `to`, negative when `to` is earlier.
+    pub fn days_between(&self, from: &str, to: &str) -> Option<i64> {
+        Some(self.parse(to)? - self.parse(from)?)
+    }
+
+    /// `3年12天` in the calendar of `date`, or plain days for Gregorian dates.
+    pub fn format_duration(&self, date: &str, days: i64) -> String {
+        let calendar = super::timeline::parse_date(date).and_then(|d| self.calendar_for(&d).map(|(c, _)| c));
+        let sign = if days < 0 { "-" } else { "" };
+        let (years, rest) = match calendar {
+            Some(c) => c.split_days(days.abs()),
+            None => (days.abs() / 365, days.abs() % 365),
+        };
+        match (years, rest) {
+            (0, d) => format!("{}{}天", sign, d),
+            (y, 0) => format!("{}{}年", sign, y),
+            (y, d) => format!("{}{}年{}天", sign, y, d),
+        }
+    }
+}
+
+fn year_index(year: i64) -> i64 {
+    if year > 0 {
+        year - 1
+    } else {
+        year
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::timeline::days_from_civil;
+
+    fn tianyuan() -> CalendarDef {
+        CalendarDef {
+            name: "天元历".to_string(),
+            aliases: vec!["天元".to_string()],
+            months: (1..=13).map(|i| MonthDef { name: format!("{}号月", i), days: 28 }).collect(),
+            eras: vec![EraDef {
+                name: "永安".to_string(),
+                start_year: 350,
+            }],
+            epoch_offset: 0,
+            default: true,
+        }
+    }
+
+    #[test]
+    fn years_before_the_epoch() {
+        let defs = [tianyuan()];
+        let calendars = Calendars::new(&defs);
+        let year = 13 * 28;
+        assert_eq!(calendars.parse("天元历元年"), Some(0));
+        assert_eq!(calendars.parse("天元历前一年"), Some(-year));
+        assert_eq!(calendars.parse("天元历前三年"), Some(-3 * year));
+        assert_eq!(calendars.parse("天元前三年二月初五"), Some(-3 * year + 28 + 4));
+        assert_eq!(calendars.parse("前三年"), Some(-3 * year));
+        // 永安 starts in 天元历 350 年, so 永安前一年 is 天元历 349 年.
+        assert_eq!(calendars.parse("永安前一年"), calendars.parse("天元历三百四十九年"));
+    }
+
+    #[test]
+    fn eras_months_and_gregorian_fallback() {
+        let defs = [tianyuan()];
+        let calendars = Calendars::new(&defs);
+        assert_eq!(calendars.parse("永安三年"), calendars.parse("天元历三百五十二年"));
+        assert_eq!(calendars.parse("天元历二年13号月初一"), Some(2 * 13 * 28 - 28));
+        assert_eq!(calendars.parse("天元历二年十四月"), None);
+        assert_eq!(calendars.parse("公元前1年"), Some(days_from_civil(-1, 1, 1)));
+        assert_eq!(calendars.format_duration("天元历元年", 13 * 28 + 3), "1年3天");
+    }
+}
@@ -0,0 +1,347 @@
+use super::front_matter::{has_invalid_front_matter, parse_front_matter, render_front_matter};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RelationKind {
+    Family,
+    Friend,
+    Ally,
+    Rival,
+    Enemy,
+    Lover,
+    Mentor,
+    Student,
+    Superior,
+    Subordinate,
+    #[serde(other)]
+    Other,
+}
+
+impl RelationKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            RelationKind::Family => "亲属",
+            RelationKind::Friend => "朋友",
+            RelationKind::Ally => "盟友",
+            RelationKind::Rival => "对手",
+            RelationKind::Enemy => "敌人",
+            RelationKind::Lover => "恋人",
+            RelationKind::Mentor => "师父",
+            RelationKind::Student => "徒弟",
+            RelationKind::Superior => "上级",
+            RelationKind::Subordinate => "下属",
+            RelationKind::Other => "其他",
+        }
+    }
+
+    /// Recognizes the Chinese words writers use for a relation, e.g. `师父` or `宿敌`.
+    pub fn from_word(word: &str) -> Option<RelationKind> {
+        const WORDS: &[(&[&str], RelationKind)] = &[
+            (&["师父", "师傅", "师尊", "老师", "导师", "mentor"], RelationKind::Mentor),
+            (&["徒弟", "弟子", "学生", "徒儿", "student"], RelationKind::Student),
+            (
+                &["父亲", "母亲", "父", "母", "兄长", "哥哥", "弟弟", "姐姐", "妹妹", "儿子", "女儿", "亲属", "家人", "family"],
+                RelationKind::Family,
+            ),
+            (&["恋人", "爱人", "妻子", "丈夫", "未婚妻", "未婚夫", "lover"], RelationKind::Lover),
+            (&["朋友", "好友", "挚友", "知己", "friend"], RelationKind::Friend),
+            (&["盟友", "同伴", "伙伴", "ally"], RelationKind::Ally),
+            (&["对手", "宿敌", "竞争者", "rival"], RelationKind::Rival),
+            (&["敌人", "仇人", "仇敌", "enemy"], RelationKind::Enemy),
+            (&["上级", "上司", "主公", "主人", "首领", "superior"], RelationKind::Superior),
+            (&["下属", "部下", "手下", "属下", "subordinate"], RelationKind::Subordinate),
+        ];
+        let word = word.trim();
+        WORDS
+            .iter()
+            .find(|(words, _)| words.iter().any(|w| w.eq_ignore_ascii_case(word)))
+            .map(|(_, kind)| *kind)
+    }
+}
+
+/// A typed edge from the character to `target` (another character's name).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Relationship {
+    pub target: String,
+    #[serde(default = "default_relation_kind")]
+    pub kind: RelationKind,
+    /// The writer's own wording such as `师父`, kept for display.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+impl Relationship {
+    pub fn display_label(&self) -> &str {
+        self.label.as_deref().filter(|s| !s.trim().is_empty()).unwrap_or(self.kind.label())
+    }
+}
+
+fn default_relation_kind() -> RelationKind {
+    RelationKind::Other
+}
+
+/// Front matter of a character file. Unknown keys are kept in `extra`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CharacterMeta {
+    #[serde(default = "default_format_version")]
+    pub format_version: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    /// As written, e.g. `17`, `十七` or `约二十`.
+    #[serde(default, deserialize_with = "number_or_text", skip_serializing_if = "Option::is_none")]
+    pub age: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub faction: Option<String>,
+    #[serde(default, alias = "relations", skip_serializing_if = "Vec::is_empty")]
+    pub relationships: Vec<Relationship>,
+    /// Stem of the chapter file the character first appears in, e.g. `0003-决战`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_appearance: Option<String>,
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_yaml::Value>,
+}
+
+impl Default for CharacterMeta {
+    fn default() -> Self {
+        Self {
+            format_version: default_format_version(),
+            aliases: Vec::new(),
+            role: None,
+            age: None,
+            faction: None,
+            relationships: Vec::new(),
+            first_appearance: None,
+            extra: BTreeMap::new(),
+        }
+    }
+}
+
+fn default_format_version() -> u32 {
+    1
+}
+
+/// Reads a plain YAML number such as `age: 17` as its text.
+fn number_or_text<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    Ok(match serde_yaml::Value::deserialize(deserializer)? {
+        serde_yaml::Value::Number(n) => Some(n.to_string()),
+        serde_yaml::Value::String(s) => Some(s).filter(|s| !s.trim().is_empty()),
+        _ => None,
+    })
+}
+
+/// Prose bullets seeded by older templates; each becomes a `## 标签` section of the body.
+const LEGACY_PROSE: &[&str] = &["外貌特征", "性格", "目标与动机"];
+const LEGACY_ROLE: &str = "角色定位";
+const LEGACY_RELATIONS: &str = "关系";
+
+pub fn parse_character(content: &str) -> (CharacterMeta, &str) {
+    let (meta, body) = parse_front_matter::<CharacterMeta>(content);
+    (meta.unwrap_or_default(), body)
+}
+
+/// Display name of a character file: the `# 角色：名字` heading, else the file stem.
+pub fn character_name(path: &Path, body: &str) -> String {
+    for line in body.lines() {
+        let line = line.trim();
+        if let Some(title) = line.strip_prefix("# ") {
+            let title = title.trim();
+            let name = title
+                .strip_prefix("角色：")
+                .or_else(|| title.strip_prefix("角色:"))
+                .unwrap_or(title)
+                .trim();
+            if !name.is_empty() {
+                return name.to_string();
+            }
+        }
+    }
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    stem.strip_prefix("角色-").unwrap_or(stem).to_string()
+}
+
+pub fn render_character(meta: &CharacterMeta, body: &str) -> String {
+    render_front_matter(meta, body)
+}
+
+/// New character file: typed front matter plus prose sections.
+pub fn new_character_md(name: &str) -> String {
+    format!(
+        "---\nformat_version: 1\naliases: []\nrole:\nage:\nfaction:\nfirst_appearance:\nrelationships: []\n---\n\n# 角色：{}\n\n## 外貌特征\n\n## 性格\n\n## 目标与动机\n\n",
+        name
+    )
+}
+
+/// Value of a `- 标签：值` bullet, accepting both full-width and ASCII colons.
+pub fn legacy_bullet<'a>(line: &'a str, label: &str) -> Option<&'a str> {
+    let rest = line.trim().strip_prefix("- ")?.trim_start().strip_prefix(label)?;
+    let rest = rest.strip_prefix('：').or_else(|| rest.strip_prefix(':'))?;
+    Some(rest.trim())
+}
+
+/// Parses a free-form relation list such as `师父：张三；对手-李四、王五（挚友）`.
+pub fn parse_relation_list(text: &str) -> Vec<Relationship> {
+    let mut out = Vec::new();
+    for entry in text.split(['；', ';', '，', ',', '、']) {
+        let entry = entry.trim();
+        if entry.is_empty() {
+            continue;
+        }
+        if let Some(rel) = parse_relation_entry(entry) {
+            out.push(rel);
+        }
+    }
+    out
+}
+
+fn parse_relation_entry(entry: &str) -> Option<Relationship> {
+    let make = |target: &str, word: &str| {
+        let target = target.trim().trim_start_matches("[[").trim_end_matches("]]").trim();
+        if target.is_empty() {
+            return None;
+        }
+        let word = word.trim();
+        Some(Relationship {
+            target: target.to_string(),
+            kind: RelationKind::from_word(word).unwrap_or(RelationKind::Other),
+            label: if word.is_empty() { None } else { Some(word.to_string()) },
+            note: None,
+        })
+    };
+
+    // 名字（关系） / 名字(关系)
+    for (open, close) in [('（', '）'), ('(', ')')] {
+        if let Some(start) = entry.find(open) {
+            let inner = entry[start + open.len_utf8()..].trim_end_matches(close);
+            return make(&entry[..start], inner);
+        }
+    }
+    // 关系：名字 / 关系-名字 / 名字-关系
+    for sep in ['：', ':', '-', '—'] {
+        if let Some((a, b)) = entry.split_once(sep) {
+            if RelationKind::from_word(b).is_some() && RelationKind::from_word(a).is_none() {
+                return make(a, b);
+            }
+            return make(b, a);
+        }
+    }
+    make(entry, "")
+}
+
+/// Converts an old bullet-style character file (`- 角色定位：…`, `- 关系：…`) into typed front matter.
+/// Returns `None` when the file has no legacy bullets left, or when its front matter does not
+/// parse: rewriting it would lose the keys in it.
+pub fn migrate_legacy_character(content: &str) -> Option<String> {
+    if has_invalid_front_matter::<CharacterMeta>(content) {
+        return None;
+    }
+    let (mut meta, body) = parse_character(content);
+    let mut found = false;
+    let mut kept: Vec<&str> = Vec::new();
+    let mut prose: Vec<(&str, &str)> = Vec::new();
+
+    for line in body.lines() {
+        if let Some(value) = legacy_bullet(line, LEGACY_ROLE) {
+            found = true;
+            if !value.is_empty() && meta.role.is_none() {
+                meta.role = Some(value.to_string());
+            }
+            continue;
+        }
+        if let Some(value) = legacy_bullet(line, LEGACY_RELATIONS) {
+            found = true;
+            for rel in parse_relation_list(value) {
+                if !meta.relationships.iter().any(|r| r.target == rel.target) {
+                    meta.relationships.push(rel);
+                }
+            }
+            continue;
+        }
+        if let Some((label, value)) = LEGACY_PROSE
+            .iter()
+            .find_map(|label| legacy_bullet(line, label).map(|v| (*label, v)))
+        {
+            found = true;
+            prose.push((label, value));
+            continue;
+        }
+        if line.trim().is_empty() && kept.last().is_some_and(|l| l.trim().is_empty()) {
+            continue;
+        }
+        kept.push(line);
+    }
+    if !found {
+        return None;
+    }
+
+    let mut new_body = kept.join("\n").trim_end().to_string();
+    new_body.push_str("\n\n");
+    for (section, value) in prose {
+        new_body.push_str(&format!("## {}\n\n", section));
+        if !value.is_empty() {
+            new_body.push_str(value);
+            new_body.push_str("\n\n");
+        }
+    }
+    Some(render_character(&meta, &new_body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ages_in_words_keep_the_relationships() {
+        let content = "---\nage: 约二十\naliases: [小风]\nrelationships:\n- target: 苏晴\n  kind: friend\n---\n\n# 角色：林风\n";
+        let (meta, _) = parse_character(content);
+        assert_eq!(meta.age.as_deref(), Some("约二十"));
+        assert_eq!(meta.aliases, ["小风"]);
+        assert_eq!(meta.relationships.len(), 1);
+        assert_eq!(meta.relationships[0].target, "苏晴");
+        assert_eq!(parse_character("---\nage: 17\n---\n").0.age.as_deref(), Some("17"));
+        assert_eq!(parse_character(&new_character_md("林风")).0.age, None);
+    }
+
+    #[test]
+    fn relation_list_forms() {
+        let rels = parse_relation_list("师父：张三；对手-李四、王五（挚友）");
+        let got: Vec<(&str, RelationKind)> = rels.iter().map(|r| (r.target.as_str(), r.kind)).collect();
+        assert_eq!(
+            got,
+            [("张三", RelationKind::Mentor), ("李四", RelationKind::Rival), ("王五", RelationKind::Friend)]
+        );
+        assert_eq!(rels[2].display_label(), "挚友");
+    }
+
+    #[test]
+    fn migrates_legacy_bullets() {
+        let content = "---\nformat_version: 1\nmood: 冷\n---\n\n# 角色：林风\n\n- 角色定位：主角\n- 关系：师父：张三\n- 性格：沉稳\n\n备注。\n";
+        let out = migrate_legacy_character(content).unwrap();
+        let (meta, body) = parse_character(&out);
+        assert_eq!(meta.role.as_deref(), Some("主角"));
+        assert_eq!(meta.relationships[0].target, "张三");
+        assert!(meta.extra.contains_key("mood"));
+        assert!(body.contains("## 性格\n\n沉稳"));
+        assert!(body.contains("备注。"));
+        assert!(!body.contains("- 角色定位"));
+        assert!(migrate_legacy_character(&out).is_none());
+    }
+
+    #[test]
+    fn migration_skips_broken_front_matter() {
+        let content = "---\nrole: [主角\nnotes: 很重要\n---\n\n- 角色定位：主角\n";
+        assert!(migrate_legacy_character(content).is_none());
+    }
+
+    #[test]
+    fn name_from_heading_or_stem() {
+        assert_eq!(character_name(Path::new("角色-林风.md"), "# 角色：林凡\n"), "林凡");
+        assert_eq!(character_name(Path::new("角色-林风.md"), "正文\n"), "林风");
+    }
+}
@@ -0,0 +1,130 @@
+//! Line diff between two versions of a text.
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DiffKind {
+    Same,
+    Added,
+    Removed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffLine {
+    pub kind: DiffKind,
+    pub text: String,
+    /// 1-based line number in the old text (`Same`, `Removed`) or the new one (`Added`).
+    pub line: usize,
+}
+
+/// Longest-common-subsequence diff of `old` and `new` by lines; removals come before additions
+/// where lines were changed.
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..].iter().rev().zip(b[prefix..].iter().rev()).take_while(|(x, y)| x == y).count();
+    let (ma, mb) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    // lcs[i][j]: length of the common subsequence of ma[i..] and mb[j..].
+    let width = mb.len() + 1;
+    let mut lcs = vec![0u32; (ma.len() + 1) * width];
+    for i in (0..ma.len()).rev() {
+        for j in (0..mb.len()).rev() {
+            lcs[i * width + j] = if ma[i] == mb[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    let line = |kind, text: &str, line| DiffLine { kind, text: text.to_string(), line };
+    let mut out: Vec<DiffLine> = a[..prefix].iter().enumerate().map(|(i, t)| line(DiffKind::Same, t, i + 1)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < ma.len() || j < mb.len() {
+        if i < ma.len() && j < mb.len() && ma[i] == mb[j] {
+            out.push(line(DiffKind::Same, ma[i], prefix + i + 1));
+            i += 1;
+            j += 1;
+        } else if j == mb.len() || (i < ma.len() && lcs[(i + 1) * width + j] >= lcs[i * width + j + 1]) {
+            out.push(line(DiffKind::Removed, ma[i], prefix + i + 1));
+            i += 1;
+        } else {
+            out.push(line(DiffKind::Added, mb[j], prefix + j + 1));
+            j += 1;
+        }
+    }
+    let tail = a.len() - suffix;
+    out.extend(a[tail..].iter().enumerate().map(|(k, t)| line(DiffKind::Same, t, tail + k + 1)));
+    out
+}
+
+/// Renders the changed lines with `context` unchanged lines around them, `-`/`+` marking
+/// removed and added lines and `@@ 第 N 行 @@` starting each hunk. Empty when nothing changed.
+pub fn format_diff(lines: &[DiffLine], context: usize) -> String {
+    let changed: Vec<usize> = lines.iter().enumerate().filter(|(_, l)| l.kind != DiffKind::Same).map(|(i, _)| i).collect();
+    let mut out = String::new();
+    let mut shown_until = 0;
+    let mut k = 0;
+    while k < changed.len() {
+        let start = changed[k].saturating_sub(context).max(shown_until);
+        let mut end = changed[k] + 1;
+        while k < changed.len() && changed[k] <= end + context * 2 {
+            end = changed[k] + 1;
+            k += 1;
+        }
+        let end = (end + context).min(lines.len());
+        out.push_str(&format!("@@ 第 {} 行 @@\n", lines[start].line));
+        for l in &lines[start..end] {
+            let mark = match l.kind {
+                DiffKind::Same => ' ',
+                DiffKind::Added => '+',
+                DiffKind::Removed => '-',
+            };
+            out.push(mark);
+            out.push(' ');
+            out.push_str(&l.text);
+            out.push('\n');
+        }
+        shown_until = end;
+    }
+    out
+}
+
+/// Combines two versions line by line: shared lines appear once, and each place where they differ
+/// becomes a Git-style conflict block with `ours` first, then `theirs`.
+pub fn merge_with_markers(ours: &str, theirs: &str, ours_label: &str, theirs_label: &str) -> String {
+    let newline = if ours.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut out = String::new();
+    let mut ours_block = Vec::new();
+    let mut theirs_block = Vec::new();
+    let flush = |out: &mut String, ours_block: &mut Vec<String>, theirs_block: &mut Vec<String>| {
+        if ours_block.is_empty() && theirs_block.is_empty() {
+            return;
+        }
+        out.push_str(&format!("<<<<<<< {}{}", ours_label, newline));
+        for line in ours_block.drain(..) {
+            out.push_str(&line);
+            out.push_str(newline);
+        }
+        out.push_str("=======");
+        out.push_str(newline);
+        for line in theirs_block.drain(..) {
+            out.push_str(&line);
+            out.push_str(newline);
+        }
+        out.push_str(&format!(">>>>>>> {}{}", theirs_label, newline));
+    };
+    for line in diff_lines(theirs, ours) {
+        match line.kind {
+            DiffKind::Same => {
+                flush(&mut out, &mut ours_block, &mut theirs_block);
+                out.push_str(&line.text);
+                out.push_str(newline);
+            }
+            DiffKind::Added => ours_block.push(line.text),
+            DiffKind::Removed => theirs_block.push(line.text),
+        }
+    }
+    flush(&mut out, &mut ours_block, &mut theirs_block);
+    out
+}
@@ -0,0 +1,80 @@
+//! YAML front matter between `---` lines at the top of every project file.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Splits `content` into its front matter, parsed as `T`, and the body after it. The meta is
+/// `None` when there is no front matter or it does not parse; see [`has_invalid_front_matter`].
+pub fn parse_front_matter<T: DeserializeOwned>(content: &str) -> (Option<T>, &str) {
+    let normalized = content.strip_prefix("\u{feff}").unwrap_or(content);
+    let content = normalized;
+
+    if !content.starts_with("---\n") && !content.starts_with("---\r\n") {
+        return (None, content);
+    }
+
+    let rest = &content[3..];
+    let rest = rest.strip_prefix("\r\n").or_else(|| rest.strip_prefix("\n")).unwrap_or(rest);
+
+    if let Some(end) = find_front_matter_end(rest) {
+        let (yaml_part, body_part) = rest.split_at(end);
+        let yaml = yaml_part.trim();
+        let body = body_part
+            .trim_start_matches("\r\n---\r\n")
+            .trim_start_matches("\n---\n")
+            .trim_start_matches("\r\n---\n")
+            .trim_start_matches("\n---\r\n");
+
+        let meta = serde_yaml::from_str::<T>(yaml).ok();
+        (meta, body)
+    } else {
+        (None, content)
+    }
+}
+
+fn find_front_matter_end(s: &str) -> Option<usize> {
+    let patterns = ["\n---\n", "\r\n---\r\n", "\n---\r\n", "\r\n---\n"];
+    patterns.iter().filter_map(|p| s.find(p)).min()
+}
+
+/// `---` fenced YAML followed by `body`, the layout every project file uses.
+pub fn render_front_matter<T: Serialize>(meta: &T, body: &str) -> String {
+    let yaml = serde_yaml::to_string(meta).unwrap_or_default();
+    let mut out = String::new();
+    out.push_str("---\n");
+    out.push_str(yaml.trim_end());
+    out.push('\n');
+    out.push_str("---\n\n");
+    out.push_str(body.trim_start());
+    out
+}
+
+/// True when `content` opens with a front matter block that does not parse as `T`. Writing
+/// fresh front matter over such a block would drop the keys in it.
+pub fn has_invalid_front_matter<T: DeserializeOwned>(content: &str) -> bool {
+    let (meta, body) = parse_front_matter::<T>(content);
+    meta.is_none() && body.len() != content.trim_start_matches('\u{feff}').len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_front_matter_is_detected() {
+        assert!(has_invalid_front_matter::<serde_yaml::Value>("---\na: [1\n---\nbody"));
+        assert!(!has_invalid_front_matter::<serde_yaml::Value>("---\na: 1\n---\nbody"));
+        assert!(!has_invalid_front_matter::<serde_yaml::Value>("no front matter"));
+    }
+
+    #[test]
+    fn render_then_parse() {
+        let mut meta = std::collections::BTreeMap::new();
+        meta.insert("name".to_string(), "青云记".to_string());
+        let text = render_front_matter(&meta, "\n\n正文\n");
+        assert_eq!(text, "---\nname: 青云记\n---\n\n正文\n");
+        let (back, body) = parse_front_matter::<std::collections::BTreeMap<String, String>>(&text);
+        assert_eq!(back, Some(meta));
+        assert_eq!(body, "\n正文\n");
+    }
+}
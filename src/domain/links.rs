@@ -0,0 +1,99 @@
+//! `[[目标]]`, `[[目标#标题]]` and `[[目标|显示文字]]` links inside Markdown.
+
+use std::ops::Range;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WikiLink {
+    pub target: String,
+    /// Heading after `#`, e.g. `势力` in `[[世界观-示例#势力]]`.
+    pub anchor: Option<String>,
+    /// Display text after `|`.
+    pub label: Option<String>,
+    /// 1-based line number.
+    pub line: usize,
+    /// Byte range of the target inside the text, so it can be replaced in place.
+    pub target_range: Range<usize>,
+}
+
+/// Every wiki link outside fenced code blocks.
+pub fn parse_wiki_links(text: &str) -> Vec<WikiLink> {
+    let mut out = Vec::new();
+    let mut in_fence = false;
+    let mut offset = 0;
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        let line_start = offset;
+        offset += line.len();
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        let mut pos = 0;
+        while let Some(start) = line[pos..].find("[[") {
+            let inner_start = pos + start + 2;
+            let Some(len) = line[inner_start..].find("]]") else { break };
+            let inner = &line[inner_start..inner_start + len];
+            pos = inner_start + len + 2;
+            if inner.contains('[') || inner.contains('\n') {
+                continue;
+            }
+            let (link, label) = match inner.split_once('|') {
+                Some((l, d)) => (l, Some(d.trim().to_string())),
+                None => (inner, None),
+            };
+            let (target, anchor) = match link.split_once('#') {
+                Some((t, a)) => (t, Some(a.trim().to_string())),
+                None => (link, None),
+            };
+            let lead = target.len() - target.trim_start().len();
+            let target_trimmed = target.trim();
+            if target_trimmed.is_empty() {
+                continue;
+            }
+            let begin = line_start + inner_start + lead;
+            out.push(WikiLink {
+                target: target_trimmed.to_string(),
+                anchor: anchor.filter(|a| !a.is_empty()),
+                label: label.filter(|l| !l.is_empty()),
+                line: i + 1,
+                target_range: begin..begin + target_trimmed.len(),
+            });
+        }
+    }
+    out
+}
+
+/// Replaces link targets for which `rename` returns a new name, keeping anchors and labels.
+/// Returns `None` when nothing changed.
+pub fn rewrite_wiki_links(text: &str, rename: impl Fn(&str) -> Option<String>) -> Option<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    let mut changed = false;
+    for link in parse_wiki_links(text) {
+        let Some(new_target) = rename(&link.target) else { continue };
+        if new_target == link.target {
+            continue;
+        }
+        out.push_str(&text[last..link.target_range.start]);
+        out.push_str(&new_target);
+        last = link.target_range.end;
+        changed = true;
+    }
+    if !changed {
+        return None;
+    }
+    out.push_str(&text[last..]);
+    Some(out)
+}
+
+/// Whether the Markdown has a heading whose text is `anchor`.
+pub fn has_heading(text: &str, anchor: &str) -> bool {
+    text.lines().any(|line| {
+        let line = line.trim_start();
+        let title = line.trim_start_matches('#');
+        title.len() < line.len() && title.starts_with(' ') && title.trim() == anchor.trim()
+    })
+}
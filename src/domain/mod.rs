@@ -1,6 +1,18 @@
- use serde::{Deserialize, Serialize};
+ use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
  use std::path::PathBuf;
  use std::time::{SystemTime, UNIX_EPOCH};
+
+pub mod calendar;
+pub mod character;
+pub mod diff;
+pub mod front_matter;
+pub mod links;
+pub mod scene;
+pub mod timeline;
+
+pub use calendar::{CalendarDef, Calendars};
+pub use character::{CharacterMeta, RelationKind, Relationship};
  
  #[derive(Debug, Clone, Serialize, Deserialize)]
  pub struct ProjectMeta {
@@ -8,12 +20,48 @@
      pub created_unix: u64,
      pub format_version: u32,
     #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    /// BCP 47 tag such as `zh-CN`, used for exported books.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub update_url: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub theme: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub left_pane_ratio: Option<f32>,
+    /// In-world calendars used to read timeline dates.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub calendars: Vec<CalendarDef>,
+    /// Words (字) to write per day.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_target: Option<u64>,
+    /// Words the finished manuscript should have; with `deadline` this gives the required pace.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub word_target: Option<u64>,
+    /// Last day of writing, `YYYY-MM-DD`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<String>,
+    /// Present when the project is versioned with Git.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git: Option<GitSettings>,
+    /// The storyline report flags a thread missing from more than this many chapters in a row.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dormant_chapters: Option<usize>,
+    /// Keys this version does not know, kept so that saving settings never drops them.
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_yaml::Value>,
  }
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GitSettings {
+    /// Also commit this often while the project is open; without it commits happen on 保存.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit_interval_minutes: Option<u32>,
+    /// Leave history to Git and stop writing snapshots to `.backup/`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub disable_backup: bool,
+}
  
  impl ProjectMeta {
      pub fn new(name: String) -> Self {
@@ -21,9 +69,18 @@
              name,
              created_unix: now_unix(),
              format_version: 1,
+            author: None,
+            language: None,
             update_url: None,
             theme: None,
             left_pane_ratio: None,
+            calendars: Vec::new(),
+            daily_target: None,
+            word_target: None,
+            deadline: None,
+            git: None,
+            dormant_chapters: None,
+            extra: BTreeMap::new(),
          }
      }
  }
@@ -34,20 +91,178 @@
              name: "Untitled".to_string(),
              created_unix: now_unix(),
              format_version: 1,
+            author: None,
+            language: None,
             update_url: None,
             theme: None,
             left_pane_ratio: None,
+            calendars: Vec::new(),
+            daily_target: None,
+            word_target: None,
+            deadline: None,
+            git: None,
+            dormant_chapters: None,
+            extra: BTreeMap::new(),
          }
      }
  }
  
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChapterStatus {
+    Draft,
+    Revising,
+    Done,
+    /// A status this version does not know, such as a hand-typed `待定`.
+    #[serde(other)]
+    Unknown,
+}
+
+impl ChapterStatus {
+    pub fn label(self) -> &'static str {
+        match self {
+            ChapterStatus::Draft => "草稿",
+            ChapterStatus::Revising => "修改中",
+            ChapterStatus::Done => "完成",
+            ChapterStatus::Unknown => "未知状态",
+        }
+    }
+}
+
+/// Front matter of a chapter file. Keys this version does not know are kept in `extra`
+/// so that rewriting the front matter never drops them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChapterMeta {
+    #[serde(default = "default_format_version")]
+    pub format_version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<ChapterStatus>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pov: Option<String>,
+    /// `None` as well when the value is not a whole number, e.g. `约3000`.
+    #[serde(default, deserialize_with = "lenient_u32", skip_serializing_if = "Option::is_none")]
+    pub target_words: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// In-world date of the chapter's events, free-form (e.g. `天元历三年二月`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scene_date: Option<String>,
+    /// Storylines (故事线) the chapter advances, e.g. `[主线, 感情线]`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub threads: Vec<String>,
+    /// Storylines the chapter brings to an end; they count as advanced here as well.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub resolves: Vec<String>,
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_yaml::Value>,
+}
+
+impl ChapterMeta {
+    pub fn new_draft() -> Self {
+        Self {
+            status: Some(ChapterStatus::Draft),
+            ..Self::default()
+        }
+    }
+
+    /// Short description for tree labels, e.g. `草稿 · 林风`; empty when nothing is set.
+    pub fn short_label(&self) -> String {
+        let mut parts: Vec<&str> = Vec::new();
+        if let Some(status) = self.status {
+            parts.push(status.label());
+        }
+        if let Some(pov) = self.pov.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+            parts.push(pov);
+        }
+        parts.join(" · ")
+    }
+}
+
+impl Default for ChapterMeta {
+    fn default() -> Self {
+        Self {
+            format_version: default_format_version(),
+            status: None,
+            pov: None,
+            target_words: None,
+            summary: None,
+            tags: Vec::new(),
+            scene_date: None,
+            threads: Vec::new(),
+            resolves: Vec::new(),
+            extra: BTreeMap::new(),
+        }
+    }
+}
+
+fn default_format_version() -> u32 {
+    1
+}
+
+/// Reads a number written as `3000` or `"3000"`; anything else is `None` rather than an error
+/// that would lose the rest of the front matter.
+fn lenient_u32<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    Ok(match serde_yaml::Value::deserialize(deserializer)? {
+        serde_yaml::Value::Number(n) => n.as_u64().and_then(|n| u32::try_from(n).ok()),
+        serde_yaml::Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    })
+}
+
+ #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+ pub enum Section {
+     Chapters,
+     Characters,
+     World,
+     Timeline,
+ }
+
+impl Section {
+    pub const ALL: [Section; 4] = [Section::Chapters, Section::Characters, Section::World, Section::Timeline];
+
+    pub fn dir_name(self) -> &'static str {
+        match self {
+            Section::Chapters => "chapters",
+            Section::Characters => "characters",
+            Section::World => "world",
+            Section::Timeline => "timeline",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Section::Chapters => "章节",
+            Section::Characters => "角色",
+            Section::World => "世界观",
+            Section::Timeline => "时间线",
+        }
+    }
+
+    pub fn default_title(self) -> &'static str {
+        match self {
+            Section::Chapters => "新建章节",
+            Section::Characters => "新建角色",
+            Section::World => "新建设定",
+            Section::Timeline => "新建时间线条目",
+        }
+    }
+
+    pub fn from_dir_name(name: &str) -> Option<Section> {
+        Self::ALL.into_iter().find(|s| s.dir_name().eq_ignore_ascii_case(name))
+    }
+}
+
  #[derive(Debug, Clone)]
  pub struct Project {
      pub root: PathBuf,
      pub meta: ProjectMeta,
+    /// Documents with unsaved text left in `.recovery/` by an earlier session, found on open.
+    pub recovered: Vec<PathBuf>,
  }
  
- fn now_unix() -> u64 {
+ pub fn now_unix() -> u64 {
      SystemTime::now()
          .duration_since(UNIX_EPOCH)
          .map(|d| d.as_secs())
@@ -0,0 +1,149 @@
+//! Scenes inside a chapter file, each opened by a `<!-- scene: 标题 -->` marker.
+//!
+//! The marker may go on over several lines with `视角:`/`pov:`, `地点:`/`location:` and
+//! `概要:`/`summary:` entries:
+//!
+//! ```text
+//! <!-- scene: 初入山门
+//! 视角: 林风
+//! 地点: 青云山门
+//! 概要: 林风通过入门考核
+//! -->
+//! ```
+//!
+//! `<!-- 场景: 标题 -->` works as well. Being a comment, the marker is neither shown in exports
+//! nor counted in 字数.
+
+use std::ops::Range;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scene {
+    pub title: String,
+    pub pov: Option<String>,
+    pub location: Option<String>,
+    pub summary: Option<String>,
+    /// 1-based line of the marker.
+    pub line: usize,
+    /// Byte range from the marker up to the next scene's marker or the end of the text.
+    pub range: Range<usize>,
+}
+
+/// The scenes of a chapter's text, in order. Text before the first marker belongs to no scene;
+/// markers inside fenced code blocks are ignored.
+pub fn parse_scenes(text: &str) -> Vec<Scene> {
+    let mut scenes: Vec<Scene> = Vec::new();
+    let mut in_fence = false;
+    // The scene whose marker is still open, waiting for `-->`.
+    let mut open: Option<Scene> = None;
+    let mut offset = 0;
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        let line_start = offset;
+        offset += line.len();
+        let trimmed = line.trim();
+        if let Some(scene) = &mut open {
+            let (entry, closed) = match trimmed.find("-->") {
+                Some(end) => (&trimmed[..end], true),
+                None => (trimmed, false),
+            };
+            set_entry(scene, entry);
+            if closed {
+                scenes.extend(open.take());
+            }
+            continue;
+        }
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        let Some(rest) = trimmed.strip_prefix("<!--").map(str::trim_start) else { continue };
+        let Some(rest) = rest.strip_prefix("scene:").or_else(|| rest.strip_prefix("场景:")).or_else(|| rest.strip_prefix("场景：")) else {
+            continue;
+        };
+        let (title, closed) = match rest.find("-->") {
+            Some(end) => (&rest[..end], true),
+            None => (rest, false),
+        };
+        let scene = Scene {
+            title: title.trim().to_string(),
+            pov: None,
+            location: None,
+            summary: None,
+            line: i + 1,
+            range: line_start..text.len(),
+        };
+        if closed {
+            scenes.push(scene);
+        } else {
+            open = Some(scene);
+        }
+    }
+    scenes.extend(open);
+
+    for i in 0..scenes.len() {
+        if let Some(next) = scenes.get(i + 1).map(|s| s.range.start) {
+            scenes[i].range.end = next;
+        }
+    }
+    scenes
+}
+
+/// Reads one `键: 值` line of a multi-line marker.
+fn set_entry(scene: &mut Scene, line: &str) {
+    let Some((key, value)) = line.split_once(':').or_else(|| line.split_once('：')) else { return };
+    let value = value.trim();
+    if value.is_empty() {
+        return;
+    }
+    let slot = match key.trim() {
+        "pov" | "视角" => &mut scene.pov,
+        "location" | "地点" => &mut scene.location,
+        "summary" | "概要" => &mut scene.summary,
+        _ => return,
+    };
+    *slot = Some(value.to_string());
+}
+
+/// A scene's tree label: its title, or `场景 N` when it has none.
+pub fn scene_label(scene: &Scene, index: usize) -> String {
+    if scene.title.is_empty() {
+        format!("场景 {}", index + 1)
+    } else {
+        scene.title.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markers_split_the_chapter_into_scenes() {
+        let text = "# 第一章\n\n<!-- scene: 初入山门\n视角: 林风\n地点：青云山门\n概要: 林风通过入门考核\n-->\n考核开始。\n```\n<!-- scene: 代码里的不算 -->\n```\n<!-- 场景：夜谈 -->\n夜里。\n<!-- scene: -->\n";
+        let scenes = parse_scenes(text);
+        assert_eq!(scenes.len(), 3);
+
+        let first = &scenes[0];
+        assert_eq!((first.title.as_str(), first.line), ("初入山门", 3));
+        assert_eq!(first.pov.as_deref(), Some("林风"));
+        assert_eq!(first.location.as_deref(), Some("青云山门"));
+        assert_eq!(first.summary.as_deref(), Some("林风通过入门考核"));
+        assert!(text[first.range.clone()].ends_with("```\n<!-- scene: 代码里的不算 -->\n```\n"));
+
+        assert_eq!((scenes[1].title.as_str(), scenes[1].line), ("夜谈", 12));
+        assert_eq!(&text[scenes[1].range.clone()], "<!-- 场景：夜谈 -->\n夜里。\n");
+        assert_eq!(scenes[2].range.end, text.len());
+        assert_eq!(scene_label(&scenes[2], 2), "场景 3");
+    }
+
+    #[test]
+    fn unclosed_markers_run_to_the_end() {
+        let scenes = parse_scenes("正文在场景之前。\n<!-- scene: 残篇\n视角: 苏晴");
+        assert_eq!(scenes.len(), 1);
+        assert_eq!(scenes[0].pov.as_deref(), Some("苏晴"));
+        assert_eq!(scenes[0].range.start, "正文在场景之前。\n".len());
+        assert!(parse_scenes("没有场景。\n").is_empty());
+    }
+}
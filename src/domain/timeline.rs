@@ -0,0 +1,715 @@
+//! Timeline tables (`| 时间 | 事件 | 关联 |`) parsed into events and written back losslessly.
+
+use super::calendar::Calendars;
+use std::cmp::Ordering;
+use std::collections::hash_map::{Entry, HashMap};
+use std::path::{Path, PathBuf};
+
+/// A date as written in the timeline, split into era, year, month and day.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DateParts {
+    /// Text before the year, e.g. `天元历`; empty when absent.
+    pub era: String,
+    pub year: i64,
+    pub month: Option<u32>,
+    pub day: Option<u32>,
+    /// Text after the year that was not read as a month, e.g. `霜月初五` for a calendar
+    /// with named months.
+    pub rest: String,
+}
+
+impl DateParts {
+    /// Day number on the proleptic Gregorian calendar; missing month/day count as the first.
+    pub fn gregorian_ordinal(&self) -> i64 {
+        days_from_civil(self.year, self.month.unwrap_or(1), self.day.unwrap_or(1))
+    }
+}
+
+/// Years further from the epoch are not read as dates, which keeps day numbers far from overflow.
+const MAX_YEAR: i64 = 1_000_000_000;
+
+/// Parses `1024-03-05`, `1024/3`, `-200`, `天元历三年二月初五`, `第3年`, `公元前210年` and similar.
+pub fn parse_date(text: &str) -> Option<DateParts> {
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    parse_cjk_date(text)
+        .or_else(|| parse_numeric_date(text))
+        .filter(|d| d.year.abs() <= MAX_YEAR)
+}
+
+fn parse_numeric_date(text: &str) -> Option<DateParts> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let start = chars.iter().position(|&(i, c)| {
+        c.is_ascii_digit() || (c == '-' && text[i + 1..].starts_with(|d: char| d.is_ascii_digit()))
+    })?;
+    let (start_byte, _) = chars[start];
+    let (era, negative_era) = split_era(&text[..start_byte]);
+    let rest = &text[start_byte..];
+    let rest = rest.split_whitespace().next().unwrap_or(rest);
+    let (negative, rest) = match rest.strip_prefix('-') {
+        Some(r) => (true, r),
+        None => (false, rest),
+    };
+    let mut fields = rest.split(['-', '/', '.']);
+    let year: i64 = fields.next()?.parse().ok()?;
+    let month = match fields.next() {
+        Some(m) => Some(m.parse::<u32>().ok()?),
+        None => None,
+    };
+    let day = match fields.next() {
+        Some(d) => Some(d.parse::<u32>().ok()?),
+        None => None,
+    };
+    if fields.next().is_some() {
+        return None;
+    }
+    let year = if negative || negative_era { -year } else { year };
+    Some(DateParts {
+        era,
+        year,
+        month,
+        day,
+        rest: String::new(),
+    })
+}
+
+fn parse_cjk_date(text: &str) -> Option<DateParts> {
+    let year_pos = text.find('年')?;
+    let before = &text[..year_pos];
+    let (num_start, year) = match before.strip_suffix('元') {
+        // 元年; the era name itself may end in 元 as in 天元元年.
+        Some(era) if !era.ends_with(is_numeral) => (era.len(), 1),
+        _ => {
+            let start = before.char_indices().rev().take_while(|&(_, c)| is_numeral(c)).last().map(|(i, _)| i)?;
+            (start, parse_numeral(&before[start..])?)
+        }
+    };
+    let prefix = before[..num_start].trim_end_matches('第');
+    let (era, negative) = split_era(prefix);
+
+    let mut rest = &text[year_pos + '年'.len_utf8()..];
+    let mut month = None;
+    if let Some(pos) = rest.find('月') {
+        let m = &rest[..pos];
+        let value = match m {
+            "正" => Some(1),
+            "冬" => Some(11),
+            "腊" => Some(12),
+            _ => parse_numeral(m).and_then(|v| u32::try_from(v).ok()),
+        };
+        if let Some(v) = value {
+            month = Some(v);
+            rest = &rest[pos + '月'.len_utf8()..];
+        }
+    }
+    let day = if month.is_some() { parse_day(rest) } else { None };
+    let rest = if month.is_some() { "" } else { rest.trim() };
+    let year = if negative { -year } else { year };
+    Some(DateParts {
+        era,
+        year,
+        month,
+        day,
+        rest: rest.to_string(),
+    })
+}
+
+/// Reads a day such as `初五`, `23日`, `廿三号` from the start of `text`.
+pub fn parse_day(text: &str) -> Option<u32> {
+    let d = text.trim_start();
+    let d = d.strip_prefix('初').unwrap_or(d);
+    let end = d
+        .find(['日', '号'])
+        .unwrap_or_else(|| d.char_indices().find(|&(_, c)| !is_numeral(c)).map(|(i, _)| i).unwrap_or(d.len()));
+    if end == 0 {
+        return None;
+    }
+    parse_numeral(&d[..end]).and_then(|v| u32::try_from(v).ok())
+}
+
+/// Splits an era prefix; `前` / `公元前` mark years before the epoch.
+fn split_era(prefix: &str) -> (String, bool) {
+    let prefix = prefix.trim();
+    let negative = prefix.ends_with('前') || prefix.eq_ignore_ascii_case("BC");
+    (prefix.to_string(), negative)
+}
+
+fn is_numeral(c: char) -> bool {
+    c.is_ascii_digit() || "〇零一二两三四五六七八九十廿卅百千万".contains(c)
+}
+
+/// Parses Arabic digits or Chinese numerals such as `二十三`, `廿五`, `一千零二十四`, `二〇二四`.
+pub fn parse_numeral(s: &str) -> Option<i64> {
+    let s = s.trim();
+    if s.is_empty() {
+        return None;
+    }
+    if s.chars().all(|c| c.is_ascii_digit()) {
+        return s.parse().ok();
+    }
+    let digit = |c: char| -> Option<i64> {
+        Some(match c {
+            '〇' | '零' => 0,
+            '一' => 1,
+            '二' | '两' => 2,
+            '三' => 3,
+            '四' => 4,
+            '五' => 5,
+            '六' => 6,
+            '七' => 7,
+            '八' => 8,
+            '九' => 9,
+            _ => return None,
+        })
+    };
+    // Digit-by-digit form: 二〇二四.
+    if s.chars().count() > 1 && s.chars().all(|c| digit(c).is_some()) {
+        return s.chars().try_fold(0i64, |acc, c| acc.checked_mul(10)?.checked_add(digit(c)?));
+    }
+    // Overflow means the text is no number we can use, so every step is checked.
+    let unit = |current: i64, factor: i64| if current == 0 { Some(factor) } else { current.checked_mul(factor) };
+    let mut total = 0i64;
+    let mut section = 0i64;
+    let mut current = 0i64;
+    for c in s.chars() {
+        if let Some(d) = digit(c) {
+            current = d;
+            continue;
+        }
+        match c {
+            '十' => {
+                section = section.checked_add(unit(current, 10)?)?;
+                current = 0;
+            }
+            '廿' => section = section.checked_add(20)?,
+            '卅' => section = section.checked_add(30)?,
+            '百' => {
+                section = section.checked_add(unit(current, 100)?)?;
+                current = 0;
+            }
+            '千' => {
+                section = section.checked_add(unit(current, 1000)?)?;
+                current = 0;
+            }
+            '万' => {
+                total = total.checked_add(section.checked_add(current)?.max(1).checked_mul(10_000)?)?;
+                section = 0;
+                current = 0;
+            }
+            d if d.is_ascii_digit() => current = current.checked_mul(10)?.checked_add(d as i64 - '0' as i64)?,
+            _ => return None,
+        }
+    }
+    total.checked_add(section)?.checked_add(current)
+}
+
+/// Days since 1970-01-01 on the proleptic Gregorian calendar.
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let month = month.clamp(1, 12) as i64;
+    let day = day.max(1) as i64;
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Inverse of [`days_from_civil`]: `(year, month, day)` of a day counted from 1970-01-01.
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (yoe + era * 400 + i64::from(month <= 2), month, day)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TableColumns {
+    time: usize,
+    event: usize,
+    links: Option<usize>,
+}
+
+/// One data row of a timeline table.
+#[derive(Debug, Clone)]
+pub struct TimelineRow {
+    pub cells: Vec<String>,
+    original_cells: Vec<String>,
+    raw: String,
+    columns: TableColumns,
+    /// Index of the table within the document.
+    pub table: usize,
+    /// 1-based line number in the file when it was parsed.
+    pub line: usize,
+}
+
+impl TimelineRow {
+    pub fn time(&self) -> &str {
+        self.cell(self.columns.time)
+    }
+
+    pub fn event(&self) -> &str {
+        self.cell(self.columns.event)
+    }
+
+    pub fn links_text(&self) -> &str {
+        self.columns.links.map(|i| self.cell(i)).unwrap_or("")
+    }
+
+    pub fn set_time(&mut self, value: &str) {
+        self.set_cell(self.columns.time, value);
+    }
+
+    pub fn set_event(&mut self, value: &str) {
+        self.set_cell(self.columns.event, value);
+    }
+
+    pub fn set_links_text(&mut self, value: &str) {
+        if let Some(i) = self.columns.links {
+            self.set_cell(i, value);
+        }
+    }
+
+    /// Names in the 关联 column: `[[0003-决战]]`, `林风、苏瑶` and the like.
+    pub fn link_targets(&self) -> Vec<String> {
+        split_link_targets(self.links_text())
+    }
+
+    /// Renames plain (non-`[[ ]]`) entries of the 关联 column for which `rename` returns a new
+    /// name, keeping separators.
+    pub fn rename_links(&mut self, rename: impl Fn(&str) -> Option<String>) -> bool {
+        let text = self.links_text();
+        let mut out = String::new();
+        let mut changed = false;
+        for piece in text.split_inclusive(['，', ',', '、', '；', ';']) {
+            let (entry, sep) = match piece.char_indices().last() {
+                Some((i, c)) if "，,、；;".contains(c) => (&piece[..i], &piece[i..]),
+                _ => (piece, ""),
+            };
+            let new_name = if entry.contains("[[") { None } else { rename(entry.trim()) };
+            if let Some(to) = new_name {
+                let lead = &entry[..entry.len() - entry.trim_start().len()];
+                let trail = &entry[entry.trim_end().len()..];
+                out.push_str(lead);
+                out.push_str(&to);
+                out.push_str(trail);
+                changed = true;
+            } else {
+                out.push_str(entry);
+            }
+            out.push_str(sep);
+        }
+        if changed {
+            self.set_links_text(&out);
+        }
+        changed
+    }
+
+    pub fn date(&self) -> Option<DateParts> {
+        parse_date(self.time())
+    }
+
+    fn cell(&self, i: usize) -> &str {
+        self.cells.get(i).map(String::as_str).unwrap_or("")
+    }
+
+    fn set_cell(&mut self, i: usize, value: &str) {
+        if self.cells.len() <= i {
+            self.cells.resize(i + 1, String::new());
+        }
+        self.cells[i] = value.trim().to_string();
+    }
+
+    fn render(&self) -> String {
+        if self.cells == self.original_cells {
+            return self.raw.clone();
+        }
+        let mut out = String::from("|");
+        for cell in &self.cells {
+            out.push(' ');
+            out.push_str(&cell.replace('|', "\\|"));
+            out.push_str(" |");
+        }
+        out
+    }
+}
+
+pub fn split_link_targets(text: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    for part in text.split(['，', ',', '、', '；', ';']) {
+        let mut rest = part.trim();
+        // Several [[links]] may share one separator-free cell.
+        while let Some(start) = rest.find("[[") {
+            let Some(end) = rest[start..].find("]]") else { break };
+            let inner = rest[start + 2..start + end].trim();
+            if !inner.is_empty() {
+                out.push(inner.to_string());
+            }
+            rest = rest[start + end + 2..].trim();
+        }
+        if !rest.is_empty() {
+            out.push(rest.to_string());
+        }
+    }
+    out
+}
+
+#[derive(Debug, Clone)]
+enum DocLine {
+    Text(String),
+    Row(TimelineRow),
+}
+
+/// A timeline file. Lines that are not table rows are kept verbatim, and rows that were not
+/// modified are written back exactly as read, so `parse(s).to_markdown() == s`.
+#[derive(Debug, Clone)]
+pub struct TimelineDoc {
+    lines: Vec<(DocLine, String)>,
+    tables: usize,
+}
+
+impl TimelineDoc {
+    pub fn parse(content: &str) -> Self {
+        let mut lines: Vec<(DocLine, String)> = Vec::new();
+        let raw_lines: Vec<(&str, &str)> = content
+            .split_inclusive('\n')
+            .map(|l| {
+                let body = l.trim_end_matches(['\n', '\r']);
+                (body, &l[body.len()..])
+            })
+            .collect();
+
+        let mut tables = 0usize;
+        let mut i = 0;
+        while i < raw_lines.len() {
+            let (text, eol) = raw_lines[i];
+            let header = split_row(text);
+            let columns = header.as_deref().and_then(header_columns);
+            let has_separator = raw_lines.get(i + 1).and_then(|(t, _)| split_row(t)).is_some_and(|cells| is_separator(&cells));
+            let (Some(columns), true) = (columns, has_separator) else {
+                lines.push((DocLine::Text(text.to_string()), eol.to_string()));
+                i += 1;
+                continue;
+            };
+
+            lines.push((DocLine::Text(text.to_string()), eol.to_string()));
+            lines.push((DocLine::Text(raw_lines[i + 1].0.to_string()), raw_lines[i + 1].1.to_string()));
+            i += 2;
+            while i < raw_lines.len() {
+                let (text, eol) = raw_lines[i];
+                let Some(cells) = split_row(text) else { break };
+                lines.push((
+                    DocLine::Row(TimelineRow {
+                        original_cells: cells.clone(),
+                        cells,
+                        raw: text.to_string(),
+                        columns,
+                        table: tables,
+                        line: i + 1,
+                    }),
+                    eol.to_string(),
+                ));
+                i += 1;
+            }
+            tables += 1;
+        }
+        Self { lines, tables }
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        for (line, eol) in &self.lines {
+            match line {
+                DocLine::Text(t) => out.push_str(t),
+                DocLine::Row(r) => out.push_str(&r.render()),
+            }
+            out.push_str(eol);
+        }
+        out
+    }
+
+    pub fn table_count(&self) -> usize {
+        self.tables
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &TimelineRow> {
+        self.lines.iter().filter_map(|(l, _)| match l {
+            DocLine::Row(r) => Some(r),
+            DocLine::Text(_) => None,
+        })
+    }
+
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut TimelineRow> {
+        self.lines.iter_mut().filter_map(|(l, _)| match l {
+            DocLine::Row(r) => Some(r),
+            DocLine::Text(_) => None,
+        })
+    }
+
+    /// Reorders the rows of every table by `key`; rows without a key keep their relative order at the end.
+    pub fn sort_rows_by<K: Ord>(&mut self, key: impl Fn(&TimelineRow) -> Option<K>) {
+        for table in 0..self.tables {
+            let slots: Vec<usize> = self
+                .lines
+                .iter()
+                .enumerate()
+                .filter(|(_, (l, _))| matches!(l, DocLine::Row(r) if r.table == table))
+                .map(|(i, _)| i)
+                .collect();
+            let mut rows: Vec<TimelineRow> = slots
+                .iter()
+                .map(|&i| match &self.lines[i].0 {
+                    DocLine::Row(r) => r.clone(),
+                    DocLine::Text(_) => unreachable!(),
+                })
+                .collect();
+            rows.sort_by(|a, b| match (key(a), key(b)) {
+                (Some(x), Some(y)) => x.cmp(&y),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            });
+            for (slot, row) in slots.into_iter().zip(rows) {
+                self.lines[slot].0 = DocLine::Row(row);
+            }
+        }
+    }
+
+    /// Sorts rows chronologically by their 时间 cell read in the project's calendars.
+    pub fn sort_by_date(&mut self, calendars: &Calendars) {
+        self.sort_rows_by(|r| r.date().and_then(|d| calendars.ordinal(&d)));
+    }
+}
+
+fn split_row(line: &str) -> Option<Vec<String>> {
+    let t = line.trim();
+    if !t.starts_with('|') {
+        return None;
+    }
+    let inner = t.strip_prefix('|')?;
+    let inner = inner.strip_suffix('|').unwrap_or(inner);
+    let mut cells = Vec::new();
+    let mut cur = String::new();
+    let mut chars = inner.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' && chars.peek() == Some(&'|') {
+            cur.push('|');
+            chars.next();
+        } else if c == '|' {
+            cells.push(cur.trim().to_string());
+            cur.clear();
+        } else {
+            cur.push(c);
+        }
+    }
+    cells.push(cur.trim().to_string());
+    Some(cells)
+}
+
+fn is_separator(cells: &[String]) -> bool {
+    !cells.is_empty()
+        && cells.iter().all(|c| {
+            let c = c.trim().trim_start_matches(':').trim_end_matches(':');
+            !c.is_empty() && c.chars().all(|ch| ch == '-')
+        })
+}
+
+fn header_columns(cells: &[String]) -> Option<TableColumns> {
+    let find = |names: &[&str]| cells.iter().position(|c| names.iter().any(|n| c.trim().eq_ignore_ascii_case(n)));
+    let time = find(&["时间", "日期", "time", "date"])?;
+    let event = find(&["事件", "event"])?;
+    let links = find(&["关联", "相关", "links"]);
+    Some(TableColumns { time, event, links })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimelineIssueKind {
+    UnparsedDate,
+    OutOfOrder,
+    Duplicate,
+    BrokenLink,
+}
+
+impl TimelineIssueKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            TimelineIssueKind::UnparsedDate => "无法识别的时间",
+            TimelineIssueKind::OutOfOrder => "顺序错乱",
+            TimelineIssueKind::Duplicate => "重复事件",
+            TimelineIssueKind::BrokenLink => "关联不存在",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TimelineIssue {
+    pub file: PathBuf,
+    pub line: usize,
+    pub kind: TimelineIssueKind,
+    pub message: String,
+}
+
+/// Consistency checks across timeline files. `ordinal` maps a row to its sortable date and
+/// `resolve` tells whether a 关联 target names an existing project file.
+pub fn check_timelines(
+    docs: &[(PathBuf, TimelineDoc)],
+    ordinal: impl Fn(&DateParts) -> Option<i64>,
+    resolve: impl Fn(&str) -> Option<PathBuf>,
+) -> Vec<TimelineIssue> {
+    let mut issues = Vec::new();
+    let mut seen: HashMap<(String, String), (&Path, usize)> = HashMap::new();
+    for (path, doc) in docs {
+        let mut last: Vec<Option<(i64, usize)>> = vec![None; doc.table_count()];
+        for row in doc.rows() {
+            let issue = |kind, message: String| TimelineIssue {
+                file: path.clone(),
+                line: row.line,
+                kind,
+                message,
+            };
+            if row.time().is_empty() && row.event().is_empty() {
+                continue;
+            }
+            match row.date().and_then(|d| ordinal(&d)) {
+                Some(ord) => {
+                    if let Some((prev, prev_line)) = last[row.table] {
+                        if ord < prev {
+                            issues.push(issue(
+                                TimelineIssueKind::OutOfOrder,
+                                format!("「{}」早于第 {} 行的事件", row.time(), prev_line),
+                            ));
+                        }
+                    }
+                    last[row.table] = Some((ord, row.line));
+                }
+                None => issues.push(issue(TimelineIssueKind::UnparsedDate, format!("「{}」", row.time()))),
+            }
+            match seen.entry((row.time().to_string(), row.event().to_string())) {
+                Entry::Occupied(first) => {
+                    let (other, other_line) = *first.get();
+                    issues.push(issue(
+                        TimelineIssueKind::Duplicate,
+                        format!("与 {} 第 {} 行相同", other.file_name().and_then(|s| s.to_str()).unwrap_or(""), other_line),
+                    ));
+                }
+                Entry::Vacant(slot) => {
+                    slot.insert((path.as_path(), row.line));
+                }
+            }
+            for target in row.link_targets() {
+                if resolve(&target).is_none() {
+                    issues.push(issue(TimelineIssueKind::BrokenLink, format!("「{}」", target)));
+                }
+            }
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chinese_numerals() {
+        assert_eq!(parse_numeral("23"), Some(23));
+        assert_eq!(parse_numeral("二十三"), Some(23));
+        assert_eq!(parse_numeral("十五"), Some(15));
+        assert_eq!(parse_numeral("廿五"), Some(25));
+        assert_eq!(parse_numeral("一千零二十四"), Some(1024));
+        assert_eq!(parse_numeral("二〇二四"), Some(2024));
+        assert_eq!(parse_numeral("三万五千"), Some(35_000));
+        assert_eq!(parse_numeral("两百"), Some(200));
+        assert_eq!(parse_numeral(""), None);
+        assert_eq!(parse_numeral("三月"), None);
+    }
+
+    #[test]
+    fn long_numerals_do_not_overflow() {
+        assert_eq!(parse_numeral(&"〇".repeat(20)), Some(0));
+        assert_eq!(parse_numeral(&"九".repeat(20)), None);
+        assert_eq!(parse_numeral(&"9".repeat(30)), None);
+        assert_eq!(parse_numeral(&format!("{}万", "9".repeat(18))), None);
+        assert_eq!(parse_numeral(&"九千".repeat(10)), Some(90_000));
+        assert!(parse_date(&format!("{}年", "九".repeat(20))).is_none());
+        assert!(parse_date(&format!("{}年三月", "九".repeat(12))).is_none());
+    }
+
+    #[test]
+    fn civil_days_round_trip() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11_017);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+        assert_eq!(days_from_civil(2024, 3, 1) - days_from_civil(2024, 2, 28), 2);
+        for days in [-800_000, -1, 0, 59, 11_016, 19_782, 2_000_000] {
+            let (y, m, d) = civil_from_days(days);
+            assert_eq!(days_from_civil(y, m, d), days);
+        }
+    }
+
+    #[test]
+    fn dates_in_several_forms() {
+        let d = parse_date("天元历三年二月初五").unwrap();
+        assert_eq!((d.era.as_str(), d.year, d.month, d.day), ("天元历", 3, Some(2), Some(5)));
+        let d = parse_date("公元前210年").unwrap();
+        assert_eq!(d.year, -210);
+        let d = parse_date("1024-03-05").unwrap();
+        assert_eq!((d.year, d.month, d.day), (1024, Some(3), Some(5)));
+        let d = parse_date("天元元年").unwrap();
+        assert_eq!((d.era.as_str(), d.year), ("天元", 1));
+    }
+
+    #[test]
+    fn timeline_doc_round_trips_and_sorts() {
+        let text = "# 时间线\n\n| 时间 | 事件 | 关联 |\n| --- | --- | --- |\n| 1025-01-01 | 下山 | 林风 |\n|1024-05-01|入门|林风|\n\n尾注\n";
+        let mut doc = TimelineDoc::parse(text);
+        assert_eq!(doc.to_markdown(), text);
+        doc.sort_by_date(&Calendars::new(&[]));
+        let events: Vec<&str> = doc.rows().map(|r| r.event()).collect();
+        assert_eq!(events, ["入门", "下山"]);
+    }
+
+    #[test]
+    fn checks_report_each_issue_at_its_line() {
+        let a = "# 时间线\n\n| 时间 | 事件 | 关联 |\n| --- | --- | --- |\n| 1024-05-01 | 入门 | 林风 |\n| 1024-03-01 | 拜师 | [[张三]] |\n| 不久之后 | 失踪 | |\n";
+        let b = "| 时间 | 事件 | 关联 |\n|---|---|---|\n| 1024-05-01 | 入门 | 林风、苏晴 |\n| 1024-01-01 | 出生 | 苏晴 |\n";
+        let docs = [(PathBuf::from("timeline/a.md"), TimelineDoc::parse(a)), (PathBuf::from("timeline/b.md"), TimelineDoc::parse(b))];
+        let calendars = Calendars::new(&[]);
+        let issues = check_timelines(
+            &docs,
+            |d| calendars.ordinal(d),
+            |name| ["林风", "苏晴"].contains(&name).then(|| PathBuf::from(name)),
+        );
+        let found: Vec<(&str, usize, TimelineIssueKind, &str)> = issues
+            .iter()
+            .map(|i| (i.file.file_name().unwrap().to_str().unwrap(), i.line, i.kind, i.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("a.md", 6, TimelineIssueKind::OutOfOrder, "「1024-03-01」早于第 5 行的事件"),
+                ("a.md", 6, TimelineIssueKind::BrokenLink, "「张三」"),
+                ("a.md", 7, TimelineIssueKind::UnparsedDate, "「不久之后」"),
+                ("b.md", 3, TimelineIssueKind::Duplicate, "与 a.md 第 5 行相同"),
+                ("b.md", 4, TimelineIssueKind::OutOfOrder, "「1024-01-01」早于第 3 行的事件"),
+            ]
+        );
+    }
+
+    #[test]
+    fn renaming_links_keeps_separators_and_wiki_links() {
+        let mut doc = TimelineDoc::parse("| 时间 | 事件 | 关联 |\n| --- | --- | --- |\n| 1024-05-01 | 入门 | 林风、 [[林风]]，苏晴 |\n");
+        let rename = |name: &str| (name == "林风").then(|| "林枫".to_string());
+        assert!(doc.rows_mut().next().unwrap().rename_links(rename));
+        assert_eq!(doc.rows().next().unwrap().links_text(), "林枫、 [[林风]]，苏晴");
+        assert!(!doc.rows_mut().next().unwrap().rename_links(rename));
+    }
+}
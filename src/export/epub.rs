@@ -0,0 +1,312 @@
+use super::zip::ZipWriter;
+use super::{book_file_stem, read_chapters, strip_leading_heading};
+use crate::domain::{now_unix, Project};
+use crate::storage;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const MIMETYPE: &str = "application/epub+zip";
+pub const OPF_PATH: &str = "OEBPS/content.opf";
+const DEFAULT_LANGUAGE: &str = "zh-CN";
+
+const STYLE_CSS: &str = "@charset \"UTF-8\";
+html {
+  font-family: \"Noto Serif CJK SC\", \"Source Han Serif SC\", \"Songti SC\", \"SimSun\", serif;
+  line-height: 1.8;
+}
+body {
+  margin: 0 5%;
+  text-align: justify;
+  line-break: strict;
+  word-break: normal;
+}
+h1 {
+  font-size: 1.4em;
+  text-align: center;
+  margin: 2em 0 1.5em;
+}
+h2, h3, h4, h5, h6 {
+  font-size: 1.1em;
+  margin: 1.5em 0 1em;
+}
+p {
+  text-indent: 2em;
+  margin: 0 0 0.4em;
+}
+blockquote {
+  margin: 1em 2em;
+}
+hr {
+  border: none;
+  text-align: center;
+  margin: 1.5em 0;
+}
+hr::after {
+  content: \"＊　＊　＊\";
+}
+nav ol {
+  list-style: none;
+  padding: 0;
+}
+";
+
+/// Writes `书名.epub` into `dst_dir` (the project's `exports/` by default).
+pub fn export_epub(project: &Project, dst_dir: Option<&Path>) -> io::Result<PathBuf> {
+    let bytes = build_epub(project)?;
+    let dir = dst_dir.map(Path::to_path_buf).unwrap_or_else(|| project.root.join("exports"));
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}.epub", book_file_stem(project)));
+    storage::write_bytes_atomic(&path, &bytes)?;
+    Ok(path)
+}
+
+/// Builds an EPUB 3 package with one XHTML document per chapter, in `NNNN-` order.
+pub fn build_epub(project: &Project) -> io::Result<Vec<u8>> {
+    let chapters = read_chapters(&project.root)?;
+    let meta = &project.meta;
+    let lang = meta.language.as_deref().map(str::trim).filter(|s| !s.is_empty()).unwrap_or(DEFAULT_LANGUAGE);
+    let title = if meta.name.trim().is_empty() { "Untitled" } else { meta.name.trim() };
+
+    let mut zip = ZipWriter::new();
+    // The mimetype entry must come first and be stored uncompressed.
+    zip.add_stored("mimetype", MIMETYPE.as_bytes())?;
+    zip.add_stored("META-INF/container.xml", container_xml().as_bytes())?;
+
+    let mut manifest = String::new();
+    let mut spine = String::new();
+    let mut nav_items = String::new();
+    for (i, chapter) in chapters.iter().enumerate() {
+        let id = format!("chapter-{:04}", i + 1);
+        let href = format!("{}.xhtml", id);
+        let chapter_title = if chapter.title.is_empty() { format!("第{}章", i + 1) } else { chapter.title.clone() };
+        let body = markdown_to_xhtml(strip_leading_heading(&chapter.body));
+        let doc = xhtml_document(lang, &chapter_title, &format!(
+            "<section epub:type=\"chapter\" role=\"doc-chapter\">\n<h1>{}</h1>\n{}</section>\n",
+            escape_xml(&chapter_title),
+            body
+        ));
+        zip.add_stored(&format!("OEBPS/{}", href), doc.as_bytes())?;
+
+        manifest.push_str(&format!(
+            "    <item id=\"{}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>\n",
+            id, href
+        ));
+        spine.push_str(&format!("    <itemref idref=\"{}\"/>\n", id));
+        nav_items.push_str(&format!(
+            "      <li><a href=\"{}\">{}</a></li>\n",
+            href,
+            escape_xml(&chapter_title)
+        ));
+    }
+
+    let nav = xhtml_document(lang, "目录", &format!(
+        "<nav epub:type=\"toc\" id=\"toc\" role=\"doc-toc\">\n  <h1>目录</h1>\n  <ol>\n{}  </ol>\n</nav>\n",
+        nav_items
+    ));
+    zip.add_stored("OEBPS/nav.xhtml", nav.as_bytes())?;
+    zip.add_stored("OEBPS/style.css", STYLE_CSS.as_bytes())?;
+
+    let mut metadata = String::new();
+    metadata.push_str(&format!(
+        "    <dc:identifier id=\"book-id\">urn:novel-outline-tool:{}:{}</dc:identifier>\n",
+        meta.created_unix,
+        escape_xml(title)
+    ));
+    metadata.push_str(&format!("    <dc:title>{}</dc:title>\n", escape_xml(title)));
+    metadata.push_str(&format!("    <dc:language>{}</dc:language>\n", escape_xml(lang)));
+    if let Some(author) = meta.author.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        metadata.push_str(&format!("    <dc:creator>{}</dc:creator>\n", escape_xml(author)));
+    }
+    metadata.push_str(&format!(
+        "    <meta property=\"dcterms:modified\">{}</meta>\n",
+        format_utc(now_unix())
+    ));
+
+    let opf = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"book-id\" xml:lang=\"{lang}\">
+  <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">
+{metadata}  </metadata>
+  <manifest>
+    <item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>
+    <item id=\"css\" href=\"style.css\" media-type=\"text/css\"/>
+{manifest}  </manifest>
+  <spine>
+{spine}  </spine>
+</package>
+",
+        lang = escape_xml(lang),
+        metadata = metadata,
+        manifest = manifest,
+        spine = spine,
+    );
+    zip.add_stored(OPF_PATH, opf.as_bytes())?;
+
+    Ok(zip.finish())
+}
+
+fn container_xml() -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">
+  <rootfiles>
+    <rootfile full-path=\"{}\" media-type=\"application/oebps-package+xml\"/>
+  </rootfiles>
+</container>
+",
+        OPF_PATH
+    )
+}
+
+fn xhtml_document(lang: &str, title: &str, body: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<!DOCTYPE html>
+<html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\" xml:lang=\"{lang}\" lang=\"{lang}\">
+<head>
+<meta charset=\"UTF-8\"/>
+<title>{title}</title>
+<link rel=\"stylesheet\" type=\"text/css\" href=\"style.css\"/>
+</head>
+<body>
+{body}</body>
+</html>
+",
+        lang = escape_xml(lang),
+        title = escape_xml(title),
+        body = body,
+    )
+}
+
+/// Converts the subset of Markdown used in chapter files. Every non-blank line
+/// becomes its own paragraph, which is how Chinese prose is normally typed.
+fn markdown_to_xhtml(md: &str) -> String {
+    let mut out = String::new();
+    let mut list_open = false;
+    for raw in without_comments(md).lines() {
+        let line = raw.trim();
+        let is_item = line.starts_with("- ") || line.starts_with("* ");
+        if list_open && !is_item {
+            out.push_str("</ul>\n");
+            list_open = false;
+        }
+        if line.is_empty() {
+            continue;
+        }
+        if is_item {
+            if !list_open {
+                out.push_str("<ul>\n");
+                list_open = true;
+            }
+            out.push_str(&format!("<li>{}</li>\n", inline_xhtml(line[2..].trim())));
+        } else if line == "---" || line == "***" || line == "* * *" {
+            out.push_str("<hr/>\n");
+        } else if let Some(rest) = line.strip_prefix('>') {
+            out.push_str(&format!("<blockquote><p>{}</p></blockquote>\n", inline_xhtml(rest.trim())));
+        } else if let Some((level, text)) = heading(line) {
+            // Chapter titles are the document's h1, so body headings start at h2.
+            let level = (level + 1).min(6);
+            out.push_str(&format!("<h{0}>{1}</h{0}>\n", level, inline_xhtml(text)));
+        } else {
+            out.push_str(&format!("<p>{}</p>\n", inline_xhtml(line)));
+        }
+    }
+    if list_open {
+        out.push_str("</ul>\n");
+    }
+    out
+}
+
+/// `md` with `<!-- -->` comments, such as scene markers, removed.
+fn without_comments(md: &str) -> String {
+    let mut out = String::with_capacity(md.len());
+    let mut rest = md;
+    while let Some(start) = rest.find("<!--") {
+        out.push_str(&rest[..start]);
+        rest = match rest[start + 4..].find("-->") {
+            Some(end) => &rest[start + 4 + end + 3..],
+            None => "",
+        };
+    }
+    out.push_str(rest);
+    out
+}
+
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    let text = line[level..].strip_prefix(' ')?;
+    Some((level, text.trim()))
+}
+
+/// Escapes text and renders `**strong**` and `*em*` spans; unmatched markers stay literal.
+fn inline_xhtml(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let (marker, tag) = if rest.starts_with("**") {
+            ("**", "strong")
+        } else if rest.starts_with('*') {
+            ("*", "em")
+        } else {
+            let next = rest.find('*').unwrap_or(rest.len());
+            out.push_str(&escape_xml(&rest[..next]));
+            rest = &rest[next..];
+            continue;
+        };
+        let after = &rest[marker.len()..];
+        match after.find(marker) {
+            Some(end) if end > 0 => {
+                out.push_str(&format!("<{0}>{1}</{0}>", tag, escape_xml(&after[..end])));
+                rest = &after[end + marker.len()..];
+            }
+            _ => {
+                out.push_str(&escape_xml(marker));
+                rest = after;
+            }
+        }
+    }
+    out
+}
+
+pub fn escape_xml(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// `YYYY-MM-DDThh:mm:ssZ`, as required by `dcterms:modified`.
+fn format_utc(unix: u64) -> String {
+    let days = (unix / 86_400) as i64;
+    let secs = unix % 86_400;
+    // Civil-from-days conversion (proleptic Gregorian calendar).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3_600,
+        (secs % 3_600) / 60,
+        secs % 60
+    )
+}
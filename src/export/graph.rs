@@ -0,0 +1,331 @@
+use super::book_file_stem;
+use super::epub::escape_xml;
+use crate::domain::character::{character_name, legacy_bullet, parse_character, parse_relation_list};
+use crate::domain::{Project, RelationKind, Section};
+use crate::storage;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+pub struct GraphNode {
+    pub name: String,
+    pub role: Option<String>,
+    pub faction: Option<String>,
+    /// False for names that only appear as relation targets and have no character file.
+    pub has_file: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct GraphEdge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: RelationKind,
+    pub label: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RelationGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+/// Collects relations from `relationships:` front matter and legacy `- 关系：` bullets of every character file.
+pub fn build_relation_graph(project_root: &Path) -> io::Result<RelationGraph> {
+    let dir = project_root.join(Section::Characters.dir_name());
+    let mut graph = RelationGraph::default();
+    let mut aliases: Vec<(String, usize)> = Vec::new();
+    let mut pending: Vec<(usize, Vec<crate::domain::Relationship>)> = Vec::new();
+
+    for path in storage::list_markdown_files(&dir)? {
+        let content = storage::read_text(&path)?;
+        let (meta, body) = parse_character(&content);
+        let name = character_name(&path, body);
+        let idx = graph.nodes.len();
+        graph.nodes.push(GraphNode {
+            name,
+            role: meta.role.clone().filter(|s| !s.trim().is_empty()),
+            faction: meta.faction.clone().filter(|s| !s.trim().is_empty()),
+            has_file: true,
+        });
+        aliases.extend(meta.aliases.iter().map(|a| (a.trim().to_string(), idx)));
+        if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+            aliases.push((stem.to_string(), idx));
+        }
+
+        let mut relations = meta.relationships.clone();
+        for line in body.lines() {
+            if let Some(value) = legacy_bullet(line, "关系") {
+                relations.extend(parse_relation_list(value));
+            }
+        }
+        pending.push((idx, relations));
+    }
+
+    for (from, relations) in pending {
+        for rel in relations {
+            let target = rel.target.trim();
+            let to = match graph
+                .nodes
+                .iter()
+                .position(|n| n.name == target)
+                .or_else(|| aliases.iter().find(|(a, _)| a == target).map(|(_, i)| *i))
+            {
+                Some(i) => i,
+                None => {
+                    graph.nodes.push(GraphNode {
+                        name: target.to_string(),
+                        role: None,
+                        faction: None,
+                        has_file: false,
+                    });
+                    graph.nodes.len() - 1
+                }
+            };
+            let label = rel.display_label().to_string();
+            if graph.edges.iter().any(|e| e.from == from && e.to == to && e.label == label) {
+                continue;
+            }
+            graph.edges.push(GraphEdge {
+                from,
+                to,
+                kind: rel.kind,
+                label,
+            });
+        }
+    }
+    Ok(graph)
+}
+
+fn kind_color(kind: RelationKind) -> &'static str {
+    match kind {
+        RelationKind::Family => "#8b5cf6",
+        RelationKind::Friend | RelationKind::Ally => "#16a34a",
+        RelationKind::Rival => "#f59e0b",
+        RelationKind::Enemy => "#dc2626",
+        RelationKind::Lover => "#db2777",
+        RelationKind::Mentor | RelationKind::Student => "#2563eb",
+        RelationKind::Superior | RelationKind::Subordinate => "#0891b2",
+        RelationKind::Other => "#6b7280",
+    }
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl RelationGraph {
+    pub fn to_dot(&self, title: &str) -> String {
+        let mut out = String::new();
+        out.push_str(&format!("digraph \"{}\" {{\n", dot_escape(title)));
+        out.push_str("  graph [fontname=\"Microsoft YaHei\", overlap=false, splines=true];\n");
+        out.push_str("  node [fontname=\"Microsoft YaHei\", shape=ellipse, style=filled, fillcolor=\"#eff6ff\"];\n");
+        out.push_str("  edge [fontname=\"Microsoft YaHei\", fontsize=10];\n");
+        for (i, node) in self.nodes.iter().enumerate() {
+            let mut label = dot_escape(&node.name);
+            if let Some(role) = &node.role {
+                label.push_str(&format!("\\n{}", dot_escape(role)));
+            }
+            let style = if node.has_file { "" } else { ", style=dashed, fillcolor=\"#ffffff\"" };
+            out.push_str(&format!("  n{} [label=\"{}\"{}];\n", i, label, style));
+        }
+        for edge in &self.edges {
+            let color = kind_color(edge.kind);
+            out.push_str(&format!(
+                "  n{} -> n{} [label=\"{}\", color=\"{}\", fontcolor=\"{}\"];\n",
+                edge.from,
+                edge.to,
+                dot_escape(&edge.label),
+                color,
+                color
+            ));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Standalone SVG with the characters laid out on a circle; no external fonts or scripts.
+    pub fn to_svg(&self, title: &str) -> String {
+        let n = self.nodes.len().max(1);
+        let radius = (60.0 * n as f64 / std::f64::consts::PI).max(140.0);
+        let node_r = 34.0;
+        let margin = 90.0;
+        let size = 2.0 * (radius + margin);
+        let center = size / 2.0;
+        let positions: Vec<(f64, f64)> = (0..self.nodes.len())
+            .map(|i| {
+                let angle = -std::f64::consts::FRAC_PI_2 + 2.0 * std::f64::consts::PI * i as f64 / n as f64;
+                (center + radius * angle.cos(), center + 30.0 + radius * angle.sin())
+            })
+            .collect();
+
+        let mut out = String::new();
+        out.push_str(&format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0:.0}\" height=\"{1:.0}\" viewBox=\"0 0 {0:.0} {1:.0}\" \
+font-family=\"'Microsoft YaHei', 'PingFang SC', 'Noto Sans CJK SC', sans-serif\">\n",
+            size,
+            size + 30.0
+        ));
+        out.push_str(&format!("<title>{}</title>\n", escape_xml(title)));
+        out.push_str("<defs>\n");
+        for (i, kind) in all_kinds().iter().enumerate() {
+            if !self.edges.iter().any(|e| e.kind == *kind) {
+                continue;
+            }
+            out.push_str(&format!(
+                "  <marker id=\"arrow{}\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" markerWidth=\"7\" markerHeight=\"7\" orient=\"auto-start-reverse\">\
+<path d=\"M 0 0 L 10 5 L 0 10 z\" fill=\"{}\"/></marker>\n",
+                i,
+                kind_color(*kind)
+            ));
+        }
+        out.push_str("</defs>\n");
+        out.push_str(&format!("<rect width=\"100%\" height=\"100%\" fill=\"#ffffff\"/>\n<text x=\"{:.1}\" y=\"36\" text-anchor=\"middle\" font-size=\"20\" fill=\"#111827\">{}</text>\n", center, escape_xml(title)));
+
+        for edge in &self.edges {
+            let (x1, y1) = positions[edge.from];
+            let (x2, y2) = positions[edge.to];
+            let color = kind_color(edge.kind);
+            let marker = all_kinds().iter().position(|k| *k == edge.kind).unwrap_or(0);
+            if edge.from == edge.to {
+                out.push_str(&format!(
+                    "<path d=\"M {:.1} {:.1} c -40 -70 40 -70 0 0\" fill=\"none\" stroke=\"{}\" stroke-width=\"1.5\"/>\n",
+                    x1,
+                    y1 - node_r,
+                    color
+                ));
+                continue;
+            }
+            let (dx, dy) = (x2 - x1, y2 - y1);
+            let len = (dx * dx + dy * dy).sqrt().max(1.0);
+            let (ux, uy) = (dx / len, dy / len);
+            // Bend each edge to its own right so A→B and B→A do not overlap.
+            let (nx, ny) = (-uy, ux);
+            let bend = 24.0;
+            let (sx, sy) = (x1 + ux * node_r, y1 + uy * node_r);
+            let (ex, ey) = (x2 - ux * node_r, y2 - uy * node_r);
+            let (cx, cy) = ((sx + ex) / 2.0 + nx * bend, (sy + ey) / 2.0 + ny * bend);
+            out.push_str(&format!(
+                "<path d=\"M {:.1} {:.1} Q {:.1} {:.1} {:.1} {:.1}\" fill=\"none\" stroke=\"{}\" stroke-width=\"1.5\" marker-end=\"url(#arrow{})\"/>\n",
+                sx, sy, cx, cy, ex, ey, color, marker
+            ));
+            let (lx, ly) = ((sx + 2.0 * cx + ex) / 4.0, (sy + 2.0 * cy + ey) / 4.0);
+            out.push_str(&format!(
+                "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\" font-size=\"12\" fill=\"{}\" stroke=\"#ffffff\" stroke-width=\"3\" paint-order=\"stroke\">{}</text>\n",
+                lx,
+                ly + 4.0,
+                color,
+                escape_xml(&edge.label)
+            ));
+        }
+
+        for (node, (x, y)) in self.nodes.iter().zip(&positions) {
+            let (fill, dash) = if node.has_file { ("#eff6ff", "") } else { ("#ffffff", " stroke-dasharray=\"4 3\"") };
+            out.push_str(&format!(
+                "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{:.0}\" fill=\"{}\" stroke=\"#3b82f6\" stroke-width=\"1.5\"{}/>\n",
+                x, y, node_r, fill, dash
+            ));
+            let name_y = if node.role.is_some() { y - 2.0 } else { y + 5.0 };
+            out.push_str(&format!(
+                "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\" font-size=\"14\" fill=\"#111827\">{}</text>\n",
+                x,
+                name_y,
+                escape_xml(&node.name)
+            ));
+            if let Some(role) = &node.role {
+                out.push_str(&format!(
+                    "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\" font-size=\"10\" fill=\"#6b7280\">{}</text>\n",
+                    x,
+                    y + 14.0,
+                    escape_xml(role)
+                ));
+            }
+        }
+        out.push_str("</svg>\n");
+        out
+    }
+}
+
+fn all_kinds() -> [RelationKind; 11] {
+    [
+        RelationKind::Family,
+        RelationKind::Friend,
+        RelationKind::Ally,
+        RelationKind::Rival,
+        RelationKind::Enemy,
+        RelationKind::Lover,
+        RelationKind::Mentor,
+        RelationKind::Student,
+        RelationKind::Superior,
+        RelationKind::Subordinate,
+        RelationKind::Other,
+    ]
+}
+
+/// Writes `书名-关系图.dot` and `书名-关系图.svg` into `dst_dir` (the project's `exports/` by default).
+pub fn export_relation_graph(project: &Project, dst_dir: Option<&Path>) -> io::Result<(PathBuf, PathBuf)> {
+    let graph = build_relation_graph(&project.root)?;
+    let dir = dst_dir.map(Path::to_path_buf).unwrap_or_else(|| project.root.join("exports"));
+    std::fs::create_dir_all(&dir)?;
+    let title = format!("{} 人物关系", project.meta.name.trim());
+    let stem = format!("{}-关系图", book_file_stem(project));
+    let dot_path = dir.join(format!("{}.dot", stem));
+    let svg_path = dir.join(format!("{}.svg", stem));
+    storage::write_text_atomic(&dot_path, &graph.to_dot(&title))?;
+    storage::write_text_atomic(&svg_path, &graph.to_svg(&title))?;
+    Ok((dot_path, svg_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_dir;
+    use std::fs;
+
+    fn graph(tag: &str) -> (PathBuf, RelationGraph) {
+        let root = test_dir(tag);
+        let dir = root.join("characters");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("林风.md"),
+            "---\naliases: [小风]\nrole: 主角\nrelationships:\n- target: 苏晴\n  kind: friend\n  label: 挚友\n- target: 张三\n  kind: mentor\n  label: 师父\n- target: 林风\n  label: 心魔\n---\n\n# 角色：林风\n\n- 关系：挚友：苏晴\n",
+        )
+        .unwrap();
+        fs::write(dir.join("老鬼.md"), "# 角色：老\"鬼\"<壹>\n\n- 关系：对手：角色-苏晴\n").unwrap();
+        fs::write(dir.join("角色-苏晴.md"), "---\nrelationships:\n- target: 小风\n  kind: enemy\n---\n\n# 角色：苏晴\n").unwrap();
+        let graph = build_relation_graph(&root).unwrap();
+        (root, graph)
+    }
+
+    #[test]
+    fn targets_resolve_by_name_alias_and_file_stem() {
+        let (root, graph) = graph("graph");
+        let names: Vec<(&str, bool)> = graph.nodes.iter().map(|n| (n.name.as_str(), n.has_file)).collect();
+        assert_eq!(names, [("林风", true), ("老\"鬼\"<壹>", true), ("苏晴", true), ("张三", false)]);
+        let edges: Vec<(usize, usize, &str)> = graph.edges.iter().map(|e| (e.from, e.to, e.label.as_str())).collect();
+        // The legacy bullet repeats the front matter's 挚友 and is dropped.
+        assert_eq!(edges, [(0, 2, "挚友"), (0, 3, "师父"), (0, 0, "心魔"), (1, 2, "对手"), (2, 0, "敌人")]);
+        assert_eq!(graph.edges[4].kind, RelationKind::Enemy);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn names_are_escaped_and_every_node_is_drawn() {
+        let (root, graph) = graph("graph-render");
+        let dot = graph.to_dot("青云\"记\"");
+        assert!(dot.starts_with("digraph \"青云\\\"记\\\"\" {\n"));
+        assert!(dot.contains("  n0 [label=\"林风\\n主角\"];\n"));
+        assert!(dot.contains("  n1 [label=\"老\\\"鬼\\\"<壹>\"];\n"));
+        assert!(dot.contains("  n3 [label=\"张三\", style=dashed, fillcolor=\"#ffffff\"];\n"));
+        assert!(dot.contains("  n0 -> n0 [label=\"心魔\""));
+
+        let svg = graph.to_svg("青云<记>");
+        assert!(svg.contains("<title>青云&lt;记&gt;</title>"));
+        assert!(svg.contains(">老&quot;鬼&quot;&lt;壹&gt;</text>"));
+        assert!(!svg.contains("<壹>"));
+        // 张三 only turns up as a target while edges are collected, and still gets a place.
+        assert_eq!(svg.matches("<circle ").count(), 4);
+        assert!(svg.contains("stroke-dasharray=\"4 3\"/>\n<text") && svg.contains(">张三</text>"));
+        assert!(svg.contains(" c -40 -70 40 -70 0 0\""));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,215 @@
+use crate::domain::{Project, Section};
+use crate::storage::{self, parse_front_matter};
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub mod epub;
+pub mod graph;
+pub mod threads;
+pub mod zip;
+
+#[derive(Debug, Clone)]
+pub struct CompileOptions {
+    /// Emit `## 标题` for each chapter from its file stem, replacing the chapter's own leading `#` heading.
+    pub inject_headings: bool,
+    pub table_of_contents: bool,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            inject_headings: true,
+            table_of_contents: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CompiledChapter {
+    pub path: PathBuf,
+    pub title: String,
+    /// Markdown body with front matter removed.
+    pub body: String,
+}
+
+/// Reads `chapters/` in outline order with front matter stripped.
+pub fn read_chapters(project_root: &Path) -> io::Result<Vec<CompiledChapter>> {
+    let dir = project_root.join(Section::Chapters.dir_name());
+    let mut out = Vec::new();
+    for path in storage::list_markdown_files_recursive(&dir)? {
+        let content = storage::read_text(&path)?;
+        let (_meta, body) = parse_front_matter::<serde_yaml::Value>(&content);
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+        let (_, title) = storage::split_chapter_stem(stem);
+        out.push(CompiledChapter {
+            title: title.trim().to_string(),
+            body: body.trim().to_string(),
+            path,
+        });
+    }
+    Ok(out)
+}
+
+pub fn compile_markdown(project: &Project, options: &CompileOptions) -> io::Result<String> {
+    let chapters = read_chapters(&project.root)?;
+
+    let mut out = String::new();
+    out.push_str("# ");
+    out.push_str(project.meta.name.trim());
+    out.push_str("\n\n");
+
+    let titles: Vec<String> = chapters
+        .iter()
+        .map(|c| {
+            if options.inject_headings {
+                c.title.clone()
+            } else {
+                leading_heading(&c.body).map(str::to_string).unwrap_or_else(|| c.title.clone())
+            }
+        })
+        .collect();
+
+    if options.table_of_contents && !chapters.is_empty() {
+        out.push_str("## 目录\n\n");
+        for (i, title) in titles.iter().enumerate() {
+            out.push_str(&format!("{}. [{}](#{})\n", i + 1, title, chapter_anchor(i)));
+        }
+        out.push('\n');
+    }
+
+    for (i, chapter) in chapters.iter().enumerate() {
+        out.push_str(&format!("<a id=\"{}\"></a>\n\n", chapter_anchor(i)));
+        let body = if options.inject_headings {
+            out.push_str("## ");
+            out.push_str(&titles[i]);
+            out.push_str("\n\n");
+            strip_leading_heading(&chapter.body)
+        } else {
+            chapter.body.as_str()
+        };
+        if !body.is_empty() {
+            out.push_str(body);
+            out.push_str("\n\n");
+        }
+    }
+
+    let trimmed = out.trim_end().len();
+    out.truncate(trimmed);
+    out.push('\n');
+    Ok(out)
+}
+
+/// Writes the compiled manuscript as `书名.md` into `dst_dir` (the project's `exports/` by default).
+pub fn export_markdown(project: &Project, dst_dir: Option<&Path>, options: &CompileOptions) -> io::Result<PathBuf> {
+    let text = compile_markdown(project, options)?;
+    let dir = dst_dir.map(Path::to_path_buf).unwrap_or_else(|| project.root.join("exports"));
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}.md", book_file_stem(project)));
+    storage::write_text_atomic(&path, &text)?;
+    Ok(path)
+}
+
+pub fn book_file_stem(project: &Project) -> String {
+    let name = storage::sanitize_filename(&project.meta.name);
+    if name.is_empty() {
+        "project".to_string()
+    } else {
+        name
+    }
+}
+
+fn chapter_anchor(i: usize) -> String {
+    format!("chapter-{:04}", i + 1)
+}
+
+fn leading_heading(body: &str) -> Option<&str> {
+    let first = body.lines().next()?;
+    let title = first.strip_prefix("# ")?.trim();
+    if title.is_empty() {
+        None
+    } else {
+        Some(title)
+    }
+}
+
+pub(crate) fn strip_leading_heading(body: &str) -> &str {
+    if leading_heading(body).is_none() {
+        return body;
+    }
+    match body.find('\n') {
+        Some(pos) => body[pos + 1..].trim_start(),
+        None => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{conflict, test_dir, ProjectStore};
+    use std::fs;
+
+    fn project(tag: &str) -> Project {
+        let mut project = ProjectStore::open_or_init(test_dir(tag)).unwrap();
+        project.meta.name = "青云记".to_string();
+        let chapters = project.root.join("chapters");
+        fs::remove_file(chapters.join("0001-第一章.md")).unwrap();
+        for (path, text) in [
+            ("02-第二卷/0001-重逢.md", "正文三。\n"),
+            ("01-第一卷/0001-下山.md", "正文二。\n"),
+            ("0001-开端.md", "---\nstatus: draft\npov: 林风\n---\n\n# 山门\n\n正文一。\n"),
+        ] {
+            let path = chapters.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+        project
+    }
+
+    #[test]
+    fn headings_replace_the_chapters_own_and_link_from_the_contents() {
+        let project = project("compile");
+        let text = compile_markdown(&project, &CompileOptions::default()).unwrap();
+        assert_eq!(
+            text,
+            "# 青云记\n\n## 目录\n\n1. [开端](#chapter-0001)\n2. [下山](#chapter-0002)\n3. [重逢](#chapter-0003)\n\n\
+<a id=\"chapter-0001\"></a>\n\n## 开端\n\n正文一。\n\n\
+<a id=\"chapter-0002\"></a>\n\n## 下山\n\n正文二。\n\n\
+<a id=\"chapter-0003\"></a>\n\n## 重逢\n\n正文三。\n"
+        );
+        fs::remove_dir_all(&project.root).unwrap();
+    }
+
+    #[test]
+    fn chapters_keep_their_own_headings_without_injection() {
+        let project = project("compile-plain");
+        let options = CompileOptions {
+            inject_headings: false,
+            table_of_contents: true,
+        };
+        let text = compile_markdown(&project, &options).unwrap();
+        assert!(text.contains("1. [山门](#chapter-0001)\n2. [下山](#chapter-0002)\n"));
+
+        let options = CompileOptions {
+            inject_headings: false,
+            table_of_contents: false,
+        };
+        assert_eq!(
+            compile_markdown(&project, &options).unwrap(),
+            "# 青云记\n\n<a id=\"chapter-0001\"></a>\n\n# 山门\n\n正文一。\n\n\
+<a id=\"chapter-0002\"></a>\n\n正文二。\n\n<a id=\"chapter-0003\"></a>\n\n正文三。\n"
+        );
+        assert_eq!(strip_leading_heading("# 山门\n\n正文"), "正文");
+        assert_eq!(strip_leading_heading("#山门\n正文"), "#山门\n正文");
+        fs::remove_dir_all(&project.root).unwrap();
+    }
+
+    #[test]
+    fn conflict_copies_are_not_chapters() {
+        let project = project("compile-conflict");
+        let path = project.root.join("chapters/0001-开端.md");
+        conflict::write_conflict_copy(&project.root, &path, "我的版本\n", "磁盘上的版本\n", 0).unwrap();
+        let titles: Vec<_> = read_chapters(&project.root).unwrap().into_iter().map(|c| c.title).collect();
+        assert_eq!(titles, ["开端", "下山", "重逢"]);
+        fs::remove_dir_all(&project.root).unwrap();
+    }
+}
@@ -0,0 +1,353 @@
+//! Storylines (故事线) across chapters: which chapters advance each thread, where a thread goes
+//! quiet for too long and which threads never come to an end.
+//!
+//! Chapters name their threads in front matter, `threads: [主线, 感情线]`, and end them with
+//! `resolves: [复仇线]`.
+
+use super::book_file_stem;
+use super::epub::escape_xml;
+use crate::domain::{Project, Section};
+use crate::storage;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Used when `project.md` has no `dormant_chapters`.
+pub const DEFAULT_DORMANT_CHAPTERS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadMark {
+    Absent,
+    Advanced,
+    Resolved,
+}
+
+#[derive(Debug, Clone)]
+pub struct ThreadRow {
+    pub name: String,
+    /// One mark per chapter of the report.
+    pub marks: Vec<ThreadMark>,
+    /// Runs of more than the limit's chapters without the thread, between its first appearance
+    /// and its resolution, or the last chapter when it is never resolved.
+    pub dormant: Vec<Range<usize>>,
+}
+
+impl ThreadRow {
+    /// Index of the chapter that resolves the thread.
+    pub fn resolved_at(&self) -> Option<usize> {
+        self.marks.iter().position(|m| *m == ThreadMark::Resolved)
+    }
+
+    pub fn last_seen(&self) -> Option<usize> {
+        self.marks.iter().rposition(|m| *m != ThreadMark::Absent)
+    }
+
+    fn is_dormant_at(&self, chapter: usize) -> bool {
+        self.dormant.iter().any(|r| r.contains(&chapter))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ThreadReport {
+    /// Chapters in outline order, as paths below `chapters/` without `.md`.
+    pub chapters: Vec<String>,
+    /// Threads in order of first appearance.
+    pub threads: Vec<ThreadRow>,
+    pub dormant_limit: usize,
+}
+
+/// Reads `threads:` and `resolves:` of every chapter in outline order.
+pub fn build_thread_report(project_root: &Path, dormant_limit: usize) -> io::Result<ThreadReport> {
+    let dir = project_root.join(Section::Chapters.dir_name());
+    let paths = storage::list_section_files(project_root, Section::Chapters)?;
+    let mut report = ThreadReport {
+        chapters: Vec::with_capacity(paths.len()),
+        threads: Vec::new(),
+        dormant_limit,
+    };
+    for (i, path) in paths.iter().enumerate() {
+        let content = storage::read_text(path)?;
+        let (meta, _body) = storage::parse_chapter(&content);
+        let rel = path.strip_prefix(&dir).unwrap_or(path).with_extension("");
+        report.chapters.push(rel.to_string_lossy().replace('\\', "/"));
+
+        let marks = meta
+            .threads
+            .iter()
+            .map(|t| (t, ThreadMark::Advanced))
+            .chain(meta.resolves.iter().map(|t| (t, ThreadMark::Resolved)));
+        for (name, mark) in marks {
+            let name = name.trim();
+            if name.is_empty() {
+                continue;
+            }
+            let row = match report.threads.iter().position(|t| t.name == name) {
+                Some(r) => &mut report.threads[r],
+                None => {
+                    report.threads.push(ThreadRow {
+                        name: name.to_string(),
+                        marks: vec![ThreadMark::Absent; paths.len()],
+                        dormant: Vec::new(),
+                    });
+                    report.threads.last_mut().expect("just pushed")
+                }
+            };
+            // `resolves:` wins over `threads:` for the same chapter.
+            if row.marks[i] != ThreadMark::Resolved {
+                row.marks[i] = mark;
+            }
+        }
+    }
+
+    let total = report.chapters.len();
+    for row in &mut report.threads {
+        let end = row.resolved_at().map_or(total, |r| r + 1);
+        let mut seen = (0..end).filter(|&i| row.marks[i] != ThreadMark::Absent).peekable();
+        let Some(mut last) = seen.next() else { continue };
+        for i in seen {
+            if i - last - 1 > dormant_limit {
+                row.dormant.push(last + 1..i);
+            }
+            last = i;
+        }
+        if row.resolved_at().is_none() && total - last - 1 > dormant_limit {
+            row.dormant.push(last + 1..total);
+        }
+    }
+    Ok(report)
+}
+
+/// `第 3 章` or `第 3–14 章`, counting chapters from 1 in outline order.
+fn chapter_span(range: &Range<usize>) -> String {
+    if range.len() == 1 {
+        format!("第 {} 章", range.start + 1)
+    } else {
+        format!("第 {}–{} 章", range.start + 1, range.end)
+    }
+}
+
+impl ThreadReport {
+    /// `沉寂` lines: a thread and a run of chapters it is missing from.
+    pub fn dormant_lines(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        for row in &self.threads {
+            for range in &row.dormant {
+                out.push(format!(
+                    "{}：{}（{} 至 {}）连续 {} 章未出现",
+                    row.name,
+                    chapter_span(range),
+                    self.chapters[range.start],
+                    self.chapters[range.end - 1],
+                    range.len()
+                ));
+            }
+        }
+        out
+    }
+
+    /// `未收束` lines: a thread no chapter resolves and where it was last seen.
+    pub fn unresolved_lines(&self) -> Vec<String> {
+        self.threads
+            .iter()
+            .filter(|row| row.resolved_at().is_none())
+            .filter_map(|row| {
+                let last = row.last_seen()?;
+                Some(format!("{}：最后出现于第 {} 章 {}", row.name, last + 1, self.chapters[last]))
+            })
+            .collect()
+    }
+
+    fn cell(&self, row: &ThreadRow, chapter: usize) -> &'static str {
+        match row.marks[chapter] {
+            ThreadMark::Advanced => "●",
+            ThreadMark::Resolved => "✔",
+            ThreadMark::Absent if row.is_dormant_at(chapter) => "○",
+            ThreadMark::Absent => "",
+        }
+    }
+
+    fn intro(&self) -> String {
+        format!(
+            "共 {} 章、{} 条故事线；连续超过 {} 章未出现的故事线视为沉寂。",
+            self.chapters.len(),
+            self.threads.len(),
+            self.dormant_limit
+        )
+    }
+
+    pub fn to_markdown(&self, title: &str) -> String {
+        let mut out = format!("# {}\n\n{}\n\n", title, self.intro());
+        for (heading, lines) in [("沉寂的故事线", self.dormant_lines()), ("未收束的故事线", self.unresolved_lines())] {
+            out.push_str(&format!("## {}\n\n", heading));
+            if lines.is_empty() {
+                out.push_str("无\n");
+            }
+            for line in lines {
+                out.push_str(&format!("- {}\n", line));
+            }
+            out.push('\n');
+        }
+
+        out.push_str("## 故事线 × 章节\n\n");
+        if self.threads.is_empty() {
+            out.push_str("还没有章节在 front matter 中写 `threads:`。\n");
+            return out;
+        }
+        out.push_str("| 章节 |");
+        for row in &self.threads {
+            out.push_str(&format!(" {} |", md_cell(&row.name)));
+        }
+        out.push_str("\n|---|");
+        out.push_str(&":---:|".repeat(self.threads.len()));
+        out.push('\n');
+        for (i, chapter) in self.chapters.iter().enumerate() {
+            out.push_str(&format!("| {}. {} |", i + 1, md_cell(chapter)));
+            for row in &self.threads {
+                out.push_str(&format!(" {} |", self.cell(row, i)));
+            }
+            out.push('\n');
+        }
+        out.push_str("\n● 推进　✔ 收束　○ 沉寂中\n");
+        out
+    }
+
+    /// Standalone HTML page with the same content; dormant runs are shaded.
+    pub fn to_html(&self, title: &str) -> String {
+        let mut out = String::new();
+        out.push_str("<!DOCTYPE html>\n<html lang=\"zh-CN\">\n<head>\n<meta charset=\"utf-8\"/>\n");
+        out.push_str(&format!("<title>{}</title>\n", escape_xml(title)));
+        out.push_str(
+            "<style>\n\
+body { font-family: 'Microsoft YaHei', 'PingFang SC', 'Noto Sans CJK SC', sans-serif; margin: 2em; color: #111827; }\n\
+table { border-collapse: collapse; }\n\
+th, td { border: 1px solid #d1d5db; padding: 4px 8px; }\n\
+thead th { position: sticky; top: 0; background: #f3f4f6; }\n\
+td.mark { text-align: center; min-width: 3em; }\n\
+td.advanced { background: #dbeafe; color: #1d4ed8; }\n\
+td.resolved { background: #dcfce7; color: #15803d; }\n\
+td.dormant { background: #fee2e2; }\n\
+</style>\n",
+        );
+        out.push_str("</head>\n<body>\n");
+        out.push_str(&format!("<h1>{}</h1>\n<p>{}</p>\n", escape_xml(title), escape_xml(&self.intro())));
+        for (heading, lines) in [("沉寂的故事线", self.dormant_lines()), ("未收束的故事线", self.unresolved_lines())] {
+            out.push_str(&format!("<h2>{}</h2>\n", heading));
+            if lines.is_empty() {
+                out.push_str("<p>无</p>\n");
+                continue;
+            }
+            out.push_str("<ul>\n");
+            for line in lines {
+                out.push_str(&format!("<li>{}</li>\n", escape_xml(&line)));
+            }
+            out.push_str("</ul>\n");
+        }
+
+        out.push_str("<h2>故事线 × 章节</h2>\n");
+        if self.threads.is_empty() {
+            out.push_str("<p>还没有章节在 front matter 中写 <code>threads:</code>。</p>\n</body>\n</html>\n");
+            return out;
+        }
+        out.push_str("<table>\n<thead><tr><th>章节</th>");
+        for row in &self.threads {
+            out.push_str(&format!("<th>{}</th>", escape_xml(&row.name)));
+        }
+        out.push_str("</tr></thead>\n<tbody>\n");
+        for (i, chapter) in self.chapters.iter().enumerate() {
+            out.push_str(&format!("<tr><th>{}. {}</th>", i + 1, escape_xml(chapter)));
+            for row in &self.threads {
+                let class = match row.marks[i] {
+                    ThreadMark::Advanced => "mark advanced",
+                    ThreadMark::Resolved => "mark resolved",
+                    ThreadMark::Absent if row.is_dormant_at(i) => "mark dormant",
+                    ThreadMark::Absent => "mark",
+                };
+                out.push_str(&format!("<td class=\"{}\">{}</td>", class, self.cell(row, i)));
+            }
+            out.push_str("</tr>\n");
+        }
+        out.push_str("</tbody>\n</table>\n<p>● 推进　✔ 收束　○ 沉寂中</p>\n</body>\n</html>\n");
+        out
+    }
+}
+
+fn md_cell(s: &str) -> String {
+    s.replace('|', "\\|")
+}
+
+/// Writes `书名-故事线.md` and `书名-故事线.html` into `dst_dir` (the project's `exports/` by
+/// default) and returns the report with both paths. `dormant_limit` overrides `dormant_chapters`
+/// of `project.md`.
+pub fn export_thread_report(project: &Project, dst_dir: Option<&Path>, dormant_limit: Option<usize>) -> io::Result<(ThreadReport, PathBuf, PathBuf)> {
+    let limit = dormant_limit.or(project.meta.dormant_chapters).unwrap_or(DEFAULT_DORMANT_CHAPTERS);
+    let report = build_thread_report(&project.root, limit)?;
+    let dir = dst_dir.map(Path::to_path_buf).unwrap_or_else(|| project.root.join("exports"));
+    std::fs::create_dir_all(&dir)?;
+    let title = format!("{} 故事线", project.meta.name.trim());
+    let stem = format!("{}-故事线", book_file_stem(project));
+    let md_path = dir.join(format!("{}.md", stem));
+    let html_path = dir.join(format!("{}.html", stem));
+    storage::write_text_atomic(&md_path, &report.to_markdown(&title))?;
+    storage::write_text_atomic(&html_path, &report.to_html(&title))?;
+    Ok((report, md_path, html_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_dir;
+    use std::fs;
+
+    fn chapter(threads: &str, resolves: &str) -> String {
+        format!("---\nthreads: [{}]\nresolves: [{}]\n---\n\n正文。\n", threads, resolves)
+    }
+
+    #[test]
+    fn dormant_runs_stop_at_the_resolution() {
+        let root = test_dir("threads");
+        let chapters = root.join("chapters");
+        fs::create_dir_all(chapters.join("01-第二卷")).unwrap();
+        for (name, threads, resolves) in [
+            ("0001-开端", "主线, 感情线", ""),
+            ("0002-下山", "主线, 复仇线", ""),
+            ("0003-入城", "主线", ""),
+            ("0004-重逢", "感情线", ""),
+            ("0005-夜谈", "", ""),
+            ("0006-离别", "", ""),
+            ("01-第二卷/0001-复仇", "复仇线", "复仇线"),
+            ("01-第二卷/0002-归来", "主线", ""),
+        ] {
+            fs::write(chapters.join(format!("{}.md", name)), chapter(threads, resolves)).unwrap();
+        }
+
+        let report = build_thread_report(&root, 2).unwrap();
+        assert_eq!(report.chapters.len(), 8);
+        assert_eq!(report.chapters[6], "01-第二卷/0001-复仇");
+        let names: Vec<&str> = report.threads.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["主线", "感情线", "复仇线"]);
+        let [main, love, revenge] = &report.threads[..] else { unreachable!() };
+        let runs = |row: &ThreadRow| row.dormant.iter().map(|r| (r.start, r.end)).collect::<Vec<_>>();
+        assert_eq!(runs(main), [(3, 7)]);
+        // Two chapters away is not more than the limit; the end of the book still counts.
+        assert_eq!(runs(love), [(4, 8)]);
+        assert_eq!(revenge.marks[6], ThreadMark::Resolved);
+        assert_eq!(runs(revenge), [(2, 6)]);
+
+        assert_eq!(
+            report.dormant_lines(),
+            [
+                "主线：第 4–7 章（0004-重逢 至 01-第二卷/0001-复仇）连续 4 章未出现",
+                "感情线：第 5–8 章（0005-夜谈 至 01-第二卷/0002-归来）连续 4 章未出现",
+                "复仇线：第 3–6 章（0003-入城 至 0006-离别）连续 4 章未出现",
+            ]
+        );
+        assert_eq!(
+            report.unresolved_lines(),
+            ["主线：最后出现于第 8 章 01-第二卷/0002-归来", "感情线：最后出现于第 4 章 0004-重逢"]
+        );
+
+        let report = build_thread_report(&root, 4).unwrap();
+        assert!(report.dormant_lines().is_empty());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,197 @@
+//! Minimal ZIP container support: uncompressed ("stored") entries only, which is
+//! all an EPUB needs and keeps the crate free of compression dependencies.
+
+use std::io;
+
+const LOCAL_HEADER_SIG: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIG: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIR_SIG: u32 = 0x0605_4b50;
+/// 1980-01-01, the earliest date a ZIP header can hold.
+const DOS_DATE: u16 = 0x0021;
+const FLAG_UTF8: u16 = 0x0800;
+
+pub struct ZipWriter {
+    buf: Vec<u8>,
+    central: Vec<u8>,
+    entries: u16,
+}
+
+impl ZipWriter {
+    pub fn new() -> Self {
+        Self {
+            buf: Vec::new(),
+            central: Vec::new(),
+            entries: 0,
+        }
+    }
+
+    pub fn add_stored(&mut self, name: &str, data: &[u8]) -> io::Result<()> {
+        if self.entries == u16::MAX || self.buf.len() + data.len() > u32::MAX as usize {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "zip archive too large"));
+        }
+        let offset = self.buf.len() as u32;
+        let crc = crc32(data);
+        let flags = if name.is_ascii() { 0 } else { FLAG_UTF8 };
+        let name_bytes = name.as_bytes();
+
+        push_u32(&mut self.buf, LOCAL_HEADER_SIG);
+        push_u16(&mut self.buf, 10);
+        push_u16(&mut self.buf, flags);
+        push_u16(&mut self.buf, 0);
+        push_u16(&mut self.buf, 0);
+        push_u16(&mut self.buf, DOS_DATE);
+        push_u32(&mut self.buf, crc);
+        push_u32(&mut self.buf, data.len() as u32);
+        push_u32(&mut self.buf, data.len() as u32);
+        push_u16(&mut self.buf, name_bytes.len() as u16);
+        push_u16(&mut self.buf, 0);
+        self.buf.extend_from_slice(name_bytes);
+        self.buf.extend_from_slice(data);
+
+        push_u32(&mut self.central, CENTRAL_HEADER_SIG);
+        push_u16(&mut self.central, 20);
+        push_u16(&mut self.central, 10);
+        push_u16(&mut self.central, flags);
+        push_u16(&mut self.central, 0);
+        push_u16(&mut self.central, 0);
+        push_u16(&mut self.central, DOS_DATE);
+        push_u32(&mut self.central, crc);
+        push_u32(&mut self.central, data.len() as u32);
+        push_u32(&mut self.central, data.len() as u32);
+        push_u16(&mut self.central, name_bytes.len() as u16);
+        push_u16(&mut self.central, 0);
+        push_u16(&mut self.central, 0);
+        push_u16(&mut self.central, 0);
+        push_u16(&mut self.central, 0);
+        push_u32(&mut self.central, 0);
+        push_u32(&mut self.central, offset);
+        self.central.extend_from_slice(name_bytes);
+
+        self.entries += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        let cd_offset = self.buf.len() as u32;
+        let cd_size = self.central.len() as u32;
+        self.buf.extend_from_slice(&self.central);
+        push_u32(&mut self.buf, END_OF_CENTRAL_DIR_SIG);
+        push_u16(&mut self.buf, 0);
+        push_u16(&mut self.buf, 0);
+        push_u16(&mut self.buf, self.entries);
+        push_u16(&mut self.buf, self.entries);
+        push_u32(&mut self.buf, cd_size);
+        push_u32(&mut self.buf, cd_offset);
+        push_u16(&mut self.buf, 0);
+        self.buf
+    }
+}
+
+impl Default for ZipWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ZipEntry {
+    pub name: String,
+    pub data: Vec<u8>,
+    /// Byte offset of the entry's local header.
+    pub offset: usize,
+    pub stored: bool,
+}
+
+/// Reads every entry through the central directory, checking CRCs. Only stored entries are supported.
+pub fn read_archive(bytes: &[u8]) -> io::Result<Vec<ZipEntry>> {
+    let eocd = (0..bytes.len().saturating_sub(21))
+        .rev()
+        .find(|&i| read_u32(bytes, i) == Some(END_OF_CENTRAL_DIR_SIG))
+        .ok_or_else(|| invalid("end of central directory not found"))?;
+    let count = read_u16(bytes, eocd + 10).ok_or_else(|| invalid("truncated archive"))? as usize;
+    let mut pos = read_u32(bytes, eocd + 16).ok_or_else(|| invalid("truncated archive"))? as usize;
+
+    let mut out = Vec::with_capacity(count);
+    for _ in 0..count {
+        if read_u32(bytes, pos) != Some(CENTRAL_HEADER_SIG) {
+            return Err(invalid("bad central directory header"));
+        }
+        let field = |off: usize| read_u16(bytes, pos + off).ok_or_else(|| invalid("truncated archive"));
+        let method = field(10)?;
+        let crc = read_u32(bytes, pos + 16).ok_or_else(|| invalid("truncated archive"))?;
+        let size = read_u32(bytes, pos + 20).ok_or_else(|| invalid("truncated archive"))? as usize;
+        let name_len = field(28)? as usize;
+        let extra_len = field(30)? as usize;
+        let comment_len = field(32)? as usize;
+        let offset = read_u32(bytes, pos + 42).ok_or_else(|| invalid("truncated archive"))? as usize;
+        let name = bytes
+            .get(pos + 46..pos + 46 + name_len)
+            .ok_or_else(|| invalid("truncated archive"))?;
+        let name = String::from_utf8_lossy(name).to_string();
+        pos += 46 + name_len + extra_len + comment_len;
+
+        if read_u32(bytes, offset) != Some(LOCAL_HEADER_SIG) {
+            return Err(invalid("bad local file header"));
+        }
+        let local_name_len = read_u16(bytes, offset + 26).ok_or_else(|| invalid("truncated archive"))? as usize;
+        let local_extra_len = read_u16(bytes, offset + 28).ok_or_else(|| invalid("truncated archive"))? as usize;
+        let start = offset + 30 + local_name_len + local_extra_len;
+        let data = bytes.get(start..start + size).ok_or_else(|| invalid("truncated entry"))?.to_vec();
+        if method == 0 && crc32(&data) != crc {
+            return Err(invalid("crc mismatch"));
+        }
+        out.push(ZipEntry {
+            name,
+            data,
+            offset,
+            stored: method == 0,
+        });
+    }
+    Ok(out)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn read_u16(bytes: &[u8], at: usize) -> Option<u16> {
+    let b = bytes.get(at..at + 2)?;
+    Some(u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    let b = bytes.get(at..at + 4)?;
+    Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn push_u16(buf: &mut Vec<u8>, v: u16) {
+    buf.extend_from_slice(&v.to_le_bytes());
+}
+
+fn push_u32(buf: &mut Vec<u8>, v: u32) {
+    buf.extend_from_slice(&v.to_le_bytes());
+}
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { 0xEDB8_8320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+};
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut c = 0xFFFF_FFFFu32;
+    for &b in data {
+        c = CRC_TABLE[((c ^ b as u32) & 0xFF) as usize] ^ (c >> 8);
+    }
+    c ^ 0xFFFF_FFFF
+}
@@ -0,0 +1,4106 @@
+ use std::mem::{size_of, MaybeUninit};
+use std::collections::HashMap;
+use std::ffi::c_void;
+ use std::path::{Path, PathBuf};
+ use std::ptr::{null, null_mut};
+ 
+ use windows_sys::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+use windows_sys::Win32::Graphics::Gdi::{
+    BeginPaint, CreateFontIndirectW, CreateFontW, CreateSolidBrush, DeleteObject, DrawTextW, EndPaint, FrameRect, GetStockObject,
+    GetTextMetricsW, SelectObject, SetBkColor, SetBkMode, SetTextColor, CLIP_DEFAULT_PRECIS, CLEARTYPE_QUALITY, DEFAULT_CHARSET,
+    DEFAULT_PITCH, DT_CENTER, DT_EDITCONTROL, DT_END_ELLIPSIS, DT_NOPREFIX, DT_SINGLELINE, DT_VCENTER, DT_WORDBREAK, FF_DONTCARE, FW_NORMAL, HBRUSH, HDC,
+    LOGFONTW, OUT_DEFAULT_PRECIS, PAINTSTRUCT, TEXTMETRICW, TRANSPARENT, WHITE_BRUSH,
+};
+ use windows_sys::Win32::System::Com::{CoInitializeEx, CoUninitialize, COINIT_APARTMENTTHREADED};
+ use windows_sys::Win32::System::LibraryLoader::{GetModuleHandleW, LoadLibraryW};
+ use windows_sys::Win32::UI::Controls::{
+     InitCommonControlsEx, INITCOMMONCONTROLSEX, ICC_STANDARD_CLASSES, SB_SETTEXTW, STATUSCLASSNAMEW,
+     TCM_GETCURSEL, TCM_INSERTITEMW, TCITEMW, TCN_SELCHANGE, TVGN_CARET, TVGN_CHILD, TVGN_NEXT, TVGN_PARENT, TVI_ROOT, TVIF_PARAM,
+     TVIF_TEXT, TVINSERTSTRUCTW, TVITEMEXW, TVM_DELETEITEM, TVM_INSERTITEMW, TVM_SELECTITEM,
+    TVHITTESTINFO, TVM_EDITLABELW, TVM_GETITEMW, TVM_GETNEXTITEM, TVM_HITTEST, TVM_SETBKCOLOR, TVM_SETEXTENDEDSTYLE,
+    TVM_GETEDITCONTROL, TVM_SETITEMHEIGHT, TVM_SETITEMW, TVM_SETLINECOLOR, TVM_SETTEXTCOLOR, TVN_BEGINDRAGW,
+    TVE_EXPAND, TVM_EXPAND, TVN_BEGINLABELEDITW, TVN_ENDLABELEDITW, TVN_SELCHANGEDW, TVS_EDITLABELS,
+    TVS_EX_DOUBLEBUFFER, TVS_FULLROWSELECT,
+     TVS_HASLINES, TVS_LINESATROOT, TVS_SHOWSELALWAYS, WC_TABCONTROLW, WC_TREEVIEWW, NMTVDISPINFOW, SB_SETPARTS,
+ };
+use windows::Win32::UI::Controls::RichEdit::{
+    CHARFORMAT2W, CFE_AUTOCOLOR, CFE_BOLD, CFM_BOLD, CFM_COLOR, EM_SETCHARFORMAT, SCF_DEFAULT, SCF_SELECTION,
+};
+use windows_sys::Win32::UI::HiDpi::{GetDpiForWindow, SetProcessDpiAwarenessContext, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2};
+ use windows_sys::Win32::UI::WindowsAndMessaging::{
+    AppendMenuW, BeginDeferWindowPos, CheckMenuItem, CreateMenu, CreatePopupMenu, CreateWindowExW, DefWindowProcW, DeferWindowPos, DestroyWindow,
+    DispatchMessageW, DrawMenuBar, EndDeferWindowPos, GetClientRect, GetMenu, GetMessageW, GetParent, LoadCursorW, LoadIconW, PostQuitMessage,
+    RegisterClassExW, SendMessageW, SetCursor, SetMenu, SetWindowLongPtrW, SetWindowPos, ShowWindow, SystemParametersInfoW,
+    TranslateMessage, CS_DBLCLKS, CW_USEDEFAULT, GWLP_USERDATA, HMENU,
+    ICON_BIG, ICON_SMALL, IDC_ARROW, IDC_SIZEWE, IDI_APPLICATION, MF_CHECKED, MF_POPUP, MF_STRING, MF_UNCHECKED, MSG, NONCLIENTMETRICSW, SPI_GETNONCLIENTMETRICS,
+    SW_HIDE, SW_SHOW,
+    SWP_NOACTIVATE, SWP_NOZORDER, WM_COMMAND, WM_CREATE, WM_CTLCOLORDLG, WM_CTLCOLOREDIT,
+    WM_CTLCOLORSTATIC, WM_DESTROY, WM_DPICHANGED, WM_ERASEBKGND, WM_KEYDOWN, WM_LBUTTONDBLCLK, WM_LBUTTONDOWN, WM_LBUTTONUP,
+    WM_CAPTURECHANGED, WM_MOUSEMOVE, WM_MOUSEWHEEL, WM_NCCREATE, WM_NOTIFY, WM_PAINT, WM_SETCURSOR, WM_SETFONT, WM_SETICON, WM_SIZE, WM_TIMER,
+     WS_EX_CLIENTEDGE,
+     ES_AUTOVSCROLL, ES_AUTOHSCROLL, ES_MULTILINE, EN_CHANGE, GetWindowTextLengthW,
+     GetWindowTextW, KillTimer, SetTimer, SetWindowTextW, WS_HSCROLL, WS_VSCROLL,
+     WNDCLASSEXW, WS_CHILD, WS_CLIPCHILDREN, WS_OVERLAPPEDWINDOW, WS_VISIBLE,
+ };
+ 
+ use novel_outline_tool::domain::diff::{diff_lines, format_diff};
+use novel_outline_tool::domain::scene::{parse_scenes, scene_label, Scene};
+ use novel_outline_tool::domain::{ChapterStatus, GitSettings, Project, Section};
+use novel_outline_tool::search::index::SearchIndex;
+use novel_outline_tool::search::replace::{self, FileReplace, ReplaceScope};
+use novel_outline_tool::search::{self, SearchHit, SearchMode, SearchOptions};
+use novel_outline_tool::stats;
+use novel_outline_tool::stats::history::{self, WritingHistory};
+use novel_outline_tool::storage::links::{apply_link_edits, link_rewrites, IndexedLink, LinkEdit, LinkIndex};
+use novel_outline_tool::storage::conflict::{self, DiskStamp, ExternalChange};
+use novel_outline_tool::storage::git::{self, GitRepo};
+use novel_outline_tool::storage::recovery;
+use novel_outline_tool::storage::outline::{self, ChapterCard, OutlineItem, OutlineKind};
+use novel_outline_tool::storage::snapshots;
+ use novel_outline_tool::storage::versions::{self, FileVersion};
+use novel_outline_tool::storage::watch::ProjectWatcher;
+ use novel_outline_tool::storage::{self, ProjectStore};
+ 
+#[link(name = "user32")]
+extern "system" {
+    fn SetCapture(hWnd: isize) -> isize;
+    fn ReleaseCapture() -> i32;
+    fn GetCursorPos(lpPoint: *mut windows_sys::Win32::Foundation::POINT) -> i32;
+    fn ScreenToClient(hWnd: isize, lpPoint: *mut windows_sys::Win32::Foundation::POINT) -> i32;
+    fn FillRect(hDC: isize, lprc: *const windows_sys::Win32::Foundation::RECT, hbr: isize) -> i32;
+    fn InvalidateRect(hWnd: isize, lpRect: *const windows_sys::Win32::Foundation::RECT, bErase: i32) -> i32;
+    fn GetDC(hWnd: isize) -> isize;
+    fn ReleaseDC(hWnd: isize, hDC: isize) -> i32;
+    fn GetSysColor(nIndex: i32) -> u32;
+    fn TrackMouseEvent(lpEventTrack: *mut TRACKMOUSEEVENT) -> i32;
+    fn GetKeyState(nVirtKey: i32) -> i16;
+    fn SetFocus(hWnd: isize) -> isize;
+    fn GetFocus() -> isize;
+}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct TRACKMOUSEEVENT {
+    cbSize: u32,
+    dwFlags: u32,
+    hwndTrack: HWND,
+    dwHoverTime: u32,
+}
+
+#[link(name = "dwmapi")]
+extern "system" {
+    fn DwmSetWindowAttribute(hwnd: isize, dwAttribute: u32, pvAttribute: *const c_void, cbAttribute: u32) -> i32;
+}
+
+#[link(name = "uxtheme")]
+extern "system" {
+    fn SetWindowTheme(hwnd: isize, pszSubAppName: *const u16, pszSubIdList: *const u16) -> i32;
+}
+
+ const APP_CLASS: &str = "NovelOutlineToolMainWindow";
+ const SPLITTER_CLASS: &str = "NovelOutlineToolSplitter";
+const CORKBOARD_CLASS: &str = "NovelOutlineToolCorkboard";
+ const APP_TITLE: &str = "Novel Outline Tool";
+const IDI_APPICON: u16 = 101;
+const WM_MOUSELEAVE: u32 = 0x02A3;
+const TME_LEAVE: u32 = 0x00000002;
+const COLOR_WINDOW: i32 = 5;
+const COLOR_WINDOWTEXT: i32 = 8;
+const COLOR_3DFACE: i32 = 15;
+const COLOR_3DSHADOW: i32 = 16;
+const COLOR_3DDKSHADOW: i32 = 21;
+ const TREE_ID: isize = 1001;
+ const EDIT_ID: isize = 1002;
+ const STATUS_ID: isize = 1003;
+ const TABS_ID: isize = 1004;
+ const TIMER_AUTOSAVE: usize = 1;
+ const TIMER_HIGHLIGHT: usize = 2;
+ const TIMER_SEARCH: usize = 3;
+const TIMER_RECOVERY: usize = 4;
+const TIMER_WATCH: usize = 5;
+const TIMER_GIT: usize = 6;
+ const SEARCH_ID: isize = 1005;
+const SPLITTER_DRAGGING: isize = 1;
+const SPLITTER_HOVER: isize = 2;
+ const SPLITTER_ID: isize = 1006;
+const REPLACE_ID: isize = 1007;
+const CORKBOARD_ID: isize = 1008;
+ 
+ const EM_GETSEL_MSG: u32 = 0x00B0;
+ const EM_SETSEL_MSG: u32 = 0x00B1;
+ const EM_GETLINE_MSG: u32 = 0x00C4;
+ const EM_LINEFROMCHAR_MSG: u32 = 0x00C9;
+ const EM_LINEINDEX_MSG: u32 = 0x00BB;
+ const EM_LINELENGTH_MSG: u32 = 0x00C1;
+ const EM_SETBKGNDCOLOR_MSG: u32 = 0x0443;
+ const EM_SETMARGINS_MSG: u32 = 0x00D3;
+ const EM_SETCUEBANNER_MSG: u32 = 0x1501;
+const EM_SCROLLCARET_MSG: u32 = 0x00B7;
+ const DWMWA_USE_IMMERSIVE_DARK_MODE: u32 = 20;
+ const EC_LEFTMARGIN: usize = 0x1;
+ const EC_RIGHTMARGIN: usize = 0x2;
+ 
+ const IDM_FILE_NEW: usize = 40001;
+ const IDM_FILE_OPEN: usize = 40002;
+ const IDM_FILE_SAVE: usize = 40003;
+ const IDM_FILE_IMPORT: usize = 40005;
+ const IDM_FILE_EXPORT: usize = 40006;
+const IDM_FILE_EXPORT_MARKDOWN: usize = 40007;
+const IDM_FILE_EXPORT_EPUB: usize = 40008;
+const IDM_FILE_EXPORT_GRAPH: usize = 40009;
+const IDM_FILE_RECOVER: usize = 40010;
+const IDM_FILE_GIT_ENABLE: usize = 40011;
+const IDM_FILE_EXPORT_THREADS: usize = 40012;
+ const IDM_FILE_EXIT: usize = 40004;
+ const IDM_ITEM_NEW: usize = 40101;
+ const IDM_ITEM_RENAME: usize = 40102;
+ const IDM_ITEM_DELETE: usize = 40103;
+const IDM_ITEM_NEW_VOLUME: usize = 40104;
+ const IDM_EDIT_UNDO: usize = 40201;
+ const IDM_EDIT_REDO: usize = 40202;
+const IDM_EDIT_MIGRATE_CHARACTERS: usize = 40203;
+const IDM_EDIT_CHECK_TIMELINE: usize = 40204;
+const IDM_EDIT_SORT_TIMELINE: usize = 40205;
+const IDM_EDIT_CHECK_LINKS: usize = 40206;
+const IDM_EDIT_CONTENT_SEARCH: usize = 40207;
+const IDM_EDIT_SEARCH_REGEX: usize = 40208;
+const IDM_EDIT_SEARCH_WORD: usize = 40209;
+const IDM_EDIT_SEARCH_CASE: usize = 40210;
+const IDM_EDIT_REPLACE_ALL: usize = 40211;
+/// Replace scope menu items: this for `project.md`, plus 1 + index in `Section::ALL` for sections.
+const IDM_EDIT_REPLACE_SCOPE: usize = 40220;
+/// Tree item lParam of a scene is this plus its index in `AppState::scene_nodes`.
+const SCENE_PARAM: isize = 1 << 22;
+/// Tree item lParam of a volume folder is this plus its index in `AppState::volume_paths`.
+const VOLUME_PARAM: isize = 1 << 23;
+/// Tree item lParam of a full-text search hit is this plus its index in `AppState::search_hits`.
+const SEARCH_HIT_PARAM: isize = 1 << 24;
+ const IDM_VIEW_TOGGLE_THEME: usize = 40301;
+const IDM_VIEW_BACKLINKS: usize = 40302;
+const IDM_VIEW_WORD_COUNT: usize = 40303;
+const IDM_VIEW_GOALS: usize = 40304;
+const IDM_VIEW_HISTORY: usize = 40305;
+const IDM_VIEW_HISTORY_DIFF: usize = 40306;
+const IDM_VIEW_HISTORY_RESTORE: usize = 40307;
+const IDM_VIEW_CORKBOARD: usize = 40308;
+/// Tree item lParam of a saved version is this plus its index in `HistoryView::versions`.
+const HISTORY_PARAM: isize = 1 << 25;
+ const IDM_HELP_CHECK_UPDATE: usize = 40401;
+ 
+ fn wide(s: &str) -> Vec<u16> {
+     let mut v: Vec<u16> = s.encode_utf16().collect();
+     v.push(0);
+     v
+ }
+ 
+#[derive(Debug, Clone)]
+enum Command {
+    CreateFile { path: PathBuf, contents: String },
+    CreateFolder { path: PathBuf },
+    DeleteFile { path: PathBuf, contents: String },
+    /// `links` holds the link rewrites of the last apply or undo; they are computed again each
+    /// time from the files as they are then, so that later edits are kept.
+    RenameFile { root: PathBuf, from: PathBuf, to: PathBuf, links: Vec<LinkEdit> },
+    ReorderChapters { root: PathBuf, pairs: Vec<(PathBuf, PathBuf)>, links: Vec<LinkEdit> },
+    ReplaceText { files: Vec<FileReplace> },
+    RestoreVersion { path: PathBuf, before: String, after: String },
+}
+
+impl Command {
+    /// Returns the files left alone because they were edited after the command was recorded.
+    fn apply(&mut self) -> Result<Vec<PathBuf>, String> {
+        match self {
+            Command::CreateFile { path, contents } => storage::write_text_atomic(path, contents).map_err(|e| e.to_string())?,
+            Command::CreateFolder { path } => std::fs::create_dir(path).map_err(|e| e.to_string())?,
+            Command::DeleteFile { path, .. } => std::fs::remove_file(path).map_err(|e| e.to_string())?,
+            Command::RenameFile { root, from, to, links } => {
+                *links = link_rewrites(root, &[(from.clone(), to.clone())]).map_err(|e| e.to_string())?;
+                std::fs::rename(from, to).map_err(|e| e.to_string())?;
+                apply_link_edits(links).map_err(|e| e.to_string())?
+            }
+            Command::ReorderChapters { root, pairs, links } => {
+                *links = link_rewrites(root, pairs).map_err(|e| e.to_string())?;
+                storage::apply_rename_pairs(pairs).map_err(|e| e.to_string())?;
+                apply_link_edits(links).map_err(|e| e.to_string())?
+            }
+            Command::ReplaceText { files } => return replace::apply_replace(files, false).map_err(|e| e.to_string()),
+            Command::RestoreVersion { path, before, after } => {
+                if !storage::write_text_if_unchanged(path, before, after).map_err(|e| e.to_string())? {
+                    return Ok(vec![path.clone()]);
+                }
+            }
+        }
+        Ok(Vec::new())
+    }
+
+    /// Files whose text the command rewrites besides moving them.
+    fn edited_files(&self) -> Vec<PathBuf> {
+        match self {
+            Command::RenameFile { links, .. } | Command::ReorderChapters { links, .. } => links.iter().map(|e| e.path.clone()).collect(),
+            Command::ReplaceText { files } => files.iter().map(|f| f.path.clone()).collect(),
+            Command::RestoreVersion { path, .. } => vec![path.clone()],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns the files left alone because they were edited after the command was applied.
+    fn undo(&mut self) -> Result<Vec<PathBuf>, String> {
+        match self {
+            Command::CreateFile { path, .. } => std::fs::remove_file(path).map_err(|e| e.to_string())?,
+            Command::CreateFolder { path } => std::fs::remove_dir(path).map_err(|e| e.to_string())?,
+            Command::DeleteFile { path, contents } => storage::write_text_atomic(path, contents).map_err(|e| e.to_string())?,
+            Command::RenameFile { root, from, to, links } => {
+                *links = link_rewrites(root, &[(to.clone(), from.clone())]).map_err(|e| e.to_string())?;
+                std::fs::rename(to, from).map_err(|e| e.to_string())?;
+                apply_link_edits(links).map_err(|e| e.to_string())?
+            }
+            Command::ReorderChapters { root, pairs, links } => {
+                let reversed: Vec<(PathBuf, PathBuf)> = pairs.iter().map(|(a, b)| (b.clone(), a.clone())).collect();
+                *links = link_rewrites(root, &reversed).map_err(|e| e.to_string())?;
+                storage::apply_rename_pairs(&reversed).map_err(|e| e.to_string())?;
+                apply_link_edits(links).map_err(|e| e.to_string())?
+            }
+            Command::ReplaceText { files } => return replace::apply_replace(files, true).map_err(|e| e.to_string()),
+            Command::RestoreVersion { path, before, after } => {
+                if !storage::write_text_if_unchanged(path, after, before).map_err(|e| e.to_string())? {
+                    return Ok(vec![path.clone()]);
+                }
+            }
+        }
+        Ok(Vec::new())
+    }
+}
+ 
+/// What the chapter tree shows of one chapter file.
+struct ChapterTreeEntry {
+    /// Modification time and size the entry was read at.
+    stamp: (Option<std::time::SystemTime>, u64),
+    /// `状态 · 视角` from the front matter; empty when neither is set.
+    meta_label: String,
+    scenes: Vec<Scene>,
+}
+
+/// Tree data of chapter files, re-read only when a file's modification time or size changes, so
+/// that reloading the tree on every filter keystroke or watcher event does not read the project.
+#[derive(Default)]
+struct ChapterCache {
+    entries: HashMap<PathBuf, ChapterTreeEntry>,
+}
+
+impl ChapterCache {
+    fn entry(&mut self, path: &Path) -> &ChapterTreeEntry {
+        let stamp = std::fs::metadata(path).map(|m| (m.modified().ok(), m.len())).unwrap_or((None, 0));
+        let fresh = self.entries.get(path).is_some_and(|e| e.stamp.0.is_some() && e.stamp == stamp);
+        if !fresh {
+            let text = storage::read_text(path).unwrap_or_default();
+            let entry = ChapterTreeEntry {
+                stamp,
+                meta_label: storage::parse_chapter(&text).0.short_label(),
+                scenes: parse_scenes(&text),
+            };
+            self.entries.insert(path.to_path_buf(), entry);
+        }
+        &self.entries[path]
+    }
+}
+
+/// The grid of chapter cards that 视图 → 卡片墙 shows in place of the editor.
+struct Corkboard {
+    cards: Vec<ChapterCard>,
+    /// Pixels scrolled down.
+    scroll: i32,
+    /// While a card is dragged: its index and the card it would be dropped in front of,
+    /// `cards.len()` for behind the last one.
+    drag: Option<(usize, usize)>,
+}
+
+/// A scene listed under its chapter in the tree.
+struct SceneNode {
+    /// Index into `item_paths` of the chapter; `usize::MAX` once the node has been replaced.
+    chapter: usize,
+    /// Position among the chapter's scenes.
+    index: usize,
+    scene: Scene,
+}
+
+/// Saved versions of one file, listed in the tree instead of the section's items.
+struct HistoryView {
+    path: PathBuf,
+    versions: Vec<FileVersion>,
+    selected: Option<usize>,
+}
+
+ struct AppState {
+     hwnd_status: HWND,
+     hwnd_tabs: HWND,
+     hwnd_search: HWND,
+    hwnd_replace: HWND,
+     hwnd_tree: HWND,
+     hwnd_edit: HWND,
+    hwnd_splitter: HWND,
+    hwnd_corkboard: HWND,
+    hfont_ui: isize,
+    left_pane_ratio: f32,
+    theme_bg: u32,
+    theme_panel_bg: u32,
+    theme_fg: u32,
+    hbr_bg: HBRUSH,
+    hbr_panel: HBRUSH,
+     project: Option<Project>,
+     current_section: Section,
+     item_paths: Vec<PathBuf>,
+    /// Volume folders shown in the chapter tree; see `VOLUME_PARAM`.
+    volume_paths: Vec<PathBuf>,
+    /// Scenes shown under the chapters of the tree; see `SCENE_PARAM`.
+    scene_nodes: Vec<SceneNode>,
+    /// Set while the corkboard replaces the editor.
+    corkboard: Option<Corkboard>,
+    chapter_cache: ChapterCache,
+     current_doc_path: Option<PathBuf>,
+     current_doc_dirty: bool,
+     filter_text: String,
+     last_highlight_line: i32,
+    last_highlight_bold: bool,
+    undo_stack: Vec<Command>,
+    redo_stack: Vec<Command>,
+    dragging: bool,
+    drag_src_idx: usize,
+    last_gc_unix: u64,
+    /// The editor changed since its text was last written to the recovery journal.
+    journal_pending: bool,
+    /// The open document as it was on disk when loaded or last saved.
+    doc_stamp: Option<DiskStamp>,
+    /// Set while the user is asked about a document changed on disk, so timers do not ask again.
+    resolving_conflict: bool,
+    /// Reports changes other programs make to the project folder; `None` when no project is open
+    /// or the folder cannot be watched.
+    watcher: Option<ProjectWatcher>,
+    /// The project's repository while `project.md` turns Git versioning on.
+    git: Option<GitRepo>,
+    dark_mode: bool,
+    content_search: bool,
+    search_options: SearchOptions,
+    /// Hits shown in the tree while `content_search` is on: index into `item_paths` and the hit.
+    search_hits: Vec<(usize, SearchHit)>,
+    /// Built on the first content search and kept current on save and on undoable commands.
+    search_index: Option<SearchIndex>,
+    replace_scope: ReplaceScope,
+    /// 字数 of the open document when it was opened or last saved.
+    doc_count: u64,
+    /// Net 字 written today as of the last save.
+    today_words: i64,
+    /// Set while the tree lists the versions of a file; any reload of the tree clears it.
+    history_view: Option<HistoryView>,
+ }
+ 
+ pub fn main() {
+     unsafe {
+         let _ = SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
+         let mut icc = INITCOMMONCONTROLSEX {
+             dwSize: size_of::<INITCOMMONCONTROLSEX>() as u32,
+             dwICC: ICC_STANDARD_CLASSES,
+         };
+         InitCommonControlsEx(&mut icc);
+     }
+ 
+     if let Err(message) = run() {
+         unsafe {
+             let title = wide("Fatal Error");
+             let msg = wide(&message);
+             windows_sys::Win32::UI::WindowsAndMessaging::MessageBoxW(
+                 0,
+                 msg.as_ptr(),
+                 title.as_ptr(),
+                 windows_sys::Win32::UI::WindowsAndMessaging::MB_ICONERROR,
+             );
+         }
+     }
+ }
+ 
+ fn run() -> Result<(), String> {
+     unsafe {
+         let hr = CoInitializeEx(null_mut(), COINIT_APARTMENTTHREADED as u32);
+         if hr < 0 {
+             return Err("CoInitializeEx failed".to_string());
+         }
+ 
+         let hinstance = GetModuleHandleW(null());
+         if hinstance == 0 {
+             CoUninitialize();
+             return Err("GetModuleHandleW failed".to_string());
+         }
+ 
+         let class_name = wide(APP_CLASS);
+         let cursor = LoadCursorW(0, IDC_ARROW);
+        let icon = {
+            let h = LoadIconW(hinstance, make_int_resource(IDI_APPICON));
+            if h != 0 { h } else { LoadIconW(0, IDI_APPLICATION) }
+        };
+         let hbr_background: HBRUSH = GetStockObject(WHITE_BRUSH as i32) as HBRUSH;
+ 
+         let wc = WNDCLASSEXW {
+             cbSize: size_of::<WNDCLASSEXW>() as u32,
+             style: 0,
+             lpfnWndProc: Some(window_proc),
+             cbClsExtra: 0,
+             cbWndExtra: 0,
+             hInstance: hinstance,
+            hIcon: icon,
+             hCursor: cursor,
+             hbrBackground: hbr_background,
+             lpszMenuName: null(),
+             lpszClassName: class_name.as_ptr(),
+            hIconSm: icon,
+         };
+ 
+         if RegisterClassExW(&wc) == 0 {
+             CoUninitialize();
+             return Err("RegisterClassExW failed".to_string());
+         }
+ 
+         let splitter_class = wide(SPLITTER_CLASS);
+         let wc_splitter = WNDCLASSEXW {
+             cbSize: size_of::<WNDCLASSEXW>() as u32,
+            style: CS_DBLCLKS,
+             lpfnWndProc: Some(splitter_proc),
+             cbClsExtra: 0,
+             cbWndExtra: 0,
+             hInstance: hinstance,
+             hIcon: 0,
+             hCursor: LoadCursorW(0, IDC_SIZEWE),
+             hbrBackground: hbr_background,
+             lpszMenuName: null(),
+             lpszClassName: splitter_class.as_ptr(),
+             hIconSm: 0,
+         };
+         if RegisterClassExW(&wc_splitter) == 0 {
+             CoUninitialize();
+             return Err("RegisterClassExW splitter failed".to_string());
+         }
+
+        let corkboard_class = wide(CORKBOARD_CLASS);
+        let wc_corkboard = WNDCLASSEXW {
+            cbSize: size_of::<WNDCLASSEXW>() as u32,
+            style: CS_DBLCLKS,
+            lpfnWndProc: Some(corkboard_proc),
+            cbClsExtra: 0,
+            cbWndExtra: 0,
+            hInstance: hinstance,
+            hIcon: 0,
+            hCursor: cursor,
+            hbrBackground: 0,
+            lpszMenuName: null(),
+            lpszClassName: corkboard_class.as_ptr(),
+            hIconSm: 0,
+        };
+        if RegisterClassExW(&wc_corkboard) == 0 {
+            CoUninitialize();
+            return Err("RegisterClassExW corkboard failed".to_string());
+        }
+ 
+         let title = wide(APP_TITLE);
+         let hwnd = CreateWindowExW(
+             0,
+             class_name.as_ptr(),
+             title.as_ptr(),
+             WS_OVERLAPPEDWINDOW | WS_CLIPCHILDREN,
+             CW_USEDEFAULT,
+             CW_USEDEFAULT,
+             1200,
+             800,
+             0,
+             0,
+             hinstance,
+             null_mut(),
+         );
+ 
+         if hwnd == 0 {
+             CoUninitialize();
+             return Err("CreateWindowExW failed".to_string());
+         }
+ 
+         ShowWindow(hwnd, SW_SHOW);
+ 
+         let mut msg = MaybeUninit::<MSG>::uninit();
+         loop {
+             let ret = GetMessageW(msg.as_mut_ptr(), 0, 0, 0);
+             if ret == 0 {
+                 break;
+             }
+             if ret == -1 {
+                 CoUninitialize();
+                 return Err("GetMessageW failed".to_string());
+             }
+             let msg = msg.assume_init();
+             TranslateMessage(&msg);
+             DispatchMessageW(&msg);
+         }
+ 
+         CoUninitialize();
+         Ok(())
+     }
+ }
+ 
+ unsafe extern "system" fn window_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+     match msg {
+         WM_NCCREATE => {
+             let state = Box::new(AppState {
+                 hwnd_status: 0,
+                 hwnd_tabs: 0,
+                 hwnd_search: 0,
+                hwnd_replace: 0,
+                 hwnd_tree: 0,
+                 hwnd_edit: 0,
+                hwnd_splitter: 0,
+                hwnd_corkboard: 0,
+                hfont_ui: 0,
+                left_pane_ratio: 0.28,
+                theme_bg: 0,
+                theme_panel_bg: 0,
+                theme_fg: 0,
+                hbr_bg: 0,
+                hbr_panel: 0,
+                 project: None,
+                 current_section: Section::Chapters,
+                 item_paths: Vec::new(),
+                volume_paths: Vec::new(),
+                scene_nodes: Vec::new(),
+                corkboard: None,
+                chapter_cache: ChapterCache::default(),
+                 current_doc_path: None,
+                 current_doc_dirty: false,
+                 filter_text: String::new(),
+                 last_highlight_line: -1,
+                last_highlight_bold: false,
+                 undo_stack: Vec::new(),
+                 redo_stack: Vec::new(),
+                 dragging: false,
+                 drag_src_idx: 0,
+                 last_gc_unix: 0,
+                journal_pending: false,
+                doc_stamp: None,
+                resolving_conflict: false,
+                watcher: None,
+                git: None,
+                 dark_mode: false,
+                content_search: false,
+                search_options: SearchOptions::default(),
+                search_hits: Vec::new(),
+                search_index: None,
+                replace_scope: ReplaceScope::default(),
+                doc_count: 0,
+                today_words: 0,
+                history_view: None,
+             });
+             SetWindowLongPtrW(hwnd, GWLP_USERDATA, Box::into_raw(state) as isize);
+             DefWindowProcW(hwnd, msg, wparam, lparam)
+         }
+         WM_CREATE => {
+             let state = state(hwnd);
+
+            let icon = LoadIconW(GetModuleHandleW(null()), make_int_resource(IDI_APPICON));
+            if icon != 0 {
+                SendMessageW(hwnd, WM_SETICON, ICON_BIG as usize, icon as LPARAM);
+                SendMessageW(hwnd, WM_SETICON, ICON_SMALL as usize, icon as LPARAM);
+            }
+ 
+             let menu = CreateMenu();
+             let file_menu = CreatePopupMenu();
+             AppendMenuW(file_menu, MF_STRING, IDM_FILE_NEW, wide("新建/初始化项目...").as_ptr());
+             AppendMenuW(file_menu, MF_STRING, IDM_FILE_OPEN, wide("打开项目文件夹...").as_ptr());
+             AppendMenuW(file_menu, MF_STRING, IDM_FILE_SAVE, wide("保存").as_ptr());
+             AppendMenuW(file_menu, MF_STRING, IDM_FILE_IMPORT, wide("从文件夹导入...").as_ptr());
+             AppendMenuW(file_menu, MF_STRING, IDM_FILE_EXPORT, wide("导出为文件夹...").as_ptr());
+            AppendMenuW(file_menu, MF_STRING, IDM_FILE_EXPORT_MARKDOWN, wide("合并导出为 Markdown 文稿").as_ptr());
+            AppendMenuW(file_menu, MF_STRING, IDM_FILE_EXPORT_EPUB, wide("导出为 EPUB 电子书").as_ptr());
+            AppendMenuW(file_menu, MF_STRING, IDM_FILE_EXPORT_GRAPH, wide("导出人物关系图").as_ptr());
+            AppendMenuW(file_menu, MF_STRING, IDM_FILE_EXPORT_THREADS, wide("导出故事线报告").as_ptr());
+            AppendMenuW(file_menu, MF_STRING, IDM_FILE_RECOVER, wide("恢复未保存的内容...").as_ptr());
+            AppendMenuW(file_menu, MF_STRING, IDM_FILE_GIT_ENABLE, wide("启用 Git 版本管理").as_ptr());
+             AppendMenuW(file_menu, MF_STRING, IDM_ITEM_NEW, wide("新建当前条目").as_ptr());
+            AppendMenuW(file_menu, MF_STRING, IDM_ITEM_NEW_VOLUME, wide("新建卷").as_ptr());
+             AppendMenuW(file_menu, MF_STRING, IDM_ITEM_RENAME, wide("重命名当前条目").as_ptr());
+             AppendMenuW(file_menu, MF_STRING, IDM_ITEM_DELETE, wide("删除当前条目").as_ptr());
+             AppendMenuW(file_menu, MF_STRING, IDM_FILE_EXIT, wide("退出").as_ptr());
+             AppendMenuW(menu, MF_POPUP, file_menu as usize, wide("文件").as_ptr());
+ 
+             let edit_menu = CreatePopupMenu();
+             AppendMenuW(edit_menu, MF_STRING, IDM_EDIT_UNDO, wide("撤销结构操作").as_ptr());
+             AppendMenuW(edit_menu, MF_STRING, IDM_EDIT_REDO, wide("重做结构操作").as_ptr());
+            AppendMenuW(edit_menu, MF_STRING, IDM_EDIT_MIGRATE_CHARACTERS, wide("迁移旧版角色卡").as_ptr());
+            AppendMenuW(edit_menu, MF_STRING, IDM_EDIT_CHECK_TIMELINE, wide("检查时间线").as_ptr());
+            AppendMenuW(edit_menu, MF_STRING, IDM_EDIT_SORT_TIMELINE, wide("按时间排序时间线").as_ptr());
+            AppendMenuW(edit_menu, MF_STRING, IDM_EDIT_CHECK_LINKS, wide("检查失效链接").as_ptr());
+            AppendMenuW(edit_menu, MF_STRING, IDM_EDIT_CONTENT_SEARCH, wide("全文搜索\tCtrl+Shift+F").as_ptr());
+            AppendMenuW(edit_menu, MF_STRING, IDM_EDIT_SEARCH_REGEX, wide("全文搜索：正则表达式").as_ptr());
+            AppendMenuW(edit_menu, MF_STRING, IDM_EDIT_SEARCH_WORD, wide("全文搜索：全词匹配").as_ptr());
+            AppendMenuW(edit_menu, MF_STRING, IDM_EDIT_SEARCH_CASE, wide("全文搜索：区分大小写").as_ptr());
+            AppendMenuW(edit_menu, MF_STRING, IDM_EDIT_REPLACE_ALL, wide("全部替换...\tCtrl+H").as_ptr());
+            let scope_menu = CreatePopupMenu();
+            AppendMenuW(scope_menu, MF_STRING | MF_CHECKED, IDM_EDIT_REPLACE_SCOPE, wide("project.md").as_ptr());
+            for (i, section) in Section::ALL.iter().enumerate() {
+                AppendMenuW(scope_menu, MF_STRING | MF_CHECKED, IDM_EDIT_REPLACE_SCOPE + 1 + i, wide(section.label()).as_ptr());
+            }
+            AppendMenuW(edit_menu, MF_POPUP, scope_menu as usize, wide("替换范围").as_ptr());
+             AppendMenuW(menu, MF_POPUP, edit_menu as usize, wide("编辑").as_ptr());
+ 
+             let view_menu = CreatePopupMenu();
+             AppendMenuW(view_menu, MF_STRING, IDM_VIEW_TOGGLE_THEME, wide("深色/浅色主题").as_ptr());
+            AppendMenuW(view_menu, MF_STRING, IDM_VIEW_BACKLINKS, wide("当前条目的链接与反向链接").as_ptr());
+            AppendMenuW(view_menu, MF_STRING, IDM_VIEW_WORD_COUNT, wide("字数统计").as_ptr());
+            AppendMenuW(view_menu, MF_STRING, IDM_VIEW_GOALS, wide("写作进度").as_ptr());
+            AppendMenuW(view_menu, MF_STRING, IDM_VIEW_HISTORY, wide("当前条目的历史版本").as_ptr());
+            AppendMenuW(view_menu, MF_STRING, IDM_VIEW_HISTORY_DIFF, wide("与所选历史版本比较").as_ptr());
+            AppendMenuW(view_menu, MF_STRING, IDM_VIEW_HISTORY_RESTORE, wide("恢复所选历史版本").as_ptr());
+            AppendMenuW(view_menu, MF_STRING, IDM_VIEW_CORKBOARD, wide("卡片墙").as_ptr());
+             AppendMenuW(menu, MF_POPUP, view_menu as usize, wide("视图").as_ptr());
+ 
+             let help_menu = CreatePopupMenu();
+             AppendMenuW(help_menu, MF_STRING, IDM_HELP_CHECK_UPDATE, wide("检查更新...").as_ptr());
+             AppendMenuW(menu, MF_POPUP, help_menu as usize, wide("帮助").as_ptr());
+ 
+             SetMenu(hwnd, menu);
+             DrawMenuBar(hwnd);
+ 
+             let _ = LoadLibraryW(wide("Msftedit.dll").as_ptr());
+
+             state.hwnd_status = CreateWindowExW(
+                 0,
+                 STATUSCLASSNAMEW,
+                 null(),
+                 WS_CHILD | WS_VISIBLE,
+                 0,
+                 0,
+                 0,
+                 0,
+                 hwnd,
+                 STATUS_ID as HMENU,
+                 GetModuleHandleW(null()),
+                 null_mut(),
+             );
+ 
+             state.hwnd_tabs = CreateWindowExW(
+                 0,
+                 WC_TABCONTROLW,
+                 null(),
+                 WS_CHILD | WS_VISIBLE,
+                 0,
+                 0,
+                 0,
+                 0,
+                 hwnd,
+                 TABS_ID as HMENU,
+                 GetModuleHandleW(null()),
+                 null_mut(),
+             );
+             add_tabs(state.hwnd_tabs);
+
+             state.hwnd_search = CreateWindowExW(
+                 WS_EX_CLIENTEDGE,
+                 wide("EDIT").as_ptr(),
+                 null(),
+                 WS_CHILD | WS_VISIBLE | ES_AUTOHSCROLL as u32,
+                 0,
+                 0,
+                 0,
+                 0,
+                 hwnd,
+                 SEARCH_ID as HMENU,
+                 GetModuleHandleW(null()),
+                 null_mut(),
+             );
+             let dpi = GetDpiForWindow(hwnd);
+             let margin = scale_px(dpi, 6);
+             SendMessageW(
+                 state.hwnd_search,
+                 EM_SETMARGINS_MSG,
+                 EC_LEFTMARGIN | EC_RIGHTMARGIN,
+                 make_lparam_u16(margin, margin),
+             );
+             let cue = wide("搜索当前模块...");
+             SendMessageW(state.hwnd_search, EM_SETCUEBANNER_MSG, 0, cue.as_ptr() as LPARAM);
+
+            // Shown below the search box while full-text search is on.
+            state.hwnd_replace = CreateWindowExW(
+                WS_EX_CLIENTEDGE,
+                wide("EDIT").as_ptr(),
+                null(),
+                WS_CHILD | ES_AUTOHSCROLL as u32,
+                0,
+                0,
+                0,
+                0,
+                hwnd,
+                REPLACE_ID as HMENU,
+                GetModuleHandleW(null()),
+                null_mut(),
+            );
+            SendMessageW(
+                state.hwnd_replace,
+                EM_SETMARGINS_MSG,
+                EC_LEFTMARGIN | EC_RIGHTMARGIN,
+                make_lparam_u16(margin, margin),
+            );
+            let cue = wide("替换为...");
+            SendMessageW(state.hwnd_replace, EM_SETCUEBANNER_MSG, 0, cue.as_ptr() as LPARAM);
+
+             state.hwnd_tree = CreateWindowExW(
+                 WS_EX_CLIENTEDGE,
+                 WC_TREEVIEWW,
+                 null(),
+                 WS_CHILD
+                     | WS_VISIBLE
+                     | TVS_EDITLABELS as u32
+                     | TVS_FULLROWSELECT as u32
+                     | TVS_SHOWSELALWAYS as u32
+                     | TVS_HASLINES as u32
+                     | TVS_LINESATROOT as u32,
+                 0,
+                 0,
+                 0,
+                 0,
+                 hwnd,
+                 TREE_ID as HMENU,
+                 GetModuleHandleW(null()),
+                 null_mut(),
+             );
+            SendMessageW(
+                state.hwnd_tree,
+                TVM_SETEXTENDEDSTYLE,
+                TVS_EX_DOUBLEBUFFER as usize,
+                TVS_EX_DOUBLEBUFFER as LPARAM,
+            );
+
+             state.hwnd_splitter = CreateWindowExW(
+                 0,
+                 wide(SPLITTER_CLASS).as_ptr(),
+                 null(),
+                 WS_CHILD | WS_VISIBLE,
+                 0,
+                 0,
+                 0,
+                 0,
+                 hwnd,
+                 SPLITTER_ID as HMENU,
+                 GetModuleHandleW(null()),
+                 null_mut(),
+             );
+
+            // Hidden until 视图 → 卡片墙; takes the editor's place when shown.
+            state.hwnd_corkboard = CreateWindowExW(
+                WS_EX_CLIENTEDGE,
+                wide(CORKBOARD_CLASS).as_ptr(),
+                null(),
+                WS_CHILD,
+                0,
+                0,
+                0,
+                0,
+                hwnd,
+                CORKBOARD_ID as HMENU,
+                GetModuleHandleW(null()),
+                null_mut(),
+            );
+
+             state.hwnd_edit = CreateWindowExW(
+                 WS_EX_CLIENTEDGE,
+                 wide("RICHEDIT50W").as_ptr(),
+                 null(),
+                 WS_CHILD
+                     | WS_VISIBLE
+                     | WS_VSCROLL
+                     | WS_HSCROLL
+                     | ES_MULTILINE as u32
+                     | ES_AUTOVSCROLL as u32
+                     | ES_AUTOHSCROLL as u32,
+                 0,
+                 0,
+                 0,
+                 0,
+                 hwnd,
+                 EDIT_ID as HMENU,
+                 GetModuleHandleW(null()),
+                 null_mut(),
+             );
+ 
+             let dpi = GetDpiForWindow(hwnd);
+             let margin = scale_px(dpi, 12);
+             SendMessageW(
+                 state.hwnd_edit,
+                 EM_SETMARGINS_MSG,
+                 EC_LEFTMARGIN | EC_RIGHTMARGIN,
+                 make_lparam_u16(margin, margin),
+             );
+
+            apply_theme(hwnd, state.dark_mode);
+ 
+             state.hfont_ui = create_ui_font(hwnd);
+             apply_ui_font(hwnd, state.hfont_ui);
+             layout(hwnd);
+ 
+             set_status_text(hwnd, "就绪");
+             0
+         }
+         WM_COMMAND => {
+             if lparam != 0 {
+                 let hwnd_from = lparam as HWND;
+                 let code = ((wparam as u32 >> 16) & 0xffff) as u16;
+                if code as u32 == EN_CHANGE {
+                    let state = state(hwnd);
+                    if hwnd_from == state.hwnd_edit {
+                        state.current_doc_dirty = true;
+                        state.journal_pending = true;
+                        let _ = SetTimer(hwnd, TIMER_HIGHLIGHT, 250, None);
+                        return 0;
+                    }
+                    if hwnd_from == state.hwnd_search {
+                        let _ = SetTimer(hwnd, TIMER_SEARCH, 150, None);
+                        return 0;
+                    }
+                }
+                 return DefWindowProcW(hwnd, msg, wparam, lparam);
+             }
+
+             let id = (wparam as u32 & 0xffff) as usize;
+             match id {
+                 IDM_FILE_NEW | IDM_FILE_OPEN => {
+                     if let Some(root) = pick_folder(hwnd) {
+                         match ProjectStore::open_or_init(root) {
+                             Ok(project) => {
+                                 let state = state(hwnd);
+                                 state.project = Some(project);
+                                 state.dark_mode = state
+                                     .project
+                                     .as_ref()
+                                     .and_then(|p| p.meta.theme.as_deref())
+                                     .map(|t| t.eq_ignore_ascii_case("dark"))
+                                     .unwrap_or(false);
+                                 apply_theme(hwnd, state.dark_mode);
+                                 state.left_pane_ratio = state
+                                     .project
+                                     .as_ref()
+                                     .and_then(|p| p.meta.left_pane_ratio)
+                                     .unwrap_or(0.28);
+                                 state.current_section = Section::Chapters;
+                                 state.item_paths.clear();
+                                state.volume_paths.clear();
+                                state.scene_nodes.clear();
+                                state.chapter_cache = ChapterCache::default();
+                                 state.current_doc_path = None;
+                                 state.current_doc_dirty = false;
+                                 state.filter_text.clear();
+                                 state.last_highlight_line = -1;
+                                state.last_highlight_bold = false;
+                                 state.undo_stack.clear();
+                                 state.redo_stack.clear();
+                                 state.dragging = false;
+                                 state.last_gc_unix = 0;
+                                state.search_index = None;
+                                load_today_words(hwnd);
+                                 SetWindowTextW(state.hwnd_search, wide("").as_ptr());
+                                 SendMessageW(state.hwnd_tree, TVM_DELETEITEM, 0, 0);
+                                 SendMessageW(state.hwnd_tabs, windows_sys::Win32::UI::Controls::TCM_SETCURSEL, 0, 0);
+                                 reload_items(hwnd);
+                                 let _ = SetTimer(hwnd, TIMER_AUTOSAVE, 30_000, None);
+                                let _ = SetTimer(hwnd, TIMER_RECOVERY, 5_000, None);
+                                start_watching(hwnd);
+                                 if let Some(project) = &state.project {
+                                     set_status_text(hwnd, &format!("已打开: {}", project.root.display()));
+                                     set_status_part(hwnd, 1, "");
+                                     set_status_part(hwnd, 2, "已保存");
+                                    set_status_part(hwnd, 3, "");
+                                 }
+                                start_git(hwnd);
+                                if let Err(e) = offer_recovery(hwnd, false) {
+                                    show_error(hwnd, &e);
+                                }
+                             }
+                             Err(e) => show_error(hwnd, &e),
+                         }
+                     }
+                     0
+                 }
+                IDM_ITEM_NEW => {
+                    if let Err(e) = create_new_item(hwnd) {
+                        show_error(hwnd, &e);
+                    }
+                    0
+                }
+                IDM_ITEM_NEW_VOLUME => {
+                    if let Err(e) = create_new_volume(hwnd) {
+                        show_error(hwnd, &e);
+                    }
+                    0
+                }
+                IDM_ITEM_RENAME => {
+                    begin_rename_selected(hwnd);
+                    0
+                }
+                IDM_ITEM_DELETE => {
+                    if let Err(e) = delete_selected_item(hwnd) {
+                        show_error(hwnd, &e);
+                    }
+                    0
+                }
+                 IDM_FILE_SAVE => {
+                     save_current_if_dirty(hwnd);
+                     let state = state(hwnd);
+                     if let Some(project) = &mut state.project {
+                         if let Err(e) = ProjectStore::save_project_meta(project) {
+                             show_error(hwnd, &e);
+                         } else {
+                             set_status_text(hwnd, "已保存");
+                            if let Err(e) = commit_to_git(hwnd) {
+                                show_error(hwnd, &e);
+                            }
+                         }
+                     }
+                     0
+                 }
+                IDM_FILE_IMPORT => {
+                    if let Err(e) = import_project(hwnd) {
+                        show_error(hwnd, &e);
+                    }
+                    0
+                }
+                IDM_FILE_GIT_ENABLE => {
+                    if let Err(e) = enable_git(hwnd) {
+                        show_error(hwnd, &e);
+                    }
+                    0
+                }
+                IDM_FILE_RECOVER => {
+                    if let Err(e) = offer_recovery(hwnd, true) {
+                        show_error(hwnd, &e);
+                    }
+                    0
+                }
+                IDM_FILE_EXPORT => {
+                    if let Err(e) = export_project(hwnd) {
+                        show_error(hwnd, &e);
+                    }
+                    0
+                }
+                IDM_FILE_EXPORT_MARKDOWN => {
+                    if let Err(e) = export_markdown(hwnd) {
+                        show_error(hwnd, &e);
+                    }
+                    0
+                }
+                IDM_FILE_EXPORT_EPUB => {
+                    if let Err(e) = export_epub(hwnd) {
+                        show_error(hwnd, &e);
+                    }
+                    0
+                }
+                IDM_FILE_EXPORT_GRAPH => {
+                    if let Err(e) = export_relation_graph(hwnd) {
+                        show_error(hwnd, &e);
+                    }
+                    0
+                }
+                IDM_FILE_EXPORT_THREADS => {
+                    if let Err(e) = export_thread_report(hwnd) {
+                        show_error(hwnd, &e);
+                    }
+                    0
+                }
+                IDM_EDIT_UNDO => {
+                    if let Err(e) = do_undo(hwnd) {
+                        show_error(hwnd, &e);
+                    }
+                    0
+                }
+                IDM_EDIT_REDO => {
+                    if let Err(e) = do_redo(hwnd) {
+                        show_error(hwnd, &e);
+                    }
+                    0
+                }
+                IDM_EDIT_MIGRATE_CHARACTERS => {
+                    if let Err(e) = migrate_characters(hwnd) {
+                        show_error(hwnd, &e);
+                    }
+                    0
+                }
+                IDM_EDIT_CHECK_TIMELINE => {
+                    if let Err(e) = check_timeline(hwnd) {
+                        show_error(hwnd, &e);
+                    }
+                    0
+                }
+                IDM_EDIT_SORT_TIMELINE => {
+                    if let Err(e) = sort_timeline(hwnd) {
+                        show_error(hwnd, &e);
+                    }
+                    0
+                }
+                IDM_EDIT_CHECK_LINKS => {
+                    if let Err(e) = check_links(hwnd) {
+                        show_error(hwnd, &e);
+                    }
+                    0
+                }
+                IDM_EDIT_CONTENT_SEARCH | IDM_EDIT_SEARCH_REGEX | IDM_EDIT_SEARCH_WORD | IDM_EDIT_SEARCH_CASE => {
+                    toggle_search_option(hwnd, id);
+                    0
+                }
+                IDM_EDIT_REPLACE_ALL => {
+                    if let Err(e) = replace_all(hwnd) {
+                        show_error(hwnd, &e);
+                    }
+                    0
+                }
+                _ if (IDM_EDIT_REPLACE_SCOPE..=IDM_EDIT_REPLACE_SCOPE + Section::ALL.len()).contains(&id) => {
+                    toggle_replace_scope(hwnd, id);
+                    0
+                }
+                IDM_VIEW_WORD_COUNT => {
+                    if let Err(e) = show_word_count(hwnd) {
+                        show_error(hwnd, &e);
+                    }
+                    0
+                }
+                IDM_VIEW_GOALS => {
+                    if let Err(e) = show_goals(hwnd) {
+                        show_error(hwnd, &e);
+                    }
+                    0
+                }
+                IDM_VIEW_HISTORY | IDM_VIEW_HISTORY_DIFF | IDM_VIEW_HISTORY_RESTORE => {
+                    let result = match id {
+                        IDM_VIEW_HISTORY => show_history(hwnd),
+                        IDM_VIEW_HISTORY_DIFF => diff_history_version(hwnd),
+                        _ => restore_history_version(hwnd),
+                    };
+                    if let Err(e) = result {
+                        show_error(hwnd, &e);
+                    }
+                    0
+                }
+                IDM_VIEW_CORKBOARD => {
+                    toggle_corkboard(hwnd);
+                    0
+                }
+                IDM_VIEW_BACKLINKS => {
+                    if let Err(e) = show_links(hwnd) {
+                        show_error(hwnd, &e);
+                    }
+                    0
+                }
+                IDM_VIEW_TOGGLE_THEME => {
+                    let state = state(hwnd);
+                    state.dark_mode = !state.dark_mode;
+                    apply_theme(hwnd, state.dark_mode);
+                    if let Some(project) = &mut state.project {
+                        project.meta.theme = Some(if state.dark_mode { "dark".to_string() } else { "light".to_string() });
+                        let _ = ProjectStore::save_project_meta(project);
+                    }
+                    0
+                }
+                IDM_HELP_CHECK_UPDATE => {
+                    if let Err(e) = check_updates(hwnd) {
+                        show_error(hwnd, &e);
+                    }
+                    0
+                }
+                 IDM_FILE_EXIT => {
+                     DestroyWindow(hwnd);
+                     0
+                 }
+                 _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+             }
+         }
+        WM_CTLCOLOREDIT => {
+            let state = state(hwnd);
+            let hdc = wparam as HDC;
+            SetBkColor(hdc, state.theme_bg);
+            SetTextColor(hdc, state.theme_fg);
+            state.hbr_bg as LRESULT
+        }
+        WM_CTLCOLORDLG | WM_CTLCOLORSTATIC => {
+            let state = state(hwnd);
+            let hdc = wparam as HDC;
+            SetBkColor(hdc, state.theme_panel_bg);
+            SetTextColor(hdc, state.theme_fg);
+            state.hbr_panel as LRESULT
+        }
+        WM_ERASEBKGND => {
+            let state = state(hwnd);
+            let hdc = wparam as HDC;
+            let mut rc = windows_sys::Win32::Foundation::RECT {
+                left: 0,
+                top: 0,
+                right: 0,
+                bottom: 0,
+            };
+            GetClientRect(hwnd, &mut rc);
+            FillRect(hdc, &rc, state.hbr_panel);
+            1
+        }
+        WM_KEYDOWN => {
+            let ctrl = (GetKeyState(0x11) as i32) < 0;
+            let shift = (GetKeyState(0x10) as i32) < 0;
+            let alt = (GetKeyState(0x12) as i32) < 0;
+            let vk = wparam as u32;
+            let focus = GetFocus();
+            let state = state(hwnd);
+
+            if ctrl && !alt && !shift && vk == ('O' as u32) {
+                SendMessageW(hwnd, WM_COMMAND, IDM_FILE_OPEN, 0);
+                return 0;
+            }
+            if ctrl && !alt && !shift && vk == ('S' as u32) {
+                SendMessageW(hwnd, WM_COMMAND, IDM_FILE_SAVE, 0);
+                return 0;
+            }
+            if ctrl && !alt && !shift && vk == ('N' as u32) {
+                SendMessageW(hwnd, WM_COMMAND, IDM_FILE_NEW, 0);
+                return 0;
+            }
+            if ctrl && !alt && shift && vk == ('F' as u32) {
+                if !state.content_search {
+                    toggle_search_option(hwnd, IDM_EDIT_CONTENT_SEARCH);
+                }
+                if state.hwnd_search != 0 {
+                    SetFocus(state.hwnd_search);
+                }
+                return 0;
+            }
+            if ctrl && !alt && !shift && vk == ('H' as u32) {
+                SendMessageW(hwnd, WM_COMMAND, IDM_EDIT_REPLACE_ALL, 0);
+                return 0;
+            }
+            if ctrl && !alt && !shift && vk == ('F' as u32) {
+                if state.hwnd_search != 0 {
+                    SetFocus(state.hwnd_search);
+                }
+                return 0;
+            }
+            if ctrl && alt && vk == ('Z' as u32) {
+                SendMessageW(hwnd, WM_COMMAND, IDM_EDIT_UNDO, 0);
+                return 0;
+            }
+            if ctrl && alt && vk == ('Y' as u32) {
+                SendMessageW(hwnd, WM_COMMAND, IDM_EDIT_REDO, 0);
+                return 0;
+            }
+            if vk == 0x71 && focus != state.hwnd_edit {
+                SendMessageW(hwnd, WM_COMMAND, IDM_ITEM_RENAME, 0);
+                return 0;
+            }
+            if vk == 0x2E && focus != state.hwnd_edit {
+                SendMessageW(hwnd, WM_COMMAND, IDM_ITEM_DELETE, 0);
+                return 0;
+            }
+            DefWindowProcW(hwnd, msg, wparam, lparam)
+        }
+         WM_NOTIFY => {
+             let state = state(hwnd);
+             if lparam == 0 {
+                 return 0;
+             }
+             let hdr = &*(lparam as *const windows_sys::Win32::UI::Controls::NMHDR);
+             if hdr.hwndFrom == state.hwnd_tabs && hdr.code as u32 == TCN_SELCHANGE {
+                 save_current_if_dirty(hwnd);
+                 let idx = SendMessageW(state.hwnd_tabs, TCM_GETCURSEL, 0, 0) as i32;
+                 state.current_section = match idx {
+                     1 => Section::Characters,
+                     2 => Section::World,
+                     3 => Section::Timeline,
+                     _ => Section::Chapters,
+                 };
+                 SendMessageW(state.hwnd_tree, TVM_DELETEITEM, 0, 0);
+                 reload_items(hwnd);
+                 return 0;
+             }
+             if hdr.hwndFrom == state.hwnd_tree && hdr.code as u32 == TVN_BEGINDRAGW {
+                 let nmtv = &*(lparam as *const windows_sys::Win32::UI::Controls::NMTREEVIEWW);
+                 let idx = nmtv.itemNew.lParam as isize;
+                 if idx >= 0 {
+                     state.dragging = true;
+                     state.drag_src_idx = idx as usize;
+                     SetCapture(hwnd);
+                 }
+                 return 0;
+             }
+             if hdr.hwndFrom == state.hwnd_tree && hdr.code as u32 == TVN_BEGINLABELEDITW {
+                 let disp = &*(lparam as *const NMTVDISPINFOW);
+                 let idx = disp.item.lParam as isize;
+                 if idx < 0 || idx as usize >= state.item_paths.len() || state.content_search {
+                     return 1;
+                 }
+                 let edit = SendMessageW(state.hwnd_tree, TVM_GETEDITCONTROL, 0, 0) as HWND;
+                 if edit != 0 {
+                     let stem = state.item_paths[idx as usize].file_stem().and_then(|s| s.to_str()).unwrap_or("").to_string();
+                     SetWindowTextW(edit, wide(&stem).as_ptr());
+                 }
+                 return 0;
+             }
+             if hdr.hwndFrom == state.hwnd_tree && hdr.code as u32 == TVN_ENDLABELEDITW {
+                 let disp = &*(lparam as *const NMTVDISPINFOW);
+                 let idx = disp.item.lParam as isize;
+                 if idx < 0 {
+                     return 0;
+                 }
+                 if disp.item.pszText.is_null() {
+                     return 0;
+                 }
+                 let new_name = wide_ptr_to_string(disp.item.pszText);
+                 match commit_rename(hwnd, idx as usize, &new_name) {
+                     Ok(true) => return 1,
+                     Ok(false) => return 0,
+                     Err(e) => {
+                         show_error(hwnd, &e);
+                         return 0;
+                     }
+                 }
+             }
+             if hdr.hwndFrom == state.hwnd_tree && hdr.code as u32 == TVN_SELCHANGEDW {
+                 let nmtv = &*(lparam as *const windows_sys::Win32::UI::Controls::NMTREEVIEWW);
+                 let idx = nmtv.itemNew.lParam as isize;
+                 if idx >= HISTORY_PARAM {
+                     select_history_version(hwnd, (idx - HISTORY_PARAM) as usize);
+                 } else if idx >= SEARCH_HIT_PARAM {
+                     open_search_hit(hwnd, (idx - SEARCH_HIT_PARAM) as usize);
+                 } else if idx >= VOLUME_PARAM {
+                     // A volume only groups chapters; the editor keeps the open one.
+                 } else if idx >= SCENE_PARAM {
+                     open_scene(hwnd, (idx - SCENE_PARAM) as usize);
+                 } else if idx >= 0 {
+                     open_item_by_index(hwnd, idx as usize);
+                 }
+                 return 0;
+             }
+             DefWindowProcW(hwnd, msg, wparam, lparam)
+         }
+         WM_MOUSEMOVE => {
+             let state = state(hwnd);
+             if !state.dragging {
+                 return DefWindowProcW(hwnd, msg, wparam, lparam);
+             }
+             drag_update_hover(hwnd);
+             0
+         }
+         WM_LBUTTONUP => {
+             let state = state(hwnd);
+             if !state.dragging {
+                 return DefWindowProcW(hwnd, msg, wparam, lparam);
+             }
+             ReleaseCapture();
+             state.dragging = false;
+             if let Err(e) = drag_commit_drop(hwnd, state.drag_src_idx) {
+                 show_error(hwnd, &e);
+             }
+             0
+         }
+        WM_DPICHANGED => {
+            let suggested = lparam as *const windows_sys::Win32::Foundation::RECT;
+            if !suggested.is_null() {
+                let rc = &*suggested;
+                let _ = SetWindowPos(
+                    hwnd,
+                    0,
+                    rc.left,
+                    rc.top,
+                    rc.right - rc.left,
+                    rc.bottom - rc.top,
+                    SWP_NOZORDER | SWP_NOACTIVATE,
+                );
+            }
+
+            let state = state(hwnd);
+            if state.hfont_ui != 0 {
+                let _ = DeleteObject(state.hfont_ui);
+                state.hfont_ui = 0;
+            }
+            state.hfont_ui = create_ui_font(hwnd);
+            apply_ui_font(hwnd, state.hfont_ui);
+
+            let dpi = GetDpiForWindow(hwnd);
+            let margin = scale_px(dpi, 6);
+            for ctrl in [state.hwnd_search, state.hwnd_replace] {
+                SendMessageW(ctrl, EM_SETMARGINS_MSG, EC_LEFTMARGIN | EC_RIGHTMARGIN, make_lparam_u16(margin, margin));
+            }
+            let margin = scale_px(dpi, 12);
+            SendMessageW(
+                state.hwnd_edit,
+                EM_SETMARGINS_MSG,
+                EC_LEFTMARGIN | EC_RIGHTMARGIN,
+                make_lparam_u16(margin, margin),
+            );
+
+            layout(hwnd);
+            0
+        }
+         WM_SIZE => {
+             layout(hwnd);
+             0
+         }
+         WM_TIMER => {
+             if wparam == TIMER_AUTOSAVE {
+                 save_current_if_dirty(hwnd);
+             }
+            if wparam == TIMER_RECOVERY {
+                write_recovery_journal(hwnd);
+            }
+            if wparam == TIMER_WATCH {
+                apply_external_changes(hwnd);
+            }
+            if wparam == TIMER_GIT {
+                save_current_if_dirty(hwnd);
+                if let Err(e) = commit_to_git(hwnd) {
+                    set_status_text(hwnd, &e);
+                }
+            }
+            if wparam == TIMER_SEARCH {
+                KillTimer(hwnd, TIMER_SEARCH);
+                let s = get_text(state(hwnd).hwnd_search);
+                state(hwnd).filter_text = s;
+                SendMessageW(state(hwnd).hwnd_tree, TVM_DELETEITEM, 0, 0);
+                reload_items(hwnd);
+            }
+            if wparam == TIMER_HIGHLIGHT {
+                KillTimer(hwnd, TIMER_HIGHLIGHT);
+                highlight_current_line(hwnd);
+                let text = get_text(state(hwnd).hwnd_edit);
+                refresh_word_count(hwnd, &text);
+            }
+             0
+         }
+         WM_DESTROY => {
+            let _ = KillTimer(hwnd, TIMER_AUTOSAVE);
+            let _ = KillTimer(hwnd, TIMER_SEARCH);
+            let _ = KillTimer(hwnd, TIMER_HIGHLIGHT);
+            let _ = KillTimer(hwnd, TIMER_RECOVERY);
+            let _ = KillTimer(hwnd, TIMER_WATCH);
+            let _ = KillTimer(hwnd, TIMER_GIT);
+            state(hwnd).watcher = None;
+             let ptr = windows_sys::Win32::UI::WindowsAndMessaging::GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut AppState;
+             if !ptr.is_null() {
+                 if (*ptr).hfont_ui != 0 {
+                     let _ = DeleteObject((*ptr).hfont_ui);
+                     (*ptr).hfont_ui = 0;
+                 }
+                if (*ptr).hbr_bg != 0 {
+                    let _ = DeleteObject((*ptr).hbr_bg);
+                    (*ptr).hbr_bg = 0;
+                }
+                if (*ptr).hbr_panel != 0 {
+                    let _ = DeleteObject((*ptr).hbr_panel);
+                    (*ptr).hbr_panel = 0;
+                 }
+                 drop(Box::from_raw(ptr));
+             }
+             PostQuitMessage(0);
+             0
+         }
+         _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+     }
+ }
+ 
+ unsafe fn state(hwnd: HWND) -> &'static mut AppState {
+     let ptr = windows_sys::Win32::UI::WindowsAndMessaging::GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut AppState;
+     &mut *ptr
+ }
+ 
+fn scale_px(dpi: u32, px: i32) -> i32 {
+    ((px as i64 * dpi as i64) / 96) as i32
+}
+
+fn make_lparam_u16(lo: i32, hi: i32) -> LPARAM {
+    let lo = (lo as u32) & 0xffff;
+    let hi = (hi as u32) & 0xffff;
+    ((lo | (hi << 16)) as isize) as LPARAM
+}
+
+fn make_int_resource(id: u16) -> *const u16 {
+    id as usize as *const u16
+}
+
+unsafe fn get_font_height_px(hwnd: HWND, hfont: isize) -> i32 {
+    let hdc = GetDC(hwnd);
+    if hdc == 0 || hfont == 0 {
+        return scale_px(GetDpiForWindow(hwnd), 16);
+    }
+    let old = SelectObject(hdc, hfont);
+    let mut tm = MaybeUninit::<TEXTMETRICW>::zeroed();
+    let ok = GetTextMetricsW(hdc, tm.as_mut_ptr());
+    let _ = SelectObject(hdc, old);
+    let _ = ReleaseDC(hwnd, hdc);
+    if ok == 0 {
+        return scale_px(GetDpiForWindow(hwnd), 16);
+    }
+    let tm = tm.assume_init();
+    (tm.tmHeight as i32 + tm.tmExternalLeading as i32).max(1)
+}
+
+unsafe fn create_ui_font(hwnd: HWND) -> isize {
+    let dpi = GetDpiForWindow(hwnd) as i32;
+
+    let mut ncm = MaybeUninit::<NONCLIENTMETRICSW>::zeroed().assume_init();
+    ncm.cbSize = size_of::<NONCLIENTMETRICSW>() as u32;
+    let mut ok = SystemParametersInfoW(
+        SPI_GETNONCLIENTMETRICS,
+        ncm.cbSize,
+        &mut ncm as *mut _ as *mut c_void,
+        0,
+    );
+    if ok == 0 {
+        let alt = (size_of::<NONCLIENTMETRICSW>() - size_of::<i32>()) as u32;
+        ncm.cbSize = alt;
+        ok = SystemParametersInfoW(
+            SPI_GETNONCLIENTMETRICS,
+            ncm.cbSize,
+            &mut ncm as *mut _ as *mut c_void,
+            0,
+        );
+    }
+
+    if ok != 0 {
+        let mut lf: LOGFONTW = ncm.lfMessageFont;
+        lf.lfHeight = ((lf.lfHeight as i64 * dpi as i64) / 96) as i32;
+        lf.lfWidth = ((lf.lfWidth as i64 * dpi as i64) / 96) as i32;
+        let hfont = CreateFontIndirectW(&lf) as isize;
+        if hfont != 0 {
+            return hfont;
+        }
+    }
+
+    let height = -((9 * dpi + 36) / 72);
+    let face = wide("Segoe UI");
+    CreateFontW(
+        height,
+        0,
+        0,
+        0,
+        FW_NORMAL as i32,
+        0,
+        0,
+        0,
+        DEFAULT_CHARSET as u32,
+        OUT_DEFAULT_PRECIS as u32,
+        CLIP_DEFAULT_PRECIS as u32,
+        CLEARTYPE_QUALITY as u32,
+        (DEFAULT_PITCH | FF_DONTCARE) as u32,
+        face.as_ptr(),
+    ) as isize
+}
+
+unsafe fn apply_ui_font(hwnd: HWND, hfont: isize) {
+    if hfont == 0 {
+        return;
+    }
+    let state = state(hwnd);
+    let ctrls = [state.hwnd_status, state.hwnd_tabs, state.hwnd_search, state.hwnd_replace, state.hwnd_tree, state.hwnd_edit];
+    for c in ctrls {
+        if c != 0 {
+            SendMessageW(c, WM_SETFONT, hfont as usize, 1);
+        }
+    }
+    if state.hwnd_tree != 0 {
+        let dpi = GetDpiForWindow(hwnd);
+        let item_h = scale_px(dpi, 24).max(18);
+        SendMessageW(state.hwnd_tree, TVM_SETITEMHEIGHT, item_h as usize, 0);
+    }
+}
+
+unsafe extern "system" fn splitter_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    match msg {
+        WM_LBUTTONDOWN => {
+            SetCapture(hwnd);
+            let flags = windows_sys::Win32::UI::WindowsAndMessaging::GetWindowLongPtrW(hwnd, GWLP_USERDATA);
+            SetWindowLongPtrW(hwnd, GWLP_USERDATA, flags | SPLITTER_DRAGGING);
+            InvalidateRect(hwnd, null(), 1);
+            0
+        }
+        WM_LBUTTONDBLCLK => {
+            let parent = GetParent(hwnd);
+            if parent != 0 {
+                state(parent).left_pane_ratio = 0.28;
+                persist_left_pane_ratio(parent);
+                layout(parent);
+            }
+            0
+        }
+        WM_MOUSEMOVE => {
+            let parent = GetParent(hwnd);
+            let flags = windows_sys::Win32::UI::WindowsAndMessaging::GetWindowLongPtrW(hwnd, GWLP_USERDATA);
+            if (flags & SPLITTER_DRAGGING) != 0 {
+                if parent != 0 {
+                    let mut pt = windows_sys::Win32::Foundation::POINT { x: 0, y: 0 };
+                    if GetCursorPos(&mut pt) != 0 {
+                        ScreenToClient(parent, &mut pt);
+                        set_left_pane_from_px(parent, pt.x);
+                        layout(parent);
+                    }
+                }
+                return 0;
+            }
+
+            if (flags & SPLITTER_HOVER) == 0 {
+                SetWindowLongPtrW(hwnd, GWLP_USERDATA, flags | SPLITTER_HOVER);
+                let mut tme = TRACKMOUSEEVENT {
+                    cbSize: size_of::<TRACKMOUSEEVENT>() as u32,
+                    dwFlags: TME_LEAVE,
+                    hwndTrack: hwnd,
+                    dwHoverTime: 0,
+                };
+                let _ = TrackMouseEvent(&mut tme);
+                InvalidateRect(hwnd, null(), 1);
+            }
+            0
+        }
+        WM_MOUSELEAVE => {
+            let flags = windows_sys::Win32::UI::WindowsAndMessaging::GetWindowLongPtrW(hwnd, GWLP_USERDATA);
+            if (flags & SPLITTER_HOVER) != 0 {
+                SetWindowLongPtrW(hwnd, GWLP_USERDATA, flags & !SPLITTER_HOVER);
+                InvalidateRect(hwnd, null(), 1);
+            }
+            0
+        }
+        WM_LBUTTONUP => {
+            let flags = windows_sys::Win32::UI::WindowsAndMessaging::GetWindowLongPtrW(hwnd, GWLP_USERDATA);
+            if (flags & SPLITTER_DRAGGING) != 0 {
+                ReleaseCapture();
+                SetWindowLongPtrW(hwnd, GWLP_USERDATA, flags & !SPLITTER_DRAGGING);
+                let parent = GetParent(hwnd);
+                if parent != 0 {
+                    persist_left_pane_ratio(parent);
+                }
+                InvalidateRect(hwnd, null(), 1);
+            }
+            0
+        }
+        WM_SETCURSOR => {
+            SetCursor(LoadCursorW(0, IDC_SIZEWE));
+            1
+        }
+        WM_ERASEBKGND => {
+            let parent = GetParent(hwnd);
+            if parent != 0 {
+                let st = state(parent);
+                let hdc = wparam as HDC;
+                let mut rc = windows_sys::Win32::Foundation::RECT {
+                    left: 0,
+                    top: 0,
+                    right: 0,
+                    bottom: 0,
+                };
+                GetClientRect(hwnd, &mut rc);
+                FillRect(hdc, &rc, st.hbr_panel);
+
+                let flags = windows_sys::Win32::UI::WindowsAndMessaging::GetWindowLongPtrW(hwnd, GWLP_USERDATA);
+                let mut line = if st.dark_mode { 0x00303030 } else { GetSysColor(COLOR_3DSHADOW) };
+                if (flags & (SPLITTER_HOVER | SPLITTER_DRAGGING)) != 0 {
+                    line = if st.dark_mode { 0x00404040 } else { GetSysColor(COLOR_3DDKSHADOW) };
+                }
+
+                let dpi = GetDpiForWindow(parent);
+                let lw = scale_px(dpi, 1).max(1);
+                let cx = (rc.right - rc.left) / 2;
+                let line_rc = windows_sys::Win32::Foundation::RECT {
+                    left: cx,
+                    top: rc.top,
+                    right: cx + lw,
+                    bottom: rc.bottom,
+                };
+                let hbr = CreateSolidBrush(line);
+                if hbr != 0 {
+                    FillRect(hdc, &line_rc, hbr);
+                    let _ = DeleteObject(hbr);
+                }
+                return 1;
+            }
+            DefWindowProcW(hwnd, msg, wparam, lparam)
+        }
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
+
+unsafe fn set_left_pane_from_px(hwnd: HWND, left_px: i32) {
+    let mut rc = windows_sys::Win32::Foundation::RECT {
+        left: 0,
+        top: 0,
+        right: 0,
+        bottom: 0,
+    };
+    GetClientRect(hwnd, &mut rc);
+    let width = (rc.right - rc.left).max(1);
+
+    let dpi = GetDpiForWindow(hwnd);
+    let min_left = scale_px(dpi, 240);
+    let min_right = scale_px(dpi, 420);
+    let max_left = (width - min_right).max(min_left);
+    let left = left_px.clamp(min_left, max_left);
+    state(hwnd).left_pane_ratio = (left as f32 / width as f32).clamp(0.18, 0.72);
+}
+
+unsafe fn persist_left_pane_ratio(hwnd: HWND) {
+    let state = state(hwnd);
+    if let Some(project) = &mut state.project {
+        project.meta.left_pane_ratio = Some(state.left_pane_ratio);
+        let _ = ProjectStore::save_project_meta(project);
+    }
+}
+
+ unsafe fn layout(hwnd: HWND) {
+     let state = state(hwnd);
+     let mut rc = windows_sys::Win32::Foundation::RECT {
+         left: 0,
+         top: 0,
+         right: 0,
+         bottom: 0,
+     };
+     GetClientRect(hwnd, &mut rc);
+ 
+     if state.hwnd_status != 0 {
+         SendMessageW(state.hwnd_status, windows_sys::Win32::UI::WindowsAndMessaging::WM_SIZE, 0, 0);
+     }
+ 
+     let mut rc_status = windows_sys::Win32::Foundation::RECT {
+         left: 0,
+         top: 0,
+         right: 0,
+         bottom: 0,
+     };
+     if state.hwnd_status != 0 {
+         windows_sys::Win32::UI::WindowsAndMessaging::GetWindowRect(state.hwnd_status, &mut rc_status);
+     }
+ 
+     let status_height = (rc_status.bottom - rc_status.top).max(0);
+     let width = (rc.right - rc.left).max(0);
+     let height = (rc.bottom - rc.top - status_height).max(0);
+ 
+     if state.hwnd_status != 0 {
+         let parts = [(width * 42) / 100, (width * 66) / 100, (width * 76) / 100, -1];
+         SendMessageW(state.hwnd_status, SB_SETPARTS, parts.len(), parts.as_ptr() as LPARAM);
+     }
+ 
+    let dpi = GetDpiForWindow(hwnd);
+    let padding = scale_px(dpi, 8);
+    let gap = scale_px(dpi, 6);
+
+    let splitter_w = scale_px(dpi, 6);
+    let min_left = scale_px(dpi, 240);
+    let min_right = scale_px(dpi, 420);
+    let mut left_width = (width as f32 * state.left_pane_ratio) as i32;
+    let max_left = (width - min_right - splitter_w).max(min_left);
+    left_width = left_width.clamp(min_left, max_left);
+    let right_width = width - left_width - splitter_w;
+    let font_h = get_font_height_px(hwnd, state.hfont_ui);
+    let tabs_height = (font_h + scale_px(dpi, 14)).max(scale_px(dpi, 30));
+    let search_height = (font_h + scale_px(dpi, 10)).max(scale_px(dpi, 26));
+
+    let replace_y = padding + tabs_height + gap + search_height + gap;
+    let tree_y = if state.content_search { replace_y + search_height + gap } else { replace_y };
+    let flags = SWP_NOZORDER | SWP_NOACTIVATE;
+    let mut hdwp = BeginDeferWindowPos(7);
+    if hdwp != 0 {
+        if state.hwnd_tabs != 0 {
+            hdwp = DeferWindowPos(
+                hdwp,
+                state.hwnd_tabs,
+                0,
+                padding,
+                padding,
+                (left_width - padding * 2).max(0),
+                tabs_height,
+                flags,
+            );
+        }
+        if state.hwnd_search != 0 {
+            hdwp = DeferWindowPos(
+                hdwp,
+                state.hwnd_search,
+                0,
+                padding,
+                padding + tabs_height + gap,
+                (left_width - padding * 2).max(0),
+                search_height,
+                flags,
+            );
+        }
+        if state.hwnd_replace != 0 {
+            hdwp = DeferWindowPos(
+                hdwp,
+                state.hwnd_replace,
+                0,
+                padding,
+                replace_y,
+                (left_width - padding * 2).max(0),
+                search_height,
+                flags,
+            );
+        }
+        if state.hwnd_tree != 0 {
+            hdwp = DeferWindowPos(
+                hdwp,
+                state.hwnd_tree,
+                0,
+                padding,
+                tree_y,
+                (left_width - padding * 2).max(0),
+                (height - tree_y - padding).max(0),
+                flags,
+            );
+        }
+        if state.hwnd_edit != 0 {
+            hdwp = DeferWindowPos(
+                hdwp,
+                state.hwnd_edit,
+                0,
+                left_width + splitter_w + padding,
+                padding,
+                (right_width - padding * 2).max(0),
+                (height - padding * 2).max(0),
+                flags,
+            );
+        }
+        if state.hwnd_corkboard != 0 {
+            hdwp = DeferWindowPos(
+                hdwp,
+                state.hwnd_corkboard,
+                0,
+                left_width + splitter_w + padding,
+                padding,
+                (right_width - padding * 2).max(0),
+                (height - padding * 2).max(0),
+                flags,
+            );
+        }
+        if state.hwnd_splitter != 0 {
+            hdwp = DeferWindowPos(
+                hdwp,
+                state.hwnd_splitter,
+                0,
+                left_width,
+                padding,
+                splitter_w.max(1),
+                (height - padding * 2).max(0),
+                flags,
+            );
+        }
+        let _ = EndDeferWindowPos(hdwp);
+        return;
+    }
+
+    if state.hwnd_tabs != 0 {
+        windows_sys::Win32::UI::WindowsAndMessaging::MoveWindow(
+            state.hwnd_tabs,
+            padding,
+            padding,
+            (left_width - padding * 2).max(0),
+            tabs_height,
+            1,
+        );
+    }
+    if state.hwnd_search != 0 {
+        windows_sys::Win32::UI::WindowsAndMessaging::MoveWindow(
+            state.hwnd_search,
+            padding,
+            padding + tabs_height + gap,
+            (left_width - padding * 2).max(0),
+            search_height,
+            1,
+        );
+    }
+    if state.hwnd_replace != 0 {
+        windows_sys::Win32::UI::WindowsAndMessaging::MoveWindow(
+            state.hwnd_replace,
+            padding,
+            replace_y,
+            (left_width - padding * 2).max(0),
+            search_height,
+            1,
+        );
+    }
+    if state.hwnd_tree != 0 {
+        windows_sys::Win32::UI::WindowsAndMessaging::MoveWindow(
+            state.hwnd_tree,
+            padding,
+            tree_y,
+            (left_width - padding * 2).max(0),
+            (height - tree_y - padding).max(0),
+            1,
+        );
+    }
+    if state.hwnd_edit != 0 {
+        windows_sys::Win32::UI::WindowsAndMessaging::MoveWindow(
+            state.hwnd_edit,
+            left_width + splitter_w + padding,
+            padding,
+            (right_width - padding * 2).max(0),
+            (height - padding * 2).max(0),
+            1,
+        );
+    }
+    if state.hwnd_corkboard != 0 {
+        windows_sys::Win32::UI::WindowsAndMessaging::MoveWindow(
+            state.hwnd_corkboard,
+            left_width + splitter_w + padding,
+            padding,
+            (right_width - padding * 2).max(0),
+            (height - padding * 2).max(0),
+            1,
+        );
+    }
+    if state.hwnd_splitter != 0 {
+        windows_sys::Win32::UI::WindowsAndMessaging::MoveWindow(
+            state.hwnd_splitter,
+            left_width,
+            padding,
+            splitter_w.max(1),
+            (height - padding * 2).max(0),
+            1,
+        );
+    }
+ }
+
+ unsafe fn reload_items(hwnd: HWND) {
+     let state = state(hwnd);
+     let Some(project) = &state.project else { return };
+    state.history_view = None;
+    // Whatever changed the tree may have changed the chapters too.
+    reload_corkboard(hwnd);
+    if state.content_search && !state.filter_text.trim().is_empty() {
+        reload_search_results(hwnd);
+        return;
+    }
+
+     let root_label = state.current_section.label();
+     let dir = project.root.join(state.current_section.dir_name());
+    let filter = state.filter_text.trim().to_lowercase();
+    state.volume_paths.clear();
+    state.scene_nodes.clear();
+    // Unfiltered chapters keep their volumes; a filter lists the matching chapters of all of them.
+    let outline = if state.current_section == Section::Chapters && filter.is_empty() {
+        outline::read_outline(&dir).unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    let paths = storage::list_section_files(&project.root, state.current_section).unwrap_or_default();
+    if filter.is_empty() {
+        state.item_paths = paths;
+    } else {
+        state.item_paths = paths
+            .into_iter()
+            .filter(|p| {
+                p.file_stem()
+                    .and_then(|s| s.to_str())
+                    .map(|s| s.to_lowercase().contains(&filter))
+                    .unwrap_or(false)
+            })
+            .collect();
+    }
+
+     let mut root_text = wide(root_label);
+     let root_item = TVITEMEXW {
+         mask: TVIF_TEXT as u32,
+         hItem: 0,
+         state: 0,
+         stateMask: 0,
+         pszText: root_text.as_mut_ptr(),
+         cchTextMax: 0,
+         iImage: 0,
+         iSelectedImage: 0,
+         cChildren: 0,
+         lParam: -1,
+         iIntegral: 0,
+         uStateEx: 0,
+         hwnd: 0,
+         iExpandedImage: 0,
+         iReserved: 0,
+     };
+     let mut ins = TVINSERTSTRUCTW {
+         hParent: TVI_ROOT as isize,
+         hInsertAfter: windows_sys::Win32::UI::Controls::TVI_LAST as isize,
+         Anonymous: windows_sys::Win32::UI::Controls::TVINSERTSTRUCTW_0 { itemex: root_item },
+     };
+     let root = SendMessageW(state.hwnd_tree, TVM_INSERTITEMW, 0, &mut ins as *mut _ as LPARAM) as isize;
+
+     if !outline.is_empty() {
+        let first_child = insert_outline_items(hwnd, root, outline);
+        if first_child != 0 {
+            SendMessageW(state.hwnd_tree, TVM_SELECTITEM, TVGN_CARET as usize, first_child as LPARAM);
+        }
+        return;
+    }
+
+     let mut first_child: isize = 0;
+     for (i, path) in state.item_paths.iter().enumerate() {
+         let name = item_label(&mut state.chapter_cache, state.current_section, path);
+         let mut text = wide(&name);
+         let item = TVITEMEXW {
+             mask: (TVIF_TEXT | TVIF_PARAM) as u32,
+             hItem: 0,
+             state: 0,
+             stateMask: 0,
+             pszText: text.as_mut_ptr(),
+             cchTextMax: 0,
+             iImage: 0,
+             iSelectedImage: 0,
+             cChildren: 0,
+             lParam: i as isize,
+             iIntegral: 0,
+             uStateEx: 0,
+             hwnd: 0,
+             iExpandedImage: 0,
+             iReserved: 0,
+         };
+         let mut ins = TVINSERTSTRUCTW {
+             hParent: root,
+             hInsertAfter: windows_sys::Win32::UI::Controls::TVI_LAST as isize,
+             Anonymous: windows_sys::Win32::UI::Controls::TVINSERTSTRUCTW_0 { itemex: item },
+         };
+         let hitem = SendMessageW(state.hwnd_tree, TVM_INSERTITEMW, 0, &mut ins as *mut _ as LPARAM) as isize;
+         if first_child == 0 {
+             first_child = hitem;
+         }
+     }
+
+     if first_child != 0 {
+         SendMessageW(state.hwnd_tree, TVM_SELECTITEM, TVGN_CARET as usize, first_child as LPARAM);
+     }
+ }
+
+/// Adds the chapter outline below `root`, each volume with its contents, and fills `item_paths`
+/// and `volume_paths`. Returns the first chapter's tree item, or 0.
+unsafe fn insert_outline_items(hwnd: HWND, root: isize, outline: Vec<OutlineItem>) -> isize {
+    let state = state(hwnd);
+    state.item_paths.clear();
+    // Tree items of the volumes that contain the current item, outermost first.
+    let mut parents: Vec<isize> = Vec::new();
+    let mut volumes = Vec::new();
+    let mut first_child = 0;
+    for item in outline {
+        parents.truncate(item.depth);
+        let parent = parents.last().copied().unwrap_or(root);
+        match item.kind {
+            OutlineKind::Volume => {
+                let label = item.path.file_name().and_then(|s| s.to_str()).unwrap_or("").to_string();
+                let hitem = insert_tree_item(state.hwnd_tree, parent, &label, VOLUME_PARAM + state.volume_paths.len() as isize);
+                state.volume_paths.push(item.path);
+                parents.push(hitem);
+                volumes.push(hitem);
+            }
+            OutlineKind::Chapter => {
+                let label = item_label(&mut state.chapter_cache, Section::Chapters, &item.path);
+                let hitem = insert_tree_item(state.hwnd_tree, parent, &label, state.item_paths.len() as isize);
+                let scenes = state.chapter_cache.entry(&item.path).scenes.clone();
+                insert_scene_items(hwnd, hitem, state.item_paths.len(), &scenes, 0);
+                state.item_paths.push(item.path);
+                if first_child == 0 {
+                    first_child = hitem;
+                }
+            }
+        }
+    }
+    for hitem in volumes {
+        SendMessageW(state.hwnd_tree, TVM_EXPAND, TVE_EXPAND as usize, hitem as LPARAM);
+    }
+    first_child
+}
+
+/// Adds `scenes`, the chapter's scenes from position `first` on, as children of the chapter item
+/// `hitem`, the chapter at `chapter` in `item_paths`.
+unsafe fn insert_scene_items(hwnd: HWND, hitem: isize, chapter: usize, scenes: &[Scene], first: usize) {
+    let state = state(hwnd);
+    for (index, scene) in (first..).zip(scenes) {
+        let param = SCENE_PARAM + state.scene_nodes.len() as isize;
+        insert_tree_item(state.hwnd_tree, hitem, &scene_tree_label(scene, index), param);
+        state.scene_nodes.push(SceneNode {
+            chapter,
+            index,
+            scene: scene.clone(),
+        });
+    }
+}
+
+fn scene_tree_label(scene: &Scene, index: usize) -> String {
+    match &scene.pov {
+        Some(pov) => format!("{}  [{}]", scene_label(scene, index), pov),
+        None => scene_label(scene, index),
+    }
+}
+
+/// Opens the scene's chapter if needed and scrolls the editor to the scene's marker.
+unsafe fn open_scene(hwnd: HWND, node: usize) {
+    let state = state(hwnd);
+    let Some(chapter) = state.scene_nodes.get(node).map(|n| n.chapter) else { return };
+    if state.current_doc_path.as_ref() != state.item_paths.get(chapter) {
+        open_item_by_index(hwnd, chapter);
+    }
+    // The editor may hold unsaved edits, so look the scene up in its text rather than on disk.
+    let text = get_text(state.hwnd_edit);
+    let scenes = parse_scenes(&text);
+    let index = state.scene_nodes[node].index;
+    let Some(scene) = scenes.get(index).or(scenes.last()) else { return };
+    let before = &text[..scene.range.start];
+    // The editor counts a line break as one unit.
+    let offset = before.encode_utf16().count() - before.matches("\r\n").count();
+    SendMessageW(state.hwnd_edit, EM_SETSEL_MSG, offset, offset as LPARAM);
+    SendMessageW(state.hwnd_edit, EM_SCROLLCARET_MSG, 0, 0);
+
+    let mut parts = vec![format!("场景: {}", scene_label(scene, index))];
+    parts.extend(scene.pov.as_ref().map(|p| format!("视角: {}", p)));
+    parts.extend(scene.location.as_ref().map(|l| format!("地点: {}", l)));
+    parts.extend(scene.summary.clone());
+    parts.push(format!("{} 字", stats::count_markdown(&text[scene.range.clone()]).total()));
+    set_status_text(hwnd, &parts.join(" · "));
+}
+
+unsafe fn insert_tree_item(hwnd_tree: HWND, parent: isize, text: &str, param: isize) -> isize {
+    let mut text = wide(text);
+    let mut item: TVITEMEXW = std::mem::zeroed();
+    item.mask = (TVIF_TEXT | TVIF_PARAM) as u32;
+    item.pszText = text.as_mut_ptr();
+    item.lParam = param;
+    let mut ins = TVINSERTSTRUCTW {
+        hParent: parent,
+        hInsertAfter: windows_sys::Win32::UI::Controls::TVI_LAST as isize,
+        Anonymous: windows_sys::Win32::UI::Controls::TVINSERTSTRUCTW_0 { itemex: item },
+    };
+    SendMessageW(hwnd_tree, TVM_INSERTITEMW, 0, &mut ins as *mut _ as LPARAM) as isize
+}
+
+/// Fills the tree with full-text search results: one node per file, one child per hit.
+unsafe fn reload_search_results(hwnd: HWND) {
+    let state = state(hwnd);
+    let Some(project) = &state.project else { return };
+    let query = state.filter_text.trim().to_string();
+    state.item_paths.clear();
+    state.volume_paths.clear();
+    state.scene_nodes.clear();
+    state.search_hits.clear();
+    if state.search_index.is_none() {
+        match SearchIndex::build(&project.root) {
+            Ok(index) => state.search_index = Some(index),
+            Err(e) => {
+                set_status_text(hwnd, &e.to_string());
+                return;
+            }
+        }
+    }
+    let Some(index) = &state.search_index else { return };
+    let results = match index.search(&query, &state.search_options) {
+        Ok(results) => results,
+        Err(e) => {
+            set_status_text(hwnd, &e);
+            return;
+        }
+    };
+
+    let total: usize = results.iter().map(|f| f.hits.len()).sum();
+    let root = insert_tree_item(state.hwnd_tree, TVI_ROOT as isize, &format!("搜索结果（{} 处）", total), -1);
+    for file in results {
+        let file_idx = state.item_paths.len();
+        let stem = file.path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+        let section = file.section.map(Section::label).unwrap_or("项目");
+        let node = insert_tree_item(
+            state.hwnd_tree,
+            root,
+            &format!("[{}] {}（{}）", section, stem, file.hits.len()),
+            file_idx as isize,
+        );
+        state.item_paths.push(file.path);
+        for hit in file.hits {
+            let param = SEARCH_HIT_PARAM + state.search_hits.len() as isize;
+            insert_tree_item(state.hwnd_tree, node, &format!("{}: {}", hit.line, hit.snippet), param);
+            state.search_hits.push((file_idx, hit));
+        }
+        SendMessageW(state.hwnd_tree, TVM_EXPAND, TVE_EXPAND as usize, node as LPARAM);
+    }
+    SendMessageW(state.hwnd_tree, TVM_EXPAND, TVE_EXPAND as usize, root as LPARAM);
+    set_status_text(hwnd, &format!("全文搜索「{}」：{} 处", query, total));
+}
+
+/// Opens the file of a search hit and selects the match in the editor.
+unsafe fn open_search_hit(hwnd: HWND, hit_idx: usize) {
+    let state = state(hwnd);
+    let Some((file_idx, hit)) = state.search_hits.get(hit_idx).cloned() else { return };
+    if state.current_doc_path.as_ref() != state.item_paths.get(file_idx) {
+        open_item_by_index(hwnd, file_idx);
+    }
+    let edit = state.hwnd_edit;
+    SendMessageW(edit, EM_SETSEL_MSG, hit.editor_offset, (hit.editor_offset + hit.editor_len) as LPARAM);
+    SendMessageW(edit, EM_SCROLLCARET_MSG, 0, 0);
+}
+
+unsafe fn toggle_search_option(hwnd: HWND, id: usize) {
+    let state = state(hwnd);
+    let options = &mut state.search_options;
+    let checked = match id {
+        IDM_EDIT_CONTENT_SEARCH => {
+            state.content_search = !state.content_search;
+            let cue = wide(if state.content_search { "全文搜索全部条目..." } else { "搜索当前模块..." });
+            SendMessageW(state.hwnd_search, EM_SETCUEBANNER_MSG, 0, cue.as_ptr() as LPARAM);
+            ShowWindow(state.hwnd_replace, if state.content_search { SW_SHOW } else { SW_HIDE });
+            layout(hwnd);
+            state.content_search
+        }
+        IDM_EDIT_SEARCH_REGEX | IDM_EDIT_SEARCH_WORD => {
+            let mode = if id == IDM_EDIT_SEARCH_REGEX { SearchMode::Regex } else { SearchMode::WholeWord };
+            options.mode = if options.mode == mode { SearchMode::Plain } else { mode };
+            let menu = GetMenu(hwnd);
+            CheckMenuItem(menu, IDM_EDIT_SEARCH_REGEX as u32, if options.mode == SearchMode::Regex { MF_CHECKED } else { MF_UNCHECKED });
+            CheckMenuItem(menu, IDM_EDIT_SEARCH_WORD as u32, if options.mode == SearchMode::WholeWord { MF_CHECKED } else { MF_UNCHECKED });
+            SendMessageW(state.hwnd_tree, TVM_DELETEITEM, 0, 0);
+            reload_items(hwnd);
+            return;
+        }
+        _ => {
+            options.case_sensitive = !options.case_sensitive;
+            options.case_sensitive
+        }
+    };
+    CheckMenuItem(GetMenu(hwnd), id as u32, if checked { MF_CHECKED } else { MF_UNCHECKED });
+    SendMessageW(state.hwnd_tree, TVM_DELETEITEM, 0, 0);
+    reload_items(hwnd);
+}
+
+unsafe fn toggle_replace_scope(hwnd: HWND, id: usize) {
+    let scope = &mut state(hwnd).replace_scope;
+    let checked = match id - IDM_EDIT_REPLACE_SCOPE {
+        0 => {
+            scope.project_md = !scope.project_md;
+            scope.project_md
+        }
+        i => {
+            let section = Section::ALL[i - 1];
+            if let Some(pos) = scope.sections.iter().position(|s| *s == section) {
+                scope.sections.remove(pos);
+                false
+            } else {
+                scope.sections.push(section);
+                true
+            }
+        }
+    };
+    CheckMenuItem(GetMenu(hwnd), id as u32, if checked { MF_CHECKED } else { MF_UNCHECKED });
+}
+
+/// Replaces the full-text search query with the text of the replace box in every file of the
+/// replace scope, after showing a preview. The whole batch is one undoable command.
+unsafe fn replace_all(hwnd: HWND) -> Result<(), String> {
+    save_current_if_dirty(hwnd);
+    let state = state(hwnd);
+    let Some(project) = &state.project else { return Ok(()) };
+    let query = state.filter_text.trim().to_string();
+    if !state.content_search || query.is_empty() {
+        if !state.content_search {
+            toggle_search_option(hwnd, IDM_EDIT_CONTENT_SEARCH);
+        }
+        SetFocus(state.hwnd_search);
+        set_status_text(hwnd, "在搜索框输入要查找的内容、在下方输入替换文字，再执行“全部替换”");
+        return Ok(());
+    }
+    let replacement = get_text(state.hwnd_replace);
+    let files = replace::plan_replace(&project.root, &query, &replacement, &state.search_options, &state.replace_scope)?;
+    if files.is_empty() {
+        set_status_text(hwnd, "替换范围内没有匹配的内容");
+        return Ok(());
+    }
+
+    const MAX_SHOWN: usize = 20;
+    let total: usize = files.iter().map(|f| f.previews.len()).sum();
+    let mut text = String::new();
+    for (file, preview) in files.iter().flat_map(|f| f.previews.iter().map(move |p| (f, p))).take(MAX_SHOWN) {
+        let name = file.path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+        text.push_str(&format!("{} 第 {} 行：\n  {}\n→ {}\n", name, preview.line, preview.before, preview.after));
+    }
+    if total > MAX_SHOWN {
+        text.push_str(&format!("……另有 {} 处\n", total - MAX_SHOWN));
+    }
+    text.push_str(&format!("\n将在 {} 个文件中替换 {} 处，可用“撤销结构操作”恢复。继续吗？", files.len(), total));
+    let title = wide("全部替换");
+    let msg = wide(&text);
+    let answer = windows_sys::Win32::UI::WindowsAndMessaging::MessageBoxW(
+        hwnd,
+        msg.as_ptr(),
+        title.as_ptr(),
+        windows_sys::Win32::UI::WindowsAndMessaging::MB_YESNO | windows_sys::Win32::UI::WindowsAndMessaging::MB_ICONQUESTION,
+    );
+    if answer != windows_sys::Win32::UI::WindowsAndMessaging::IDYES {
+        return Ok(());
+    }
+
+    let mut cmd = Command::ReplaceText { files };
+    cmd.apply()?;
+    refresh_search_index(hwnd);
+    reload_current_if_edited(hwnd, &cmd.edited_files());
+    let count = cmd.edited_files().len();
+    state.undo_stack.push(cmd);
+    state.redo_stack.clear();
+    SendMessageW(state.hwnd_tree, TVM_DELETEITEM, 0, 0);
+    reload_items(hwnd);
+    set_status_text(hwnd, &format!("已在 {} 个文件中替换 {} 处", count, total));
+    Ok(())
+}
+
+fn item_label(cache: &mut ChapterCache, section: Section, path: &std::path::Path) -> String {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("chapter").to_string();
+    if section != Section::Chapters {
+        return stem;
+    }
+    let extra = &cache.entry(path).meta_label;
+    if extra.is_empty() {
+        stem
+    } else {
+        format!("{}  [{}]", stem, extra)
+    }
+}
+
+fn chapter_status_text(meta: &novel_outline_tool::domain::ChapterMeta) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    if let Some(status) = meta.status {
+        parts.push(status.label().to_string());
+    }
+    if let Some(pov) = meta.pov.as_deref().filter(|s| !s.trim().is_empty()) {
+        parts.push(format!("视角: {}", pov.trim()));
+    }
+    if let Some(target) = meta.target_words {
+        parts.push(format!("目标 {} 字", target));
+    }
+    if let Some(date) = meta.scene_date.as_deref().filter(|s| !s.trim().is_empty()) {
+        parts.push(date.trim().to_string());
+    }
+    if !meta.tags.is_empty() {
+        parts.push(meta.tags.join("/"));
+    }
+    parts.join(" · ")
+}
+
+/// Updates part 1 of the status bar and the selected tree label from the current chapter's front matter.
+unsafe fn refresh_chapter_meta(hwnd: HWND, content: &str) {
+    let state = state(hwnd);
+    let Some(project) = &state.project else { return };
+    let Some(path) = state.current_doc_path.clone() else { return };
+    // Search results may open files of any section, so look at the file rather than the tab.
+    if search::section_of(&project.root, &path) != Some(Section::Chapters) {
+        set_status_part(hwnd, 1, "");
+        return;
+    }
+    let (meta, _body) = storage::parse_chapter(content);
+    set_status_part(hwnd, 1, &chapter_status_text(&meta));
+    if state.content_search && !state.filter_text.trim().is_empty() {
+        return;
+    }
+
+    let Some(idx) = state.item_paths.iter().position(|p| p == &path) else { return };
+    let mut hitem = SendMessageW(state.hwnd_tree, TVM_GETNEXTITEM, TVGN_CARET as usize, 0) as isize;
+    // With one of its scenes selected, the chapter is the selection's parent.
+    let param = tree_item_param(state.hwnd_tree, hitem).map(|p| p as isize);
+    if let Some(node) = param.filter(|p| (SCENE_PARAM..VOLUME_PARAM).contains(p)) {
+        if state.scene_nodes.get((node - SCENE_PARAM) as usize).map(|n| n.chapter) != Some(idx) {
+            return;
+        }
+        hitem = SendMessageW(state.hwnd_tree, TVM_GETNEXTITEM, TVGN_PARENT as usize, hitem as LPARAM) as isize;
+    } else if param != Some(idx as isize) {
+        return;
+    }
+    let mut item: TVITEMEXW = std::mem::zeroed();
+    item.hItem = hitem;
+    let mut text = wide(&item_label(&mut state.chapter_cache, Section::Chapters, &path));
+    item.mask = TVIF_TEXT as u32;
+    item.pszText = text.as_mut_ptr();
+    SendMessageW(state.hwnd_tree, TVM_SETITEMW, 0, &item as *const _ as LPARAM);
+    refresh_scene_items(hwnd, hitem, idx, content);
+}
+
+/// Brings the scene children of the chapter item `hitem` in line with `content`, relabelling
+/// the existing ones in place so that the selection and the editor stay where they are.
+unsafe fn refresh_scene_items(hwnd: HWND, hitem: isize, chapter: usize, content: &str) {
+    let state = state(hwnd);
+    let scenes = parse_scenes(content);
+    let mut children = Vec::new();
+    let mut child = SendMessageW(state.hwnd_tree, TVM_GETNEXTITEM, TVGN_CHILD as usize, hitem as LPARAM) as isize;
+    while child != 0 {
+        children.push(child);
+        child = SendMessageW(state.hwnd_tree, TVM_GETNEXTITEM, TVGN_NEXT as usize, child as LPARAM) as isize;
+    }
+    for (index, &child) in children.iter().enumerate() {
+        let Some(node) = tree_item_param(state.hwnd_tree, child).map(|p| p as isize - SCENE_PARAM) else { continue };
+        let Some(scene) = scenes.get(index) else {
+            if let Some(node) = state.scene_nodes.get_mut(node as usize) {
+                node.chapter = usize::MAX;
+            }
+            SendMessageW(state.hwnd_tree, TVM_DELETEITEM, 0, child as LPARAM);
+            continue;
+        };
+        let mut item: TVITEMEXW = std::mem::zeroed();
+        item.mask = TVIF_TEXT as u32;
+        item.hItem = child;
+        let mut text = wide(&scene_tree_label(scene, index));
+        item.pszText = text.as_mut_ptr();
+        SendMessageW(state.hwnd_tree, TVM_SETITEMW, 0, &item as *const _ as LPARAM);
+        if let Some(node) = state.scene_nodes.get_mut(node as usize) {
+            node.scene = scene.clone();
+        }
+    }
+    if scenes.len() > children.len() {
+        insert_scene_items(hwnd, hitem, chapter, &scenes[children.len()..], children.len());
+    }
+}
+
+/// Shows the 字数 of the open document in part 3 of the status bar, and today's progress
+/// including unsaved edits when the project has a daily target.
+unsafe fn refresh_word_count(hwnd: HWND, content: &str) {
+    let state = state(hwnd);
+    let count = stats::count_document(content).total() as u64;
+    let mut text = format!("{} 字", count);
+    if let Some(target) = state.project.as_ref().and_then(|p| p.meta.daily_target) {
+        let today = state.today_words + count as i64 - state.doc_count as i64;
+        text.push_str(&format!(" · 今日 {}/{}", today, target));
+    }
+    set_status_part(hwnd, 3, &text);
+}
+
+unsafe fn load_today_words(hwnd: HWND) {
+    let state = state(hwnd);
+    state.doc_count = 0;
+    state.today_words = match &state.project {
+        Some(project) => WritingHistory::load(&project.root).map(|h| h.day(&history::today()).net()).unwrap_or(0),
+        None => 0,
+    };
+}
+
+ unsafe fn open_item_by_index(hwnd: HWND, idx: usize) {
+     save_current_if_dirty(hwnd);
+ 
+     let state = state(hwnd);
+     if idx >= state.item_paths.len() {
+         return;
+     }
+     let path = state.item_paths[idx].clone();
+     match storage::read_text(&path) {
+         Ok(content) => {
+             let w = wide(&content);
+             SetWindowTextW(state.hwnd_edit, w.as_ptr());
+             state.current_doc_path = Some(path.clone());
+             state.current_doc_dirty = false;
+            state.doc_stamp = Some(DiskStamp::of_text(&path, &content));
+             set_status_text(hwnd, &format!("编辑: {}", path.file_name().and_then(|s| s.to_str()).unwrap_or("")));
+            refresh_chapter_meta(hwnd, &content);
+            state.doc_count = stats::count_document(&content).total() as u64;
+            refresh_word_count(hwnd, &content);
+         }
+         Err(e) => show_error(hwnd, &e.to_string()),
+     }
+ }
+
+ unsafe fn add_tabs(hwnd_tabs: HWND) {
+     let tabs = ["章节", "角色", "世界观", "时间线"];
+     for (i, t) in tabs.iter().enumerate() {
+         let mut text = wide(t);
+         let item = TCITEMW {
+             mask: windows_sys::Win32::UI::Controls::TCIF_TEXT as u32,
+             dwState: 0,
+             dwStateMask: 0,
+             pszText: text.as_mut_ptr(),
+             cchTextMax: 0,
+             iImage: 0,
+             lParam: 0,
+         };
+         SendMessageW(hwnd_tabs, TCM_INSERTITEMW, i, &item as *const _ as LPARAM);
+     }
+ }
+
+ unsafe fn save_current_if_dirty(hwnd: HWND) {
+     let state = state(hwnd);
+    if !state.current_doc_dirty || state.resolving_conflict {
+         return;
+     }
+    let Some(path) = state.current_doc_path.clone() else { return };
+ 
+     let len = GetWindowTextLengthW(state.hwnd_edit);
+     let mut buf = vec![0u16; (len as usize) + 1];
+     let read = GetWindowTextW(state.hwnd_edit, buf.as_mut_ptr(), buf.len() as i32);
+     if read <= 0 {
+         return;
+     }
+     let s = String::from_utf16_lossy(&buf[..read as usize]);
+    if let Some(stamp) = &state.doc_stamp {
+        match conflict::external_change(&path, stamp) {
+            Ok(None) => {}
+            Ok(Some(change)) => {
+                state.resolving_conflict = true;
+                let result = resolve_external_change(hwnd, &path, s, change);
+                state.resolving_conflict = false;
+                if let Err(e) = result {
+                    show_error(hwnd, &e);
+                }
+                return;
+            }
+            Err(e) => {
+                show_error(hwnd, &e.to_string());
+                return;
+            }
+        }
+    }
+    write_current_doc(hwnd, &path, &s);
+}
+
+unsafe fn write_current_doc(hwnd: HWND, path: &Path, s: &str) {
+    let state = state(hwnd);
+     if let Err(e) = storage::write_text_atomic(path, s) {
+         show_error(hwnd, &e.to_string());
+         return;
+     }
+    state.doc_stamp = Some(DiskStamp::of_text(path, s));
+    if let Some(index) = &mut state.search_index {
+        index.update_file(path, s);
+    }
+    let mut status = "已自动保存".to_string();
+    if let Some(project) = &state.project {
+        let now = now_unix();
+        let _ = recovery::clear_journal(&project.root, path);
+        if !git_replaces_backup(state) {
+            let _ = snapshots::snapshot_project(&project.root, &[]);
+            if now >= state.last_gc_unix.saturating_add(3600) {
+                let _ = snapshots::gc(&project.root, &snapshots::RetentionPolicy::default(), now);
+                state.last_gc_unix = now;
+            }
+        }
+        let count = stats::count_document(s).total() as u64;
+        let delta = count as i64 - state.doc_count as i64;
+        if delta != 0 {
+            match history::record_words(&project.root, &history::today(), delta) {
+                Ok(day) => state.today_words = day.net(),
+                Err(e) => status = format!("已自动保存，但记录字数失败：{}", e),
+            }
+        }
+        state.doc_count = count;
+    }
+     state.current_doc_dirty = false;
+     set_status_text(hwnd, &status);
+    refresh_chapter_meta(hwnd, s);
+    if state.project.as_ref().is_some_and(|p| path == p.root.join("project.md")) {
+        reload_project_meta(hwnd);
+    }
+ }
+
+/// Takes up `project.md` again after it was edited in the editor or outside the program, so that
+/// goals, calendars and Git settings typed there apply now and later saves keep them.
+unsafe fn reload_project_meta(hwnd: HWND) {
+    let state = state(hwnd);
+    let Some(project) = &mut state.project else { return };
+    match ProjectStore::read_project_meta(&project.root) {
+        Ok(meta) => {
+            let git_changed = meta.git != project.meta.git;
+            project.meta = meta;
+            if git_changed {
+                start_git(hwnd);
+            }
+        }
+        Err(e) => set_status_text(hwnd, &e),
+    }
+}
+
+/// Asks what to do when the open document changed on disk since it was loaded: keep the editor's
+/// text, take the file's, or keep both by writing a conflict copy. Whichever text loses is backed up.
+unsafe fn resolve_external_change(hwnd: HWND, path: &Path, mine: String, change: ExternalChange) -> Result<(), String> {
+    let state = state(hwnd);
+    let Some(project) = &state.project else { return Ok(()) };
+    let root = project.root.clone();
+    let name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+    let title = wide("文件已在外部更改");
+    let theirs = match change {
+        ExternalChange::Modified(theirs) => theirs,
+        ExternalChange::Deleted => {
+            let msg = wide(&format!(
+                "“{}”已被其他程序删除或移走。\n\n是：用编辑中的内容重新创建\n否：关闭该条目（编辑中的内容先备份到 .backup/）",
+                name
+            ));
+            let answer = windows_sys::Win32::UI::WindowsAndMessaging::MessageBoxW(
+                hwnd,
+                msg.as_ptr(),
+                title.as_ptr(),
+                windows_sys::Win32::UI::WindowsAndMessaging::MB_YESNO | windows_sys::Win32::UI::WindowsAndMessaging::MB_ICONWARNING,
+            );
+            if answer == windows_sys::Win32::UI::WindowsAndMessaging::IDYES {
+                write_current_doc(hwnd, path, &mine);
+            } else {
+                storage::backup_text(&root, path, &mine).map_err(|e| e.to_string())?;
+                let _ = recovery::clear_journal(&root, path);
+                state.current_doc_path = None;
+                state.doc_stamp = None;
+                SetWindowTextW(state.hwnd_edit, wide("").as_ptr());
+                state.current_doc_dirty = false;
+                SendMessageW(state.hwnd_tree, TVM_DELETEITEM, 0, 0);
+                reload_items(hwnd);
+                set_status_text(hwnd, &format!("已关闭 {}，编辑中的内容已备份", name));
+            }
+            return Ok(());
+        }
+    };
+
+    const MAX_LINES: usize = 30;
+    let diff = format_diff(&diff_lines(&theirs, &mine), 2);
+    let mut text = format!(
+        "“{}”在打开后被其他程序修改过（磁盘上 {} 字，编辑中 {} 字）。\n\n差异（- 磁盘上的内容，+ 编辑中的内容）：\n",
+        name,
+        stats::count_document(&theirs).total(),
+        stats::count_document(&mine).total()
+    );
+    text.extend(diff.lines().take(MAX_LINES).map(|l| format!("{}\n", l)));
+    let total = diff.lines().count();
+    if total > MAX_LINES {
+        text.push_str(&format!("……另有 {} 行\n", total - MAX_LINES));
+    }
+    text.push_str("\n是：保留我的版本（覆盖前备份磁盘上的内容）\n否：采用磁盘上的版本（编辑中的内容先备份）\n取消：两者都保留，在 .conflicts/ 另存一份标出差异的冲突副本，并载入磁盘上的版本");
+    let msg = wide(&text);
+    let answer = windows_sys::Win32::UI::WindowsAndMessaging::MessageBoxW(
+        hwnd,
+        msg.as_ptr(),
+        title.as_ptr(),
+        windows_sys::Win32::UI::WindowsAndMessaging::MB_YESNOCANCEL | windows_sys::Win32::UI::WindowsAndMessaging::MB_ICONWARNING,
+    );
+    if answer == windows_sys::Win32::UI::WindowsAndMessaging::IDYES {
+        storage::backup_text(&root, path, &theirs).map_err(|e| e.to_string())?;
+        write_current_doc(hwnd, path, &mine);
+        return Ok(());
+    }
+    if answer == windows_sys::Win32::UI::WindowsAndMessaging::IDNO {
+        storage::backup_text(&root, path, &mine).map_err(|e| e.to_string())?;
+        show_document_text(hwnd, path, &theirs);
+        set_status_text(hwnd, &format!("已载入磁盘上的 {}，编辑中的内容已备份", name));
+    } else {
+        let copy = conflict::write_conflict_copy(&root, path, &mine, &theirs, now_unix()).map_err(|e| e.to_string())?;
+        show_document_text(hwnd, path, &theirs);
+        let copy_name = copy.file_name().and_then(|s| s.to_str()).unwrap_or("");
+        set_status_text(hwnd, &format!("已另存冲突副本: .conflicts/{}", copy_name));
+    }
+    let _ = recovery::clear_journal(&root, path);
+    if let Some(index) = &mut state.search_index {
+        index.update_file(path, &theirs);
+    }
+    Ok(())
+}
+
+/// Opens the project's repository when `project.md` turns Git versioning on, and starts the
+/// commit timer when an interval is configured.
+unsafe fn start_git(hwnd: HWND) {
+    let state = state(hwnd);
+    let _ = KillTimer(hwnd, TIMER_GIT);
+    state.git = None;
+    let Some(project) = &state.project else { return };
+    let Some(settings) = project.meta.git.clone() else { return };
+    match GitRepo::open(&project.root) {
+        Ok(Some(repo)) => {
+            state.git = Some(repo);
+            if let Some(minutes) = settings.commit_interval_minutes.filter(|m| *m > 0) {
+                let _ = SetTimer(hwnd, TIMER_GIT, minutes.saturating_mul(60_000), None);
+            }
+        }
+        Ok(None) => set_status_text(hwnd, "project.md 启用了 Git，但项目不在 Git 仓库中，可用“文件 → 启用 Git 版本管理”创建"),
+        Err(e) => set_status_text(hwnd, &format!("无法运行 git: {}", e)),
+    }
+}
+
+fn git_replaces_backup(state: &AppState) -> bool {
+    state.git.is_some()
+        && state
+            .project
+            .as_ref()
+            .and_then(|p| p.meta.git.as_ref())
+            .is_some_and(|g| g.disable_backup)
+}
+
+/// Commits the changed Markdown files, if Git versioning is on.
+unsafe fn commit_to_git(hwnd: HWND) -> Result<(), String> {
+    let Some(repo) = &state(hwnd).git else { return Ok(()) };
+    if let Some(summary) = repo.commit_changes().map_err(|e| e.to_string())? {
+        set_status_text(hwnd, &format!("已提交 {}: {}", summary.commit, summary.subject));
+    }
+    Ok(())
+}
+
+/// Creates a repository in the project folder unless it is already in one, turns Git versioning
+/// on in `project.md` and commits the current state.
+unsafe fn enable_git(hwnd: HWND) -> Result<(), String> {
+    save_current_if_dirty(hwnd);
+    let state = state(hwnd);
+    let Some(project) = &mut state.project else { return Ok(()) };
+    if !git::git_available() {
+        return Err("找不到 git 程序，请先安装 Git 并确认它在 PATH 中".to_string());
+    }
+    GitRepo::init(&project.root).map_err(|e| e.to_string())?;
+    if project.meta.git.is_none() {
+        ProjectStore::update_project_meta(project, |meta| meta.git = Some(GitSettings::default()))?;
+    }
+    start_git(hwnd);
+    commit_to_git(hwnd)?;
+    show_info(
+        hwnd,
+        "Git 版本管理",
+        "已启用。每次“文件 → 保存”都会提交改动过的条目，提交说明如“更新 0003-决战.md (+1200字)”。\n\n\
+         在 project.md 的 git: 下可设置 commit_interval_minutes（定时提交的间隔分钟数）与 disable_backup: true（不再写入 .backup/）。",
+    );
+    Ok(())
+}
+
+unsafe fn start_watching(hwnd: HWND) {
+    let state = state(hwnd);
+    state.watcher = state.project.as_ref().and_then(|p| ProjectWatcher::new(&p.root).ok());
+    if state.watcher.is_some() {
+        let _ = SetTimer(hwnd, TIMER_WATCH, 500, None);
+    }
+}
+
+/// Refreshes the tree when other programs add, remove, rename or edit project files, and reloads
+/// the open document when it changed on disk while it has no unsaved edits. The tool's own saves
+/// of the open document need no refresh.
+unsafe fn apply_external_changes(hwnd: HWND) {
+    let state = state(hwnd);
+    if state.dragging || state.resolving_conflict {
+        return;
+    }
+    let Some(watcher) = &mut state.watcher else { return };
+    let changed = match watcher.poll() {
+        Ok(changed) => changed,
+        Err(_) => {
+            state.watcher = None;
+            let _ = KillTimer(hwnd, TIMER_WATCH);
+            return;
+        }
+    };
+    if changed.is_empty() {
+        return;
+    }
+    refresh_search_index(hwnd);
+    if let Some(root) = state.project.as_ref().map(|p| p.root.clone()) {
+        if changed.iter().any(|p| *p == root || *p == root.join("project.md")) {
+            reload_project_meta(hwnd);
+        }
+    }
+
+    let current = state.current_doc_path.clone();
+    let mut reloaded = false;
+    if let (Some(path), Some(stamp)) = (&current, &state.doc_stamp) {
+        let root = state.project.as_ref().map(|p| p.root.as_path());
+        if !state.current_doc_dirty && changed.iter().any(|p| p == path || Some(p.as_path()) == root) {
+            if let Ok(Some(ExternalChange::Modified(text))) = conflict::external_change(path, stamp) {
+                show_document_text(hwnd, path, &text);
+                refresh_chapter_meta(hwnd, &text);
+                let name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+                set_status_text(hwnd, &format!("已载入外部修改: {}", name));
+                reloaded = true;
+            }
+        }
+    }
+    if state.history_view.is_some() || (!reloaded && changed.iter().all(|p| Some(p) == current.as_ref())) {
+        return;
+    }
+    SendMessageW(state.hwnd_tree, TVM_DELETEITEM, 0, 0);
+    reload_items(hwnd);
+}
+
+/// Writes the unsaved editor text to `.recovery/` so a crash before the next save loses at most a few seconds.
+unsafe fn write_recovery_journal(hwnd: HWND) {
+    let state = state(hwnd);
+    if !state.journal_pending || !state.current_doc_dirty {
+        return;
+    }
+    let (Some(project), Some(path)) = (&state.project, &state.current_doc_path) else { return };
+    let text = get_text(state.hwnd_edit);
+    if recovery::write_journal(&project.root, path, &text).is_ok() {
+        state.journal_pending = false;
+    }
+}
+
+/// Asks, journal by journal, whether to restore text a previous session could not save. Restoring
+/// is an undoable command; cancelling keeps the journal so the question comes back next time.
+/// `explicit` is set when the user asked from the menu, to report that nothing is pending.
+unsafe fn offer_recovery(hwnd: HWND, explicit: bool) -> Result<(), String> {
+    save_current_if_dirty(hwnd);
+    let state = state(hwnd);
+    let Some(project) = &state.project else { return Ok(()) };
+    if !explicit && project.recovered.is_empty() {
+        return Ok(());
+    }
+    let root = project.root.clone();
+    let entries = recovery::pending_journals(&root).map_err(|e| e.to_string())?;
+    if entries.is_empty() {
+        if explicit {
+            set_status_text(hwnd, "没有需要恢复的未保存内容");
+        }
+        return Ok(());
+    }
+
+    const MAX_LINES: usize = 30;
+    let mut restored = 0;
+    for entry in entries {
+        let name = entry.path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+        let disk = entry.disk_text();
+        let diff = format_diff(&diff_lines(&disk, &entry.text), 2);
+        let mut text = format!(
+            "“{}”有上次未能保存的内容（{}，{} 字；文件中为 {} 字）。\n\n差异（- 文件中的内容，+ 未保存的内容）：\n",
+            name,
+            storage::format_local_time(entry.saved_unix),
+            stats::count_document(&entry.text).total(),
+            stats::count_document(&disk).total()
+        );
+        text.extend(diff.lines().take(MAX_LINES).map(|l| format!("{}\n", l)));
+        let total = diff.lines().count();
+        if total > MAX_LINES {
+            text.push_str(&format!("……另有 {} 行\n", total - MAX_LINES));
+        }
+        text.push_str("\n是：恢复未保存的内容（可用“撤销结构操作”撤回）\n否：丢弃\n取消：暂不处理，下次打开项目时再询问");
+        let title = wide("恢复未保存的内容");
+        let msg = wide(&text);
+        let answer = windows_sys::Win32::UI::WindowsAndMessaging::MessageBoxW(
+            hwnd,
+            msg.as_ptr(),
+            title.as_ptr(),
+            windows_sys::Win32::UI::WindowsAndMessaging::MB_YESNOCANCEL | windows_sys::Win32::UI::WindowsAndMessaging::MB_ICONWARNING,
+        );
+        if answer == windows_sys::Win32::UI::WindowsAndMessaging::IDYES {
+            if entry.path.exists() {
+                storage::backup_text(&root, &entry.path, &disk).map_err(|e| e.to_string())?;
+            }
+            let mut cmd = Command::RestoreVersion {
+                path: entry.path.clone(),
+                before: disk,
+                after: entry.text.clone(),
+            };
+            cmd.apply()?;
+            entry.discard().map_err(|e| e.to_string())?;
+            reload_current_if_edited(hwnd, &cmd.edited_files());
+            state.undo_stack.push(cmd);
+            state.redo_stack.clear();
+            restored += 1;
+        } else if answer == windows_sys::Win32::UI::WindowsAndMessaging::IDNO {
+            entry.discard().map_err(|e| e.to_string())?;
+        }
+    }
+    if restored > 0 {
+        refresh_search_index(hwnd);
+        SendMessageW(state.hwnd_tree, TVM_DELETEITEM, 0, 0);
+        reload_items(hwnd);
+        set_status_text(hwnd, &format!("已恢复 {} 个文件的未保存内容", restored));
+    }
+    Ok(())
+}
+
+unsafe fn get_text(hwnd_ctrl: HWND) -> String {
+    if hwnd_ctrl == 0 {
+        return String::new();
+    }
+    let len = GetWindowTextLengthW(hwnd_ctrl);
+    if len <= 0 {
+        return String::new();
+    }
+    let mut buf = vec![0u16; (len as usize) + 1];
+    let read = GetWindowTextW(hwnd_ctrl, buf.as_mut_ptr(), buf.len() as i32);
+    if read <= 0 {
+        return String::new();
+    }
+    String::from_utf16_lossy(&buf[..read as usize])
+}
+
+unsafe fn highlight_current_line(hwnd: HWND) {
+    let state = state(hwnd);
+    let edit = state.hwnd_edit;
+    if edit == 0 {
+        return;
+    }
+    let len = GetWindowTextLengthW(edit);
+    if len > 200_000 {
+        return;
+    }
+
+    let mut sel_start: u32 = 0;
+    let mut sel_end: u32 = 0;
+    SendMessageW(
+        edit,
+        EM_GETSEL_MSG,
+        &mut sel_start as *mut _ as usize,
+        &mut sel_end as *mut _ as LPARAM,
+    );
+
+    let line = SendMessageW(edit, EM_LINEFROMCHAR_MSG, sel_start as usize, 0) as i32;
+    let heading = line_starts_with(edit, line, "#");
+    if state.last_highlight_line == line && state.last_highlight_bold == heading {
+        return;
+    }
+
+    if state.last_highlight_line != -1 && state.last_highlight_line != line && state.last_highlight_bold {
+        apply_line_bold(edit, state.last_highlight_line, false);
+    }
+    apply_line_bold(edit, line, heading);
+    state.last_highlight_line = line;
+    state.last_highlight_bold = heading;
+
+    SendMessageW(edit, EM_SETSEL_MSG, sel_start as usize, sel_end as LPARAM);
+}
+
+unsafe fn line_starts_with(edit: HWND, line: i32, prefix: &str) -> bool {
+    let start = SendMessageW(edit, EM_LINEINDEX_MSG, line as usize, 0) as i32;
+    if start < 0 {
+        return false;
+    }
+    let line_len = SendMessageW(edit, EM_LINELENGTH_MSG, start as usize, 0) as usize;
+    if line_len == 0 {
+        return false;
+    }
+    let max = line_len.min(256);
+    let mut buf = vec![0u16; max + 1];
+    buf[0] = max as u16;
+    let copied = SendMessageW(edit, EM_GETLINE_MSG, line as usize, buf.as_mut_ptr() as LPARAM) as usize;
+    let s = String::from_utf16_lossy(&buf[..copied]);
+    s.trim_start().starts_with(prefix)
+}
+
+unsafe fn apply_line_bold(edit: HWND, line: i32, bold: bool) {
+    let start = SendMessageW(edit, EM_LINEINDEX_MSG, line as usize, 0) as i32;
+    if start < 0 {
+        return;
+    }
+    let len = SendMessageW(edit, EM_LINELENGTH_MSG, start as usize, 0) as i32;
+    if len <= 0 {
+        return;
+    }
+    SendMessageW(edit, EM_SETSEL_MSG, start as usize, (start + len) as LPARAM);
+
+    let mut cf: CHARFORMAT2W = std::mem::zeroed();
+    cf.Base.cbSize = std::mem::size_of::<CHARFORMAT2W>() as u32;
+    cf.Base.dwMask = CFM_BOLD;
+    cf.Base.dwEffects = if bold {
+        CFE_BOLD
+    } else {
+        windows::Win32::UI::Controls::RichEdit::CFE_EFFECTS(0)
+    };
+    SendMessageW(edit, EM_SETCHARFORMAT, SCF_SELECTION as usize, &cf as *const _ as LPARAM);
+}
+ 
+ unsafe fn show_error(owner: HWND, message: &str) {
+     let title = wide("错误");
+     let msg = wide(message);
+     windows_sys::Win32::UI::WindowsAndMessaging::MessageBoxW(
+         owner,
+         msg.as_ptr(),
+         title.as_ptr(),
+         windows_sys::Win32::UI::WindowsAndMessaging::MB_ICONERROR,
+     );
+ }
+ 
+unsafe fn show_info(owner: HWND, title: &str, message: &str) {
+    let title = wide(title);
+    let msg = wide(message);
+    windows_sys::Win32::UI::WindowsAndMessaging::MessageBoxW(
+        owner,
+        msg.as_ptr(),
+        title.as_ptr(),
+        windows_sys::Win32::UI::WindowsAndMessaging::MB_ICONINFORMATION,
+    );
+}
+
+ unsafe fn set_status_text(hwnd: HWND, text: &str) {
+    set_status_part(hwnd, 0, text);
+}
+
+unsafe fn set_status_part(hwnd: HWND, part: usize, text: &str) {
+    let state = state(hwnd);
+    if state.hwnd_status == 0 {
+        return;
+    }
+    let t = wide(text);
+    SendMessageW(state.hwnd_status, SB_SETTEXTW, part, t.as_ptr() as LPARAM);
+ }
+ 
+unsafe fn theme_palette(dark: bool) -> (u32, u32, u32, u32) {
+    if dark {
+        (0x00202020u32, 0x00262626u32, 0x00E0E0E0u32, 0x00303030u32)
+    } else {
+        let bg = GetSysColor(COLOR_WINDOW);
+        let fg = GetSysColor(COLOR_WINDOWTEXT);
+        let panel = GetSysColor(COLOR_3DFACE);
+        let line = GetSysColor(COLOR_3DSHADOW);
+        (bg, panel, fg, line)
+    }
+}
+
+unsafe fn update_theme_resources(hwnd: HWND, dark: bool) -> (u32, u32, u32, u32) {
+    let (bg, panel, fg, line) = theme_palette(dark);
+    let state = state(hwnd);
+    state.theme_bg = bg;
+    state.theme_panel_bg = panel;
+    state.theme_fg = fg;
+
+    if state.hbr_bg != 0 {
+        let _ = DeleteObject(state.hbr_bg);
+        state.hbr_bg = 0;
+    }
+    if state.hbr_panel != 0 {
+        let _ = DeleteObject(state.hbr_panel);
+        state.hbr_panel = 0;
+    }
+    state.hbr_bg = CreateSolidBrush(bg);
+    state.hbr_panel = CreateSolidBrush(panel);
+
+    (bg, panel, fg, line)
+}
+
+unsafe fn apply_theme(hwnd: HWND, dark: bool) {
+    let value: u32 = if dark { 1 } else { 0 };
+    let _ = DwmSetWindowAttribute(
+        hwnd,
+        DWMWA_USE_IMMERSIVE_DARK_MODE,
+        &value as *const _ as *const c_void,
+        std::mem::size_of_val(&value) as u32,
+    );
+
+    let state = state(hwnd);
+    let (bg, _panel, fg, line) = update_theme_resources(hwnd, dark);
+    let theme = if dark { wide("DarkMode_Explorer") } else { wide("Explorer") };
+
+    if state.hwnd_tabs != 0 {
+        let _ = SetWindowTheme(state.hwnd_tabs, theme.as_ptr(), null());
+    }
+    if state.hwnd_search != 0 {
+        let _ = SetWindowTheme(state.hwnd_search, theme.as_ptr(), null());
+    }
+    if state.hwnd_replace != 0 {
+        let _ = SetWindowTheme(state.hwnd_replace, theme.as_ptr(), null());
+    }
+    if state.hwnd_tree != 0 {
+        let _ = SetWindowTheme(state.hwnd_tree, theme.as_ptr(), null());
+        SendMessageW(state.hwnd_tree, TVM_SETBKCOLOR, 0, bg as LPARAM);
+        SendMessageW(state.hwnd_tree, TVM_SETTEXTCOLOR, 0, fg as LPARAM);
+        SendMessageW(state.hwnd_tree, TVM_SETLINECOLOR, 0, line as LPARAM);
+    }
+    if state.hwnd_status != 0 {
+        let _ = SetWindowTheme(state.hwnd_status, theme.as_ptr(), null());
+    }
+    if state.hwnd_splitter != 0 {
+        let _ = SetWindowTheme(state.hwnd_splitter, theme.as_ptr(), null());
+    }
+    if state.hwnd_edit != 0 {
+        SendMessageW(state.hwnd_edit, EM_SETBKGNDCOLOR_MSG, 0, bg as LPARAM);
+        let mut cf: CHARFORMAT2W = std::mem::zeroed();
+        cf.Base.cbSize = std::mem::size_of::<CHARFORMAT2W>() as u32;
+        cf.Base.dwMask = CFM_COLOR;
+        if dark {
+            cf.Base.crTextColor = windows::Win32::Foundation::COLORREF(fg);
+            cf.Base.dwEffects = windows::Win32::UI::Controls::RichEdit::CFE_EFFECTS(0);
+        } else {
+            cf.Base.dwEffects = CFE_AUTOCOLOR;
+        }
+        SendMessageW(state.hwnd_edit, EM_SETCHARFORMAT, SCF_DEFAULT as usize, &cf as *const _ as LPARAM);
+    }
+    if state.hwnd_corkboard != 0 {
+        InvalidateRect(state.hwnd_corkboard, null(), 1);
+    }
+    InvalidateRect(hwnd, null(), 1);
+}
+
+unsafe fn check_updates(hwnd: HWND) -> Result<(), String> {
+    let state = state(hwnd);
+    let Some(project) = &state.project else {
+        return Err("未打开项目".to_string());
+    };
+    let Some(url) = project.meta.update_url.as_deref() else {
+        return Err("请在project.md的front matter中配置update_url".to_string());
+    };
+    let current = env!("CARGO_PKG_VERSION");
+    match crate::update::check_update(current, url)? {
+        Some(info) => {
+            let mut msg = format!("发现新版本: {}\n当前版本: {}", info.version, current);
+            if let Some(u) = info.url.as_deref() {
+                msg.push_str("\n下载: ");
+                msg.push_str(u);
+            }
+            if let Some(notes) = info.notes {
+                msg.push_str("\n\n");
+                msg.push_str(&notes);
+            }
+            show_error(hwnd, &msg);
+            Ok(())
+        }
+        None => {
+            set_status_text(hwnd, "已是最新版本");
+            Ok(())
+        }
+    }
+}
+
+ unsafe fn pick_folder(owner: HWND) -> Option<PathBuf> {
+     use windows::Win32::Foundation::HWND as WndHwnd;
+     use windows::Win32::System::Com::{CoCreateInstance, CoTaskMemFree, CLSCTX_INPROC_SERVER};
+     use windows::Win32::UI::Shell::{
+         FileOpenDialog, IFileOpenDialog, FOS_FORCEFILESYSTEM, FOS_PICKFOLDERS, SIGDN_FILESYSPATH,
+     };
+ 
+     let dialog: IFileOpenDialog = CoCreateInstance(&FileOpenDialog, None, CLSCTX_INPROC_SERVER).ok()?;
+     let options = dialog.GetOptions().ok()?;
+     dialog
+         .SetOptions(options | FOS_PICKFOLDERS | FOS_FORCEFILESYSTEM)
+         .ok()?;
+     let _ = dialog.SetTitle(windows::core::w!("选择或创建项目文件夹"));
+     dialog.Show(WndHwnd(owner)).ok()?;
+ 
+     let item = dialog.GetResult().ok()?;
+     let p = item.GetDisplayName(SIGDN_FILESYSPATH).ok()?;
+     let s = p.to_string().ok()?;
+     CoTaskMemFree(Some(p.0 as _));
+     Some(PathBuf::from(s))
+ }
+
+unsafe fn export_project(hwnd: HWND) -> Result<(), String> {
+    let state = state(hwnd);
+    let Some(project) = &state.project else { return Ok(()) };
+    let Some(dst_root) = pick_folder(hwnd) else { return Ok(()) };
+    let ts = now_unix();
+    let name = storage::sanitize_filename(&project.meta.name);
+    let base = if name.is_empty() { "project".to_string() } else { name };
+    let dst = dst_root.join(format!("{}-export-{}", base, ts));
+    storage::copy_project(&project.root, &dst).map_err(|e| e.to_string())?;
+    set_status_text(hwnd, &format!("已导出: {}", dst.display()));
+    Ok(())
+}
+
+unsafe fn export_markdown(hwnd: HWND) -> Result<(), String> {
+    save_current_if_dirty(hwnd);
+    let state = state(hwnd);
+    let Some(project) = &state.project else { return Ok(()) };
+    let path = novel_outline_tool::export::export_markdown(project, None, &Default::default()).map_err(|e| e.to_string())?;
+    set_status_text(hwnd, &format!("已导出: {}", path.display()));
+    Ok(())
+}
+
+unsafe fn export_epub(hwnd: HWND) -> Result<(), String> {
+    save_current_if_dirty(hwnd);
+    let state = state(hwnd);
+    let Some(project) = &state.project else { return Ok(()) };
+    let path = novel_outline_tool::export::epub::export_epub(project, None).map_err(|e| e.to_string())?;
+    set_status_text(hwnd, &format!("已导出: {}", path.display()));
+    Ok(())
+}
+
+unsafe fn migrate_characters(hwnd: HWND) -> Result<(), String> {
+    save_current_if_dirty(hwnd);
+    let state = state(hwnd);
+    let Some(project) = &state.project else { return Ok(()) };
+    let migrated = storage::migrate_characters(&project.root).map_err(|e| e.to_string())?;
+    if let Some(current) = state.current_doc_path.clone() {
+        if migrated.contains(&current) {
+            let content = storage::read_text(&current).map_err(|e| e.to_string())?;
+            SetWindowTextW(state.hwnd_edit, wide(&content).as_ptr());
+            state.current_doc_dirty = false;
+        }
+    }
+    set_status_text(hwnd, &format!("已迁移 {} 个角色文件", migrated.len()));
+    Ok(())
+}
+
+unsafe fn check_timeline(hwnd: HWND) -> Result<(), String> {
+    save_current_if_dirty(hwnd);
+    let state = state(hwnd);
+    let Some(project) = &state.project else { return Ok(()) };
+    let issues = storage::check_timelines(project).map_err(|e| e.to_string())?;
+    if issues.is_empty() {
+        set_status_text(hwnd, "时间线没有发现问题");
+        return Ok(());
+    }
+    const MAX_SHOWN: usize = 30;
+    let mut text = String::new();
+    for issue in issues.iter().take(MAX_SHOWN) {
+        let name = issue.file.file_name().and_then(|s| s.to_str()).unwrap_or("");
+        text.push_str(&format!("{} 第 {} 行：{} {}\n", name, issue.line, issue.kind.label(), issue.message));
+    }
+    if issues.len() > MAX_SHOWN {
+        text.push_str(&format!("……另有 {} 个问题\n", issues.len() - MAX_SHOWN));
+    }
+    set_status_text(hwnd, &format!("时间线发现 {} 个问题", issues.len()));
+    show_info(hwnd, "检查时间线", &text);
+    Ok(())
+}
+
+unsafe fn sort_timeline(hwnd: HWND) -> Result<(), String> {
+    save_current_if_dirty(hwnd);
+    let state = state(hwnd);
+    let Some(project) = &state.project else { return Ok(()) };
+    let changed = storage::sort_timelines(project).map_err(|e| e.to_string())?;
+    refresh_search_index(hwnd);
+    if let Some(current) = state.current_doc_path.clone() {
+        if changed.contains(&current) {
+            let content = storage::read_text(&current).map_err(|e| e.to_string())?;
+            SetWindowTextW(state.hwnd_edit, wide(&content).as_ptr());
+            state.current_doc_dirty = false;
+        }
+    }
+    set_status_text(hwnd, &format!("已重排 {} 个时间线文件", changed.len()));
+    Ok(())
+}
+
+fn link_line(link: &IndexedLink) -> String {
+    let name = link.source.file_name().and_then(|s| s.to_str()).unwrap_or("");
+    match &link.link.anchor {
+        Some(anchor) => format!("{} 第 {} 行：[[{}#{}]]", name, link.link.line, link.link.target, anchor),
+        None => format!("{} 第 {} 行：[[{}]]", name, link.link.line, link.link.target),
+    }
+}
+
+unsafe fn check_links(hwnd: HWND) -> Result<(), String> {
+    save_current_if_dirty(hwnd);
+    let state = state(hwnd);
+    let Some(project) = &state.project else { return Ok(()) };
+    let index = LinkIndex::build(&project.root).map_err(|e| e.to_string())?;
+    let broken: Vec<&IndexedLink> = index.broken().collect();
+    if broken.is_empty() {
+        set_status_text(hwnd, "没有失效的链接");
+        return Ok(());
+    }
+    const MAX_SHOWN: usize = 30;
+    let mut text = String::new();
+    for link in broken.iter().take(MAX_SHOWN) {
+        text.push_str(&link_line(link));
+        text.push('\n');
+    }
+    if broken.len() > MAX_SHOWN {
+        text.push_str(&format!("……另有 {} 个失效链接\n", broken.len() - MAX_SHOWN));
+    }
+    set_status_text(hwnd, &format!("发现 {} 个失效链接", broken.len()));
+    show_info(hwnd, "检查失效链接", &text);
+    Ok(())
+}
+
+unsafe fn show_links(hwnd: HWND) -> Result<(), String> {
+    save_current_if_dirty(hwnd);
+    let state = state(hwnd);
+    let Some(project) = &state.project else { return Ok(()) };
+    let Some(current) = state.current_doc_path.clone() else { return Ok(()) };
+    let index = LinkIndex::build(&project.root).map_err(|e| e.to_string())?;
+    let mut text = String::from("链接：\n");
+    for link in index.outgoing(&current) {
+        text.push_str(&link_line(link));
+        if link.is_broken() {
+            text.push_str("（失效）");
+        }
+        text.push('\n');
+    }
+    text.push_str("\n反向链接：\n");
+    for link in index.backlinks(&current) {
+        text.push_str(&link_line(link));
+        text.push('\n');
+    }
+    show_info(hwnd, "链接", &text);
+    Ok(())
+}
+
+unsafe fn show_word_count(hwnd: HWND) -> Result<(), String> {
+    save_current_if_dirty(hwnd);
+    let state = state(hwnd);
+    let Some(project) = &state.project else { return Ok(()) };
+    let report = stats::project_stats(&project.root).map_err(|e| e.to_string())?;
+    let mut text = String::new();
+    for section in &report.sections {
+        text.push_str(&format!("{}：{} 字（{} 个", section.section.label(), section.total.total(), section.files.len()));
+        if !section.files.is_empty() {
+            text.push_str(&format!("，平均 {} 字", section.total.total() / section.files.len()));
+        }
+        text.push_str("）\n");
+    }
+    text.push_str(&format!("全部：{} 字（汉字 {}，英文词 {}）\n", report.total.total(), report.total.cjk_chars, report.total.latin_words));
+    if let Some(count) = state.current_doc_path.as_ref().and_then(|p| report.file(p)) {
+        text.push_str(&format!("\n当前条目：{} 字（汉字 {}，英文词 {}）", count.total(), count.cjk_chars, count.latin_words));
+    }
+    show_info(hwnd, "字数统计", &text);
+    Ok(())
+}
+
+unsafe fn show_goals(hwnd: HWND) -> Result<(), String> {
+    save_current_if_dirty(hwnd);
+    let state = state(hwnd);
+    let Some(project) = &state.project else { return Ok(()) };
+    let report = stats::project_stats(&project.root).map_err(|e| e.to_string())?;
+    let total = report.section(Section::Chapters).map_or(0, |s| s.total.total()) as u64;
+    let history = WritingHistory::load(&project.root).map_err(|e| e.to_string())?;
+    let progress = history::goal_progress(&project.meta, &history, total, &history::today());
+    let mut text = progress.lines().join("\n");
+    if project.meta.daily_target.is_none() {
+        text.push_str("\n\n在 project.md 的 front matter 中设置 daily_target（每日字数）、word_target（全书字数）与 deadline（YYYY-MM-DD）即可跟踪目标。");
+    }
+    show_info(hwnd, "写作进度", &text);
+    Ok(())
+}
+
+/// Lists the saved versions of the open document in the tree.
+unsafe fn show_history(hwnd: HWND) -> Result<(), String> {
+    save_current_if_dirty(hwnd);
+    let state = state(hwnd);
+    let Some(project) = &state.project else { return Ok(()) };
+    let Some(path) = state.current_doc_path.clone() else {
+        set_status_text(hwnd, "请先打开一个条目");
+        return Ok(());
+    };
+    let versions = versions::list_versions(&project.root, &path).map_err(|e| e.to_string())?;
+    let current = stats::count_document(&get_text(state.hwnd_edit)).total() as i64;
+
+    SendMessageW(state.hwnd_tree, TVM_DELETEITEM, 0, 0);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let root = insert_tree_item(
+        state.hwnd_tree,
+        TVI_ROOT as isize,
+        &format!("历史版本：{}（{} 个）", stem, versions.len()),
+        -1,
+    );
+    for (i, version) in versions.iter().enumerate() {
+        let count = version.read().map(|t| stats::count_document(&t).total() as i64).unwrap_or(0);
+        let mut label = format!("{}  {} 字（{:+}）", storage::format_local_time(version.saved_unix), count, count - current);
+        if let Some(note) = version.note() {
+            label.push_str(&format!("  Git: {}", note));
+        }
+        insert_tree_item(state.hwnd_tree, root, &label, HISTORY_PARAM + i as isize);
+    }
+    SendMessageW(state.hwnd_tree, TVM_EXPAND, TVE_EXPAND as usize, root as LPARAM);
+    state.history_view = Some(HistoryView {
+        path,
+        versions,
+        selected: None,
+    });
+    set_status_text(hwnd, "选择一个版本后，可在“视图”菜单中与当前内容比较或恢复它");
+    Ok(())
+}
+
+unsafe fn select_history_version(hwnd: HWND, idx: usize) {
+    let Some(view) = &mut state(hwnd).history_view else { return };
+    let Some(version) = view.versions.get(idx) else { return };
+    let time = storage::format_local_time(version.saved_unix);
+    view.selected = Some(idx);
+    set_status_text(hwnd, &format!("已选择 {} 的版本", time));
+}
+
+unsafe fn selected_history_version(hwnd: HWND) -> Option<(PathBuf, FileVersion)> {
+    let view = state(hwnd).history_view.as_ref()?;
+    let version = view.versions.get(view.selected?)?;
+    Some((view.path.clone(), version.clone()))
+}
+
+unsafe fn diff_history_version(hwnd: HWND) -> Result<(), String> {
+    let Some((path, version)) = selected_history_version(hwnd) else {
+        set_status_text(hwnd, "请先在“当前条目的历史版本”中选择一个版本");
+        return Ok(());
+    };
+    let old = version.read().map_err(|e| e.to_string())?;
+    let state = state(hwnd);
+    let current = if state.current_doc_path.as_ref() == Some(&path) {
+        get_text(state.hwnd_edit)
+    } else {
+        storage::read_text(&path).map_err(|e| e.to_string())?
+    };
+    let diff = format_diff(&diff_lines(&old, &current), 2);
+    const MAX_LINES: usize = 60;
+    let mut text: String = diff.lines().take(MAX_LINES).map(|l| format!("{}\n", l)).collect();
+    let total = diff.lines().count();
+    if total > MAX_LINES {
+        text.push_str(&format!("……另有 {} 行\n", total - MAX_LINES));
+    }
+    if text.is_empty() {
+        text = "与当前内容相同".to_string();
+    }
+    let title = format!("{} 的版本 → 当前（- 旧版本，+ 当前）", storage::format_local_time(version.saved_unix));
+    show_info(hwnd, &title, &text);
+    Ok(())
+}
+
+/// Puts the selected version back as one undoable command; the text it replaces is backed up first.
+unsafe fn restore_history_version(hwnd: HWND) -> Result<(), String> {
+    let Some((path, version)) = selected_history_version(hwnd) else {
+        set_status_text(hwnd, "请先在“当前条目的历史版本”中选择一个版本");
+        return Ok(());
+    };
+    save_current_if_dirty(hwnd);
+    let state = state(hwnd);
+    let Some(project) = &state.project else { return Ok(()) };
+    let before = storage::read_text(&path).map_err(|e| e.to_string())?;
+    let after = version.read().map_err(|e| e.to_string())?;
+    if before == after {
+        set_status_text(hwnd, "所选版本与当前内容相同");
+        return Ok(());
+    }
+    storage::backup_text(&project.root, &path, &before).map_err(|e| e.to_string())?;
+    let mut cmd = Command::RestoreVersion { path, before, after };
+    cmd.apply()?;
+    refresh_search_index(hwnd);
+    reload_current_if_edited(hwnd, &cmd.edited_files());
+    state.undo_stack.push(cmd);
+    state.redo_stack.clear();
+    SendMessageW(state.hwnd_tree, TVM_DELETEITEM, 0, 0);
+    reload_items(hwnd);
+    set_status_text(hwnd, &format!("已恢复 {} 的版本，可用“撤销结构操作”撤回", storage::format_local_time(version.saved_unix)));
+    Ok(())
+}
+
+unsafe fn export_relation_graph(hwnd: HWND) -> Result<(), String> {
+    save_current_if_dirty(hwnd);
+    let state = state(hwnd);
+    let Some(project) = &state.project else { return Ok(()) };
+    let (_dot, svg) = novel_outline_tool::export::graph::export_relation_graph(project, None).map_err(|e| e.to_string())?;
+    set_status_text(hwnd, &format!("已导出: {}", svg.display()));
+    Ok(())
+}
+
+unsafe fn export_thread_report(hwnd: HWND) -> Result<(), String> {
+    save_current_if_dirty(hwnd);
+    let state = state(hwnd);
+    let Some(project) = &state.project else { return Ok(()) };
+    let (_report, _md, html) = novel_outline_tool::export::threads::export_thread_report(project, None, None).map_err(|e| e.to_string())?;
+    set_status_text(hwnd, &format!("已导出: {}", html.display()));
+    Ok(())
+}
+
+unsafe fn import_project(hwnd: HWND) -> Result<(), String> {
+    let Some(src_root) = pick_folder(hwnd) else { return Ok(()) };
+    let Some(dst_parent) = pick_folder(hwnd) else { return Ok(()) };
+    let ts = now_unix();
+    let src_name = src_root.file_name().and_then(|s| s.to_str()).unwrap_or("import");
+    let dst = dst_parent.join(format!("{}-import-{}", storage::sanitize_filename(src_name), ts));
+    storage::copy_project(&src_root, &dst).map_err(|e| e.to_string())?;
+
+    match ProjectStore::open_or_init(dst) {
+        Ok(project) => {
+            let state = state(hwnd);
+            state.project = Some(project);
+            state.dark_mode = state
+                .project
+                .as_ref()
+                .and_then(|p| p.meta.theme.as_deref())
+                .map(|t| t.eq_ignore_ascii_case("dark"))
+                .unwrap_or(false);
+            apply_theme(hwnd, state.dark_mode);
+            state.left_pane_ratio = state
+                .project
+                .as_ref()
+                .and_then(|p| p.meta.left_pane_ratio)
+                .unwrap_or(0.28);
+            state.current_section = Section::Chapters;
+            state.item_paths.clear();
+            state.volume_paths.clear();
+            state.scene_nodes.clear();
+            state.chapter_cache = ChapterCache::default();
+            state.current_doc_path = None;
+            state.current_doc_dirty = false;
+            state.filter_text.clear();
+            state.last_highlight_line = -1;
+            state.last_highlight_bold = false;
+            state.undo_stack.clear();
+            state.redo_stack.clear();
+            state.dragging = false;
+            state.last_gc_unix = 0;
+            state.search_index = None;
+            load_today_words(hwnd);
+            SetWindowTextW(state.hwnd_search, wide("").as_ptr());
+            SendMessageW(state.hwnd_tree, TVM_DELETEITEM, 0, 0);
+            SendMessageW(state.hwnd_tabs, windows_sys::Win32::UI::Controls::TCM_SETCURSEL, 0, 0);
+            reload_items(hwnd);
+            let _ = SetTimer(hwnd, TIMER_AUTOSAVE, 30_000, None);
+            let _ = SetTimer(hwnd, TIMER_RECOVERY, 5_000, None);
+            start_watching(hwnd);
+            if let Some(project) = &state.project {
+                set_status_text(hwnd, &format!("已导入并打开: {}", project.root.display()));
+                set_status_part(hwnd, 1, "");
+                set_status_part(hwnd, 2, "已保存");
+                set_status_part(hwnd, 3, "");
+            }
+            start_git(hwnd);
+            offer_recovery(hwnd, false)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn now_unix() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+unsafe fn begin_rename_selected(hwnd: HWND) {
+    let state = state(hwnd);
+    if state.hwnd_tree == 0 {
+        return;
+    }
+    let hitem = SendMessageW(state.hwnd_tree, TVM_GETNEXTITEM, TVGN_CARET as usize, 0) as isize;
+    if hitem == 0 {
+        return;
+    }
+    SendMessageW(state.hwnd_tree, TVM_EDITLABELW, 0, hitem as LPARAM);
+}
+
+unsafe fn commit_rename(hwnd: HWND, idx: usize, new_name: &str) -> Result<bool, String> {
+    save_current_if_dirty(hwnd);
+    let state = state(hwnd);
+    if idx >= state.item_paths.len() {
+        return Ok(false);
+    }
+    let Some(project) = &state.project else { return Ok(false) };
+    let from = state.item_paths[idx].clone();
+
+    let name = new_name.trim();
+    if name.is_empty() {
+        return Ok(false);
+    }
+
+    let to = storage::renamed_item_path(&project.root, state.current_section, &from, name);
+
+    if from == to {
+        return Ok(false);
+    }
+
+    let mut cmd = Command::RenameFile {
+        root: project.root.clone(),
+        from: from.clone(),
+        to: to.clone(),
+        links: Vec::new(),
+    };
+    cmd.apply()?;
+    refresh_search_index(hwnd);
+    if state.current_doc_path.as_ref() == Some(&from) {
+        state.current_doc_path = Some(to.clone());
+    }
+    reload_current_if_edited(hwnd, &cmd.edited_files());
+    state.undo_stack.push(cmd);
+    state.redo_stack.clear();
+    SendMessageW(state.hwnd_tree, TVM_DELETEITEM, 0, 0);
+    reload_items(hwnd);
+    Ok(true)
+}
+
+/// Picks up files a command created, renamed, rewrote or deleted.
+unsafe fn refresh_search_index(hwnd: HWND) {
+    if let Some(index) = &mut state(hwnd).search_index {
+        let _ = index.refresh();
+    }
+}
+
+/// Reloads the editor when a command rewrote the open file, e.g. to update its `[[链接]]`.
+unsafe fn reload_current_if_edited(hwnd: HWND, edited: &[PathBuf]) {
+    let state = state(hwnd);
+    let Some(current) = state.current_doc_path.clone() else { return };
+    if !edited.contains(&current) {
+        return;
+    }
+    if let Ok(content) = storage::read_text(&current) {
+        show_document_text(hwnd, &current, &content);
+    }
+}
+
+/// Replaces the editor's text with what `path` now holds on disk.
+unsafe fn show_document_text(hwnd: HWND, path: &Path, content: &str) {
+    let state = state(hwnd);
+    SetWindowTextW(state.hwnd_edit, wide(content).as_ptr());
+    state.current_doc_dirty = false;
+    state.doc_stamp = Some(DiskStamp::of_text(path, content));
+    state.doc_count = stats::count_document(content).total() as u64;
+    refresh_word_count(hwnd, content);
+}
+
+unsafe fn create_new_item(hwnd: HWND) -> Result<(), String> {
+    let state = state(hwnd);
+    let Some(project) = &state.project else { return Ok(()) };
+
+    state.filter_text.clear();
+    SetWindowTextW(state.hwnd_search, wide("").as_ptr());
+
+    let default_title = state.current_section.default_title();
+    let path = match selected_chapter_dir(hwnd) {
+        Some(dir) => storage::new_chapter_path(&dir, default_title),
+        None => storage::new_item_path(&project.root, state.current_section, default_title),
+    }
+    .map_err(|e| e.to_string())?;
+    let contents = storage::new_item_md(state.current_section, default_title);
+    let mut cmd = Command::CreateFile { path: path.clone(), contents };
+    cmd.apply()?;
+    refresh_search_index(hwnd);
+    state.undo_stack.push(cmd);
+    state.redo_stack.clear();
+
+    SendMessageW(state.hwnd_tree, TVM_DELETEITEM, 0, 0);
+    reload_items(hwnd);
+    if let Some(i) = state.item_paths.iter().position(|p| p == &path) {
+        open_item_by_index(hwnd, i);
+    }
+    Ok(())
+}
+
+/// Folder of the chapter or volume selected in the chapter tree: where new chapters and volumes go.
+unsafe fn selected_chapter_dir(hwnd: HWND) -> Option<PathBuf> {
+    let state = state(hwnd);
+    if state.current_section != Section::Chapters {
+        return None;
+    }
+    let hitem = SendMessageW(state.hwnd_tree, TVM_GETNEXTITEM, TVGN_CARET as usize, 0) as isize;
+    let param = tree_item_param(state.hwnd_tree, hitem)?;
+    match tree_param_path(hwnd, param)? {
+        (path, OutlineKind::Volume) => Some(path),
+        (path, OutlineKind::Chapter) => path.parent().map(Path::to_path_buf),
+    }
+}
+
+/// The chapter or volume a tree item's lParam stands for; a scene stands for its chapter.
+unsafe fn tree_param_path(hwnd: HWND, param: usize) -> Option<(PathBuf, OutlineKind)> {
+    let state = state(hwnd);
+    let param = param as isize;
+    if param >= SEARCH_HIT_PARAM {
+        None
+    } else if param >= VOLUME_PARAM {
+        let path = state.volume_paths.get((param - VOLUME_PARAM) as usize)?;
+        Some((path.clone(), OutlineKind::Volume))
+    } else if param >= SCENE_PARAM {
+        let node = state.scene_nodes.get((param - SCENE_PARAM) as usize)?;
+        let path = state.item_paths.get(node.chapter)?;
+        Some((path.clone(), OutlineKind::Chapter))
+    } else {
+        let path = state.item_paths.get(param as usize)?;
+        Some((path.clone(), OutlineKind::Chapter))
+    }
+}
+
+/// Creates a volume folder at the end of the selected volume's folder, or of `chapters/`.
+unsafe fn create_new_volume(hwnd: HWND) -> Result<(), String> {
+    let state = state(hwnd);
+    let Some(project) = &state.project else { return Ok(()) };
+    if state.current_section != Section::Chapters || !state.filter_text.trim().is_empty() {
+        return Err("请先切换到章节列表并清空筛选".to_string());
+    }
+    let parent = selected_chapter_dir(hwnd).unwrap_or_else(|| project.root.join(Section::Chapters.dir_name()));
+    let path = outline::new_volume_path(&parent, "新卷").map_err(|e| e.to_string())?;
+    let mut cmd = Command::CreateFolder { path };
+    cmd.apply()?;
+    state.undo_stack.push(cmd);
+    state.redo_stack.clear();
+    SendMessageW(state.hwnd_tree, TVM_DELETEITEM, 0, 0);
+    reload_items(hwnd);
+    Ok(())
+}
+
+unsafe fn delete_selected_item(hwnd: HWND) -> Result<(), String> {
+    save_current_if_dirty(hwnd);
+    let state = state(hwnd);
+    let Some(path) = state.current_doc_path.clone() else { return Ok(()) };
+    let contents = storage::read_text(&path).unwrap_or_default();
+    let mut cmd = Command::DeleteFile { path: path.clone(), contents };
+    cmd.apply()?;
+    refresh_search_index(hwnd);
+    state.undo_stack.push(cmd);
+    state.redo_stack.clear();
+    state.current_doc_path = None;
+    state.current_doc_dirty = false;
+    SendMessageW(state.hwnd_tree, TVM_DELETEITEM, 0, 0);
+    reload_items(hwnd);
+    Ok(())
+}
+
+unsafe fn do_undo(hwnd: HWND) -> Result<(), String> {
+    save_current_if_dirty(hwnd);
+    let state = state(hwnd);
+    let Some(mut cmd) = state.undo_stack.pop() else { return Ok(()) };
+    let skipped = cmd.undo()?;
+    refresh_search_index(hwnd);
+    reload_current_if_edited(hwnd, &cmd.edited_files());
+    state.redo_stack.push(cmd);
+    SendMessageW(state.hwnd_tree, TVM_DELETEITEM, 0, 0);
+    reload_items(hwnd);
+    report_skipped(hwnd, "撤销", &skipped);
+    Ok(())
+}
+
+unsafe fn do_redo(hwnd: HWND) -> Result<(), String> {
+    save_current_if_dirty(hwnd);
+    let state = state(hwnd);
+    let Some(mut cmd) = state.redo_stack.pop() else { return Ok(()) };
+    let skipped = cmd.apply()?;
+    refresh_search_index(hwnd);
+    reload_current_if_edited(hwnd, &cmd.edited_files());
+    state.undo_stack.push(cmd);
+    SendMessageW(state.hwnd_tree, TVM_DELETEITEM, 0, 0);
+    reload_items(hwnd);
+    report_skipped(hwnd, "重做", &skipped);
+    Ok(())
+}
+
+/// Lists the files an undo or redo did not touch because they were edited in between.
+unsafe fn report_skipped(hwnd: HWND, action: &str, skipped: &[PathBuf]) {
+    if skipped.is_empty() {
+        return;
+    }
+    let state = state(hwnd);
+    let root = state.project.as_ref().map(|p| p.root.clone()).unwrap_or_default();
+    let list: Vec<String> = skipped
+        .iter()
+        .map(|p| p.strip_prefix(&root).unwrap_or(p).display().to_string())
+        .collect();
+    show_info(
+        hwnd,
+        action,
+        &format!("以下文件在之后又被修改过，为保留这些修改未{}：\n\n{}", action, list.join("\n")),
+    );
+}
+
+unsafe fn drag_update_hover(hwnd: HWND) {
+    let state = state(hwnd);
+    if state.hwnd_tree == 0 {
+        return;
+    }
+    let Some(hitem) = tree_item_at_cursor(state.hwnd_tree) else { return };
+    SendMessageW(state.hwnd_tree, TVM_SELECTITEM, TVGN_CARET as usize, hitem as LPARAM);
+}
+
+unsafe fn drag_commit_drop(hwnd: HWND, src_idx: usize) -> Result<(), String> {
+    save_current_if_dirty(hwnd);
+    let state = state(hwnd);
+    if state.current_section != Section::Chapters {
+        return Ok(());
+    }
+    if !state.filter_text.trim().is_empty() {
+        return Ok(());
+    }
+    let Some(project) = &state.project else { return Ok(()) };
+    let Some((src_path, _)) = tree_param_path(hwnd, src_idx) else { return Ok(()) };
+    let Some(dst_idx) = tree_index_at_cursor(state.hwnd_tree) else { return Ok(()) };
+    if dst_idx == src_idx {
+        return Ok(());
+    }
+    let Some((dst_path, dst_kind)) = tree_param_path(hwnd, dst_idx) else { return Ok(()) };
+
+    let chapters_dir = project.root.join("chapters");
+    let mut items = outline::read_outline(&chapters_dir).map_err(|e| e.to_string())?;
+    let src_pos = items.iter().position(|i| i.path == src_path).ok_or_else(|| "source not found".to_string())?;
+    let dst_pos = items.iter().position(|i| i.path == dst_path).ok_or_else(|| "target not found".to_string())?;
+
+    // A chapter dropped on a volume goes to its end; anything else goes in front of the target.
+    let moved = if dst_kind == OutlineKind::Volume && items[src_pos].kind == OutlineKind::Chapter {
+        outline::move_into(&mut items, src_pos, dst_pos)
+    } else {
+        outline::move_before(&mut items, src_pos, dst_pos)
+    };
+    if !moved {
+        return Ok(());
+    }
+    commit_outline_order(hwnd, &chapters_dir, &items)
+}
+
+/// Renumbers the chapter outline to the order of `items` as one undoable command, keeping
+/// `[[链接]]` working. Shared by dragging in the tree and on the corkboard.
+unsafe fn commit_outline_order(hwnd: HWND, chapters_dir: &Path, items: &[OutlineItem]) -> Result<(), String> {
+    let state = state(hwnd);
+    let Some(project) = &state.project else { return Ok(()) };
+    let pairs = outline::renumber_outline(chapters_dir, items);
+    if pairs.is_empty() {
+        return Ok(());
+    }
+    let mut cmd = Command::ReorderChapters {
+        root: project.root.clone(),
+        pairs,
+        links: Vec::new(),
+    };
+    cmd.apply()?;
+    refresh_search_index(hwnd);
+    reload_current_if_edited(hwnd, &cmd.edited_files());
+    state.undo_stack.push(cmd);
+    state.redo_stack.clear();
+    SendMessageW(state.hwnd_tree, TVM_DELETEITEM, 0, 0);
+    reload_items(hwnd);
+    Ok(())
+}
+
+/// Shows the corkboard in place of the editor, or the editor again.
+unsafe fn toggle_corkboard(hwnd: HWND) {
+    let state = state(hwnd);
+    let show = state.corkboard.is_none();
+    if show {
+        if state.project.is_none() {
+            return;
+        }
+        // The cards are read from disk.
+        save_current_if_dirty(hwnd);
+        state.corkboard = Some(Corkboard {
+            cards: Vec::new(),
+            scroll: 0,
+            drag: None,
+        });
+        reload_corkboard(hwnd);
+    } else {
+        state.corkboard = None;
+    }
+    ShowWindow(state.hwnd_edit, if show { SW_HIDE } else { SW_SHOW });
+    ShowWindow(state.hwnd_corkboard, if show { SW_SHOW } else { SW_HIDE });
+    CheckMenuItem(GetMenu(hwnd), IDM_VIEW_CORKBOARD as u32, if show { MF_CHECKED } else { MF_UNCHECKED });
+    SetFocus(if show { state.hwnd_corkboard } else { state.hwnd_edit });
+}
+
+unsafe fn reload_corkboard(hwnd: HWND) {
+    let state = state(hwnd);
+    let Some(board) = &mut state.corkboard else { return };
+    let Some(project) = &state.project else { return };
+    board.cards = outline::read_cards(&project.root.join(Section::Chapters.dir_name())).unwrap_or_default();
+    board.drag = None;
+    InvalidateRect(state.hwnd_corkboard, null(), 1);
+}
+
+/// Card size, spacing and the number of columns that fit the corkboard's width.
+struct CardGrid {
+    width: i32,
+    height: i32,
+    gap: i32,
+    columns: i32,
+}
+
+impl CardGrid {
+    unsafe fn of(hwnd_board: HWND) -> Self {
+        let dpi = GetDpiForWindow(hwnd_board);
+        let mut rc = windows_sys::Win32::Foundation::RECT {
+            left: 0,
+            top: 0,
+            right: 0,
+            bottom: 0,
+        };
+        GetClientRect(hwnd_board, &mut rc);
+        let width = scale_px(dpi, 220);
+        let height = scale_px(dpi, 140);
+        let gap = scale_px(dpi, 12);
+        let columns = ((rc.right - gap) / (width + gap)).max(1);
+        Self {
+            width,
+            height,
+            gap,
+            columns,
+        }
+    }
+
+    /// Client rectangle of card `index` with the board scrolled down by `scroll`.
+    fn rect(&self, index: usize, scroll: i32) -> windows_sys::Win32::Foundation::RECT {
+        let col = index as i32 % self.columns;
+        let row = index as i32 / self.columns;
+        let left = self.gap + col * (self.width + self.gap);
+        let top = self.gap + row * (self.height + self.gap) - scroll;
+        windows_sys::Win32::Foundation::RECT {
+            left,
+            top,
+            right: left + self.width,
+            bottom: top + self.height,
+        }
+    }
+
+    fn content_height(&self, cards: usize) -> i32 {
+        let rows = (cards as i32 + self.columns - 1) / self.columns;
+        self.gap + rows * (self.height + self.gap)
+    }
+
+    /// The card at a client point.
+    fn hit(&self, cards: usize, scroll: i32, x: i32, y: i32) -> Option<usize> {
+        (0..cards).find(|&i| {
+            let rc = self.rect(i, scroll);
+            x >= rc.left && x < rc.right && y >= rc.top && y < rc.bottom
+        })
+    }
+
+    /// Where a card dropped at a client point goes: in front of the card under it, or behind
+    /// that card over its right half. `cards` means behind the last card.
+    fn drop_slot(&self, cards: usize, scroll: i32, x: i32, y: i32) -> usize {
+        let col = ((x - self.gap / 2) / (self.width + self.gap)).clamp(0, self.columns - 1);
+        let row = ((y + scroll - self.gap / 2).max(0)) / (self.height + self.gap);
+        let index = (row * self.columns + col) as usize;
+        if index >= cards {
+            return cards;
+        }
+        let rc = self.rect(index, scroll);
+        if x > (rc.left + rc.right) / 2 {
+            index + 1
+        } else {
+            index
+        }
+    }
+}
+
+/// Card background by chapter status (COLORREF).
+fn card_color(status: Option<ChapterStatus>, dark: bool) -> u32 {
+    match (status, dark) {
+        (Some(ChapterStatus::Draft), false) => 0x00C4F4FF,
+        (Some(ChapterStatus::Draft), true) => 0x00244A54,
+        (Some(ChapterStatus::Revising), false) => 0x00FFE8D6,
+        (Some(ChapterStatus::Revising), true) => 0x00543824,
+        (Some(ChapterStatus::Done), false) => 0x00D6F2D6,
+        (Some(ChapterStatus::Done), true) => 0x002C4824,
+        (None | Some(ChapterStatus::Unknown), false) => 0x00F5F5F5,
+        (None | Some(ChapterStatus::Unknown), true) => 0x00323232,
+    }
+}
+
+/// `状态 · 视角 · N 字 · 卷`, leaving out what the chapter does not have.
+fn card_info(card: &ChapterCard) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    if let Some(status) = card.status {
+        parts.push(status.label().to_string());
+    }
+    parts.extend(card.pov.clone());
+    parts.push(format!("{} 字", card.words));
+    parts.extend(card.volume.clone());
+    parts.join(" · ")
+}
+
+unsafe fn paint_corkboard(hwnd: HWND, st: &mut AppState) {
+    let mut ps: PAINTSTRUCT = std::mem::zeroed();
+    let hdc = BeginPaint(hwnd, &mut ps);
+    let mut rc = windows_sys::Win32::Foundation::RECT {
+        left: 0,
+        top: 0,
+        right: 0,
+        bottom: 0,
+    };
+    GetClientRect(hwnd, &mut rc);
+    FillRect(hdc, &rc, st.hbr_bg);
+
+    let old_font = SelectObject(hdc, st.hfont_ui);
+    SetBkMode(hdc, TRANSPARENT as i32);
+    SetTextColor(hdc, st.theme_fg);
+    let grid = CardGrid::of(hwnd);
+    let dpi = GetDpiForWindow(hwnd);
+    let pad = scale_px(dpi, 8);
+    let line_h = get_font_height_px(hwnd, st.hfont_ui).max(1) + scale_px(dpi, 4);
+    let frame = CreateSolidBrush(theme_palette(st.dark_mode).3);
+
+    if let Some(board) = &mut st.corkboard {
+        board.scroll = board.scroll.clamp(0, (grid.content_height(board.cards.len()) - rc.bottom).max(0));
+        if board.cards.is_empty() {
+            let mut text = wide("还没有章节");
+            DrawTextW(hdc, text.as_mut_ptr(), -1, &mut rc, DT_SINGLELINE | DT_CENTER | DT_VCENTER | DT_NOPREFIX);
+        }
+        for (i, card) in board.cards.iter().enumerate() {
+            let card_rc = grid.rect(i, board.scroll);
+            if card_rc.bottom < rc.top || card_rc.top > rc.bottom {
+                continue;
+            }
+            let fill = CreateSolidBrush(card_color(card.status, st.dark_mode));
+            FillRect(hdc, &card_rc, fill);
+            let _ = DeleteObject(fill);
+            FrameRect(hdc, &card_rc, frame);
+
+            let mut text_rc = windows_sys::Win32::Foundation::RECT {
+                left: card_rc.left + pad,
+                top: card_rc.top + pad,
+                right: card_rc.right - pad,
+                bottom: card_rc.top + pad + line_h,
+            };
+            let stem = card.path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+            let mut title = wide(stem);
+            DrawTextW(hdc, title.as_mut_ptr(), -1, &mut text_rc, DT_SINGLELINE | DT_END_ELLIPSIS | DT_NOPREFIX);
+            text_rc.top += line_h;
+            text_rc.bottom += line_h;
+            let mut info = wide(&card_info(card));
+            DrawTextW(hdc, info.as_mut_ptr(), -1, &mut text_rc, DT_SINGLELINE | DT_END_ELLIPSIS | DT_NOPREFIX);
+            text_rc.top += line_h;
+            text_rc.bottom = card_rc.bottom - pad;
+            let mut summary = wide(&card.summary);
+            DrawTextW(hdc, summary.as_mut_ptr(), -1, &mut text_rc, DT_WORDBREAK | DT_EDITCONTROL | DT_END_ELLIPSIS | DT_NOPREFIX);
+        }
+        // Insertion bar where a dragged card would land.
+        if let Some((src, slot)) = board.drag {
+            if slot != src && slot != src + 1 {
+                let (x, card_rc) = if slot < board.cards.len() {
+                    let r = grid.rect(slot, board.scroll);
+                    (r.left - grid.gap / 2, r)
+                } else {
+                    let r = grid.rect(board.cards.len() - 1, board.scroll);
+                    (r.right + grid.gap / 2, r)
+                };
+                let w = scale_px(dpi, 3).max(2);
+                let bar = windows_sys::Win32::Foundation::RECT {
+                    left: x - w / 2,
+                    top: card_rc.top,
+                    right: x - w / 2 + w,
+                    bottom: card_rc.bottom,
+                };
+                let brush = CreateSolidBrush(st.theme_fg);
+                FillRect(hdc, &bar, brush);
+                let _ = DeleteObject(brush);
+            }
+        }
+    }
+    let _ = DeleteObject(frame);
+    SelectObject(hdc, old_font);
+    EndPaint(hwnd, &ps);
+}
+
+unsafe extern "system" fn corkboard_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    let parent = GetParent(hwnd);
+    if parent == 0 || state(parent).corkboard.is_none() {
+        return DefWindowProcW(hwnd, msg, wparam, lparam);
+    }
+    let st = state(parent);
+    let x = (lparam as i16) as i32;
+    let y = ((lparam >> 16) as i16) as i32;
+    match msg {
+        WM_PAINT => {
+            paint_corkboard(hwnd, st);
+            0
+        }
+        WM_ERASEBKGND => 1,
+        WM_LBUTTONDOWN => {
+            SetFocus(hwnd);
+            let Some(board) = &mut st.corkboard else { return 0 };
+            if let Some(i) = CardGrid::of(hwnd).hit(board.cards.len(), board.scroll, x, y) {
+                board.drag = Some((i, i));
+                SetCapture(hwnd);
+            }
+            0
+        }
+        WM_MOUSEMOVE => {
+            let Some(board) = &mut st.corkboard else { return 0 };
+            if let Some((src, slot)) = board.drag {
+                let to = CardGrid::of(hwnd).drop_slot(board.cards.len(), board.scroll, x, y);
+                if to != slot {
+                    board.drag = Some((src, to));
+                    InvalidateRect(hwnd, null(), 1);
+                }
+            }
+            0
+        }
+        WM_LBUTTONUP => {
+            let Some(board) = &mut st.corkboard else { return 0 };
+            let Some((src, slot)) = board.drag.take() else { return 0 };
+            ReleaseCapture();
+            InvalidateRect(hwnd, null(), 1);
+            if slot != src && slot != src + 1 {
+                if let Err(e) = corkboard_move(parent, src, slot) {
+                    show_error(parent, &e);
+                }
+            }
+            0
+        }
+        WM_CAPTURECHANGED => {
+            if let Some(board) = &mut st.corkboard {
+                if board.drag.take().is_some() {
+                    InvalidateRect(hwnd, null(), 1);
+                }
+            }
+            0
+        }
+        WM_LBUTTONDBLCLK => {
+            let Some(board) = &st.corkboard else { return 0 };
+            if let Some(i) = CardGrid::of(hwnd).hit(board.cards.len(), board.scroll, x, y) {
+                open_card(parent, i);
+            }
+            0
+        }
+        WM_MOUSEWHEEL => {
+            let Some(board) = &mut st.corkboard else { return 0 };
+            let delta = ((wparam >> 16) as i16) as i32;
+            let step = get_font_height_px(hwnd, st.hfont_ui).max(1) * 3;
+            board.scroll -= delta * step / 120;
+            // Clamped to the content when painted.
+            board.scroll = board.scroll.max(0);
+            InvalidateRect(hwnd, null(), 1);
+            0
+        }
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
+
+/// Leaves the corkboard and opens the chapter of card `index` in the editor.
+unsafe fn open_card(hwnd: HWND, index: usize) {
+    let state = state(hwnd);
+    let Some(path) = state.corkboard.as_ref().and_then(|b| b.cards.get(index)).map(|c| c.path.clone()) else { return };
+    toggle_corkboard(hwnd);
+    if state.current_section != Section::Chapters || !state.filter_text.trim().is_empty() {
+        state.current_section = Section::Chapters;
+        state.filter_text.clear();
+        SetWindowTextW(state.hwnd_search, wide("").as_ptr());
+        SendMessageW(state.hwnd_tabs, windows_sys::Win32::UI::Controls::TCM_SETCURSEL, 0, 0);
+        SendMessageW(state.hwnd_tree, TVM_DELETEITEM, 0, 0);
+        reload_items(hwnd);
+    }
+    if let Some(idx) = state.item_paths.iter().position(|p| *p == path) {
+        open_item_by_index(hwnd, idx);
+    }
+}
+
+/// Moves the chapter of card `src` in front of card `slot`, into that card's volume, or behind
+/// the last card when `slot` is past the end.
+unsafe fn corkboard_move(hwnd: HWND, src: usize, slot: usize) -> Result<(), String> {
+    save_current_if_dirty(hwnd);
+    let state = state(hwnd);
+    let Some(project) = &state.project else { return Ok(()) };
+    let Some(board) = &state.corkboard else { return Ok(()) };
+    let chapters_dir = project.root.join(Section::Chapters.dir_name());
+    let mut items = outline::read_outline(&chapters_dir).map_err(|e| e.to_string())?;
+    let position = |card: Option<&ChapterCard>| card.and_then(|c| items.iter().position(|i| i.path == c.path));
+    let from = position(board.cards.get(src));
+    let to = position(board.cards.get(slot));
+    let last = position(board.cards.last());
+    let moved = match (from, to, last) {
+        (Some(from), Some(to), _) => outline::move_before(&mut items, from, to),
+        (Some(from), None, Some(last)) if slot >= board.cards.len() => outline::move_after(&mut items, from, last),
+        // The cards are out of date; show what is on disk instead.
+        _ => {
+            reload_corkboard(hwnd);
+            return Ok(());
+        }
+    };
+    if !moved {
+        return Ok(());
+    }
+    commit_outline_order(hwnd, &chapters_dir, &items)
+}
+
+unsafe fn tree_item_at_cursor(hwnd_tree: HWND) -> Option<isize> {
+    let mut pt = windows_sys::Win32::Foundation::POINT { x: 0, y: 0 };
+    if GetCursorPos(&mut pt) == 0 {
+        return None;
+    }
+    ScreenToClient(hwnd_tree, &mut pt);
+    let mut hti: TVHITTESTINFO = std::mem::zeroed();
+    hti.pt = pt;
+    SendMessageW(hwnd_tree, TVM_HITTEST, 0, &mut hti as *mut _ as LPARAM);
+    if hti.hItem == 0 {
+        None
+    } else {
+        Some(hti.hItem as isize)
+    }
+}
+
+unsafe fn tree_index_at_cursor(hwnd_tree: HWND) -> Option<usize> {
+    tree_item_param(hwnd_tree, tree_item_at_cursor(hwnd_tree)?)
+}
+
+/// lParam of a tree item; `None` for the section's root item.
+unsafe fn tree_item_param(hwnd_tree: HWND, hitem: isize) -> Option<usize> {
+    if hitem == 0 {
+        return None;
+    }
+    let mut item: TVITEMEXW = std::mem::zeroed();
+    item.mask = TVIF_PARAM as u32;
+    item.hItem = hitem;
+    let ok = SendMessageW(hwnd_tree, TVM_GETITEMW, 0, &mut item as *mut _ as LPARAM);
+    if ok == 0 {
+        return None;
+    }
+    let idx = item.lParam as isize;
+    if idx < 0 {
+        None
+    } else {
+        Some(idx as usize)
+    }
+}
+
+unsafe fn wide_ptr_to_string(ptr: *mut u16) -> String {
+    if ptr.is_null() {
+        return String::new();
+    }
+    let mut len = 0usize;
+    loop {
+        let c = *ptr.add(len);
+        if c == 0 {
+            break;
+        }
+        len += 1;
+        if len > 32_768 {
+            break;
+        }
+    }
+    let slice = std::slice::from_raw_parts(ptr, len);
+    String::from_utf16_lossy(slice)
+}
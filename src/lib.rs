@@ -0,0 +1,11 @@
+//! Project model and on-disk storage for Novel Outline Tool.
+//!
+//! This crate is platform independent. Its frontends are the Win32 desktop UI in `src/gui.rs`,
+//! built into `novel-outline-tool` on Windows only (`src/main.rs` is a stub elsewhere), and the
+//! portable command-line tool `novel-outline` in `src/cli.rs`.
+
+pub mod domain;
+pub mod export;
+pub mod search;
+pub mod stats;
+pub mod storage;
@@ -0,0 +1,316 @@
+//! In-memory search index kept up to date file by file, so that content search does not
+//! re-read the whole project on every keystroke.
+//!
+//! Every file is split into overlapping character bigrams of its case-folded text (plus
+//! single characters for one-character queries). CJK text needs no word segmentation this
+//! way, and any substring query of two or more characters can only occur in files that
+//! contain all of its bigrams. Candidates are then confirmed with the regular [`Matcher`].
+
+use super::{fold_char, search_texts, FileHits, Matcher, SearchMode, SearchOptions};
+use crate::storage::{self, links::project_markdown_files};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+type Token = u64;
+
+fn unigram(c: char) -> Token {
+    c as u64
+}
+
+fn bigram(a: char, b: char) -> Token {
+    ((a as u64) << 32) | b as u64 | (1 << 63)
+}
+
+/// Distinct tokens of `text`; line breaks end a run since matches never span lines.
+fn tokens(text: &str) -> HashSet<Token> {
+    let mut out = HashSet::new();
+    let mut prev: Option<char> = None;
+    for c in text.chars() {
+        if c == '\n' || c == '\r' {
+            prev = None;
+            continue;
+        }
+        let c = fold_char(c, false);
+        out.insert(unigram(c));
+        if let Some(p) = prev {
+            out.insert(bigram(p, c));
+        }
+        prev = Some(c);
+    }
+    out
+}
+
+fn query_tokens(query: &str) -> Vec<Token> {
+    let chars: Vec<char> = query.chars().map(|c| fold_char(c, false)).collect();
+    if chars.len() == 1 {
+        return vec![unigram(chars[0])];
+    }
+    let mut out: Vec<Token> = chars.windows(2).map(|w| bigram(w[0], w[1])).collect();
+    out.sort_unstable();
+    out.dedup();
+    out
+}
+
+struct IndexedFile {
+    path: PathBuf,
+    text: String,
+    modified: Option<SystemTime>,
+    len: u64,
+    tokens: Vec<Token>,
+    /// Position in project order: `project.md`, then the sections in tab order with chapters in
+    /// outline order. Files added since the last [`SearchIndex::refresh`] come last.
+    order: usize,
+}
+
+#[derive(Default)]
+pub struct SearchIndex {
+    root: PathBuf,
+    files: Vec<Option<IndexedFile>>,
+    by_path: HashMap<PathBuf, usize>,
+    /// Token to the sorted ids of the files containing it.
+    postings: HashMap<Token, Vec<u32>>,
+}
+
+impl SearchIndex {
+    pub fn new(project_root: &Path) -> Self {
+        Self {
+            root: project_root.to_path_buf(),
+            ..Self::default()
+        }
+    }
+
+    /// Builds the index of every Markdown file of the project.
+    pub fn build(project_root: &Path) -> io::Result<Self> {
+        let mut index = Self::new(project_root);
+        index.refresh()?;
+        Ok(index)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn file_count(&self) -> usize {
+        self.by_path.len()
+    }
+
+    /// Total number of characters indexed.
+    pub fn char_count(&self) -> usize {
+        self.files.iter().flatten().map(|f| f.text.chars().count()).sum()
+    }
+
+    /// Re-reads files whose modification time or size changed, adds new files and drops
+    /// deleted ones. Returns how many files were (re)indexed or removed.
+    pub fn refresh(&mut self) -> io::Result<usize> {
+        let paths = project_markdown_files(&self.root)?;
+        let mut changed = 0;
+        let present: HashSet<&PathBuf> = paths.iter().collect();
+        let gone: Vec<PathBuf> = self.by_path.keys().filter(|p| !present.contains(p)).cloned().collect();
+        for path in gone {
+            self.remove_file(&path);
+            changed += 1;
+        }
+        for path in &paths {
+            let Ok(meta) = std::fs::metadata(path) else { continue };
+            let modified = meta.modified().ok();
+            let up_to_date = self
+                .by_path
+                .get(path)
+                .and_then(|&id| self.files[id].as_ref())
+                .is_some_and(|f| f.modified.is_some() && f.modified == modified && f.len == meta.len());
+            if up_to_date {
+                continue;
+            }
+            let text = storage::read_text(path)?;
+            self.insert(path, text, modified, meta.len());
+            changed += 1;
+        }
+        for (order, path) in paths.iter().enumerate() {
+            if let Some(file) = self.by_path.get(path).and_then(|&id| self.files[id].as_mut()) {
+                file.order = order;
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Replaces the indexed text of one file, e.g. right after the editor saved it.
+    pub fn update_file(&mut self, path: &Path, text: &str) {
+        let meta = std::fs::metadata(path).ok();
+        let modified = meta.as_ref().and_then(|m| m.modified().ok());
+        let len = meta.map(|m| m.len()).unwrap_or(text.len() as u64);
+        self.insert(path, text.to_string(), modified, len);
+    }
+
+    pub fn remove_file(&mut self, path: &Path) {
+        let Some(id) = self.by_path.remove(path) else { return };
+        let Some(file) = self.files[id].take() else { return };
+        for token in file.tokens {
+            if let Some(list) = self.postings.get_mut(&token) {
+                if let Ok(pos) = list.binary_search(&(id as u32)) {
+                    list.remove(pos);
+                }
+                if list.is_empty() {
+                    self.postings.remove(&token);
+                }
+            }
+        }
+    }
+
+    fn insert(&mut self, path: &Path, text: String, modified: Option<SystemTime>, len: u64) {
+        let order = self.by_path.get(path).and_then(|&id| self.files[id].as_ref()).map_or(usize::MAX, |f| f.order);
+        self.remove_file(path);
+        let id = match self.files.iter().position(Option::is_none) {
+            Some(free) => free,
+            None => {
+                self.files.push(None);
+                self.files.len() - 1
+            }
+        };
+        let mut file_tokens: Vec<Token> = tokens(&text).into_iter().collect();
+        file_tokens.sort_unstable();
+        for &token in &file_tokens {
+            let list = self.postings.entry(token).or_default();
+            if let Err(pos) = list.binary_search(&(id as u32)) {
+                list.insert(pos, id as u32);
+            }
+        }
+        self.files[id] = Some(IndexedFile {
+            path: path.to_path_buf(),
+            text,
+            modified,
+            len,
+            tokens: file_tokens,
+            order,
+        });
+        self.by_path.insert(path.to_path_buf(), id);
+    }
+
+    /// Ids of files that may contain `query`; every file for regular expressions.
+    fn candidates(&self, query: &str, mode: SearchMode) -> Vec<usize> {
+        let all = || self.files.iter().enumerate().filter(|(_, f)| f.is_some()).map(|(i, _)| i).collect();
+        if mode == SearchMode::Regex {
+            return all();
+        }
+        let mut lists: Vec<&Vec<u32>> = Vec::new();
+        for token in query_tokens(query) {
+            match self.postings.get(&token) {
+                Some(list) => lists.push(list),
+                None => return Vec::new(),
+            }
+        }
+        if lists.is_empty() {
+            return all();
+        }
+        lists.sort_by_key(|l| l.len());
+        let mut result: Vec<u32> = lists[0].clone();
+        for list in &lists[1..] {
+            result.retain(|id| list.binary_search(id).is_ok());
+            if result.is_empty() {
+                break;
+            }
+        }
+        result.into_iter().map(|id| id as usize).collect()
+    }
+
+    /// Same result as [`super::search_project`] without touching the disk.
+    pub fn search(&self, query: &str, options: &SearchOptions) -> Result<Vec<FileHits>, String> {
+        let matcher = Matcher::new(query, options)?;
+        let mut files: Vec<&IndexedFile> = self
+            .candidates(query, options.mode)
+            .into_iter()
+            .filter_map(|id| self.files[id].as_ref())
+            .collect();
+        files.sort_by(|a, b| a.order.cmp(&b.order).then_with(|| a.path.cmp(&b.path)));
+        Ok(search_texts(
+            &self.root,
+            files.iter().map(|f| (f.path.as_path(), f.text.as_str())),
+            query,
+            &matcher,
+            options,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::search_project;
+    use crate::storage::test_dir;
+    use std::fs;
+
+    fn paths(hits: &[FileHits]) -> Vec<PathBuf> {
+        hits.iter().map(|f| f.path.clone()).collect()
+    }
+
+    #[test]
+    fn query_tokens_are_bigrams_of_folded_text() {
+        assert_eq!(query_tokens("青"), vec![unigram('青')]);
+        assert_eq!(query_tokens("ＡＢ"), query_tokens("ab"));
+        assert_eq!(query_tokens("青云青云").len(), 2);
+        // A bigram across a line break never matches.
+        assert!(!tokens("青\n云").contains(&bigram('青', '云')));
+    }
+
+    #[test]
+    fn refresh_picks_up_changes_and_matches_a_full_search() {
+        let root = test_dir("index");
+        fs::create_dir_all(root.join("chapters/第一卷")).unwrap();
+        fs::create_dir_all(root.join("characters")).unwrap();
+        let one = root.join("chapters/第一卷/0001-开端.md");
+        let two = root.join("chapters/0002-青云.md");
+        let lin = root.join("characters/角色-林风.md");
+        fs::write(&one, "林风拔出青云剑。\n").unwrap();
+        fs::write(&two, "山中无事。\n").unwrap();
+        fs::write(&lin, "# 角色：林风\n").unwrap();
+
+        let mut index = SearchIndex::build(&root).unwrap();
+        assert_eq!(index.file_count(), 3);
+        let options = SearchOptions::default();
+        let found = index.search("青云剑", &options).unwrap();
+        assert_eq!(paths(&found), vec![one.clone()]);
+
+        fs::write(&two, "山中无事，只有一柄青云剑。\n").unwrap();
+        fs::remove_file(&lin).unwrap();
+        assert_eq!(index.refresh().unwrap(), 2);
+        assert_eq!(index.refresh().unwrap(), 0);
+        assert_eq!(index.file_count(), 2);
+        for query in ["青云剑", "林风", "青", "不存在"] {
+            let indexed = index.search(query, &options).unwrap();
+            let full = search_project(&root, query, &options).unwrap();
+            assert_eq!(paths(&indexed), paths(&full), "{}", query);
+        }
+
+        index.update_file(&one, "改写之后没有那柄剑了。\n");
+        assert_eq!(paths(&index.search("青云剑", &options).unwrap()), vec![two.clone()]);
+        index.remove_file(&two);
+        assert!(index.search("青云剑", &options).unwrap().is_empty());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn hits_come_in_outline_order() {
+        let root = test_dir("index-order");
+        let chapters = root.join("chapters");
+        fs::create_dir_all(chapters.join("01-第一卷")).unwrap();
+        // By path the volume would sort before 番外; the outline lists loose chapters first.
+        let files = [chapters.join("0001-开端.md"), chapters.join("番外.md"), chapters.join("01-第一卷/0001-下山.md")];
+        for path in &files {
+            fs::write(path, "青云剑\n").unwrap();
+        }
+        let mut index = SearchIndex::build(&root).unwrap();
+        let options = SearchOptions::default();
+        assert_eq!(paths(&index.search("青云剑", &options).unwrap()), files);
+        assert_eq!(paths(&search_project(&root, "青云剑", &options).unwrap()), files);
+
+        // A file the editor creates goes last until the next refresh puts it in its place.
+        let new = chapters.join("0000-楔子.md");
+        fs::write(&new, "青云剑\n").unwrap();
+        index.update_file(&new, "青云剑\n");
+        assert_eq!(index.search("青云剑", &options).unwrap()[3].path, new);
+        index.refresh().unwrap();
+        assert_eq!(index.search("青云剑", &options).unwrap()[0].path, new);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
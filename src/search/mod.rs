@@ -0,0 +1,356 @@
+//! Project-wide full-text search over every Markdown file of a project.
+
+use crate::domain::Section;
+use crate::storage::{self, links::project_markdown_files};
+use regex::{Regex, RegexBuilder};
+use std::path::{Path, PathBuf};
+
+pub mod index;
+pub mod replace;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum SearchMode {
+    /// Substring match; full-width and half-width forms are treated as equal.
+    #[default]
+    Plain,
+    /// Like `Plain`, but Latin words and numbers must not continue on either side.
+    /// CJK characters always count as a boundary since they are not separated by spaces.
+    WholeWord,
+    Regex,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    pub mode: SearchMode,
+    pub case_sensitive: bool,
+    /// Stops after this many hits in total.
+    pub max_hits: usize,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            mode: SearchMode::Plain,
+            case_sensitive: false,
+            max_hits: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    /// 1-based line number.
+    pub line: usize,
+    /// 0-based column in characters.
+    pub column: usize,
+    /// Length of the match in characters.
+    pub len: usize,
+    /// Position in UTF-16 units with every line break counted as one unit, which is how the
+    /// editor control addresses text.
+    pub editor_offset: usize,
+    pub editor_len: usize,
+    /// The line around the match, shortened with `…`.
+    pub snippet: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileHits {
+    pub path: PathBuf,
+    /// `None` for `project.md`.
+    pub section: Option<Section>,
+    /// True when the file name itself contains the query; such files rank first.
+    pub name_match: bool,
+    pub hits: Vec<SearchHit>,
+}
+
+enum Pattern {
+    Text { needle: Vec<char>, whole_word: bool, case_sensitive: bool },
+    Regex(Regex),
+}
+
+/// A compiled query.
+pub struct Matcher {
+    pattern: Pattern,
+}
+
+impl Matcher {
+    pub fn new(query: &str, options: &SearchOptions) -> Result<Self, String> {
+        if query.is_empty() {
+            return Err("empty query".to_string());
+        }
+        let pattern = match options.mode {
+            SearchMode::Regex => Pattern::Regex(
+                RegexBuilder::new(query)
+                    .case_insensitive(!options.case_sensitive)
+                    .build()
+                    .map_err(|e| format!("正则表达式无效: {}", e))?,
+            ),
+            mode => Pattern::Text {
+                needle: query.chars().map(|c| fold_char(c, options.case_sensitive)).collect(),
+                whole_word: mode == SearchMode::WholeWord,
+                case_sensitive: options.case_sensitive,
+            },
+        };
+        Ok(Self { pattern })
+    }
+
+    /// Character ranges `(start, len)` of the matches within one line.
+    pub fn find_in_line(&self, line: &str) -> Vec<(usize, usize)> {
+        let mut out = Vec::new();
+        match &self.pattern {
+            Pattern::Regex(re) => {
+                for m in re.find_iter(line) {
+                    if m.is_empty() {
+                        continue;
+                    }
+                    let start = line[..m.start()].chars().count();
+                    out.push((start, m.as_str().chars().count()));
+                }
+            }
+            Pattern::Text {
+                needle,
+                whole_word,
+                case_sensitive,
+            } => {
+                if needle.is_empty() || line.len() < needle.len() {
+                    return out;
+                }
+                let hay: Vec<char> = line.chars().map(|c| fold_char(c, *case_sensitive)).collect();
+                if hay.len() < needle.len() {
+                    return out;
+                }
+                let mut i = 0;
+                while i + needle.len() <= hay.len() {
+                    if hay[i..i + needle.len()] == needle[..] {
+                        let end = i + needle.len();
+                        let bounded = !*whole_word
+                            || ((i == 0 || !is_word_char(hay[i - 1]) || !is_word_char(hay[i]))
+                                && (end == hay.len() || !is_word_char(hay[end]) || !is_word_char(hay[end - 1])));
+                        if bounded {
+                            out.push((i, needle.len()));
+                            i = end;
+                            continue;
+                        }
+                    }
+                    i += 1;
+                }
+            }
+        }
+        out
+    }
+
+    /// Text that replaces the match starting at byte `start` of `line`. In regex mode `$1` and
+    /// `${name}` expand to capture groups; otherwise `replacement` is inserted as is.
+    pub fn expand(&self, line: &str, start: usize, replacement: &str) -> String {
+        match &self.pattern {
+            Pattern::Regex(re) => {
+                let mut out = String::new();
+                if let Some(caps) = re.captures_at(line, start) {
+                    caps.expand(replacement, &mut out);
+                }
+                out
+            }
+            Pattern::Text { .. } => replacement.to_string(),
+        }
+    }
+
+    /// All hits in `text`, at most `limit`.
+    pub fn find_in_text(&self, text: &str, limit: usize) -> Vec<SearchHit> {
+        let mut hits = Vec::new();
+        let mut line_offset = 0usize;
+        for (i, raw) in text.split('\n').enumerate() {
+            let line = raw.strip_suffix('\r').unwrap_or(raw);
+            for (start, len) in self.find_in_line(line) {
+                if hits.len() >= limit {
+                    return hits;
+                }
+                let prefix: usize = line.chars().take(start).map(char::len_utf16).sum();
+                let matched: usize = line.chars().skip(start).take(len).map(char::len_utf16).sum();
+                hits.push(SearchHit {
+                    line: i + 1,
+                    column: start,
+                    len,
+                    editor_offset: line_offset + prefix,
+                    editor_len: matched,
+                    snippet: snippet(line, start, len),
+                });
+            }
+            line_offset += line.encode_utf16().count() + 1;
+        }
+        hits
+    }
+}
+
+/// Folds full-width ASCII and the ideographic space to their half-width forms, and case unless
+/// `case_sensitive`. Always maps one character to one character.
+pub fn fold_char(c: char, case_sensitive: bool) -> char {
+    let c = match c {
+        '\u{3000}' => ' ',
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+        _ => c,
+    };
+    if case_sensitive || is_cjk(c) {
+        return c;
+    }
+    if c.is_ascii() {
+        return c.to_ascii_lowercase();
+    }
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(l), None) => l,
+        _ => c,
+    }
+}
+
+pub fn fold(text: &str, case_sensitive: bool) -> String {
+    text.chars().map(|c| fold_char(c, case_sensitive)).collect()
+}
+
+/// Latin letters, digits and `_` form words; CJK characters stand alone.
+pub fn is_word_char(c: char) -> bool {
+    (c.is_alphanumeric() || c == '_') && !is_cjk(c)
+}
+
+pub fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30FF      // Hiragana, Katakana
+        | 0x3400..=0x4DBF    // CJK Extension A
+        | 0x4E00..=0x9FFF    // CJK Unified Ideographs
+        | 0xAC00..=0xD7AF    // Hangul
+        | 0xF900..=0xFAFF    // CJK Compatibility Ideographs
+        | 0x20000..=0x2FA1F) // Extensions B–F and supplements
+}
+
+const SNIPPET_CONTEXT: usize = 24;
+
+fn snippet(line: &str, start: usize, len: usize) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let from = start.saturating_sub(SNIPPET_CONTEXT);
+    let to = (start + len + SNIPPET_CONTEXT).min(chars.len());
+    let mut out = String::new();
+    if from > 0 {
+        out.push('…');
+    }
+    out.extend(chars[from..to].iter());
+    if to < chars.len() {
+        out.push('…');
+    }
+    out.trim().to_string()
+}
+
+/// The section `path` belongs to; chapters may sit in volume folders below `chapters/`.
+pub fn section_of(project_root: &Path, path: &Path) -> Option<Section> {
+    let mut components = path.strip_prefix(project_root).ok()?.components();
+    let section = Section::from_dir_name(components.next()?.as_os_str().to_str()?)?;
+    let depth = components.count();
+    match (section, depth) {
+        (_, 1) | (Section::Chapters, 2..) => Some(section),
+        _ => None,
+    }
+}
+
+/// Searches `project.md` and every section, files with more hits first.
+pub fn search_project(project_root: &Path, query: &str, options: &SearchOptions) -> Result<Vec<FileHits>, String> {
+    let matcher = Matcher::new(query, options)?;
+    let files = project_markdown_files(project_root).map_err(|e| e.to_string())?;
+    let mut texts = Vec::with_capacity(files.len());
+    for path in files {
+        let text = storage::read_text(&path).map_err(|e| e.to_string())?;
+        texts.push((path, text));
+    }
+    Ok(search_texts(project_root, texts.iter().map(|(p, t)| (p.as_path(), t.as_str())), query, &matcher, options))
+}
+
+/// Runs `matcher` over already loaded files and ranks the result.
+pub fn search_texts<'a>(
+    project_root: &Path,
+    texts: impl Iterator<Item = (&'a Path, &'a str)>,
+    query: &str,
+    matcher: &Matcher,
+    options: &SearchOptions,
+) -> Vec<FileHits> {
+    let folded_query = fold(query, options.case_sensitive);
+    let mut remaining = options.max_hits;
+    let mut results = Vec::new();
+    for (path, text) in texts {
+        if remaining == 0 {
+            break;
+        }
+        let hits = matcher.find_in_text(text, remaining);
+        if hits.is_empty() {
+            continue;
+        }
+        remaining -= hits.len();
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+        results.push(FileHits {
+            path: path.to_path_buf(),
+            section: section_of(project_root, path),
+            name_match: options.mode != SearchMode::Regex && fold(stem, options.case_sensitive).contains(&folded_query),
+            hits,
+        });
+    }
+    // Stable: files with equal rank keep project order (project.md, chapters, characters, ...).
+    results.sort_by(|a, b| b.name_match.cmp(&a.name_match).then(b.hits.len().cmp(&a.hits.len())));
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(query: &str, mode: SearchMode, line: &str) -> Vec<(usize, usize)> {
+        let options = SearchOptions {
+            mode,
+            ..SearchOptions::default()
+        };
+        Matcher::new(query, &options).unwrap().find_in_line(line)
+    }
+
+    #[test]
+    fn plain_and_whole_word_matching() {
+        assert_eq!(matches("ｓｗｏｒｄ", SearchMode::Plain, "The Sword, swords"), vec![(4, 5), (11, 5)]);
+        assert_eq!(matches("sword", SearchMode::WholeWord, "The Sword, swords"), vec![(4, 5)]);
+        // CJK characters are a boundary on their own.
+        assert_eq!(matches("ABC", SearchMode::WholeWord, "青云ABC剑 xABC"), vec![(2, 3)]);
+        assert_eq!(matches("青云(.)", SearchMode::Regex, "青云剑与青云山"), vec![(0, 3), (4, 3)]);
+        assert!(Matcher::new("(", &SearchOptions { mode: SearchMode::Regex, ..SearchOptions::default() }).is_err());
+    }
+
+    #[test]
+    fn hits_carry_editor_offsets_in_utf16() {
+        let matcher = Matcher::new("剑", &SearchOptions::default()).unwrap();
+        let hits = matcher.find_in_text("𠀀剑\r\n青云剑\n", 10);
+        assert_eq!(hits.len(), 2);
+        assert_eq!((hits[0].line, hits[0].column, hits[0].editor_offset), (1, 1, 2));
+        assert_eq!((hits[1].line, hits[1].column, hits[1].editor_offset), (2, 2, 6));
+        assert_eq!(matcher.find_in_text("剑剑剑", 2).len(), 2);
+    }
+
+    #[test]
+    fn name_matches_rank_first_then_hit_counts() {
+        let root = Path::new("/p");
+        let texts = [
+            (root.join("project.md"), "青云"),
+            (root.join("chapters/0001-开端.md"), "青云，青云，青云"),
+            (root.join("chapters/0002-青云.md"), "青云"),
+            (root.join("chapters/0003-下山.md"), "无"),
+            (root.join("characters/角色-林风.md"), "青云山下"),
+        ];
+        let options = SearchOptions::default();
+        let matcher = Matcher::new("青云", &options).unwrap();
+        let results = search_texts(root, texts.iter().map(|(p, t)| (p.as_path(), *t)), "青云", &matcher, &options);
+        let stems: Vec<&str> = results.iter().map(|f| f.path.file_stem().unwrap().to_str().unwrap()).collect();
+        // Files with equal rank keep project order; files without hits are left out.
+        assert_eq!(stems, ["0002-青云", "0001-开端", "project", "角色-林风"]);
+        assert!(results[0].name_match);
+        assert_eq!(results[2].section, None);
+    }
+
+    #[test]
+    fn sections_of_nested_chapters() {
+        let root = Path::new("/p");
+        assert_eq!(section_of(root, &root.join("chapters/第一卷/0001-开端.md")), Some(Section::Chapters));
+        assert_eq!(section_of(root, &root.join("characters/a/b.md")), None);
+        assert_eq!(section_of(root, &root.join("project.md")), None);
+    }
+}
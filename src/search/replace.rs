@@ -0,0 +1,191 @@
+//! Project-wide find and replace, planned as whole-file rewrites so the caller can preview
+//! every change before writing and undo the batch afterwards.
+
+use super::{section_of, snippet, Matcher, SearchOptions};
+use crate::domain::Section;
+use crate::storage::{self, links::project_markdown_files};
+use std::path::{Path, PathBuf};
+
+/// Which files a replacement may touch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplaceScope {
+    pub project_md: bool,
+    pub sections: Vec<Section>,
+}
+
+impl Default for ReplaceScope {
+    fn default() -> Self {
+        Self {
+            project_md: true,
+            sections: Section::ALL.to_vec(),
+        }
+    }
+}
+
+impl ReplaceScope {
+    /// `section` is `None` for `project.md`.
+    pub fn includes(&self, section: Option<Section>) -> bool {
+        match section {
+            Some(section) => self.sections.contains(&section),
+            None => self.project_md,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplacePreview {
+    /// 1-based line number.
+    pub line: usize,
+    /// The line around the match before and after replacing, shortened with `…`.
+    pub before: String,
+    pub after: String,
+}
+
+/// One file of a planned replacement.
+#[derive(Debug, Clone)]
+pub struct FileReplace {
+    pub path: PathBuf,
+    /// `None` for `project.md`.
+    pub section: Option<Section>,
+    pub previews: Vec<ReplacePreview>,
+    pub before: String,
+    pub after: String,
+}
+
+fn byte_offset(line: &str, chars: usize) -> usize {
+    line.char_indices().nth(chars).map(|(i, _)| i).unwrap_or(line.len())
+}
+
+/// Replaces every match of `matcher` in `text`, line by line.
+pub fn replace_in_text(text: &str, matcher: &Matcher, replacement: &str) -> (String, Vec<ReplacePreview>) {
+    let mut out = String::with_capacity(text.len());
+    let mut previews = Vec::new();
+    for (i, raw) in text.split_inclusive('\n').enumerate() {
+        let body = raw.strip_suffix('\n').unwrap_or(raw);
+        let body = body.strip_suffix('\r').unwrap_or(body);
+        let ending = &raw[body.len()..];
+        let matches = matcher.find_in_line(body);
+        if matches.is_empty() {
+            out.push_str(raw);
+            continue;
+        }
+        let mut line = String::with_capacity(body.len());
+        let mut last = 0;
+        let mut spans = Vec::new();
+        for (start, len) in matches {
+            let from = byte_offset(body, start);
+            let to = from + body[from..].chars().take(len).map(char::len_utf8).sum::<usize>();
+            line.push_str(&body[last..from]);
+            let new_start = line.chars().count();
+            let text = matcher.expand(body, from, replacement);
+            line.push_str(&text);
+            spans.push((start, len, new_start, text.chars().count()));
+            last = to;
+        }
+        line.push_str(&body[last..]);
+        for (start, len, new_start, new_len) in spans {
+            previews.push(ReplacePreview {
+                line: i + 1,
+                before: snippet(body, start, len),
+                after: snippet(&line, new_start, new_len),
+            });
+        }
+        out.push_str(&line);
+        out.push_str(ending);
+    }
+    (out, previews)
+}
+
+/// Plans replacing `query` with `replacement` in every file of `scope`, without writing anything.
+/// Files in which nothing changes are left out.
+pub fn plan_replace(
+    project_root: &Path,
+    query: &str,
+    replacement: &str,
+    options: &SearchOptions,
+    scope: &ReplaceScope,
+) -> Result<Vec<FileReplace>, String> {
+    let matcher = Matcher::new(query, options)?;
+    let mut plan = Vec::new();
+    for path in project_markdown_files(project_root).map_err(|e| e.to_string())? {
+        let section = section_of(project_root, &path);
+        if !scope.includes(section) {
+            continue;
+        }
+        let before = storage::read_text(&path).map_err(|e| e.to_string())?;
+        let (after, previews) = replace_in_text(&before, &matcher, replacement);
+        if after != before {
+            plan.push(FileReplace {
+                path,
+                section,
+                previews,
+                before,
+                after,
+            });
+        }
+    }
+    Ok(plan)
+}
+
+/// Writes `after` (or `before` when undoing) of every file atomically. Files whose text is no
+/// longer what the plan expects are left alone and returned.
+pub fn apply_replace(files: &[FileReplace], undo: bool) -> std::io::Result<Vec<PathBuf>> {
+    let mut skipped = Vec::new();
+    for file in files {
+        let (expected, text) = if undo { (&file.after, &file.before) } else { (&file.before, &file.after) };
+        if !storage::write_text_if_unchanged(&file.path, expected, text)? {
+            skipped.push(file.path.clone());
+        }
+    }
+    Ok(skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::SearchMode;
+    use crate::storage::test_dir;
+    use std::fs;
+
+    #[test]
+    fn replaces_line_by_line_keeping_line_endings() {
+        let matcher = Matcher::new("林风", &SearchOptions::default()).unwrap();
+        let (out, previews) = replace_in_text("林风说：\r\n“林风来了。”\n无关\n", &matcher, "林枫");
+        assert_eq!(out, "林枫说：\r\n“林枫来了。”\n无关\n");
+        assert_eq!(previews.len(), 2);
+        assert_eq!((previews[1].line, previews[1].before.as_str(), previews[1].after.as_str()), (2, "“林风来了。”", "“林枫来了。”"));
+
+        let options = SearchOptions {
+            mode: SearchMode::Regex,
+            ..SearchOptions::default()
+        };
+        let matcher = Matcher::new(r"第(\d+)章", &options).unwrap();
+        assert_eq!(replace_in_text("见第12章", &matcher, "第 $1 章").0, "见第 12 章");
+    }
+
+    #[test]
+    fn undo_leaves_files_edited_since_alone() {
+        let root = test_dir("replace");
+        fs::create_dir_all(root.join("chapters")).unwrap();
+        let one = root.join("chapters/0001-开端.md");
+        let two = root.join("chapters/0002-下山.md");
+        fs::write(&one, "林风上山。\n").unwrap();
+        fs::write(&two, "林风下山。\n").unwrap();
+        fs::write(root.join("project.md"), "# 书\n").unwrap();
+
+        let plan = plan_replace(&root, "林风", "林枫", &SearchOptions::default(), &ReplaceScope::default()).unwrap();
+        assert_eq!(plan.len(), 2);
+        assert!(apply_replace(&plan, false).unwrap().is_empty());
+        assert_eq!(storage::read_text(&one).unwrap(), "林枫上山。\n");
+
+        fs::write(&two, "林枫下山，又回来了。\n").unwrap();
+        assert_eq!(apply_replace(&plan, true).unwrap(), vec![two.clone()]);
+        assert_eq!(storage::read_text(&one).unwrap(), "林风上山。\n");
+        assert_eq!(storage::read_text(&two).unwrap(), "林枫下山，又回来了。\n");
+
+        // Redo skips the edited file again and redoes the rest.
+        assert_eq!(apply_replace(&plan, false).unwrap(), vec![two]);
+        assert_eq!(storage::read_text(&one).unwrap(), "林枫上山。\n");
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,274 @@
+//! Daily writing history kept in `.stats/history.json`.
+//!
+//! Callers report how many 字 a save added or removed (the difference between a document's
+//! count before and after), so renaming or reordering files never shows up as writing.
+
+use crate::domain::timeline::{civil_from_days, days_from_civil};
+use crate::domain::ProjectMeta;
+use crate::storage;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DayRecord {
+    pub added: u64,
+    pub removed: u64,
+}
+
+impl DayRecord {
+    pub fn net(&self) -> i64 {
+        self.added as i64 - self.removed as i64
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WritingHistory {
+    /// Keyed by `YYYY-MM-DD`.
+    #[serde(default)]
+    pub days: BTreeMap<String, DayRecord>,
+}
+
+pub fn history_path(project_root: &Path) -> PathBuf {
+    project_root.join(".stats").join("history.json")
+}
+
+impl WritingHistory {
+    /// An empty history when the project has none yet.
+    pub fn load(project_root: &Path) -> io::Result<Self> {
+        let path = history_path(project_root);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = storage::read_text(&path)?;
+        serde_json::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn save(&self, project_root: &Path) -> io::Result<()> {
+        let path = history_path(project_root);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        storage::write_text_atomic(&path, &json)
+    }
+
+    /// Adds a change of `delta` 字 to `day`.
+    pub fn record(&mut self, day: &str, delta: i64) {
+        let entry = self.days.entry(day.to_string()).or_default();
+        if delta >= 0 {
+            entry.added += delta as u64;
+        } else {
+            entry.removed += delta.unsigned_abs();
+        }
+    }
+
+    pub fn day(&self, day: &str) -> DayRecord {
+        self.days.get(day).copied().unwrap_or_default()
+    }
+
+    /// Consecutive days up to `today` on which at least `target` 字 were written. Today only
+    /// counts once it is reached, so an unfinished today does not break the streak.
+    pub fn streak(&self, today: &str, target: u64) -> usize {
+        let Some(mut ordinal) = day_ordinal(today) else { return 0 };
+        let reached = |ordinal: i64| self.day(&format_day(ordinal)).net() >= target.max(1) as i64;
+        let mut streak = 0;
+        if reached(ordinal) {
+            streak += 1;
+        }
+        ordinal -= 1;
+        while reached(ordinal) {
+            streak += 1;
+            ordinal -= 1;
+        }
+        streak
+    }
+}
+
+/// Loads the history, records `delta` for `day` and saves it again. Returns that day's record.
+/// A history file that does not parse is moved aside to `history.broken-<unix time>.json` and
+/// a new one is started, so that one bad file does not stop recording for good.
+pub fn record_words(project_root: &Path, day: &str, delta: i64) -> io::Result<DayRecord> {
+    let mut history = match WritingHistory::load(project_root) {
+        Ok(history) => history,
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+            let path = history_path(project_root);
+            std::fs::rename(&path, path.with_file_name(format!("history.broken-{}.json", crate::domain::now_unix())))?;
+            WritingHistory::default()
+        }
+        Err(e) => return Err(e),
+    };
+    history.record(day, delta);
+    history.save(project_root)?;
+    Ok(history.day(day))
+}
+
+/// Days since 1970-01-01 of a `YYYY-MM-DD` date.
+pub fn day_ordinal(day: &str) -> Option<i64> {
+    let mut parts = day.trim().splitn(3, '-');
+    let year = parts.next()?.parse::<i64>().ok()?;
+    let month = parts.next()?.parse::<u32>().ok().filter(|m| (1..=12).contains(m))?;
+    let d = parts.next()?.parse::<u32>().ok().filter(|d| (1..=31).contains(d))?;
+    Some(days_from_civil(year, month, d))
+}
+
+/// `YYYY-MM-DD` of a day counted from 1970-01-01.
+pub fn format_day(ordinal: i64) -> String {
+    let (year, month, day) = civil_from_days(ordinal);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Today's date in local time (UTC where the offset is unknown, see [`storage::local_offset_secs`]).
+pub fn today() -> String {
+    let local = crate::domain::now_unix() as i64 + storage::local_offset_secs();
+    format_day(local.div_euclid(86_400))
+}
+
+#[derive(Debug, Clone)]
+pub struct GoalProgress {
+    pub today: DayRecord,
+    pub daily_target: Option<u64>,
+    /// Days in a row the daily target was met.
+    pub streak: usize,
+    /// 字数 of the chapters now.
+    pub total: u64,
+    pub word_target: Option<u64>,
+    pub deadline: Option<String>,
+    /// Days from today through the deadline, today included; 0 once it has passed.
+    pub days_left: Option<i64>,
+    /// 字 per day needed from today on to reach `word_target` by the deadline.
+    pub required_pace: Option<u64>,
+    /// Net 字 of the last seven days, oldest first, today last.
+    pub recent: Vec<(String, i64)>,
+}
+
+fn percent(part: i64, whole: u64) -> i64 {
+    if whole == 0 {
+        return 100;
+    }
+    part.max(0) * 100 / whole as i64
+}
+
+impl GoalProgress {
+    /// Human-readable summary, one line per fact.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut today = format!("今日：{:+} 字（新增 {}，删除 {}）", self.today.net(), self.today.added, self.today.removed);
+        if let Some(target) = self.daily_target {
+            today.push_str(&format!("，目标 {} 字，完成 {}%", target, percent(self.today.net(), target)));
+        }
+        lines.push(today);
+        if self.streak > 0 {
+            lines.push(format!("已连续 {} 天达成每日目标", self.streak));
+        }
+        let mut total = format!("正文：{} 字", self.total);
+        if let Some(target) = self.word_target {
+            total.push_str(&format!(" / {} 字（{}%）", target, percent(self.total as i64, target)));
+        }
+        lines.push(total);
+        if let (Some(deadline), Some(days)) = (&self.deadline, self.days_left) {
+            if days == 0 {
+                lines.push(format!("截止日期 {} 已过", deadline));
+            } else {
+                let mut line = format!("截止日期：{}，含今天还剩 {} 天", deadline, days);
+                if let Some(pace) = self.required_pace {
+                    line.push_str(&format!("，需每天 {} 字", pace));
+                }
+                lines.push(line);
+            }
+        }
+        let recent: Vec<String> = self.recent.iter().map(|(day, net)| format!("{} {:+}", &day[5..], net)).collect();
+        lines.push(format!("最近 7 天：{}", recent.join("  ")));
+        lines
+    }
+}
+
+pub fn goal_progress(meta: &ProjectMeta, history: &WritingHistory, total: u64, today: &str) -> GoalProgress {
+    let days_left = meta
+        .deadline
+        .as_deref()
+        .and_then(day_ordinal)
+        .zip(day_ordinal(today))
+        .map(|(deadline, today)| (deadline - today + 1).max(0));
+    let required_pace = match (meta.word_target, days_left) {
+        (Some(target), Some(days)) if days > 0 => Some(target.saturating_sub(total).div_ceil(days as u64)),
+        _ => None,
+    };
+    GoalProgress {
+        today: history.day(today),
+        daily_target: meta.daily_target,
+        streak: meta.daily_target.map_or(0, |t| history.streak(today, t)),
+        total,
+        word_target: meta.word_target,
+        deadline: meta.deadline.clone(),
+        days_left,
+        required_pace,
+        recent: match day_ordinal(today) {
+            Some(ordinal) => (ordinal - 6..=ordinal)
+                .map(|o| {
+                    let day = format_day(o);
+                    let net = history.day(&day).net();
+                    (day, net)
+                })
+                .collect(),
+            None => Vec::new(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_dir;
+
+    #[test]
+    fn streak_counts_back_from_today() {
+        let mut history = WritingHistory::default();
+        for (day, delta) in [("2026-02-26", 1200), ("2026-02-27", 800), ("2026-02-28", 1500), ("2026-03-01", 1000)] {
+            history.record(day, delta);
+        }
+        history.record("2026-03-01", -100);
+        assert_eq!(history.day("2026-03-01"), DayRecord { added: 1000, removed: 100 });
+        // Today is short of the target, which does not break yesterday's streak.
+        assert_eq!(history.streak("2026-03-01", 1000), 1);
+        assert_eq!(history.streak("2026-03-01", 800), 4);
+        assert_eq!(history.streak("2026-03-03", 800), 0);
+        assert_eq!(format_day(day_ordinal("2024-02-29").unwrap() + 1), "2024-03-01");
+        assert_eq!(day_ordinal("2024-13-01"), None);
+    }
+
+    #[test]
+    fn goal_progress_pace_and_recent_days() {
+        let meta: ProjectMeta = serde_yaml::from_str(
+            "name: 书\ncreated_unix: 0\nformat_version: 1\ndaily_target: 1000\nword_target: 100000\ndeadline: 2026-03-10\n",
+        )
+        .unwrap();
+        let mut history = WritingHistory::default();
+        history.record("2026-03-01", 1500);
+        let progress = goal_progress(&meta, &history, 40_000, "2026-03-01");
+        assert_eq!(progress.days_left, Some(10));
+        assert_eq!(progress.required_pace, Some(6_000));
+        assert_eq!(progress.streak, 1);
+        assert_eq!(progress.recent.len(), 7);
+        assert_eq!(progress.recent[6], ("2026-03-01".to_string(), 1500));
+        assert_eq!(goal_progress(&meta, &history, 40_000, "2026-03-11").days_left, Some(0));
+    }
+
+    #[test]
+    fn corrupt_history_is_moved_aside() {
+        let root = test_dir("history");
+        assert_eq!(record_words(&root, "2026-03-01", 30).unwrap().net(), 30);
+        assert_eq!(record_words(&root, "2026-03-01", -5).unwrap().net(), 25);
+
+        std::fs::write(history_path(&root), "{\"days\": {\"2026-03-01\": ").unwrap();
+        assert_eq!(record_words(&root, "2026-03-01", 10).unwrap().net(), 10);
+        let broken: Vec<_> = std::fs::read_dir(root.join(".stats"))
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with("history.broken-"))
+            .collect();
+        assert_eq!(broken.len(), 1);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
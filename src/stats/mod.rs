@@ -0,0 +1,224 @@
+//! 字数 statistics suited to Chinese prose.
+//!
+//! Every CJK character counts as one, each run of Latin letters or digits counts as one word,
+//! and punctuation, whitespace, YAML front matter and Markdown syntax (link targets, HTML tags
+//! and comments, code blocks, list numbering) are not counted at all.
+
+use crate::domain::Section;
+use crate::search::{is_cjk, is_word_char};
+use crate::storage::{self, parse_front_matter};
+use regex::Regex;
+use std::io;
+use std::ops::AddAssign;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+pub mod history;
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct TextCount {
+    pub cjk_chars: usize,
+    pub latin_words: usize,
+}
+
+impl TextCount {
+    /// 字数: CJK characters plus Latin words.
+    pub fn total(&self) -> usize {
+        self.cjk_chars + self.latin_words
+    }
+}
+
+impl AddAssign for TextCount {
+    fn add_assign(&mut self, other: Self) {
+        self.cjk_chars += other.cjk_chars;
+        self.latin_words += other.latin_words;
+    }
+}
+
+struct Syntax {
+    image: Regex,
+    link: Regex,
+    wiki_link: Regex,
+    footnote: Regex,
+    reference: Regex,
+    tag: Regex,
+    url: Regex,
+    list_number: Regex,
+}
+
+fn syntax() -> &'static Syntax {
+    static SYNTAX: OnceLock<Syntax> = OnceLock::new();
+    SYNTAX.get_or_init(|| Syntax {
+        image: Regex::new(r"!\[[^\]]*\]\([^)]*\)").unwrap(),
+        link: Regex::new(r"\[([^\]]*)\]\([^)]*\)").unwrap(),
+        wiki_link: Regex::new(r"\[\[([^\]|#]*)(?:#[^\]|]*)?(?:\|([^\]]*))?\]\]").unwrap(),
+        footnote: Regex::new(r"\[\^[^\]]*\]:?").unwrap(),
+        reference: Regex::new(r"^\s*\[[^\]]+\]:\s*\S+.*$").unwrap(),
+        tag: Regex::new(r"</?[A-Za-z][^>]*>").unwrap(),
+        url: Regex::new(r"[A-Za-z][A-Za-z0-9+.-]*://\S+").unwrap(),
+        list_number: Regex::new(r"^\s*\d+[.)]\s").unwrap(),
+    })
+}
+
+/// Counts the prose of one line with the Markdown syntax removed.
+fn count_line(line: &str, count: &mut TextCount) {
+    let s = syntax();
+    if s.reference.is_match(line) {
+        return;
+    }
+    let line = s.list_number.replace(line, "");
+    let line = s.image.replace_all(&line, "");
+    let line = s.wiki_link.replace_all(&line, |caps: &regex::Captures| {
+        caps.get(2).or(caps.get(1)).map_or(String::new(), |m| m.as_str().to_string())
+    });
+    let line = s.link.replace_all(&line, "$1");
+    let line = s.footnote.replace_all(&line, "");
+    let line = s.tag.replace_all(&line, "");
+    let line = s.url.replace_all(&line, "");
+
+    let mut in_word = false;
+    for c in line.chars() {
+        if is_cjk(c) && c != '・' && c != 'ー' {
+            count.cjk_chars += 1;
+            in_word = false;
+        } else if is_word_char(c) {
+            if !in_word {
+                count.latin_words += 1;
+            }
+            in_word = true;
+        } else {
+            in_word = c == '\'' && in_word;
+        }
+    }
+}
+
+/// Counts the Markdown `body` of a file whose front matter has already been removed.
+pub fn count_markdown(body: &str) -> TextCount {
+    let mut count = TextCount::default();
+    let mut in_fence = false;
+    let mut in_comment = false;
+    for line in body.lines() {
+        let trimmed = line.trim_start();
+        if !in_comment && (trimmed.starts_with("```") || trimmed.starts_with("~~~")) {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        // HTML comments may span lines; only the text outside them counts.
+        let mut rest = line;
+        let mut visible = String::new();
+        loop {
+            if in_comment {
+                match rest.find("-->") {
+                    Some(end) => {
+                        rest = &rest[end + 3..];
+                        in_comment = false;
+                    }
+                    None => break,
+                }
+            } else {
+                match rest.find("<!--") {
+                    Some(start) => {
+                        visible.push_str(&rest[..start]);
+                        rest = &rest[start + 4..];
+                        in_comment = true;
+                    }
+                    None => {
+                        visible.push_str(rest);
+                        break;
+                    }
+                }
+            }
+        }
+        count_line(&visible, &mut count);
+    }
+    count
+}
+
+/// Counts a whole file's content, skipping its YAML front matter.
+pub fn count_document(content: &str) -> TextCount {
+    let (_meta, body) = parse_front_matter::<serde_yaml::Value>(content);
+    count_markdown(body)
+}
+
+#[derive(Debug, Clone)]
+pub struct FileStats {
+    pub path: PathBuf,
+    pub count: TextCount,
+}
+
+#[derive(Debug, Clone)]
+pub struct SectionStats {
+    pub section: Section,
+    /// In the order the files are listed in the section.
+    pub files: Vec<FileStats>,
+    pub total: TextCount,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProjectStats {
+    /// Every section in tab order, including empty ones.
+    pub sections: Vec<SectionStats>,
+    /// All sections together; `project.md` is not counted.
+    pub total: TextCount,
+}
+
+impl ProjectStats {
+    pub fn section(&self, section: Section) -> Option<&SectionStats> {
+        self.sections.iter().find(|s| s.section == section)
+    }
+
+    /// Count of one file, if it belongs to a section of the project.
+    pub fn file(&self, path: &Path) -> Option<TextCount> {
+        self.sections.iter().flat_map(|s| &s.files).find(|f| f.path == path).map(|f| f.count)
+    }
+}
+
+/// Counts every section of the project at `project_root`.
+pub fn project_stats(project_root: &Path) -> io::Result<ProjectStats> {
+    let mut sections = Vec::new();
+    let mut total = TextCount::default();
+    for section in Section::ALL {
+        let paths = storage::list_section_files(project_root, section)?;
+        let mut files = Vec::with_capacity(paths.len());
+        let mut section_total = TextCount::default();
+        for path in paths {
+            let count = count_document(&storage::read_text(&path)?);
+            section_total += count;
+            files.push(FileStats { path, count });
+        }
+        total += section_total;
+        sections.push(SectionStats {
+            section,
+            files,
+            total: section_total,
+        });
+    }
+    Ok(ProjectStats { sections, total })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(text: &str) -> (usize, usize) {
+        let c = count_document(text);
+        (c.cjk_chars, c.latin_words)
+    }
+
+    #[test]
+    fn chinese_characters_and_latin_words() {
+        assert_eq!(count("林风拔出了剑。"), (6, 0));
+        assert_eq!(count("他说：“OK, let's go 2024 年！”"), (3, 4));
+        assert_eq!(count("ＡＢＣ，　全角空格"), (4, 1));
+    }
+
+    #[test]
+    fn markdown_syntax_and_front_matter_are_not_counted() {
+        let text = "---\nstatus: draft\npov: 林风\n---\n\n# 第一章\n\n1. 看[山](https://example.com/mountain)\n![图](a.png)见[[0001-开端#开头|开端]]和[[林风]]。<br/>\n<!-- 注释\n跨行 -->正文\n```\n代码不算\n```\n[1]: https://example.com\n";
+        assert_eq!(count(text), (3 + 2 + 6 + 2, 0));
+        assert_eq!(count("脚注[^1]。"), (2, 0));
+    }
+}
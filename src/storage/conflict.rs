@@ -0,0 +1,123 @@
+//! Noticing files changed on disk by someone else (Git, a sync client, another editor) between
+//! loading a document and saving it.
+
+use super::sha256::sha256_hex;
+use super::{format_local_time, read_text, unique_file_name, write_text_atomic};
+use crate::domain::diff::merge_with_markers;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// What a file looked like when it was loaded or last saved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiskStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: String,
+}
+
+impl DiskStamp {
+    /// Stamps `path`, which has just been read or written with `text`.
+    pub fn of_text(path: &Path, text: &str) -> Self {
+        let meta = fs::metadata(path).ok();
+        Self {
+            modified: meta.as_ref().and_then(|m| m.modified().ok()),
+            len: meta.map(|m| m.len()).unwrap_or(0),
+            hash: sha256_hex(text.as_bytes()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExternalChange {
+    /// The file now holds this text.
+    Modified(String),
+    Deleted,
+}
+
+/// Compares `path` with `stamp`. An unchanged size and modification time are trusted without
+/// reading the file; otherwise the content decides, so a touched but identical file is no conflict.
+pub fn external_change(path: &Path, stamp: &DiskStamp) -> io::Result<Option<ExternalChange>> {
+    let meta = match fs::metadata(path) {
+        Ok(meta) => meta,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Some(ExternalChange::Deleted)),
+        Err(e) => return Err(e),
+    };
+    if stamp.modified.is_some() && meta.modified().ok() == stamp.modified && meta.len() == stamp.len {
+        return Ok(None);
+    }
+    let text = read_text(path)?;
+    if sha256_hex(text.as_bytes()) == stamp.hash {
+        return Ok(None);
+    }
+    Ok(Some(ExternalChange::Modified(text)))
+}
+
+/// Conflict copies live outside the section folders, so they never become chapters or notes.
+pub fn conflicts_dir(project_root: &Path) -> PathBuf {
+    project_root.join(".conflicts")
+}
+
+/// Writes `ours` merged with the text now on disk, differences marked Git-style, to
+/// `.conflicts/标题（冲突副本 YYYY-MM-DD HHMM）.md`, and returns the copy's path. `path` itself is left alone.
+pub fn write_conflict_copy(project_root: &Path, path: &Path, ours: &str, theirs: &str, now_unix: u64) -> io::Result<PathBuf> {
+    let dir = conflicts_dir(project_root);
+    fs::create_dir_all(&dir)?;
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("conflict");
+    let time = format_local_time(now_unix).replace(':', "");
+    let name = unique_file_name(&dir, &format!("{}（冲突副本 {}）.md", stem, time));
+    let copy = dir.join(name);
+    write_text_atomic(&copy, &merge_with_markers(ours, theirs, "我的版本", "磁盘上的版本"))?;
+    Ok(copy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_dir;
+
+    #[test]
+    fn external_changes_are_decided_by_content() {
+        let dir = test_dir("conflict");
+        let path = dir.join("0001-开端.md");
+        fs::write(&path, "原文\n").unwrap();
+        let stamp = DiskStamp::of_text(&path, "原文\n");
+        assert_eq!(external_change(&path, &stamp).unwrap(), None);
+
+        // Rewritten with the same text, e.g. by a sync client: no conflict.
+        fs::write(&path, "原文\n").unwrap();
+        assert_eq!(external_change(&path, &stamp).unwrap(), None);
+
+        fs::write(&path, "别处改过的原文\n").unwrap();
+        assert_eq!(
+            external_change(&path, &stamp).unwrap(),
+            Some(ExternalChange::Modified("别处改过的原文\n".to_string()))
+        );
+        fs::remove_file(&path).unwrap();
+        assert_eq!(external_change(&path, &stamp).unwrap(), Some(ExternalChange::Deleted));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn conflict_copies_go_outside_the_sections_with_markers() {
+        let dir = test_dir("conflict-copy");
+        fs::create_dir_all(dir.join("chapters")).unwrap();
+        let path = dir.join("chapters/0001-开端.md");
+        fs::write(&path, "第一行\n磁盘上的第二行\n第三行\n").unwrap();
+        let ours = "第一行\n我的第二行\n第三行\n";
+        let theirs = read_text(&path).unwrap();
+
+        let copy = write_conflict_copy(&dir, &path, ours, &theirs, 0).unwrap();
+        assert_eq!(copy.parent(), Some(conflicts_dir(&dir).as_path()));
+        let name = copy.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("0001-开端（冲突副本 ") && name.ends_with("）.md"), "{}", name);
+        assert_eq!(
+            read_text(&copy).unwrap(),
+            "第一行\n<<<<<<< 我的版本\n我的第二行\n=======\n磁盘上的第二行\n>>>>>>> 磁盘上的版本\n第三行\n"
+        );
+        assert_eq!(read_text(&path).unwrap(), theirs);
+        assert_ne!(write_conflict_copy(&dir, &path, ours, &theirs, 0).unwrap(), copy);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
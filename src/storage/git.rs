@@ -0,0 +1,301 @@
+//! Optional Git versioning: commits the project's Markdown files through the `git` binary with
+//! messages such as `更新 0003-决战.md (+1200字)`, and reads per-file history back. Works with a
+//! purely local repository; nothing is ever pushed.
+
+use crate::stats::count_document;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// What `git` looks at besides `project.md`: the Markdown files of the section folders.
+const SECTION_DIRS: [&str; 4] = ["chapters", "characters", "world", "timeline"];
+
+/// Written to a new repository so backups, journals and exports stay out of it.
+const GITIGNORE: &str = ".backup/\n.recovery/\nexports/\n*.tmp.*\n";
+
+#[derive(Debug, Clone)]
+pub struct GitRepo {
+    /// The project folder, which may be anywhere inside the work tree.
+    root: PathBuf,
+    /// Top of the work tree; paths Git reports are relative to it.
+    top: PathBuf,
+}
+
+#[derive(Debug, Clone)]
+pub struct CommitSummary {
+    pub commit: String,
+    /// First line of the message.
+    pub subject: String,
+    pub files: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct GitVersion {
+    pub commit: String,
+    pub time_unix: u64,
+    pub subject: String,
+    /// The file's path at that commit, relative to the top of the work tree.
+    pub path: String,
+}
+
+fn git_command(dir: &Path) -> Command {
+    let mut cmd = Command::new("git");
+    cmd.arg("-C").arg(dir).args(["-c", "core.quotepath=false"]);
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        // CREATE_NO_WINDOW: keep a console from flashing up on every commit.
+        cmd.creation_flags(0x0800_0000);
+    }
+    cmd
+}
+
+fn run(dir: &Path, args: &[&str]) -> io::Result<String> {
+    let output = git_command(dir).args(args).output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(io::Error::other(format!("git {}: {}", args.join(" "), stderr.trim())));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// True when a `git` binary can be run.
+pub fn git_available() -> bool {
+    git_command(Path::new(".")).arg("--version").output().is_ok_and(|o| o.status.success())
+}
+
+impl GitRepo {
+    /// The repository `project_root` belongs to, `None` when it is not inside a Git work tree.
+    pub fn open(project_root: &Path) -> io::Result<Option<Self>> {
+        match run(project_root, &["rev-parse", "--show-toplevel"]) {
+            Ok(top) => Ok(Some(Self {
+                root: project_root.to_path_buf(),
+                top: PathBuf::from(top.trim()),
+            })),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Err(e),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Opens the repository of `project_root`, creating one in it first if there is none.
+    pub fn init(project_root: &Path) -> io::Result<Self> {
+        if let Some(repo) = Self::open(project_root)? {
+            return Ok(repo);
+        }
+        run(project_root, &["init", "--quiet"])?;
+        let ignore = project_root.join(".gitignore");
+        if !ignore.exists() {
+            super::write_text_atomic(&ignore, GITIGNORE)?;
+        }
+        Self::open(project_root)?.ok_or_else(|| io::Error::other("git init did not create a repository"))
+    }
+
+    pub fn top(&self) -> &Path {
+        &self.top
+    }
+
+    /// Text of `path` (relative to the top of the work tree) at `commit`.
+    pub fn read_at(&self, commit: &str, path: &str) -> io::Result<String> {
+        run(&self.top, &["show", &format!("{}:{}", commit, path)])
+    }
+
+    /// Stages every change to the project's Markdown files and commits it with a generated
+    /// message. Returns `None` when nothing changed. Anything else the user has staged stays staged.
+    pub fn commit_changes(&self) -> io::Result<Option<CommitSummary>> {
+        let pathspecs = self.markdown_pathspecs()?;
+        if pathspecs.is_empty() {
+            return Ok(None);
+        }
+        let mut add = vec!["add", "-A", "--"];
+        add.extend(pathspecs.iter().map(String::as_str));
+        run(&self.root, &add)?;
+
+        let mut status = vec!["status", "--porcelain=v1", "-z", "--"];
+        status.push("project.md");
+        status.extend(SECTION_DIRS);
+        let status = run(&self.root, &status)?;
+        let has_head = run(&self.root, &["rev-parse", "--verify", "--quiet", "HEAD"]).is_ok();
+        let mut lines = Vec::new();
+        let mut total_delta = 0i64;
+        let mut entries = status.split('\0').filter(|e| !e.is_empty());
+        while let Some(entry) = entries.next() {
+            let (Some(index), Some(path)) = (entry.chars().next(), entry.get(3..)) else { continue };
+            let old_path = if index == 'R' || index == 'C' { entries.next() } else { None };
+            if !matches!(index, 'A' | 'M' | 'D' | 'R' | 'C') {
+                continue;
+            }
+            let new_count = match index {
+                'D' => 0,
+                _ => super::read_text(&self.top.join(path)).map(|t| count_document(&t).total() as i64).unwrap_or(0),
+            };
+            let old_count = match (index, has_head) {
+                ('A', _) | (_, false) => 0,
+                _ => self
+                    .read_at("HEAD", old_path.unwrap_or(path))
+                    .map(|t| count_document(&t).total() as i64)
+                    .unwrap_or(0),
+            };
+            let delta = new_count - old_count;
+            total_delta += delta;
+            lines.push(describe_change(index, path, old_path, delta));
+        }
+        if lines.is_empty() {
+            return Ok(None);
+        }
+
+        let subject = if lines.len() == 1 {
+            lines[0].clone()
+        } else {
+            format!("更新 {} 个文件{}", lines.len(), delta_suffix(total_delta))
+        };
+        let mut commit = vec!["commit", "--quiet", "-m", &subject];
+        let body = lines.join("\n");
+        if lines.len() > 1 {
+            commit.extend(["-m", &body]);
+        }
+        commit.push("--");
+        commit.extend(pathspecs.iter().map(String::as_str));
+        let identity = self.fallback_identity();
+        let mut args: Vec<&str> = identity.iter().map(String::as_str).collect();
+        args.extend(commit);
+        run(&self.root, &args)?;
+        let commit = run(&self.root, &["rev-parse", "--short", "HEAD"])?.trim().to_string();
+        Ok(Some(CommitSummary {
+            commit,
+            subject,
+            files: lines.len(),
+        }))
+    }
+
+    /// `project.md` plus a `*.md` glob for each section folder that has Markdown files on disk or
+    /// in the index, so swap and temporary files are never staged. Git rejects pathspecs that
+    /// match nothing, hence the check.
+    fn markdown_pathspecs(&self) -> io::Result<Vec<String>> {
+        let mut specs = Vec::new();
+        if self.root.join("project.md").exists() {
+            specs.push("project.md".to_string());
+        }
+        let mut args = vec!["ls-files", "-z", "--cached", "--others", "--exclude-standard", "--"];
+        args.extend(SECTION_DIRS);
+        let listed = run(&self.root, &args)?;
+        let markdown: Vec<&Path> = listed
+            .split('\0')
+            .map(Path::new)
+            .filter(|p| p.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("md")))
+            .collect();
+        for section in SECTION_DIRS {
+            if markdown.iter().any(|p| p.starts_with(section)) {
+                specs.push(format!(":(glob,icase){}/**/*.md", section));
+            }
+        }
+        Ok(specs)
+    }
+
+    /// `-c user.name=… -c user.email=…` when the repository has no identity configured, so
+    /// committing works on a fresh machine.
+    fn fallback_identity(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        for (key, value) in [("user.name", "novel-outline"), ("user.email", "novel-outline@localhost")] {
+            if run(&self.root, &["config", "--get", key]).map(|v| v.trim().is_empty()).unwrap_or(true) {
+                args.push("-c".to_string());
+                args.push(format!("{}={}", key, value));
+            }
+        }
+        args
+    }
+
+    /// Commits that touched `path`, newest first, following renames.
+    pub fn file_history(&self, path: &Path) -> io::Result<Vec<GitVersion>> {
+        let path = path.to_string_lossy();
+        let log = run(&self.root, &["log", "--follow", "--name-only", "--format=%x1e%H%x1f%ct%x1f%s", "--", &path])?;
+        let mut versions = Vec::new();
+        for record in log.split('\u{1e}').filter(|r| !r.trim().is_empty()) {
+            let mut lines = record.lines();
+            let header = lines.next().unwrap_or("");
+            let mut fields = header.split('\u{1f}');
+            let (Some(commit), Some(time), Some(subject)) = (fields.next(), fields.next(), fields.next()) else { continue };
+            let Some(file) = lines.map(str::trim).find(|l| !l.is_empty()) else { continue };
+            versions.push(GitVersion {
+                commit: commit.to_string(),
+                time_unix: time.parse().unwrap_or(0),
+                subject: subject.to_string(),
+                path: file.to_string(),
+            });
+        }
+        Ok(versions)
+    }
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+fn delta_suffix(delta: i64) -> String {
+    if delta == 0 {
+        String::new()
+    } else {
+        format!(" ({:+}字)", delta)
+    }
+}
+
+fn describe_change(index: char, path: &str, old_path: Option<&str>, delta: i64) -> String {
+    let name = file_name(path);
+    match (index, old_path) {
+        ('A', _) => format!("新增 {}{}", name, delta_suffix(delta)),
+        ('D', _) => format!("删除 {}{}", name, delta_suffix(delta)),
+        ('R', Some(old)) => format!("重命名 {} → {}{}", file_name(old), name, delta_suffix(delta)),
+        ('C', Some(old)) => format!("复制 {} → {}{}", file_name(old), name, delta_suffix(delta)),
+        _ => format!("更新 {}{}", name, delta_suffix(delta)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_dir;
+    use std::fs;
+
+    #[test]
+    fn change_descriptions() {
+        assert_eq!(describe_change('A', "chapters/第一卷/0001-开端.md", None, 1200), "新增 0001-开端.md (+1200字)");
+        assert_eq!(describe_change('M', "chapters/0002-下山.md", None, -35), "更新 0002-下山.md (-35字)");
+        assert_eq!(describe_change('D', "world/地图.md", None, 0), "删除 地图.md");
+        assert_eq!(
+            describe_change('R', "chapters/0002-开端.md", Some("chapters/0001-开端.md"), 0),
+            "重命名 0001-开端.md → 0002-开端.md"
+        );
+    }
+
+    #[test]
+    fn commits_leave_other_staged_files_alone() {
+        if !git_available() {
+            return;
+        }
+        let root = test_dir("git");
+        fs::create_dir_all(root.join("chapters")).unwrap();
+        fs::write(root.join("chapters/0001-开端.md"), "林风上山。\n").unwrap();
+        let repo = GitRepo::init(&root).unwrap();
+        fs::write(root.join("notes.txt"), "私人笔记").unwrap();
+        run(&root, &["add", "notes.txt"]).unwrap();
+        // Editor swap files and half-written temporary files are never committed.
+        fs::write(root.join("chapters/.0001-开端.md.swp"), "swap").unwrap();
+        fs::write(root.join("chapters/0002-下山.md.tmp.1"), "半截").unwrap();
+        fs::create_dir_all(root.join("world")).unwrap();
+        fs::write(root.join("world/地图.png"), "png").unwrap();
+
+        let summary = repo.commit_changes().unwrap().unwrap();
+        assert_eq!(summary.subject, "新增 0001-开端.md (+4字)");
+        let committed = run(&root, &["show", "--name-only", "--format=", "HEAD"]).unwrap();
+        assert_eq!(committed.trim(), "chapters/0001-开端.md");
+        assert_eq!(run(&root, &["diff", "--cached", "--name-only"]).unwrap().trim(), "notes.txt");
+        assert!(repo.commit_changes().unwrap().is_none());
+
+        fs::write(root.join("chapters/0001-开端.md"), "林风上山，又下山。\n").unwrap();
+        let summary = repo.commit_changes().unwrap().unwrap();
+        assert_eq!(summary.subject, "更新 0001-开端.md (+3字)");
+        let versions = repo.file_history(&root.join("chapters/0001-开端.md")).unwrap();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(repo.read_at(&versions[1].commit, &versions[1].path).unwrap(), "林风上山。\n");
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,223 @@
+use super::{file_item_names, item_names, list_section_files, read_text, resolve_item_name, split_chapter_stem, write_text_atomic};
+use crate::domain::links::{has_heading, parse_wiki_links, rewrite_wiki_links, WikiLink};
+use crate::domain::timeline::TimelineDoc;
+use crate::domain::Section;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// `project.md` followed by the Markdown files of every section.
+pub fn project_markdown_files(project_root: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let project_md = project_root.join("project.md");
+    if project_md.is_file() {
+        files.push(project_md);
+    }
+    for section in Section::ALL {
+        files.extend(list_section_files(project_root, section)?);
+    }
+    Ok(files)
+}
+
+#[derive(Debug, Clone)]
+pub struct IndexedLink {
+    pub source: PathBuf,
+    pub link: WikiLink,
+    /// File the target names, `None` when it names nothing.
+    pub target: Option<PathBuf>,
+    /// False when the `#标题` part names a heading the target does not have.
+    pub anchor_found: bool,
+}
+
+impl IndexedLink {
+    pub fn is_broken(&self) -> bool {
+        self.target.is_none() || !self.anchor_found
+    }
+}
+
+/// Every wiki link of the project with its resolved target.
+#[derive(Debug, Clone, Default)]
+pub struct LinkIndex {
+    pub links: Vec<IndexedLink>,
+}
+
+impl LinkIndex {
+    pub fn build(project_root: &Path) -> io::Result<Self> {
+        let names = item_names(project_root)?;
+        let files = project_markdown_files(project_root)?;
+        let mut contents = Vec::with_capacity(files.len());
+        for path in &files {
+            contents.push(read_text(path)?);
+        }
+        let mut links = Vec::new();
+        for (source, text) in files.iter().zip(&contents) {
+            for link in parse_wiki_links(text) {
+                let target = resolve_item_name(&names, &link.target);
+                let anchor_found = match (&target, &link.anchor) {
+                    (Some(t), Some(anchor)) => files
+                        .iter()
+                        .position(|f| f == t)
+                        .is_some_and(|i| has_heading(&contents[i], anchor)),
+                    _ => true,
+                };
+                links.push(IndexedLink {
+                    source: source.clone(),
+                    link,
+                    target,
+                    anchor_found,
+                });
+            }
+        }
+        Ok(Self { links })
+    }
+
+    pub fn outgoing<'a>(&'a self, path: &'a Path) -> impl Iterator<Item = &'a IndexedLink> + 'a {
+        self.links.iter().filter(move |l| l.source == path)
+    }
+
+    pub fn backlinks<'a>(&'a self, path: &'a Path) -> impl Iterator<Item = &'a IndexedLink> + 'a {
+        self.links.iter().filter(move |l| l.target.as_deref() == Some(path))
+    }
+
+    pub fn broken(&self) -> impl Iterator<Item = &IndexedLink> {
+        self.links.iter().filter(|l| l.is_broken())
+    }
+}
+
+/// A whole-file rewrite that can be applied and undone.
+#[derive(Debug, Clone)]
+pub struct LinkEdit {
+    pub path: PathBuf,
+    pub before: String,
+    pub after: String,
+}
+
+/// Names by which links may refer to `path` and that depend on its file name:
+/// the stem, a chapter's title without `NNNN-`, and a character stem without `角色-`.
+fn file_names(path: &Path) -> Vec<String> {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let mut names = vec![stem.to_string()];
+    let (number, title) = split_chapter_stem(stem);
+    if number.is_some() {
+        names.push(title.to_string());
+    }
+    if let Some(name) = stem.strip_prefix("角色-") {
+        names.push(name.to_string());
+    }
+    names
+}
+
+/// Link rewrites that keep references working after the files in `renames` (old, new) are moved.
+/// Paths in the result are where the files live after the renames.
+pub fn link_rewrites(project_root: &Path, renames: &[(PathBuf, PathBuf)]) -> io::Result<Vec<LinkEdit>> {
+    let names = item_names(project_root)?;
+    // Old name -> new name, only for names that resolve to the renamed file today.
+    // Names that still resolve after the move (a character's heading, aliases) are left alone.
+    let mut mapping: Vec<(String, String)> = Vec::new();
+    for (from, to) in renames {
+        // Volume folders are renamed along with their chapters; links never name them.
+        if from.is_dir() {
+            continue;
+        }
+        let section = crate::search::section_of(project_root, from);
+        let kept = match section {
+            Some(section) => file_item_names(section, to, &read_text(from)?),
+            None => Vec::new(),
+        };
+        for (old, new) in file_names(from).into_iter().zip(file_names(to)) {
+            if old != new && !kept.contains(&old) && resolve_item_name(&names, &old).as_deref() == Some(from.as_path()) {
+                mapping.push((old, new));
+            }
+        }
+    }
+    if mapping.is_empty() {
+        return Ok(Vec::new());
+    }
+    let rename = |target: &str| mapping.iter().find(|(old, _)| old == target).map(|(_, new)| new.clone());
+
+    let timeline_dir = project_root.join(Section::Timeline.dir_name());
+    let mut edits = Vec::new();
+    for path in project_markdown_files(project_root)? {
+        let before = read_text(&path)?;
+        let mut after = rewrite_wiki_links(&before, rename).unwrap_or_else(|| before.clone());
+        if path.parent() == Some(timeline_dir.as_path()) {
+            let mut doc = TimelineDoc::parse(&after);
+            let mut changed = false;
+            for row in doc.rows_mut() {
+                changed |= row.rename_links(rename);
+            }
+            if changed {
+                after = doc.to_markdown();
+            }
+        }
+        if after != before {
+            let path = renames.iter().find(|(from, _)| *from == path).map(|(_, to)| to.clone()).unwrap_or(path);
+            edits.push(LinkEdit { path, before, after });
+        }
+    }
+    Ok(edits)
+}
+
+/// Writes `after` of each edit. Edits are not undone by writing `before` back, which would lose
+/// what was typed since; undoing a rename rewrites links again with the pairs swapped.
+pub fn apply_link_edits(edits: &[LinkEdit]) -> io::Result<()> {
+    for edit in edits {
+        write_text_atomic(&edit.path, &edit.after)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_dir;
+    use std::fs;
+
+    fn rename_with_links(root: &Path, from: &Path, to: &Path) -> Vec<LinkEdit> {
+        let edits = link_rewrites(root, &[(from.to_path_buf(), to.to_path_buf())]).unwrap();
+        fs::rename(from, to).unwrap();
+        apply_link_edits(&edits).unwrap();
+        edits
+    }
+
+    #[test]
+    fn renames_rewrite_links_and_swapped_pairs_undo_them() {
+        let root = test_dir("links");
+        fs::create_dir_all(root.join("chapters")).unwrap();
+        fs::create_dir_all(root.join("characters")).unwrap();
+        let old = root.join("chapters/0001-开端.md");
+        let new = root.join("chapters/0001-序章.md");
+        let lin = root.join("characters/角色-林风.md");
+        fs::write(&old, "# 开端\n").unwrap();
+        fs::write(&lin, "# 角色：林风\n\n初登场见[[开端]]，另见[[0001-开端#开端|第一章]]。\n").unwrap();
+
+        let edits = rename_with_links(&root, &old, &new);
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].path, lin);
+        let renamed = read_text(&lin).unwrap();
+        assert!(renamed.contains("[[序章]]") && renamed.contains("[[0001-序章#开端|第一章]]"));
+
+        // Text added after the rename survives undoing it.
+        fs::write(&lin, format!("{}又在[[序章]]里出现。\n", renamed)).unwrap();
+        rename_with_links(&root, &new, &old);
+        let undone = read_text(&lin).unwrap();
+        assert!(undone.contains("初登场见[[开端]]，另见[[0001-开端#开端|第一章]]。"));
+        assert!(undone.contains("又在[[开端]]里出现。"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn names_that_still_resolve_are_kept() {
+        let root = test_dir("links-kept");
+        fs::create_dir_all(root.join("characters")).unwrap();
+        fs::create_dir_all(root.join("chapters")).unwrap();
+        let old = root.join("characters/角色-林风.md");
+        fs::write(&old, "# 角色：林风\n").unwrap();
+        fs::write(root.join("chapters/0001-开端.md"), "[[林风]]与[[角色-林风]]\n").unwrap();
+
+        // The heading still names 林风, so only the stem link changes.
+        let edits = link_rewrites(&root, &[(old, root.join("characters/角色-林枫.md"))]).unwrap();
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].after, "[[林风]]与[[角色-林枫]]\n");
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
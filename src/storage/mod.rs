@@ -1,14 +1,30 @@
- use crate::domain::{Project, ProjectMeta};
- use serde::de::DeserializeOwned;
+ use crate::domain::character::{character_name, migrate_legacy_character, new_character_md, parse_character};
+use crate::domain::timeline::{self, TimelineDoc, TimelineIssue};
+ use crate::domain::{Calendars, ChapterMeta, Project, ProjectMeta, Section};
+pub use crate::domain::front_matter::{parse_front_matter, render_front_matter};
+use crate::domain::front_matter::has_invalid_front_matter;
  use std::fs;
  use std::io;
+use std::io::Write;
+#[cfg(windows)]
  use std::os::windows::ffi::OsStrExt;
  use std::path::{Path, PathBuf};
  
+#[cfg(windows)]
  use windows_sys::Win32::Storage::FileSystem::{
      MoveFileExW, MOVEFILE_REPLACE_EXISTING, MOVEFILE_WRITE_THROUGH,
  };
  
+pub mod conflict;
+pub mod git;
+pub mod links;
+pub mod outline;
+pub mod recovery;
+mod sha256;
+pub mod snapshots;
+pub mod versions;
+pub mod watch;
+
  pub struct ProjectStore {
      root: PathBuf,
  }
@@ -26,6 +42,29 @@ pub fn list_markdown_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
     Ok(out)
 }
 
+/// Markdown files under `dir` and its folders in outline order: at each level the files by name,
+/// then the contents of each folder in turn. See [`outline::read_outline`].
+pub fn list_markdown_files_recursive(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    Ok(outline::read_outline(dir)?
+        .into_iter()
+        .filter(|item| item.kind == outline::OutlineKind::Chapter)
+        .map(|item| item.path)
+        .collect())
+}
+
+/// The Markdown files of `section`, including those in volume folders for chapters.
+/// Empty when the section folder does not exist.
+pub fn list_section_files(project_root: &Path, section: Section) -> io::Result<Vec<PathBuf>> {
+    let dir = project_root.join(section.dir_name());
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    match section {
+        Section::Chapters => list_markdown_files_recursive(&dir),
+        _ => list_markdown_files(&dir),
+    }
+}
+
 pub fn read_text(path: &Path) -> io::Result<String> {
     fs::read_to_string(path)
 }
@@ -34,59 +73,489 @@ pub fn write_text_atomic(path: &Path, text: &str) -> io::Result<()> {
     atomic_write(path, text.as_bytes())
 }
 
+/// Writes `text` only while the file still holds `expected`, so that undoing a batch never
+/// overwrites edits made since. Returns false when the file was left alone; a file that
+/// already holds `text` counts as written, and a missing file as empty.
+pub fn write_text_if_unchanged(path: &Path, expected: &str, text: &str) -> io::Result<bool> {
+    let current = match read_text(path) {
+        Ok(current) => current,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    if current == text {
+        return Ok(true);
+    }
+    if current != expected {
+        return Ok(false);
+    }
+    write_text_atomic(path, text)?;
+    Ok(true)
+}
+
+pub fn write_bytes_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    atomic_write(path, bytes)
+}
+
+/// Removes the `.tmp.` files an interrupted atomic write left behind, in `project.md`'s folder
+/// and every section, volume folders included.
 pub fn cleanup_temp_files(project_root: &Path) -> io::Result<()> {
-    let dirs = [
-        project_root.to_path_buf(),
-        project_root.join("chapters"),
-        project_root.join("characters"),
-        project_root.join("world"),
-        project_root.join("timeline"),
-    ];
-    for dir in dirs {
-        if !dir.exists() {
+    remove_temp_files(project_root, false)?;
+    for section in Section::ALL {
+        let dir = project_root.join(section.dir_name());
+        if dir.is_dir() {
+            remove_temp_files(&dir, section == Section::Chapters)?;
+        }
+    }
+    Ok(())
+}
+
+/// Skips hidden folders and the tool's own staging folders, like [`outline::read_outline`].
+fn remove_temp_files(dir: &Path, recursive: bool) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+        if path.is_file() && name.contains(".tmp.") {
+            let _ = fs::remove_file(&path);
+        } else if recursive && path.is_dir() && !name.starts_with('.') && !name.contains(".tmp.") {
+            remove_temp_files(&path, true)?;
+        }
+    }
+    Ok(())
+}
+
+/// Offset of local time from UTC in seconds.
+#[cfg(windows)]
+pub fn local_offset_secs() -> i64 {
+    use windows_sys::Win32::Foundation::SYSTEMTIME;
+    use windows_sys::Win32::System::SystemInformation::{GetLocalTime, GetSystemTime};
+    let secs = |t: &SYSTEMTIME| {
+        crate::domain::timeline::days_from_civil(t.wYear as i64, t.wMonth as u32, t.wDay as u32) * 86_400
+            + t.wHour as i64 * 3600
+            + t.wMinute as i64 * 60
+    };
+    let mut local: SYSTEMTIME = unsafe { std::mem::zeroed() };
+    let mut utc: SYSTEMTIME = unsafe { std::mem::zeroed() };
+    unsafe {
+        GetSystemTime(&mut utc);
+        GetLocalTime(&mut local);
+    }
+    // Round to quarter hours in case the clock ticked between the two calls.
+    let diff = secs(&local) - secs(&utc);
+    (diff as f64 / 900.0).round() as i64 * 900
+}
+
+/// Offset of local time from UTC in seconds, from the C library's time zone (`TZ` or
+/// `/etc/localtime`).
+#[cfg(target_os = "linux")]
+pub fn local_offset_secs() -> i64 {
+    let now = crate::domain::now_unix() as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&now, &mut tm) }.is_null() {
+        return 0;
+    }
+    tm.tm_gmtoff as i64
+}
+
+/// Offset of local time from UTC in seconds. Not known on this platform, so dates are UTC.
+#[cfg(not(any(windows, target_os = "linux")))]
+pub fn local_offset_secs() -> i64 {
+    0
+}
+
+/// `YYYY-MM-DD HH:MM` in local time.
+pub fn format_local_time(unix: u64) -> String {
+    let local = unix as i64 + local_offset_secs();
+    let (year, month, day) = crate::domain::timeline::civil_from_days(local.div_euclid(86_400));
+    let secs = local.rem_euclid(86_400);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, secs / 3600, secs % 3600 / 60)
+}
+
+/// Snapshots the project into `.backup/`, recording `text` as the content of `source_path`.
+/// When the project leaves history to Git and `text` is what the file holds, the project is
+/// committed instead; text that exists nowhere else still goes to `.backup/`.
+pub fn backup_text(project_root: &Path, source_path: &Path, text: &str) -> io::Result<()> {
+    if backup_disabled(project_root) && read_text(source_path).is_ok_and(|t| t == text) {
+        if let Ok(Some(repo)) = git::GitRepo::open(project_root) {
+            repo.commit_changes()?;
+            return Ok(());
+        }
+    }
+    snapshots::snapshot_project(project_root, &[(source_path, text)])?;
+    Ok(())
+}
+
+/// True when `project.md` turns `.backup/` snapshots off in favour of Git.
+pub fn backup_disabled(project_root: &Path) -> bool {
+    let store = ProjectStore {
+        root: project_root.to_path_buf(),
+    };
+    store
+        .read_project_md()
+        .is_ok_and(|(meta, _)| meta.git.is_some_and(|g| g.disable_backup))
+}
+
+pub fn new_item_md(section: Section, title: &str) -> String {
+    match section {
+        Section::Chapters => return render_front_matter(&ChapterMeta::new_draft(), &format!("# {}\n\n", title)),
+        Section::Characters => return new_character_md(title),
+        _ => {}
+    }
+    format!("---\nformat_version: 1\n---\n\n# {}\n\n", title)
+}
+
+pub fn parse_chapter(content: &str) -> (ChapterMeta, &str) {
+    let (meta, body) = parse_front_matter::<ChapterMeta>(content);
+    (meta.unwrap_or_default(), body)
+}
+
+/// Rewrites bullet-style character files in `characters/` into typed front matter.
+/// Each original is written to `.backup/` first. Returns the migrated files.
+pub fn migrate_characters(project_root: &Path) -> io::Result<Vec<PathBuf>> {
+    let dir = project_root.join(Section::Characters.dir_name());
+    let mut migrated = Vec::new();
+    for path in list_markdown_files(&dir)? {
+        let content = read_text(&path)?;
+        let Some(updated) = migrate_legacy_character(&content) else { continue };
+        backup_text(project_root, &path, &content)?;
+        write_text_atomic(&path, &updated)?;
+        migrated.push(path);
+    }
+    Ok(migrated)
+}
+
+/// Every timeline file in `timeline/`, in file-name order.
+pub fn load_timelines(project_root: &Path) -> io::Result<Vec<(PathBuf, TimelineDoc)>> {
+    let dir = project_root.join(Section::Timeline.dir_name());
+    let mut docs = Vec::new();
+    for path in list_markdown_files(&dir)? {
+        let content = read_text(&path)?;
+        docs.push((path, TimelineDoc::parse(&content)));
+    }
+    Ok(docs)
+}
+
+/// Names an item can be referred to by, e.g. from `[[链接]]` or the 关联 column of a timeline:
+/// file stems, chapter titles without the `NNNN-` prefix, character names and aliases.
+pub fn item_names(project_root: &Path) -> io::Result<Vec<(String, PathBuf)>> {
+    let mut names = Vec::new();
+    for section in Section::ALL {
+        for path in list_section_files(project_root, section)? {
+            let content = if section == Section::Characters { read_text(&path)? } else { String::new() };
+            names.extend(file_item_names(section, &path, &content).into_iter().map(|n| (n, path.clone())));
+        }
+    }
+    Ok(names)
+}
+
+/// The names of one file for [`item_names`]; `content` is only read for characters.
+pub fn file_item_names(section: Section, path: &Path, content: &str) -> Vec<String> {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let mut names = Vec::new();
+    match section {
+        Section::Chapters => names.push(split_chapter_stem(stem).1.to_string()),
+        Section::Characters => {
+            let (meta, body) = parse_character(content);
+            names.push(character_name(path, body));
+            names.extend(stem.strip_prefix("角色-").map(str::to_string));
+            names.extend(meta.aliases.iter().map(|a| a.trim().to_string()));
+        }
+        _ => {}
+    }
+    names.push(stem.to_string());
+    names.retain(|n| !n.is_empty());
+    names
+}
+
+/// Looks `target` up in [`item_names`]; a `#标题` suffix is ignored.
+pub fn resolve_item_name(names: &[(String, PathBuf)], target: &str) -> Option<PathBuf> {
+    let target = target.split('#').next().unwrap_or(target).trim();
+    names.iter().find(|(name, _)| name == target).map(|(_, path)| path.clone())
+}
+
+pub fn check_timelines(project: &Project) -> io::Result<Vec<TimelineIssue>> {
+    let docs = load_timelines(&project.root)?;
+    let names = item_names(&project.root)?;
+    let calendars = Calendars::new(&project.meta.calendars);
+    Ok(timeline::check_timelines(
+        &docs,
+        |d| calendars.ordinal(d),
+        |target| resolve_item_name(&names, target),
+    ))
+}
+
+/// Sorts the event rows of every timeline file by date in the project's calendars. Each changed
+/// file is written to `.backup/` first. Returns the files that were rewritten.
+pub fn sort_timelines(project: &Project) -> io::Result<Vec<PathBuf>> {
+    let calendars = Calendars::new(&project.meta.calendars);
+    let mut changed = Vec::new();
+    for (path, mut doc) in load_timelines(&project.root)? {
+        let before = doc.to_markdown();
+        doc.sort_by_date(&calendars);
+        let after = doc.to_markdown();
+        if after == before {
+            continue;
+        }
+        backup_text(&project.root, &path, &before)?;
+        write_text_atomic(&path, &after)?;
+        changed.push(path);
+    }
+    Ok(changed)
+}
+
+/// Splits a chapter stem like `0003-决战` into its number and title.
+pub fn split_chapter_stem(stem: &str) -> (Option<u32>, &str) {
+    if stem.len() >= 5 && stem.as_bytes()[4] == b'-' && stem[..4].chars().all(|c| c.is_ascii_digit()) {
+        (stem[..4].parse().ok(), &stem[5..])
+    } else {
+        (None, stem)
+    }
+}
+
+/// Path for a new item in `section`; chapters go to [`outline::default_chapter_dir`] with its
+/// next free `NNNN-` number.
+pub fn new_item_path(project_root: &Path, section: Section, title: &str) -> io::Result<PathBuf> {
+    let dir = project_root.join(section.dir_name());
+    if section == Section::Chapters {
+        return new_chapter_path(&outline::default_chapter_dir(&dir), title);
+    }
+    let title = sanitize_filename(title);
+    Ok(dir.join(unique_file_name(&dir, &format!("{}.md", title))))
+}
+
+/// Path for a new chapter at the end of `dir`, `chapters/` or one of its volumes.
+pub fn new_chapter_path(dir: &Path, title: &str) -> io::Result<PathBuf> {
+    let existing = list_markdown_files(dir)?;
+    let next_num = existing
+        .iter()
+        .filter_map(|p| p.file_stem().and_then(|s| s.to_str()))
+        .filter_map(|s| s.get(0..4).and_then(|n| n.parse::<u32>().ok()))
+        .max()
+        .unwrap_or(0)
+        + 1;
+    let file = format!("{:04}-{}.md", next_num, sanitize_filename(title));
+    Ok(dir.join(unique_file_name(dir, &file)))
+}
+
+/// Target path when renaming `from` to `new_name`; chapters keep their number prefix.
+pub fn renamed_item_path(project_root: &Path, section: Section, from: &Path, new_name: &str) -> PathBuf {
+    // Chapters may sit in a volume folder and stay there.
+    let dir = match (section, from.parent()) {
+        (Section::Chapters, Some(parent)) => parent.to_path_buf(),
+        _ => project_root.join(section.dir_name()),
+    };
+    let mut base = sanitize_filename(new_name);
+    if base.ends_with(".md") {
+        base.truncate(base.len() - 3);
+    }
+    let file = if section == Section::Chapters {
+        let stem = from.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+        let prefix = match split_chapter_stem(stem) {
+            (Some(_), _) => &stem[..5],
+            (None, _) => "",
+        };
+        // Labels show the full stem, so a typed-in `NNNN-` prefix must not be doubled.
+        let (_, title) = split_chapter_stem(&base);
+        format!("{}{}.md", prefix, title)
+    } else {
+        format!("{}.md", base)
+    };
+    dir.join(unique_file_name(&dir, &file))
+}
+
+/// Prefix of the folder [`apply_rename_pairs`] moves files through.
+const REORDER_STAGING: &str = ".reorder.tmp.";
+/// Log in the staging folder of every move made, relative to the staging folder's parent.
+const REORDER_LOG: &str = "moves.log";
+
+/// Moves every `from` to its `to`, also when the targets overlap the sources as in a renumbering.
+/// Pairs may name volume folders; their files can have pairs of their own. When a move fails
+/// the ones already made are undone, so files never stay stranded in the staging folder.
+pub fn apply_rename_pairs(pairs: &[(PathBuf, PathBuf)]) -> io::Result<()> {
+    if pairs.is_empty() {
+        return Ok(());
+    }
+    // Told apart before anything moves: once its files are gone a folder is only an empty name.
+    let is_dir: Vec<bool> = pairs.iter().map(|(from, _)| from.is_dir()).collect();
+    let base = common_parent(pairs);
+    let staging = base.join(format!("{}{}", REORDER_STAGING, std::process::id()));
+    fs::create_dir_all(&staging)?;
+    let mut log = fs::OpenOptions::new().create(true).append(true).open(staging.join(REORDER_LOG))?;
+    // Logged before moving, so that a crash between the two leaves nothing unaccounted for.
+    let mut step = |from: &Path, to: &Path| -> io::Result<()> {
+        let rel = |p: &Path| p.strip_prefix(&base).unwrap_or(p).to_path_buf();
+        let line = serde_json::to_string(&(rel(from), rel(to))).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        writeln!(log, "{}", line)?;
+        log.sync_data()?;
+        fs::rename(from, to)
+    };
+
+    // Files first, then folders from the deepest up, each to a numbered place in `staging`...
+    let mut order: Vec<usize> = (0..pairs.len()).collect();
+    order.sort_by_key(|&i| (is_dir[i], std::cmp::Reverse(pairs[i].0.components().count())));
+    let result = (|| {
+        for &i in &order {
+            step(&pairs[i].0, &staging.join(i.to_string()))?;
+        }
+        // ...and back out the other way round, so folders exist before the files that go into them.
+        for &i in order.iter().rev() {
+            let to = &pairs[i].1;
+            if let Some(parent) = to.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            step(&staging.join(i.to_string()), to)?;
+        }
+        Ok(())
+    })();
+    match result {
+        Ok(()) => {
+            fs::remove_file(staging.join(REORDER_LOG))?;
+            fs::remove_dir(&staging)
+        }
+        Err(e) => {
+            // If undoing fails too, the log stays for `recover_interrupted_reorders`.
+            let _ = undo_reorder(&staging);
+            Err(e)
+        }
+    }
+}
+
+/// Undoes the moves logged in `staging`, newest first, and removes the folder. A move whose
+/// target is gone or whose source is taken again was undone already and is skipped.
+fn undo_reorder(staging: &Path) -> io::Result<()> {
+    let base = staging.parent().unwrap_or(staging);
+    let log = read_text(&staging.join(REORDER_LOG))?;
+    for line in log.lines().rev() {
+        let Ok((from, to)) = serde_json::from_str::<(PathBuf, PathBuf)>(line) else { continue };
+        let (from, to) = (base.join(from), base.join(to));
+        if fs::symlink_metadata(&to).is_err() || fs::symlink_metadata(&from).is_ok() {
             continue;
         }
+        if let Some(parent) = from.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(&to, &from)?;
+    }
+    fs::remove_file(staging.join(REORDER_LOG))?;
+    fs::remove_dir(staging)
+}
+
+/// Puts back the files of a renumbering that was cut off by a crash or power loss, looking for
+/// staging folders at any depth below `chapters/`. Returns how many were undone.
+pub fn recover_interrupted_reorders(project_root: &Path) -> io::Result<usize> {
+    fn walk(dir: &Path, found: &mut Vec<PathBuf>) -> io::Result<()> {
         for entry in fs::read_dir(dir)? {
-            let entry = entry?;
-            let path = entry.path();
-            if !path.is_file() {
+            let path = entry?.path();
+            let name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+            if !path.is_dir() {
                 continue;
             }
-            let name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
-            if name.contains(".tmp.") {
-                let _ = fs::remove_file(path);
+            if name.starts_with(REORDER_STAGING) {
+                found.push(path);
+            } else if !name.starts_with('.') {
+                walk(&path, found)?;
             }
         }
+        Ok(())
     }
-    Ok(())
+    let chapters = project_root.join(Section::Chapters.dir_name());
+    let mut found = Vec::new();
+    if chapters.is_dir() {
+        walk(&chapters, &mut found)?;
+    }
+    let mut undone = 0;
+    for staging in found {
+        if staging.join(REORDER_LOG).is_file() {
+            undo_reorder(&staging)?;
+            undone += 1;
+        }
+    }
+    Ok(undone)
 }
 
-pub fn backup_text(project_root: &Path, source_path: &Path, text: &str) -> io::Result<()> {
-    let backup_root = project_root.join(".backup");
-    ensure_dir(&backup_root)?;
-
-    let ts = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .map(|d| d.as_secs())
-        .unwrap_or(0);
-    let snapshot_dir = backup_root.join(format!("backup-{}", ts));
-    ensure_dir(&snapshot_dir)?;
+/// The deepest folder holding every path of `pairs`.
+fn common_parent(pairs: &[(PathBuf, PathBuf)]) -> PathBuf {
+    let mut common = pairs[0].0.parent().map(Path::to_path_buf).unwrap_or_default();
+    for path in pairs.iter().flat_map(|(from, to)| [from, to]) {
+        while !path.starts_with(&common) || path == &common {
+            if !common.pop() {
+                return common;
+            }
+        }
+    }
+    common
+}
 
-    let rel = source_path.strip_prefix(project_root).unwrap_or(source_path);
-    let dst = snapshot_dir.join(rel);
-    if let Some(parent) = dst.parent() {
-        ensure_dir(parent)?;
+pub fn copy_project(src: &Path, dst: &Path) -> io::Result<()> {
+    fs::create_dir_all(dst)?;
+    let files = ["project.md"];
+    for f in files {
+        let from = src.join(f);
+        if from.exists() {
+            let to = dst.join(f);
+            fs::copy(from, to)?;
+        }
     }
-    atomic_write(&dst, text.as_bytes())?;
+    for section in Section::ALL {
+        let from_dir = src.join(section.dir_name());
+        if !from_dir.exists() {
+            continue;
+        }
+        let to_dir = dst.join(section.dir_name());
+        copy_dir_recursive(&from_dir, &to_dir)?;
+    }
+    Ok(())
+}
 
-    cleanup_old_backups(&backup_root, 30)?;
+fn copy_dir_recursive(src: &Path, dst: &Path) -> io::Result<()> {
+    fs::create_dir_all(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = entry.file_name();
+        let to = dst.join(name);
+        if path.is_dir() {
+            copy_dir_recursive(&path, &to)?;
+        } else if path.is_file() {
+            fs::copy(path, to)?;
+        }
+    }
     Ok(())
 }
 
+pub fn unique_file_name(dir: &Path, file_name: &str) -> String {
+    if !dir.join(file_name).exists() {
+        return file_name.to_string();
+    }
+    let (stem, ext) = match file_name.rsplit_once('.') {
+        Some((s, e)) => (s.to_string(), format!(".{}", e)),
+        None => (file_name.to_string(), String::new()),
+    };
+    for i in 1..10_000 {
+        let cand = format!("{} ({}){}", stem, i, ext);
+        if !dir.join(&cand).exists() {
+            return cand;
+        }
+    }
+    file_name.to_string()
+}
+
+pub fn sanitize_filename(name: &str) -> String {
+    let mut out = String::new();
+    for c in name.chars() {
+        if matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*') {
+            out.push('_');
+        } else {
+            out.push(c);
+        }
+    }
+    out.trim().trim_matches('.').to_string()
+}
+
  impl ProjectStore {
      pub fn open_or_init(root: PathBuf) -> Result<Project, String> {
          let store = Self { root };
          store.ensure_layout().map_err(|e| e.to_string())?;
+        let _ = recover_interrupted_reorders(&store.root);
         let _ = cleanup_temp_files(&store.root);
  
          let project_md = store.root.join("project.md");
@@ -104,25 +573,54 @@ pub fn backup_text(project_root: &Path, source_path: &Path, text: &str) -> io::R
              return Ok(Project {
                  root: store.root,
                  meta,
+                recovered: Vec::new(),
              });
          }
  
          let (meta, _body) = store.read_project_md().map_err(|e| e.to_string())?;
+        let recovered = recovery::pending_journals(&store.root)
+            .map(|entries| entries.into_iter().map(|e| e.path).collect())
+            .unwrap_or_default();
          Ok(Project {
              root: store.root,
              meta,
+            recovered,
          })
      }
  
-     pub fn save_project_meta(project: &Project) -> Result<(), String> {
-         let store = Self {
-             root: project.root.clone(),
-         };
-         let (_old_meta, body) = store.read_project_md().unwrap_or((ProjectMeta::default(), String::new()));
-         store
-             .write_project_md(&project.meta, if body.trim().is_empty() { default_project_body() } else { body })
-             .map_err(|e| e.to_string())
-     }
+    /// Writes the settings the program keeps in `project.meta` itself, the theme and the pane
+    /// ratio, leaving every other key as it now is in `project.md`.
+    pub fn save_project_meta(project: &mut Project) -> Result<(), String> {
+        let (theme, ratio) = (project.meta.theme.clone(), project.meta.left_pane_ratio);
+        Self::update_project_meta(project, |meta| {
+            meta.theme = theme;
+            meta.left_pane_ratio = ratio;
+        })
+    }
+
+    /// Re-reads `project.md`, lets `patch` change its front matter and writes it back, so that
+    /// keys edited by hand since the project was opened survive. `project.meta` becomes what
+    /// was written.
+    pub fn update_project_meta(project: &mut Project, patch: impl FnOnce(&mut ProjectMeta)) -> Result<(), String> {
+        let store = Self {
+            root: project.root.clone(),
+        };
+        let (mut meta, body) = store.read_project_md_checked()?;
+        patch(&mut meta);
+        store
+            .write_project_md(&meta, if body.trim().is_empty() { default_project_body() } else { body })
+            .map_err(|e| e.to_string())?;
+        project.meta = meta;
+        Ok(())
+    }
+
+    /// The front matter of `project.md` as it is on disk now.
+    pub fn read_project_meta(project_root: &Path) -> Result<ProjectMeta, String> {
+        let store = Self {
+            root: project_root.to_path_buf(),
+        };
+        store.read_project_md_checked().map(|(meta, _)| meta)
+    }
  
      fn ensure_layout(&self) -> io::Result<()> {
          ensure_dir(&self.root)?;
@@ -137,7 +635,7 @@ pub fn backup_text(project_root: &Path, source_path: &Path, text: &str) -> io::R
          ensure_dir(&self.root.join("exports"))?;
          ensure_dir(&self.root.join(".backup"))?;
  
-        if list_markdown_files(&chapters_dir)?.is_empty() {
+        if list_markdown_files_recursive(&chapters_dir)?.is_empty() {
             let first = chapters_dir.join("0001-第一章.md");
             atomic_write(&first, default_chapter_md().as_bytes())?;
         }
@@ -167,14 +665,19 @@ pub fn backup_text(project_root: &Path, source_path: &Path, text: &str) -> io::R
          Ok((meta.unwrap_or_default(), body.to_string()))
      }
  
+    /// Like `read_project_md`, but an error instead of defaults when the front matter does not
+    /// parse: writing defaults over it would lose the name, calendars and Git settings in it.
+    fn read_project_md_checked(&self) -> Result<(ProjectMeta, String), String> {
+        let content = fs::read_to_string(self.root.join("project.md")).map_err(|e| e.to_string())?;
+        if has_invalid_front_matter::<ProjectMeta>(&content) {
+            return Err("project.md 的 front matter 格式有误，请先修正后再保存设置".to_string());
+        }
+        let (meta, body) = parse_front_matter::<ProjectMeta>(&content);
+        Ok((meta.unwrap_or_default(), body.to_string()))
+    }
+
      fn write_project_md(&self, meta: &ProjectMeta, body: String) -> io::Result<()> {
-         let yaml = serde_yaml::to_string(meta).unwrap_or_default();
-         let mut out = String::new();
-         out.push_str("---\n");
-         out.push_str(yaml.trim_end());
-         out.push('\n');
-         out.push_str("---\n\n");
-         out.push_str(body.trim_start());
+         let mut out = render_front_matter(meta, &body);
          out.push('\n');
  
          let path = self.root.join("project.md");
@@ -182,66 +685,10 @@ pub fn backup_text(project_root: &Path, source_path: &Path, text: &str) -> io::R
      }
  }
 
-fn cleanup_old_backups(backup_root: &Path, keep: usize) -> io::Result<()> {
-    let mut dirs: Vec<PathBuf> = Vec::new();
-    for entry in fs::read_dir(backup_root)? {
-        let entry = entry?;
-        let path = entry.path();
-        if path.is_dir() {
-            let name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
-            if name.starts_with("backup-") {
-                dirs.push(path);
-            }
-        }
-    }
-    dirs.sort();
-    if dirs.len() <= keep {
-        return Ok(());
-    }
-    let remove = dirs.len() - keep;
-    for path in dirs.into_iter().take(remove) {
-        let _ = fs::remove_dir_all(path);
-    }
-    Ok(())
-}
- 
  fn ensure_dir(path: &Path) -> io::Result<()> {
      fs::create_dir_all(path)
  }
- 
- fn parse_front_matter<T: DeserializeOwned>(content: &str) -> (Option<T>, &str) {
-     let normalized = content.strip_prefix("\u{feff}").unwrap_or(content);
-     let content = normalized;
- 
-     if !content.starts_with("---\n") && !content.starts_with("---\r\n") {
-         return (None, content);
-     }
- 
-     let rest = &content[3..];
-     let rest = rest.strip_prefix("\r\n").or_else(|| rest.strip_prefix("\n")).unwrap_or(rest);
- 
-     if let Some(end) = find_front_matter_end(rest) {
-         let (yaml_part, body_part) = rest.split_at(end);
-         let yaml = yaml_part.trim();
-         let body = body_part
-             .trim_start_matches("\r\n---\r\n")
-             .trim_start_matches("\n---\n")
-             .trim_start_matches("\r\n---\n")
-             .trim_start_matches("\n---\r\n");
- 
-         let meta = serde_yaml::from_str::<T>(yaml).ok();
-         (meta, body)
-     } else {
-         (None, content)
-     }
- }
- 
- fn find_front_matter_end(s: &str) -> Option<usize> {
-     let patterns = ["\n---\n", "\r\n---\r\n", "\n---\r\n", "\r\n---\n"];
-     patterns.iter().filter_map(|p| s.find(p)).min()
- }
- 
- fn atomic_write(path: &Path, bytes: &[u8]) -> io::Result<()> {
+  fn atomic_write(path: &Path, bytes: &[u8]) -> io::Result<()> {
      let tmp = temp_path(path);
      fs::write(&tmp, bytes)?;
      replace_file(&tmp, path)?;
@@ -254,6 +701,7 @@ fn cleanup_old_backups(backup_root: &Path, keep: usize) -> io::Result<()> {
      PathBuf::from(tmp)
  }
  
+#[cfg(windows)]
  fn replace_file(src: &Path, dst: &Path) -> io::Result<()> {
      let src_w = to_wide_path(src);
      let dst_w = to_wide_path(dst);
@@ -271,6 +719,20 @@ fn cleanup_old_backups(backup_root: &Path, keep: usize) -> io::Result<()> {
      Ok(())
  }
  
+#[cfg(not(windows))]
+fn replace_file(src: &Path, dst: &Path) -> io::Result<()> {
+    fs::File::open(src)?.sync_all()?;
+    fs::rename(src, dst)?;
+    // Persist the directory entry as well, otherwise the rename itself may be lost on power failure.
+    if let Some(parent) = dst.parent().filter(|p| !p.as_os_str().is_empty()) {
+        if let Ok(dir) = fs::File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
  fn to_wide_path(path: &Path) -> Vec<u16> {
      let mut buf: Vec<u16> = path.as_os_str().encode_wide().collect();
      buf.push(0);
@@ -288,16 +750,217 @@ fn cleanup_old_backups(backup_root: &Path, keep: usize) -> io::Result<()> {
  }
 
 fn default_chapter_md() -> String {
-    "---\nformat_version: 1\n---\n\n# 第一章\n\n"
-        .to_string()
+    render_front_matter(&ChapterMeta::new_draft(), "# 第一章\n\n")
 }
 
 fn default_character_md() -> String {
-    "---\nformat_version: 1\n---\n\n# 角色：示例\n\n- 角色定位：\n- 外貌特征：\n- 性格：\n- 目标与动机：\n- 关系：\n"
-        .to_string()
+    new_character_md("示例")
 }
 
 fn default_world_md() -> String {
     "---\nformat_version: 1\n---\n\n# 世界观：示例\n\n## 规则\n\n## 地理\n\n## 势力\n\n"
         .to_string()
 }
+
+
+/// A fresh empty folder under the system temp dir, unique per call, for tests.
+#[cfg(test)]
+pub(crate) fn test_dir(tag: &str) -> PathBuf {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    let dir = std::env::temp_dir().join(format!("novel-outline-{}-{}-{}", tag, std::process::id(), n));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::ChapterStatus;
+
+    #[test]
+    fn conditional_writes_leave_changed_files_alone() {
+        let dir = test_dir("write-if-unchanged");
+        let path = dir.join("a.md");
+        fs::write(&path, "新").unwrap();
+        assert!(write_text_if_unchanged(&path, "新", "旧").unwrap());
+        assert_eq!(read_text(&path).unwrap(), "旧");
+        assert!(write_text_if_unchanged(&path, "新", "旧").unwrap());
+        fs::write(&path, "旧，又改了").unwrap();
+        assert!(!write_text_if_unchanged(&path, "新", "旧").unwrap());
+        assert_eq!(read_text(&path).unwrap(), "旧，又改了");
+        assert!(write_text_if_unchanged(&dir.join("b.md"), "", "恢复").unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Every file below `dir` with its text, as `a/b.md: text` lines in name order.
+    fn tree(dir: &Path) -> Vec<String> {
+        fn walk(root: &Path, dir: &Path, out: &mut Vec<String>) {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    walk(root, &path, out);
+                } else {
+                    let rel = path.strip_prefix(root).unwrap().to_string_lossy().replace('\\', "/");
+                    out.push(format!("{}: {}", rel, read_text(&path).unwrap()));
+                }
+            }
+        }
+        let mut out = Vec::new();
+        walk(dir, dir, &mut out);
+        out.sort();
+        out
+    }
+
+    fn volumes(root: &Path) -> PathBuf {
+        let chapters = root.join("chapters");
+        for (path, text) in [
+            ("0001-开端.md", "开端"),
+            ("01-第一卷/0001-下山.md", "下山"),
+            ("01-第一卷/0002-入城.md", "入城"),
+            ("02-第二卷/0001-重逢.md", "重逢"),
+        ] {
+            let path = chapters.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+        chapters
+    }
+
+    #[test]
+    fn renumbering_volumes_moves_their_chapters_along() {
+        let root = test_dir("renumber");
+        let chapters = volumes(&root);
+        let before = tree(&chapters);
+        let mut items = outline::read_outline(&chapters).unwrap();
+        assert!(outline::move_before(&mut items, 4, 1));
+        let pairs = outline::renumber_outline(&chapters, &items);
+        apply_rename_pairs(&pairs).unwrap();
+        assert_eq!(
+            tree(&chapters),
+            ["0001-开端.md: 开端", "01-第二卷/0001-重逢.md: 重逢", "02-第一卷/0001-下山.md: 下山", "02-第一卷/0002-入城.md: 入城"]
+        );
+
+        let back: Vec<_> = pairs.into_iter().map(|(from, to)| (to, from)).collect();
+        apply_rename_pairs(&back).unwrap();
+        assert_eq!(tree(&chapters), before);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn a_failed_renumbering_is_undone() {
+        let root = test_dir("renumber-fail");
+        let chapters = volumes(&root);
+        fs::write(chapters.join("挡路.txt"), "挡路").unwrap();
+        let before = tree(&chapters);
+        let mut items = outline::read_outline(&chapters).unwrap();
+        outline::move_before(&mut items, 4, 1);
+        let mut pairs = outline::renumber_outline(&chapters, &items);
+        // Put back last, after the volumes have moved: its target folder is a file.
+        pairs.insert(0, (chapters.join("0001-开端.md"), chapters.join("挡路.txt/0001-开端.md")));
+        assert!(apply_rename_pairs(&pairs).is_err());
+        assert_eq!(tree(&chapters), before);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn reorders_cut_off_inside_a_volume_are_recovered() {
+        let root = test_dir("renumber-recover");
+        let chapters = volumes(&root);
+        let volume = chapters.join("01-第一卷");
+        let before = tree(&chapters);
+        // A swap of the volume's two chapters that stopped halfway through putting them back.
+        let staging = volume.join(".reorder.tmp.999");
+        fs::create_dir(&staging).unwrap();
+        let moves = [
+            ("0001-下山.md", ".reorder.tmp.999/0"),
+            ("0002-入城.md", ".reorder.tmp.999/1"),
+            (".reorder.tmp.999/1", "0001-下山.md"),
+        ];
+        let mut log = String::new();
+        for (from, to) in moves {
+            fs::rename(volume.join(from), volume.join(to)).unwrap();
+            log.push_str(&serde_json::to_string(&(from, to)).unwrap());
+            log.push('\n');
+        }
+        fs::write(staging.join(REORDER_LOG), log).unwrap();
+        fs::write(volume.join("0002-入城.md.tmp.42"), "半截").unwrap();
+
+        assert_eq!(recover_interrupted_reorders(&root).unwrap(), 1);
+        cleanup_temp_files(&root).unwrap();
+        assert_eq!(tree(&chapters), before);
+        assert_eq!(recover_interrupted_reorders(&root).unwrap(), 0);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn saving_settings_keeps_what_was_edited_by_hand() {
+        let mut project = ProjectStore::open_or_init(test_dir("project-md")).unwrap();
+        let path = project.root.join("project.md");
+        let edited = read_text(&path).unwrap().replacen("---\n", "---\ndaily_target: 3000\ncover: 封面.png\ngit:\n  disable_backup: true\n", 1);
+        fs::write(&path, &edited).unwrap();
+
+        project.meta.theme = Some("dark".to_string());
+        ProjectStore::save_project_meta(&mut project).unwrap();
+        let meta = ProjectStore::read_project_meta(&project.root).unwrap();
+        assert_eq!((meta.theme.as_deref(), meta.daily_target), (Some("dark"), Some(3000)));
+        assert!(meta.git.is_some_and(|g| g.disable_backup));
+        assert_eq!(meta.extra.get("cover").and_then(|v| v.as_str()), Some("封面.png"));
+        assert_eq!(project.meta.daily_target, Some(3000));
+
+        let broken = read_text(&path).unwrap().replacen("daily_target: 3000", "daily_target: [3000", 1);
+        fs::write(&path, &broken).unwrap();
+        assert!(ProjectStore::save_project_meta(&mut project).is_err());
+        assert!(ProjectStore::update_project_meta(&mut project, |meta| meta.word_target = Some(1)).is_err());
+        assert_eq!(read_text(&path).unwrap(), broken);
+        fs::remove_dir_all(&project.root).unwrap();
+    }
+
+    #[test]
+    fn chapter_front_matter_keeps_unknown_keys() {
+        let content = "---\nstatus: revising\npov: 林风\nmood: 紧张\nbeats:\n- 开场\n- 冲突\n---\n\n# 决战\n\n正文。\n";
+        let (mut meta, body) = parse_chapter(content);
+        assert_eq!(meta.status, Some(ChapterStatus::Revising));
+        assert_eq!(meta.pov.as_deref(), Some("林风"));
+        assert_eq!(body.trim_start(), "# 决战\n\n正文。\n");
+        assert!(meta.extra.contains_key("mood"));
+
+        meta.status = Some(ChapterStatus::Done);
+        let rendered = render_front_matter(&meta, body);
+        let (again, again_body) = parse_chapter(&rendered);
+        assert_eq!(again.status, Some(ChapterStatus::Done));
+        assert_eq!(again.extra, meta.extra);
+        assert_eq!(again_body.trim_start(), body.trim_start());
+    }
+
+    #[test]
+    fn unknown_status_and_loose_targets_keep_the_other_keys() {
+        let (meta, _) = parse_chapter("---\nstatus: 待定\ntarget_words: 约3000\npov: 林风\nthreads: [主线]\nresolves: [复仇线]\n---\n正文");
+        assert_eq!(meta.status, Some(ChapterStatus::Unknown));
+        assert_eq!(meta.target_words, None);
+        assert_eq!(meta.pov.as_deref(), Some("林风"));
+        assert_eq!((meta.threads, meta.resolves), (vec!["主线".to_string()], vec!["复仇线".to_string()]));
+
+        let (meta, _) = parse_chapter("---\ntarget_words: \"3000\"\n---\n");
+        assert_eq!(meta.target_words, Some(3000));
+        assert_eq!(parse_chapter("---\ntarget_words: 2500\n---\n").0.target_words, Some(2500));
+    }
+
+    #[test]
+    fn front_matter_with_bom_and_crlf() {
+        let content = "\u{feff}---\r\nstatus: draft\r\n---\r\n正文";
+        let (meta, body) = parse_chapter(content);
+        assert_eq!(meta.status, Some(ChapterStatus::Draft));
+        assert_eq!(body, "正文");
+    }
+
+    #[test]
+    fn text_without_front_matter_is_all_body() {
+        let (meta, body) = parse_front_matter::<ChapterMeta>("# 标题\n\n---\n");
+        assert!(meta.is_none());
+        assert_eq!(body, "# 标题\n\n---\n");
+    }
+}
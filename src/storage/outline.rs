@@ -0,0 +1,370 @@
+//! Volumes and parts as nested folders of `chapters/`.
+//!
+//! A volume is a folder named `NN-标题` and may hold chapters and further volumes (parts);
+//! chapters are `NNNN-标题.md` files. Each folder is numbered on its own, so
+//! `chapters/01-第一卷/0001-开端.md` is the first chapter of the first volume. Within a folder the
+//! outline lists its chapters first, then its volumes, each in file-name order.
+//!
+//! [`read_cards`] collects what the corkboard shows of each chapter.
+
+use super::{parse_chapter, read_text, sanitize_filename, split_chapter_stem, unique_file_name};
+use crate::domain::ChapterStatus;
+use crate::stats::count_markdown;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutlineKind {
+    Volume,
+    Chapter,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlineItem {
+    pub path: PathBuf,
+    /// 0 for items directly in `chapters/`.
+    pub depth: usize,
+    pub kind: OutlineKind,
+}
+
+impl OutlineItem {
+    /// The name without its number prefix and, for chapters, without `.md`.
+    pub fn title(&self) -> &str {
+        match self.kind {
+            OutlineKind::Chapter => split_chapter_stem(self.path.file_stem().and_then(|s| s.to_str()).unwrap_or("")).1,
+            OutlineKind::Volume => split_volume_name(self.path.file_name().and_then(|s| s.to_str()).unwrap_or("")).1,
+        }
+    }
+}
+
+/// Splits a volume folder name like `02-第二卷` into its number and title.
+pub fn split_volume_name(name: &str) -> (Option<u32>, &str) {
+    if name.len() >= 3 && name.as_bytes()[2] == b'-' && name[..2].chars().all(|c| c.is_ascii_digit()) {
+        (name[..2].parse().ok(), &name[3..])
+    } else {
+        (None, name)
+    }
+}
+
+/// Every volume and chapter under `chapters_dir`, each volume followed by its contents.
+/// Hidden folders and leftovers of interrupted writes are skipped.
+pub fn read_outline(chapters_dir: &Path) -> io::Result<Vec<OutlineItem>> {
+    let mut out = Vec::new();
+    read_level(chapters_dir, 0, &mut out)?;
+    Ok(out)
+}
+
+fn read_level(dir: &Path, depth: usize, out: &mut Vec<OutlineItem>) -> io::Result<()> {
+    let mut files = Vec::new();
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+        if name.starts_with('.') || name.contains(".tmp.") {
+            continue;
+        }
+        if path.is_dir() {
+            dirs.push(path);
+        } else if path.is_file() && path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("md")) {
+            files.push(path);
+        }
+    }
+    files.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+    dirs.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+    out.extend(files.into_iter().map(|path| OutlineItem {
+        path,
+        depth,
+        kind: OutlineKind::Chapter,
+    }));
+    for path in dirs {
+        out.push(OutlineItem {
+            path: path.clone(),
+            depth,
+            kind: OutlineKind::Volume,
+        });
+        read_level(&path, depth + 1, out)?;
+    }
+    Ok(())
+}
+
+/// Index just past the contents of `items[index]`; `index + 1` for a chapter.
+pub fn subtree_end(items: &[OutlineItem], index: usize) -> usize {
+    let depth = items[index].depth;
+    items[index + 1..]
+        .iter()
+        .position(|item| item.depth <= depth)
+        .map_or(items.len(), |p| index + 1 + p)
+}
+
+/// Moves `items[src]` with its contents in front of `items[dst]`, at the level of `dst`.
+/// Returns false, leaving `items` alone, when `dst` lies inside `src`.
+pub fn move_before(items: &mut Vec<OutlineItem>, src: usize, dst: usize) -> bool {
+    let end = subtree_end(items, src);
+    if (src..end).contains(&dst) {
+        return false;
+    }
+    let depth = items[dst].depth;
+    move_block(items, src, end, dst, depth);
+    true
+}
+
+/// Moves `items[src]` with its contents just behind `items[dst]` and its contents, at the level
+/// of `dst`. Returns false, leaving `items` alone, when `dst` lies inside `src`.
+pub fn move_after(items: &mut Vec<OutlineItem>, src: usize, dst: usize) -> bool {
+    let end = subtree_end(items, src);
+    if (src..end).contains(&dst) {
+        return false;
+    }
+    let depth = items[dst].depth;
+    let at = subtree_end(items, dst);
+    move_block(items, src, end, at, depth);
+    true
+}
+
+/// Moves `items[src]` with its contents to the end of the volume `items[volume]`.
+/// Returns false, leaving `items` alone, when `volume` is not a volume or lies inside `src`.
+pub fn move_into(items: &mut Vec<OutlineItem>, src: usize, volume: usize) -> bool {
+    let end = subtree_end(items, src);
+    if items[volume].kind != OutlineKind::Volume || (src..end).contains(&volume) {
+        return false;
+    }
+    let depth = items[volume].depth + 1;
+    let at = subtree_end(items, volume);
+    move_block(items, src, end, at, depth);
+    true
+}
+
+fn move_block(items: &mut Vec<OutlineItem>, start: usize, end: usize, at: usize, depth: usize) {
+    let shift = depth as isize - items[start].depth as isize;
+    let mut block: Vec<OutlineItem> = items.drain(start..end).collect();
+    for item in &mut block {
+        item.depth = (item.depth as isize + shift) as usize;
+    }
+    let at = if at > start { at - block.len() } else { at };
+    items.splice(at..at, block);
+}
+
+/// Rename pairs that put every item of `items` where its position says: into the volume above it
+/// and numbered within that folder, `0001-` for chapters and `01-` for volumes. Volumes appear as
+/// folder pairs; [`super::apply_rename_pairs`] moves both kinds.
+pub fn renumber_outline(chapters_dir: &Path, items: &[OutlineItem]) -> Vec<(PathBuf, PathBuf)> {
+    // Per open folder: its new path and how many chapters and volumes it has so far.
+    let mut folders: Vec<(PathBuf, u32, u32)> = vec![(chapters_dir.to_path_buf(), 0, 0)];
+    let mut pairs = Vec::new();
+    for item in items {
+        folders.truncate(item.depth + 1);
+        let Some((dir, chapters, volumes)) = folders.last_mut() else { continue };
+        let title = sanitize_filename(item.title());
+        let to = match item.kind {
+            OutlineKind::Chapter => {
+                *chapters += 1;
+                let title = if title.is_empty() { "章节".to_string() } else { title };
+                dir.join(format!("{:04}-{}.md", chapters, title))
+            }
+            OutlineKind::Volume => {
+                *volumes += 1;
+                let title = if title.is_empty() { "卷".to_string() } else { title };
+                dir.join(format!("{:02}-{}", volumes, title))
+            }
+        };
+        if item.kind == OutlineKind::Volume {
+            folders.push((to.clone(), 0, 0));
+        }
+        if to != item.path {
+            pairs.push((item.path.clone(), to));
+        }
+    }
+    pairs
+}
+
+/// Path for a new volume at the end of `parent`.
+pub fn new_volume_path(parent: &Path, title: &str) -> io::Result<PathBuf> {
+    let next = fs::read_dir(parent)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.is_dir())
+        .filter_map(|p| p.file_name().and_then(|s| s.to_str()).and_then(|n| split_volume_name(n).0))
+        .max()
+        .unwrap_or(0)
+        + 1;
+    let title = sanitize_filename(title);
+    let title = if title.is_empty() { "卷".to_string() } else { title };
+    Ok(parent.join(unique_file_name(parent, &format!("{:02}-{}", next, title))))
+}
+
+/// Folder new chapters go to when no other is chosen: the last volume of the last volume, and so
+/// on down, or `chapters_dir` itself when it has no volumes.
+pub fn default_chapter_dir(chapters_dir: &Path) -> PathBuf {
+    let mut dir = chapters_dir.to_path_buf();
+    while let Some(last) = read_outline(&dir)
+        .ok()
+        .and_then(|items| items.into_iter().rev().find(|i| i.depth == 0 && i.kind == OutlineKind::Volume))
+    {
+        dir = last.path;
+    }
+    dir
+}
+
+/// What an index card on the corkboard shows of a chapter.
+#[derive(Debug, Clone)]
+pub struct ChapterCard {
+    pub path: PathBuf,
+    /// Title of the volume the chapter is in, `None` directly in `chapters/`.
+    pub volume: Option<String>,
+    pub status: Option<ChapterStatus>,
+    pub pov: Option<String>,
+    /// `summary:` from the front matter, or else the first paragraph of the text.
+    pub summary: String,
+    /// 字数 of the chapter.
+    pub words: usize,
+}
+
+/// A card for every chapter under `chapters_dir`, in outline order.
+pub fn read_cards(chapters_dir: &Path) -> io::Result<Vec<ChapterCard>> {
+    let mut cards = Vec::new();
+    let mut volumes: Vec<String> = Vec::new();
+    for item in read_outline(chapters_dir)? {
+        volumes.truncate(item.depth);
+        if item.kind == OutlineKind::Volume {
+            volumes.push(item.title().to_string());
+            continue;
+        }
+        let content = read_text(&item.path)?;
+        let (meta, body) = parse_chapter(&content);
+        let summary = meta
+            .summary
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+            .unwrap_or_else(|| first_paragraph(body));
+        cards.push(ChapterCard {
+            volume: volumes.last().cloned(),
+            status: meta.status,
+            pov: meta.pov.filter(|p| !p.trim().is_empty()),
+            summary,
+            words: count_markdown(body).total(),
+            path: item.path,
+        });
+    }
+    Ok(cards)
+}
+
+/// The first run of text lines, skipping headings, comments and code blocks.
+fn first_paragraph(body: &str) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    let mut in_fence = false;
+    let mut in_comment = false;
+    for line in body.lines() {
+        let line = line.trim();
+        if in_comment {
+            in_comment = !line.contains("-->");
+            continue;
+        }
+        if line.starts_with("```") || line.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        if line.starts_with("<!--") {
+            in_comment = !line.contains("-->");
+            continue;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            if !lines.is_empty() {
+                break;
+            }
+            continue;
+        }
+        lines.push(line);
+    }
+    lines.join("")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(path: &str, depth: usize, kind: OutlineKind) -> OutlineItem {
+        OutlineItem {
+            path: Path::new("/书/chapters").join(path),
+            depth,
+            kind,
+        }
+    }
+
+    fn outline() -> Vec<OutlineItem> {
+        vec![
+            item("0001-开端.md", 0, OutlineKind::Chapter),
+            item("01-第一卷", 0, OutlineKind::Volume),
+            item("01-第一卷/0001-下山.md", 1, OutlineKind::Chapter),
+            item("01-第一卷/0002-入城.md", 1, OutlineKind::Chapter),
+            item("02-第二卷", 0, OutlineKind::Volume),
+            item("02-第二卷/0001-重逢.md", 1, OutlineKind::Chapter),
+        ]
+    }
+
+    fn paths(items: &[OutlineItem]) -> Vec<String> {
+        items.iter().map(|i| format!("{}{}", "  ".repeat(i.depth), i.title())).collect()
+    }
+
+    #[test]
+    fn moves_keep_subtrees_together() {
+        let mut items = outline();
+        assert_eq!(subtree_end(&items, 1), 4);
+        assert!(move_before(&mut items, 4, 1));
+        assert_eq!(paths(&items), ["开端", "第二卷", "  重逢", "第一卷", "  下山", "  入城"]);
+
+        let mut items = outline();
+        assert!(move_into(&mut items, 0, 4));
+        assert_eq!(paths(&items), ["第一卷", "  下山", "  入城", "第二卷", "  重逢", "  开端"]);
+
+        let mut items = outline();
+        assert!(move_after(&mut items, 5, 2));
+        assert_eq!(paths(&items), ["开端", "第一卷", "  下山", "  重逢", "  入城", "第二卷"]);
+
+        let mut items = outline();
+        assert!(!move_into(&mut items, 1, 2));
+        assert!(!move_before(&mut items, 1, 3));
+        assert_eq!(items, outline());
+    }
+
+    #[test]
+    fn renumbering_follows_positions() {
+        let chapters = Path::new("/书/chapters");
+        assert!(renumber_outline(chapters, &outline()).is_empty());
+
+        let mut items = outline();
+        move_before(&mut items, 4, 1);
+        let pairs = renumber_outline(chapters, &items);
+        let rel = |p: &Path| p.strip_prefix(chapters).unwrap().to_string_lossy().replace('\\', "/");
+        let pairs: Vec<(String, String)> = pairs.iter().map(|(a, b)| (rel(a), rel(b))).collect();
+        assert_eq!(
+            pairs,
+            [
+                ("02-第二卷".to_string(), "01-第二卷".to_string()),
+                ("02-第二卷/0001-重逢.md".to_string(), "01-第二卷/0001-重逢.md".to_string()),
+                ("01-第一卷".to_string(), "02-第一卷".to_string()),
+                ("01-第一卷/0001-下山.md".to_string(), "02-第一卷/0001-下山.md".to_string()),
+                ("01-第一卷/0002-入城.md".to_string(), "02-第一卷/0002-入城.md".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn volume_names() {
+        assert_eq!(split_volume_name("02-第二卷"), (Some(2), "第二卷"));
+        assert_eq!(split_volume_name("番外"), (None, "番外"));
+    }
+
+    #[test]
+    fn first_paragraph_skips_headings_comments_and_code() {
+        let body = "# 第一章\n\n<!-- scene: 初入山门\n视角: 林风\n-->\n```\n代码\n```\n  林风来到山门，\n抬头望去。\n\n第二段不算。\n";
+        assert_eq!(first_paragraph(body), "林风来到山门，抬头望去。");
+        assert_eq!(first_paragraph("开头一句。\n## 小节\n小节正文。"), "开头一句。");
+        assert_eq!(first_paragraph("第一行\n<!-- 批注 -->\n第二行"), "第一行第二行");
+        assert_eq!(first_paragraph("# 只有标题\n\n<!-- 待写 -->\n"), "");
+    }
+}
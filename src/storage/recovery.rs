@@ -0,0 +1,158 @@
+//! Crash recovery journal in `.recovery/`.
+//!
+//! While a document has unsaved edits the editor writes its text here every few seconds, and
+//! removes the journal once the document is saved. A journal still present when the project is
+//! opened holds text a crash or power cut kept from being saved.
+
+use super::sha256::sha256_hex;
+use super::snapshots::relative_key;
+use super::{atomic_write, backup_text, read_text, write_text_atomic};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Journal {
+    /// Path relative to the project root, with `/` separators.
+    path: String,
+    saved_unix: u64,
+    text: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct RecoveryEntry {
+    /// The journal file.
+    pub journal: PathBuf,
+    /// The document the text belongs to.
+    pub path: PathBuf,
+    pub saved_unix: u64,
+    pub text: String,
+}
+
+impl RecoveryEntry {
+    /// What the document holds now, empty when it no longer exists.
+    pub fn disk_text(&self) -> String {
+        read_text(&self.path).unwrap_or_default()
+    }
+
+    /// Replaces the document with the journal's text, snapshotting the old content first.
+    pub fn restore(&self, project_root: &Path) -> io::Result<()> {
+        if self.path.exists() {
+            backup_text(project_root, &self.path, &self.disk_text())?;
+        } else if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        write_text_atomic(&self.path, &self.text)?;
+        self.discard()
+    }
+
+    pub fn discard(&self) -> io::Result<()> {
+        match fs::remove_file(&self.journal) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn recovery_dir(project_root: &Path) -> PathBuf {
+    project_root.join(".recovery")
+}
+
+fn journal_path(project_root: &Path, source_path: &Path) -> PathBuf {
+    let key = relative_key(project_root, source_path);
+    recovery_dir(project_root).join(format!("{}.json", &sha256_hex(key.as_bytes())[..16]))
+}
+
+pub fn write_journal(project_root: &Path, source_path: &Path, text: &str) -> io::Result<()> {
+    fs::create_dir_all(recovery_dir(project_root))?;
+    let journal = Journal {
+        path: relative_key(project_root, source_path),
+        saved_unix: crate::domain::now_unix(),
+        text: text.to_string(),
+    };
+    let json = serde_json::to_string(&journal).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    atomic_write(&journal_path(project_root, source_path), json.as_bytes())
+}
+
+/// Removes the journal of `source_path`, once its text is safely on disk.
+pub fn clear_journal(project_root: &Path, source_path: &Path) -> io::Result<()> {
+    match fs::remove_file(journal_path(project_root, source_path)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Journals left behind by an earlier session, oldest first. Journals whose text matches the
+/// document on disk are removed instead of being returned.
+pub fn pending_journals(project_root: &Path) -> io::Result<Vec<RecoveryEntry>> {
+    let dir = recovery_dir(project_root);
+    let mut entries = Vec::new();
+    if !dir.is_dir() {
+        return Ok(entries);
+    }
+    for entry in fs::read_dir(&dir)? {
+        let journal = entry?.path();
+        if journal.extension().and_then(|s| s.to_str()) != Some("json") {
+            continue;
+        }
+        let Ok(text) = read_text(&journal) else { continue };
+        let Ok(parsed) = serde_json::from_str::<Journal>(&text) else { continue };
+        let entry = RecoveryEntry {
+            journal,
+            path: project_root.join(&parsed.path),
+            saved_unix: parsed.saved_unix,
+            text: parsed.text,
+        };
+        if entry.disk_text() == entry.text {
+            entry.discard()?;
+        } else {
+            entries.push(entry);
+        }
+    }
+    entries.sort_by_key(|e| e.saved_unix);
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_dir;
+    use crate::storage::versions::list_versions;
+
+    #[test]
+    fn pending_journals_drop_saved_text_and_restore_the_rest() {
+        let root = test_dir("recovery");
+        fs::create_dir_all(root.join("chapters")).unwrap();
+        let saved = root.join("chapters/0001-开端.md");
+        let unsaved = root.join("chapters/0002-下山.md");
+        let gone = root.join("chapters/第二卷/0003-重逢.md");
+        fs::write(&saved, "已保存").unwrap();
+        fs::write(&unsaved, "旧文").unwrap();
+        write_journal(&root, &saved, "已保存").unwrap();
+        write_journal(&root, &unsaved, "崩溃前写的新文").unwrap();
+        write_journal(&root, &gone, "被删掉的章节").unwrap();
+
+        let entries = pending_journals(&root).unwrap();
+        let mut paths: Vec<&Path> = entries.iter().map(|e| e.path.as_path()).collect();
+        paths.sort();
+        assert_eq!(paths, [unsaved.as_path(), gone.as_path()]);
+        assert_eq!(fs::read_dir(recovery_dir(&root)).unwrap().count(), 2);
+
+        for entry in &entries {
+            entry.restore(&root).unwrap();
+        }
+        assert_eq!(read_text(&unsaved).unwrap(), "崩溃前写的新文");
+        assert_eq!(read_text(&gone).unwrap(), "被删掉的章节");
+        // The text the restore replaced is kept as a version.
+        let versions = list_versions(&root, &unsaved).unwrap();
+        assert!(versions.iter().any(|v| v.read().unwrap() == "旧文"));
+        assert!(pending_journals(&root).unwrap().is_empty());
+
+        write_journal(&root, &saved, "又改了").unwrap();
+        clear_journal(&root, &saved).unwrap();
+        clear_journal(&root, &saved).unwrap();
+        assert!(pending_journals(&root).unwrap().is_empty());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
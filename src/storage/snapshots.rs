@@ -0,0 +1,321 @@
+//! Content-addressed backup store in `.backup/`.
+//!
+//! Every file's text is stored once under `objects/xx/<sha256>`, and each snapshot is a small
+//! manifest in `snapshots/` mapping the project's Markdown files to their blobs. Unchanged files
+//! cost nothing, so the whole project can be snapshotted on every save; [`gc`] later thins old
+//! snapshots out and deletes blobs no snapshot refers to.
+
+use super::links::project_markdown_files;
+use super::sha256::sha256_hex;
+use super::{atomic_write, local_offset_secs, read_text};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub created_unix: u64,
+    /// Path relative to the project root, with `/` separators, to the blob's hash.
+    pub files: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    /// The manifest file.
+    pub path: PathBuf,
+    pub manifest: Manifest,
+}
+
+/// Hashes of files whose size and modification time have not changed since they were stored.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StatCache {
+    files: HashMap<String, (u64, u128, String)>,
+}
+
+fn backup_root(project_root: &Path) -> PathBuf {
+    project_root.join(".backup")
+}
+
+fn snapshots_dir(project_root: &Path) -> PathBuf {
+    backup_root(project_root).join("snapshots")
+}
+
+fn cache_path(project_root: &Path) -> PathBuf {
+    backup_root(project_root).join("stat-cache.json")
+}
+
+pub fn object_path(project_root: &Path, hash: &str) -> PathBuf {
+    backup_root(project_root).join("objects").join(&hash[..2]).join(&hash[2..])
+}
+
+pub fn relative_key(project_root: &Path, path: &Path) -> String {
+    let rel = path.strip_prefix(project_root).unwrap_or(path);
+    rel.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn stat(path: &Path) -> Option<(u64, u128)> {
+    let meta = fs::metadata(path).ok()?;
+    let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_nanos();
+    Some((meta.len(), modified))
+}
+
+/// Stores `text` as a blob unless it is already there. Returns its hash.
+fn store_blob(project_root: &Path, text: &str) -> io::Result<String> {
+    let hash = sha256_hex(text.as_bytes());
+    let path = object_path(project_root, &hash);
+    if !path.exists() {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        atomic_write(&path, text.as_bytes())?;
+    }
+    Ok(hash)
+}
+
+fn json_error(e: serde_json::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// Records the current state of every Markdown file of the project. `overrides` replace the
+/// text of a file, e.g. the content a file had before it is deleted or rewritten.
+/// Returns `None` when nothing changed since the latest snapshot.
+pub fn snapshot_project(project_root: &Path, overrides: &[(&Path, &str)]) -> io::Result<Option<Snapshot>> {
+    let cache_file = cache_path(project_root);
+    let mut cache: StatCache = match read_text(&cache_file) {
+        Ok(text) => serde_json::from_str(&text).unwrap_or_default(),
+        Err(_) => StatCache::default(),
+    };
+
+    let mut files = BTreeMap::new();
+    let mut paths = project_markdown_files(project_root)?;
+    for (path, _) in overrides {
+        if !paths.iter().any(|p| p == path) {
+            paths.push(path.to_path_buf());
+        }
+    }
+    for path in &paths {
+        let key = relative_key(project_root, path);
+        if let Some((_, text)) = overrides.iter().find(|(p, _)| p == path) {
+            files.insert(key, store_blob(project_root, text)?);
+            continue;
+        }
+        let Some((len, modified)) = stat(path) else { continue };
+        let cached = cache
+            .files
+            .get(&key)
+            .filter(|(l, m, hash)| *l == len && *m == modified && object_path(project_root, hash).exists());
+        let hash = match cached {
+            Some((_, _, hash)) => hash.clone(),
+            None => {
+                let hash = store_blob(project_root, &read_text(path)?)?;
+                cache.files.insert(key.clone(), (len, modified, hash.clone()));
+                hash
+            }
+        };
+        files.insert(key, hash);
+    }
+    cache.files.retain(|key, _| files.contains_key(key));
+    atomic_write(&cache_file, serde_json::to_string(&cache).map_err(json_error)?.as_bytes())?;
+
+    if latest_snapshot(project_root)?.is_some_and(|s| s.manifest.files == files) {
+        return Ok(None);
+    }
+    let dir = snapshots_dir(project_root);
+    fs::create_dir_all(&dir)?;
+    let created_unix = crate::domain::now_unix();
+    let mut seq = 0;
+    let path = loop {
+        let candidate = dir.join(format!("{}-{}.json", created_unix, seq));
+        if !candidate.exists() {
+            break candidate;
+        }
+        seq += 1;
+    };
+    let manifest = Manifest { created_unix, files };
+    atomic_write(&path, serde_json::to_string_pretty(&manifest).map_err(json_error)?.as_bytes())?;
+    Ok(Some(Snapshot { path, manifest }))
+}
+
+fn snapshot_order(path: &Path) -> Option<(u64, u64)> {
+    let stem = path.file_stem()?.to_str()?;
+    let (ts, seq) = stem.split_once('-')?;
+    Some((ts.parse().ok()?, seq.parse().ok()?))
+}
+
+/// Manifest files, oldest first, without reading them.
+fn manifest_paths(project_root: &Path) -> io::Result<Vec<PathBuf>> {
+    let dir = snapshots_dir(project_root);
+    let mut found = Vec::new();
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        let Some(order) = snapshot_order(&path) else { continue };
+        found.push((order, path));
+    }
+    found.sort();
+    Ok(found.into_iter().map(|(_, path)| path).collect())
+}
+
+fn read_snapshot(path: PathBuf) -> Option<Snapshot> {
+    let manifest = serde_json::from_str::<Manifest>(&read_text(&path).ok()?).ok()?;
+    Some(Snapshot { path, manifest })
+}
+
+/// All snapshots, oldest first. Unreadable manifests are skipped.
+pub fn list_snapshots(project_root: &Path) -> io::Result<Vec<Snapshot>> {
+    Ok(manifest_paths(project_root)?.into_iter().filter_map(read_snapshot).collect())
+}
+
+/// The newest readable snapshot; only its manifest is parsed.
+pub fn latest_snapshot(project_root: &Path) -> io::Result<Option<Snapshot>> {
+    Ok(manifest_paths(project_root)?.into_iter().rev().find_map(read_snapshot))
+}
+
+/// How long snapshots are kept: all of them for `keep_all_secs`, then the newest of every hour
+/// up to `hourly_secs`, then the newest of every day up to `daily_secs`. The latest snapshot is
+/// always kept.
+#[derive(Debug, Copy, Clone)]
+pub struct RetentionPolicy {
+    pub keep_all_secs: u64,
+    pub hourly_secs: u64,
+    pub daily_secs: u64,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            keep_all_secs: 3600,
+            hourly_secs: 24 * 3600,
+            daily_secs: 30 * 24 * 3600,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GcReport {
+    pub kept_snapshots: usize,
+    pub removed_snapshots: usize,
+    pub removed_objects: usize,
+}
+
+/// Applies `policy` as of `now_unix` and deletes blobs that no remaining snapshot refers to.
+pub fn gc(project_root: &Path, policy: &RetentionPolicy, now_unix: u64) -> io::Result<GcReport> {
+    let snapshots = list_snapshots(project_root)?;
+    let mut report = GcReport::default();
+    let mut buckets = HashSet::new();
+    let mut referenced = HashSet::new();
+    for (i, snapshot) in snapshots.iter().enumerate().rev() {
+        let created = snapshot.manifest.created_unix;
+        let age = now_unix.saturating_sub(created);
+        let local = created as i64 + local_offset_secs();
+        // Newest first, so the first snapshot seen in an hour or day is the one that stands for it.
+        let first_of_hour = buckets.insert(('h', local.div_euclid(3600)));
+        let first_of_day = buckets.insert(('d', local.div_euclid(86_400)));
+        let keep = i + 1 == snapshots.len()
+            || age <= policy.keep_all_secs
+            || (age <= policy.hourly_secs && first_of_hour)
+            || (age <= policy.daily_secs && first_of_day);
+        if keep {
+            referenced.extend(snapshot.manifest.files.values().cloned());
+            report.kept_snapshots += 1;
+        } else {
+            fs::remove_file(&snapshot.path)?;
+            report.removed_snapshots += 1;
+        }
+    }
+
+    let objects = backup_root(project_root).join("objects");
+    if objects.is_dir() {
+        for prefix in fs::read_dir(&objects)? {
+            let prefix = prefix?.path();
+            let Some(head) = prefix.file_name().and_then(|s| s.to_str()).map(str::to_string) else { continue };
+            if !prefix.is_dir() {
+                continue;
+            }
+            for blob in fs::read_dir(&prefix)? {
+                let blob = blob?.path();
+                let Some(tail) = blob.file_name().and_then(|s| s.to_str()) else { continue };
+                if !referenced.contains(&format!("{}{}", head, tail)) {
+                    fs::remove_file(&blob)?;
+                    report.removed_objects += 1;
+                }
+            }
+            let _ = fs::remove_dir(&prefix);
+        }
+    }
+    super::versions::cleanup_old_backups(&backup_root(project_root), 30)?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_dir;
+
+    /// Writes a snapshot dated `created_unix` holding `text` as the only file.
+    fn snapshot_at(root: &Path, created_unix: u64, text: &str) {
+        let mut files = BTreeMap::new();
+        files.insert("chapters/0001-开端.md".to_string(), store_blob(root, text).unwrap());
+        let dir = snapshots_dir(root);
+        fs::create_dir_all(&dir).unwrap();
+        let manifest = Manifest { created_unix, files };
+        fs::write(dir.join(format!("{}-0.json", created_unix)), serde_json::to_string(&manifest).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn unchanged_projects_are_not_snapshotted_twice() {
+        let root = test_dir("snapshot");
+        fs::create_dir_all(root.join("chapters")).unwrap();
+        let chapter = root.join("chapters/0001-开端.md");
+        fs::write(&chapter, "初稿").unwrap();
+        let first = snapshot_project(&root, &[]).unwrap().unwrap();
+        assert!(snapshot_project(&root, &[]).unwrap().is_none());
+        let hash = &first.manifest.files["chapters/0001-开端.md"];
+        assert_eq!(read_text(&object_path(&root, hash)).unwrap(), "初稿");
+
+        // An override records text the file does not hold (yet).
+        let second = snapshot_project(&root, &[(&chapter, "删除前")]).unwrap().unwrap();
+        assert_ne!(&second.manifest.files["chapters/0001-开端.md"], hash);
+        assert_eq!(list_snapshots(&root).unwrap().len(), 2);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn gc_thins_out_old_snapshots_and_their_blobs() {
+        let root = test_dir("gc");
+        // Quarter-hour aligned, so that no hour or day boundary of any time zone falls between
+        // two snapshots meant to share a bucket.
+        let now: u64 = 1_800_000_000 / 900 * 900 + 10;
+        let hour = 3600;
+        let day = 24 * hour;
+        snapshot_at(&root, now - 60 * day, "两个月前");
+        snapshot_at(&root, now - 3 * day, "三天前 1");
+        snapshot_at(&root, now - 3 * day + 5, "三天前 2");
+        snapshot_at(&root, now - 2 * hour, "两小时前 1");
+        snapshot_at(&root, now - 2 * hour + 5, "两小时前 2");
+        snapshot_at(&root, now - 60, "刚才 1");
+        snapshot_at(&root, now - 30, "刚才 2");
+
+        let report = gc(&root, &RetentionPolicy::default(), now).unwrap();
+        assert_eq!((report.kept_snapshots, report.removed_snapshots, report.removed_objects), (4, 3, 3));
+        let kept: Vec<String> = list_snapshots(&root)
+            .unwrap()
+            .iter()
+            .map(|s| read_text(&object_path(&root, s.manifest.files.values().next().unwrap())).unwrap())
+            .collect();
+        assert_eq!(kept, ["三天前 2", "两小时前 2", "刚才 1", "刚才 2"]);
+
+        // The latest snapshot survives however old it is.
+        let report = gc(&root, &RetentionPolicy::default(), now + 365 * day).unwrap();
+        assert_eq!(report.kept_snapshots, 1);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
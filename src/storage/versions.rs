@@ -0,0 +1,206 @@
+//! Earlier versions of single files, read from the snapshots of `.backup/`, from the
+//! `backup-{ts}` folders older releases wrote there, and from Git when the project is in a
+//! repository.
+
+use super::git::GitRepo;
+use super::read_text;
+use super::snapshots::{list_snapshots, object_path, relative_key};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+pub struct FileVersion {
+    pub saved_unix: u64,
+    pub source: VersionSource,
+}
+
+#[derive(Debug, Clone)]
+pub enum VersionSource {
+    /// A copy inside `.backup/`: a snapshot blob or a file in a legacy `backup-{ts}` folder.
+    Copy(PathBuf),
+    /// The file as committed to Git.
+    Git { repo: GitRepo, commit: String, path: String, subject: String },
+}
+
+impl FileVersion {
+    pub fn read(&self) -> io::Result<String> {
+        match &self.source {
+            VersionSource::Copy(path) => read_text(path),
+            VersionSource::Git { repo, commit, path, .. } => repo.read_at(commit, path),
+        }
+    }
+
+    /// The commit message of a Git version.
+    pub fn note(&self) -> Option<&str> {
+        match &self.source {
+            VersionSource::Copy(_) => None,
+            VersionSource::Git { subject, .. } => Some(subject),
+        }
+    }
+}
+
+/// `backup-{ts}` folders with their timestamps, oldest first.
+fn backup_dirs(backup_root: &Path) -> io::Result<Vec<(u64, PathBuf)>> {
+    let mut dirs = Vec::new();
+    if !backup_root.is_dir() {
+        return Ok(dirs);
+    }
+    for entry in fs::read_dir(backup_root)? {
+        let path = entry?.path();
+        let ts = path
+            .file_name()
+            .and_then(|s| s.to_str())
+            .and_then(|n| n.strip_prefix("backup-"))
+            .and_then(|ts| ts.parse::<u64>().ok());
+        if let (Some(ts), true) = (ts, path.is_dir()) {
+            dirs.push((ts, path));
+        }
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+/// Saved versions of `source_path`, newest first. Consecutive snapshots holding the same text
+/// count as one version, dated by the first of them.
+pub fn list_versions(project_root: &Path, source_path: &Path) -> io::Result<Vec<FileVersion>> {
+    let rel = source_path.strip_prefix(project_root).unwrap_or(source_path);
+    let mut versions: Vec<FileVersion> = backup_dirs(&project_root.join(".backup"))?
+        .into_iter()
+        .map(|(saved_unix, dir)| (saved_unix, dir.join(rel)))
+        .filter(|(_, path)| path.is_file())
+        .map(|(saved_unix, path)| FileVersion {
+            saved_unix,
+            source: VersionSource::Copy(path),
+        })
+        .collect();
+
+    let key = relative_key(project_root, source_path);
+    let mut last_hash: Option<&str> = None;
+    let snapshots = list_snapshots(project_root)?;
+    for snapshot in &snapshots {
+        let hash = snapshot.manifest.files.get(&key).map(String::as_str);
+        if let Some(hash) = hash.filter(|h| last_hash != Some(*h)) {
+            versions.push(FileVersion {
+                saved_unix: snapshot.manifest.created_unix,
+                source: VersionSource::Copy(object_path(project_root, hash)),
+            });
+        }
+        last_hash = hash;
+    }
+
+    if let Ok(Some(repo)) = GitRepo::open(project_root) {
+        for version in repo.file_history(source_path).unwrap_or_default() {
+            versions.push(FileVersion {
+                saved_unix: version.time_unix,
+                source: VersionSource::Git {
+                    repo: repo.clone(),
+                    commit: version.commit,
+                    path: version.path,
+                    subject: version.subject,
+                },
+            });
+        }
+    }
+    versions.sort_by_key(|v| std::cmp::Reverse(v.saved_unix));
+    Ok(versions)
+}
+
+fn files_under(dir: &Path, out: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files_under(&path, out)?;
+        } else {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Removes `dir` and the folders below it that are empty.
+fn remove_empty_dirs(dir: &Path) {
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            if entry.path().is_dir() {
+                remove_empty_dirs(&entry.path());
+            }
+        }
+    }
+    let _ = fs::remove_dir(dir);
+}
+
+/// Keeps the newest `keep` versions of every file, so edits to a busy chapter never push
+/// out the versions of a quiet one, and drops snapshot folders left empty.
+pub(super) fn cleanup_old_backups(backup_root: &Path, keep: usize) -> io::Result<()> {
+    let mut seen: HashMap<PathBuf, usize> = HashMap::new();
+    for (_, dir) in backup_dirs(backup_root)?.into_iter().rev() {
+        let mut files = Vec::new();
+        files_under(&dir, &mut files)?;
+        for file in files {
+            let rel = file.strip_prefix(&dir).unwrap_or(&file).to_path_buf();
+            let count = seen.entry(rel).or_insert(0);
+            *count += 1;
+            if *count > keep {
+                let _ = fs::remove_file(&file);
+            }
+        }
+        remove_empty_dirs(&dir);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::snapshots::snapshot_project;
+    use crate::storage::test_dir;
+
+    #[test]
+    fn versions_from_snapshots_and_legacy_folders() {
+        let root = test_dir("versions");
+        fs::create_dir_all(root.join("chapters")).unwrap();
+        let chapter = root.join("chapters/0001-开端.md");
+        let other = root.join("chapters/0002-下山.md");
+        fs::write(&chapter, "第三稿").unwrap();
+        fs::write(&other, "一").unwrap();
+        let legacy = root.join(".backup/backup-100/chapters/0001-开端.md");
+        fs::create_dir_all(legacy.parent().unwrap()).unwrap();
+        fs::write(&legacy, "旧版").unwrap();
+
+        snapshot_project(&root, &[(&chapter, "初稿")]).unwrap();
+        snapshot_project(&root, &[(&chapter, "二稿")]).unwrap();
+        // Only the other chapter changes: no new version of this one.
+        fs::write(&other, "二").unwrap();
+        snapshot_project(&root, &[(&chapter, "二稿")]).unwrap();
+        snapshot_project(&root, &[(&chapter, "初稿")]).unwrap();
+
+        let versions = list_versions(&root, &chapter).unwrap();
+        let texts: Vec<String> = versions.iter().map(|v| v.read().unwrap()).collect();
+        assert_eq!(texts, ["初稿", "二稿", "初稿", "旧版"]);
+        assert_eq!(versions[3].saved_unix, 100);
+        assert!(versions.iter().all(|v| v.note().is_none()));
+        assert!(list_versions(&root, &other).unwrap().len() >= 2);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn old_backup_folders_keep_the_newest_copies_of_each_file() {
+        let root = test_dir("legacy-backups");
+        let backup = root.join(".backup");
+        for ts in [1, 2, 3] {
+            let dir = backup.join(format!("backup-{}", ts));
+            fs::create_dir_all(dir.join("chapters")).unwrap();
+            fs::write(dir.join("chapters/a.md"), ts.to_string()).unwrap();
+            if ts == 1 {
+                fs::write(dir.join("chapters/b.md"), "b").unwrap();
+            }
+        }
+        cleanup_old_backups(&backup, 2).unwrap();
+        assert!(!backup.join("backup-1/chapters/a.md").exists());
+        assert!(backup.join("backup-1/chapters/b.md").exists());
+        assert!(backup.join("backup-3/chapters/a.md").exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,132 @@
+//! inotify backend. inotify does not watch subfolders by itself, so every folder below the
+//! project root gets its own watch, and folders created later are added as they appear.
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+const MASK: u32 = libc::IN_CREATE | libc::IN_DELETE | libc::IN_MOVED_FROM | libc::IN_MOVED_TO | libc::IN_CLOSE_WRITE;
+
+pub(super) struct Backend {
+    fd: i32,
+    root: PathBuf,
+    dirs: HashMap<i32, PathBuf>,
+}
+
+impl Backend {
+    pub(super) fn new(root: &Path) -> io::Result<Self> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut backend = Self {
+            fd,
+            root: root.to_path_buf(),
+            dirs: HashMap::new(),
+        };
+        backend.watch_tree(root)?;
+        Ok(backend)
+    }
+
+    fn watch_tree(&mut self, dir: &Path) -> io::Result<()> {
+        let name = dir.file_name().map(|n| n.as_bytes()).unwrap_or_default();
+        if name.starts_with(b".") && dir != self.root {
+            return Ok(());
+        }
+        let c_path = CString::new(dir.as_os_str().as_bytes()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let wd = unsafe { libc::inotify_add_watch(self.fd, c_path.as_ptr(), MASK) };
+        if wd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        self.dirs.insert(wd, dir.to_path_buf());
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                self.watch_tree(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn read_changes(&mut self, out: &mut Vec<PathBuf>) -> io::Result<()> {
+        let mut buf = [0u8; 8192];
+        loop {
+            let n = unsafe { libc::read(self.fd, buf.as_mut_ptr().cast(), buf.len()) };
+            if n < 0 {
+                let err = io::Error::last_os_error();
+                return if err.kind() == io::ErrorKind::WouldBlock { Ok(()) } else { Err(err) };
+            }
+            let mut offset = 0;
+            while offset + std::mem::size_of::<libc::inotify_event>() <= n as usize {
+                let event: libc::inotify_event = unsafe { std::ptr::read_unaligned(buf.as_ptr().add(offset).cast()) };
+                let name_start = offset + std::mem::size_of::<libc::inotify_event>();
+                let name = &buf[name_start..name_start + event.len as usize];
+                let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+                offset = name_start + event.len as usize;
+
+                if event.mask & libc::IN_Q_OVERFLOW != 0 {
+                    // Events were dropped: report the root so everything is reloaded, and watch
+                    // the folders that may have been created meanwhile.
+                    let root = self.root.clone();
+                    let _ = self.watch_tree(&root);
+                    out.push(root);
+                    continue;
+                }
+                let Some(dir) = self.dirs.get(&event.wd) else { continue };
+                let path = dir.join(std::ffi::OsStr::from_bytes(name));
+                if event.mask & libc::IN_ISDIR != 0 && event.mask & (libc::IN_CREATE | libc::IN_MOVED_TO) != 0 {
+                    // Files may have landed in the new folder before its watch exists.
+                    let _ = self.watch_tree(&path);
+                    push_tree(&path, out);
+                }
+                out.push(path);
+            }
+        }
+    }
+}
+
+/// Everything below `dir`, at any depth.
+fn push_tree(dir: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            push_tree(&path, out);
+        }
+        out.push(path);
+    }
+}
+
+impl Drop for Backend {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queue_overflow_reports_the_root() {
+        let root = crate::storage::test_dir("inotify-overflow");
+        let mut backend = Backend::new(&root).unwrap();
+        let limit: usize = fs::read_to_string("/proc/sys/fs/inotify/max_queued_events")
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(16_384);
+        // Each new file queues a create and a close-after-write event.
+        for i in 0..limit / 2 + 10 {
+            fs::write(root.join(format!("{}.md", i)), "").unwrap();
+        }
+        let mut out = Vec::new();
+        backend.read_changes(&mut out).unwrap();
+        assert_eq!(out.last(), Some(&root));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,140 @@
+//! Watching a project folder for Markdown files added, removed, renamed or rewritten by other
+//! programs, so views built from the folder can refresh themselves.
+//!
+//! Events come from `ReadDirectoryChangesW` on Windows, inotify on Linux and a periodic scan
+//! elsewhere. [`ProjectWatcher::poll`] never blocks; it hands out changed paths in batches once
+//! they have been quiet for the debounce delay, so a save or a renumbering of many chapters
+//! arrives as one refresh.
+
+use std::collections::BTreeSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+#[cfg(target_os = "linux")]
+mod inotify;
+#[cfg(target_os = "linux")]
+use inotify::Backend;
+
+#[cfg(windows)]
+mod windows;
+#[cfg(windows)]
+use self::windows::Backend;
+
+#[cfg(not(any(windows, target_os = "linux")))]
+mod scan;
+#[cfg(not(any(windows, target_os = "linux")))]
+use scan::Backend;
+
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(400);
+
+pub struct ProjectWatcher {
+    root: PathBuf,
+    backend: Backend,
+    debounce: Duration,
+    pending: BTreeSet<PathBuf>,
+    last_event: Option<Instant>,
+}
+
+impl ProjectWatcher {
+    pub fn new(project_root: &Path) -> io::Result<Self> {
+        Ok(Self {
+            root: project_root.to_path_buf(),
+            backend: Backend::new(project_root)?,
+            debounce: DEFAULT_DEBOUNCE,
+            pending: BTreeSet::new(),
+            last_event: None,
+        })
+    }
+
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Paths changed since the last batch, once no further change arrived for the debounce delay;
+    /// empty while changes are still coming in or nothing happened. The project root itself
+    /// means events were lost and anything may have changed.
+    pub fn poll(&mut self) -> io::Result<Vec<PathBuf>> {
+        let now = Instant::now();
+        let mut changed = Vec::new();
+        self.backend.read_changes(&mut changed)?;
+        for path in changed {
+            if !is_ignored(&self.root, &path) {
+                self.pending.insert(path);
+                self.last_event = Some(now);
+            }
+        }
+        match self.last_event {
+            Some(last) if now.duration_since(last) >= self.debounce => {
+                self.last_event = None;
+                Ok(std::mem::take(&mut self.pending).into_iter().collect())
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+}
+
+/// True for paths no view of the project shows: the tool's own `.tmp.`/`.reorder.tmp.` files,
+/// hidden folders such as `.backup/` and `.recovery/`, `exports/`, and files that are not Markdown.
+/// Paths without an extension are kept, since a removed folder can no longer be told from a file.
+pub fn is_ignored(project_root: &Path, path: &Path) -> bool {
+    let rel = path.strip_prefix(project_root).unwrap_or(path);
+    let mut components = rel.components().map(|c| c.as_os_str().to_string_lossy());
+    if components.any(|c| c.starts_with('.') || c.contains(".tmp.")) {
+        return true;
+    }
+    if rel.components().next().is_some_and(|c| c.as_os_str() == "exports") {
+        return true;
+    }
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => !ext.eq_ignore_ascii_case("md"),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ignored_paths() {
+        let root = Path::new("/书");
+        let ignored = |rel: &str| is_ignored(root, &root.join(rel));
+        assert!(!ignored("chapters/第一卷/0001-开端.md"));
+        assert!(!ignored("project.md"));
+        assert!(!ignored("chapters/第一卷"));
+        assert!(ignored("chapters/0001-开端.md.tmp.123"));
+        assert!(ignored("chapters/.reorder.tmp.42/0001-开端.md"));
+        assert!(ignored(".backup/snapshots/1-0.json"));
+        assert!(ignored(".recovery/abc.md"));
+        assert!(ignored("exports/书.md"));
+        assert!(ignored("chapters/封面.png"));
+        assert!(!ignored("chapters/README.MD"));
+        assert!(!is_ignored(root, root));
+    }
+
+    #[test]
+    fn batches_arrive_after_the_debounce_delay() {
+        let root = crate::storage::test_dir("watch");
+        let mut watcher = ProjectWatcher::new(&root).unwrap().with_debounce(Duration::from_millis(50));
+        std::fs::create_dir_all(root.join("chapters/第一卷")).unwrap();
+        std::fs::write(root.join("chapters/第一卷/0001-开端.md"), "新").unwrap();
+        std::fs::write(root.join("chapters/封面.png"), "").unwrap();
+        let mut changed = Vec::new();
+        for _ in 0..100 {
+            changed.extend(watcher.poll().unwrap());
+            if changed.iter().any(|p| p.ends_with("0001-开端.md")) {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert!(changed.contains(&root.join("chapters/第一卷/0001-开端.md")), "{:?}", changed);
+        assert!(!changed.iter().any(|p| p.ends_with("封面.png")));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
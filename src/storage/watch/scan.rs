@@ -0,0 +1,62 @@
+//! Fallback backend for platforms without a native one: compares the size and modification time
+//! of every file below the project root with the previous scan.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+const SCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+pub(super) struct Backend {
+    root: PathBuf,
+    files: HashMap<PathBuf, (u64, Option<SystemTime>)>,
+    last_scan: Instant,
+}
+
+impl Backend {
+    pub(super) fn new(root: &Path) -> io::Result<Self> {
+        let mut files = HashMap::new();
+        scan(root, &mut files)?;
+        Ok(Self {
+            root: root.to_path_buf(),
+            files,
+            last_scan: Instant::now(),
+        })
+    }
+
+    pub(super) fn read_changes(&mut self, out: &mut Vec<PathBuf>) -> io::Result<()> {
+        if self.last_scan.elapsed() < SCAN_INTERVAL {
+            return Ok(());
+        }
+        self.last_scan = Instant::now();
+        let mut files = HashMap::new();
+        scan(&self.root, &mut files)?;
+        for (path, stamp) in &files {
+            if self.files.get(path) != Some(stamp) {
+                out.push(path.clone());
+            }
+        }
+        out.extend(self.files.keys().filter(|p| !files.contains_key(*p)).cloned());
+        self.files = files;
+        Ok(())
+    }
+}
+
+fn scan(dir: &Path, files: &mut HashMap<PathBuf, (u64, Option<SystemTime>)>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let meta = entry.metadata()?;
+        if meta.is_dir() {
+            scan(&path, files)?;
+        } else {
+            files.insert(path, (meta.len(), meta.modified().ok()));
+        }
+    }
+    Ok(())
+}
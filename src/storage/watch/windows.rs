@@ -0,0 +1,123 @@
+//! `ReadDirectoryChangesW` backend. A thread blocks on the call for the whole tree and forwards
+//! changed paths through a channel; dropping the backend cancels the pending call.
+
+use std::ffi::OsString;
+use std::io;
+use std::os::windows::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use windows_sys::Win32::Foundation::{CloseHandle, HANDLE, INVALID_HANDLE_VALUE};
+use windows_sys::Win32::Storage::FileSystem::{
+    CreateFileW, ReadDirectoryChangesW, FILE_FLAG_BACKUP_SEMANTICS, FILE_LIST_DIRECTORY, FILE_NOTIFY_CHANGE_DIR_NAME,
+    FILE_NOTIFY_CHANGE_FILE_NAME, FILE_NOTIFY_CHANGE_LAST_WRITE, FILE_NOTIFY_INFORMATION, FILE_SHARE_DELETE, FILE_SHARE_READ,
+    FILE_SHARE_WRITE, OPEN_EXISTING,
+};
+use windows_sys::Win32::System::IO::CancelIoEx;
+
+pub(super) struct Backend {
+    handle: HANDLE,
+    events: Receiver<PathBuf>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Backend {
+    pub(super) fn new(root: &Path) -> io::Result<Self> {
+        let wide: Vec<u16> = root.as_os_str().encode_wide().chain(Some(0)).collect();
+        let handle = unsafe {
+            CreateFileW(
+                wide.as_ptr(),
+                FILE_LIST_DIRECTORY,
+                FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+                std::ptr::null(),
+                OPEN_EXISTING,
+                FILE_FLAG_BACKUP_SEMANTICS,
+                0,
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error());
+        }
+        let (tx, events) = mpsc::channel();
+        let root = root.to_path_buf();
+        let thread = std::thread::spawn(move || watch_loop(handle, &root, tx));
+        Ok(Self {
+            handle,
+            events,
+            thread: Some(thread),
+        })
+    }
+
+    pub(super) fn read_changes(&mut self, out: &mut Vec<PathBuf>) -> io::Result<()> {
+        loop {
+            match self.events.try_recv() {
+                Ok(path) => out.push(path),
+                Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => return Ok(()),
+            }
+        }
+    }
+}
+
+fn watch_loop(handle: HANDLE, root: &Path, tx: Sender<PathBuf>) {
+    // u32 elements keep the buffer DWORD-aligned, as ReadDirectoryChangesW requires.
+    let mut buf = vec![0u32; 16 * 1024];
+    let filter = FILE_NOTIFY_CHANGE_FILE_NAME | FILE_NOTIFY_CHANGE_DIR_NAME | FILE_NOTIFY_CHANGE_LAST_WRITE;
+    loop {
+        let mut returned = 0u32;
+        let ok = unsafe {
+            ReadDirectoryChangesW(
+                handle,
+                buf.as_mut_ptr().cast(),
+                (buf.len() * 4) as u32,
+                1,
+                filter,
+                &mut returned,
+                std::ptr::null_mut(),
+                None,
+            )
+        };
+        if ok == 0 {
+            // Cancelled by drop, or the folder went away.
+            return;
+        }
+        if returned == 0 {
+            // Too many changes for the buffer: report the root so everything is reloaded.
+            if tx.send(root.to_path_buf()).is_err() {
+                return;
+            }
+            continue;
+        }
+        let bytes = buf.as_ptr() as *const u8;
+        let mut offset = 0usize;
+        loop {
+            let info = unsafe { &*(bytes.add(offset) as *const FILE_NOTIFY_INFORMATION) };
+            let name = unsafe { std::slice::from_raw_parts(info.FileName.as_ptr(), info.FileNameLength as usize / 2) };
+            if tx.send(root.join(OsString::from_wide(name))).is_err() {
+                return;
+            }
+            if info.NextEntryOffset == 0 {
+                break;
+            }
+            offset += info.NextEntryOffset as usize;
+        }
+    }
+}
+
+impl Drop for Backend {
+    fn drop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            // The thread may not have entered ReadDirectoryChangesW yet, so keep cancelling until it ends.
+            while !thread.is_finished() {
+                unsafe {
+                    CancelIoEx(self.handle, std::ptr::null());
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            let _ = thread.join();
+        }
+        unsafe {
+            CloseHandle(self.handle);
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn temp_project(tag: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("novel-outline-cli-{}-{}", tag, std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    root
+}
+
+fn run(root: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_novel-outline"))
+        .arg("-p")
+        .arg(root)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Runs a command that must succeed and returns its standard output.
+fn ok(root: &Path, args: &[&str]) -> String {
+    let out = run(root, args);
+    assert!(out.status.success(), "{:?} failed: {}", args, String::from_utf8_lossy(&out.stderr));
+    String::from_utf8(out.stdout).unwrap()
+}
+
+/// Runs a command that must fail with exit code 1 and returns its error output.
+fn err(root: &Path, args: &[&str]) -> String {
+    let out = run(root, args);
+    assert_eq!(out.status.code(), Some(1), "{:?} should fail", args);
+    String::from_utf8(out.stderr).unwrap()
+}
+
+fn chapter_names(root: &Path) -> Vec<String> {
+    ok(root, &["list", "chapters"]).lines().map(str::to_string).collect()
+}
+
+#[test]
+fn bad_arguments_fail_with_a_message() {
+    let root = temp_project("args");
+    let bin = env!("CARGO_BIN_EXE_novel-outline");
+    assert_eq!(Command::new(bin).output().unwrap().status.code(), Some(2));
+    assert_eq!(Command::new(bin).arg("-p").output().unwrap().status.code(), Some(2));
+
+    assert!(err(&root, &["list", "chapters"]).contains("缺少 project.md"));
+    ok(&root, &["init", root.to_str().unwrap()]);
+    assert!(err(&root, &["frobnicate"]).contains("未知命令: frobnicate"));
+    assert!(err(&root, &["list", "notes"]).contains("未知 section: notes"));
+    assert!(err(&root, &["move-chapter", "1", "--under", "2"]).contains("用法: move-chapter"));
+    assert!(err(&root, &["move-chapter", "1", "--before", "9"]).contains("找不到条目: 9"));
+    assert!(err(&root, &["rename", "chapters", "1"]).contains("用法: rename"));
+
+    ok(&root, &["new", "volume", "第一卷"]);
+    ok(&root, &["new", "volume", "上部", "--in", "第一卷"]);
+    ok(&root, &["new", "chapters", "开端", "--in", "第一卷"]);
+    assert!(err(&root, &["move-chapter", "第一卷", "--into", "上部"]).contains("不能把卷移动到它自己里面"));
+    assert!(err(&root, &["move-chapter", "第一卷", "--into", "开端"]).contains("开端 不是卷"));
+    assert!(err(&root, &["rename", "chapters", "开端", " "]).contains("新名称不能为空"));
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn moving_and_renaming_chapters_renumbers_and_rewrites_links() {
+    let root = temp_project("move");
+    ok(&root, &["init", root.to_str().unwrap()]);
+    // `init` writes the first chapter.
+    for title in ["下山", "入城"] {
+        ok(&root, &["new", "chapters", title]);
+    }
+    assert_eq!(chapter_names(&root), ["0001-第一章", "0002-下山", "0003-入城"]);
+    let lin = root.join("characters/林风.md");
+    fs::write(&lin, "# 林风\n\n初登场见[[0003-入城]]，下山见[[0002-下山|第二章]]。\n").unwrap();
+
+    let out = ok(&root, &["move-chapter", "3", "--before", "1"]);
+    assert!(out.contains("0003-入城.md -> 0001-入城.md"));
+    assert!(out.contains("已更新链接: 林风.md"));
+    assert_eq!(chapter_names(&root), ["0001-入城", "0002-第一章", "0003-下山"]);
+    assert_eq!(fs::read_to_string(&lin).unwrap(), "# 林风\n\n初登场见[[0001-入城]]，下山见[[0003-下山|第二章]]。\n");
+
+    ok(&root, &["new", "volume", "第一卷"]);
+    ok(&root, &["move-chapter", "第一章", "--into", "第一卷"]);
+    assert_eq!(chapter_names(&root), ["0001-入城", "0002-下山", "01-第一卷/", "  0001-第一章"]);
+
+    let out = ok(&root, &["rename", "chapters", "入城", "进城"]);
+    assert!(out.contains("0001-入城.md -> 0001-进城.md"));
+    assert_eq!(fs::read_to_string(&lin).unwrap(), "# 林风\n\n初登场见[[0001-进城]]，下山见[[0002-下山|第二章]]。\n");
+    fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,85 @@
+use novel_outline_tool::export::epub::{build_epub, MIMETYPE, OPF_PATH};
+use novel_outline_tool::export::zip::read_archive;
+use novel_outline_tool::storage::{self, ProjectStore};
+use std::path::PathBuf;
+
+fn temp_project(tag: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("novel-outline-{}-{}", tag, std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    root
+}
+
+fn attr_values<'a>(xml: &'a str, attr: &str) -> Vec<&'a str> {
+    let needle = format!("{}=\"", attr);
+    xml.match_indices(&needle)
+        .map(|(i, _)| {
+            let start = i + needle.len();
+            let end = start + xml[start..].find('"').unwrap();
+            &xml[start..end]
+        })
+        .collect()
+}
+
+#[test]
+fn epub_round_trip_archive_structure() {
+    let root = temp_project("epub");
+    let mut project = ProjectStore::open_or_init(root.clone()).unwrap();
+    ProjectStore::update_project_meta(&mut project, |meta| {
+        meta.name = "青云记".to_string();
+        meta.author = Some("佚名".to_string());
+    })
+    .unwrap();
+
+    let chapters = root.join("chapters");
+    storage::write_text_atomic(
+        &chapters.join("0001-第一章.md"),
+        "---\nformat_version: 1\n---\n\n# 第一章\n\n天色渐暗。\n他拔出了**青云剑**。\n",
+    )
+    .unwrap();
+    storage::write_text_atomic(&chapters.join("0002-风起&云涌.md"), "山门之外，风起云涌。\n").unwrap();
+
+    let bytes = build_epub(&project).unwrap();
+    let entries = read_archive(&bytes).unwrap();
+    let get = |name: &str| {
+        let entry = entries.iter().find(|e| e.name == name).unwrap_or_else(|| panic!("missing {}", name));
+        String::from_utf8(entry.data.clone()).unwrap()
+    };
+
+    let first = &entries[0];
+    assert_eq!(first.name, "mimetype");
+    assert_eq!(first.offset, 0);
+    assert!(first.stored);
+    assert_eq!(first.data, MIMETYPE.as_bytes());
+    assert_eq!(&bytes[30..38], b"mimetype");
+
+    let container = get("META-INF/container.xml");
+    assert_eq!(attr_values(&container, "full-path"), vec![OPF_PATH]);
+
+    let opf = get(OPF_PATH);
+    assert!(opf.contains("<dc:title>青云记</dc:title>"));
+    assert!(opf.contains("<dc:creator>佚名</dc:creator>"));
+    assert!(opf.contains("<dc:language>zh-CN</dc:language>"));
+    assert!(opf.contains("property=\"dcterms:modified\""));
+    for href in attr_values(&opf, "href") {
+        assert!(entries.iter().any(|e| e.name == format!("OEBPS/{}", href)), "manifest item {} not in archive", href);
+    }
+    assert_eq!(attr_values(&opf, "idref"), vec!["chapter-0001", "chapter-0002"]);
+
+    let nav = get("OEBPS/nav.xhtml");
+    assert!(nav.contains("epub:type=\"toc\""));
+    let nav_links = attr_values(&nav, "href");
+    assert_eq!(nav_links, vec!["style.css", "chapter-0001.xhtml", "chapter-0002.xhtml"]);
+
+    let ch1 = get("OEBPS/chapter-0001.xhtml");
+    assert!(ch1.contains("<h1>第一章</h1>"));
+    assert_eq!(ch1.matches("第一章").count(), 2, "front matter heading should not be repeated");
+    assert!(ch1.contains("<p>天色渐暗。</p>"));
+    assert!(ch1.contains("<p>他拔出了<strong>青云剑</strong>。</p>"));
+    assert!(!ch1.contains("format_version"));
+
+    let ch2 = get("OEBPS/chapter-0002.xhtml");
+    assert!(ch2.contains("<h1>风起&amp;云涌</h1>"));
+    assert!(nav.contains(">风起&amp;云涌</a>"));
+
+    let _ = std::fs::remove_dir_all(&root);
+}